@@ -0,0 +1,31 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use pngme::{chunk::Chunk, chunk_type::ChunkType};
+use std::{hint::black_box, str::FromStr};
+
+fn chunk_new(c: &mut Criterion) {
+    let chunk_type = ChunkType::from_str("IDAT").unwrap();
+    let mut group = c.benchmark_group("chunk_new");
+
+    for size in [4 * 1024, 256 * 1024, 8 * 1024 * 1024] {
+        let data: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &data, |b, data| {
+            b.iter(|| Chunk::new(chunk_type, black_box(data.clone())))
+        });
+    }
+
+    group.finish();
+}
+
+fn chunk_parse(c: &mut Criterion) {
+    let chunk_type = ChunkType::from_str("IDAT").unwrap();
+    let data: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+    let bytes = Chunk::new(chunk_type, data).as_bytes();
+
+    c.bench_function("chunk_try_from_1MiB", |b| {
+        b.iter(|| Chunk::try_from(black_box(bytes.as_slice())).unwrap())
+    });
+}
+
+criterion_group!(benches, chunk_new, chunk_parse);
+criterion_main!(benches);
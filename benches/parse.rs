@@ -0,0 +1,35 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use pngme::{chunk::Chunk, chunk_type::ChunkType, png::Png};
+use std::{hint::black_box, str::FromStr};
+
+// Many small chunks, where per-chunk overhead rather than checksumming dominates.
+fn testing_png(count: usize) -> Vec<u8> {
+    let mut chunks = vec![Chunk::new(
+        ChunkType::IHDR,
+        vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0],
+    )];
+    let text = ChunkType::from_str("tEXt").unwrap();
+    chunks.extend((0..count).map(|i| Chunk::new(text, format!("Key{}\0value", i).into_bytes())));
+    chunks.push(Chunk::new(ChunkType::IEND, Vec::new()));
+    Png::from_chunks(chunks).as_bytes()
+}
+
+fn png_parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("png_parse");
+
+    for count in [10, 100, 1000] {
+        let bytes = testing_png(count);
+        group.throughput(Throughput::Elements(count as u64 + 2));
+        group.bench_with_input(BenchmarkId::new("try_from", count), &bytes, |b, bytes| {
+            b.iter(|| Png::try_from(black_box(bytes.as_slice())).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("read_from", count), &bytes, |b, bytes| {
+            b.iter(|| Png::read_from(black_box(bytes.as_slice())).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, png_parse);
+criterion_main!(benches);
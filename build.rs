@@ -0,0 +1,17 @@
+// With the ffi feature, regenerates the C header for src/ffi.rs so it cannot drift from the code.
+fn main() {
+    #[cfg(feature = "ffi")]
+    {
+        println!("cargo:rerun-if-changed=src/ffi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+        let dir = std::path::PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+        let config = cbindgen::Config::from_file(dir.join("cbindgen.toml"))
+            .expect("could not read cbindgen.toml");
+        cbindgen::Builder::new()
+            .with_config(config)
+            .with_src(dir.join("src/ffi.rs"))
+            .generate()
+            .expect("could not generate the C header")
+            .write_to_file(dir.join("include/pngme.h"));
+    }
+}
@@ -0,0 +1,76 @@
+#ifndef PNGME_H
+#define PNGME_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define PNGME_OK 0
+
+#define PNGME_ERROR 1
+
+#define PNGME_NOT_FOUND 2
+
+#define PNGME_INVALID_PNG 3
+
+/**
+ * Bytes allocated by pngme. Release them with pngme_free.
+ */
+typedef struct PngmeBuffer {
+  uint8_t *data;
+  size_t len;
+} PngmeBuffer;
+
+/**
+ * Lists the chunks of a PNG as a JSON array of `{"type", "length", "crc"}` objects.
+ *
+ * # Safety
+ * `png` must point to `png_len` readable bytes and `out` must be writable.
+ */
+int32_t pngme_parse(const uint8_t *png, size_t png_len, struct PngmeBuffer *out);
+
+/**
+ * Hides `message` in a chunk of `chunk_type`, writing the new PNG to `out`.
+ *
+ * # Safety
+ * `png` and `message` must point to their lengths in readable bytes, `chunk_type` must be a nul
+ * terminated string and `out` must be writable.
+ */
+int32_t pngme_encode(const uint8_t *png,
+                     size_t png_len,
+                     const char *chunk_type,
+                     const uint8_t *message,
+                     size_t message_len,
+                     struct PngmeBuffer *out);
+
+/**
+ * Reveals the payload hidden in a chunk of `chunk_type`, writing it to `out`.
+ *
+ * # Safety
+ * `png` must point to `png_len` readable bytes, `chunk_type` must be a nul terminated string and
+ * `out` must be writable.
+ */
+int32_t pngme_decode(const uint8_t *png,
+                     size_t png_len,
+                     const char *chunk_type,
+                     struct PngmeBuffer *out);
+
+/**
+ * Releases a buffer returned by pngme. Freeing an empty buffer does nothing.
+ *
+ * # Safety
+ * `buffer` must come from pngme and not have been freed already.
+ */
+void pngme_free(struct PngmeBuffer buffer);
+
+/**
+ * Describes the last failure on this thread, or returns null if nothing has failed. The string
+ * stays valid until the next call into pngme on the same thread.
+ */
+const char *pngme_last_error(void);
+
+#endif  /* PNGME_H */
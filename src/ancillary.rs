@@ -0,0 +1,367 @@
+use crate::{chunk::Chunk, chunk_type::ChunkType, png::Png};
+use anyhow::Result;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum AncillaryError {
+    #[error("Invalid {chunk_type} data length {found:?}.")]
+    InvalidLength {
+        chunk_type: &'static str,
+        found: usize,
+    },
+    #[error("Invalid {chunk_type} value {found:?}.")]
+    InvalidValue { chunk_type: &'static str, found: u8 },
+}
+
+pub trait TypedChunk: Sized {
+    const CHUNK_TYPE: ChunkType;
+
+    fn from_data(data: &[u8]) -> Result<Self>;
+
+    fn to_data(&self) -> Vec<u8>;
+
+    fn to_chunk(&self) -> Chunk {
+        Chunk::new(Self::CHUNK_TYPE, self.to_data())
+    }
+}
+
+pub(crate) fn fixed<const N: usize>(chunk_type: &'static str, data: &[u8]) -> Result<[u8; N]> {
+    <[u8; N]>::try_from(data).map_err(|_| {
+        AncillaryError::InvalidLength {
+            chunk_type,
+            found: data.len(),
+        }
+        .into()
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhysUnit {
+    Unknown = 0,
+    Meter = 1,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Phys {
+    pub pixels_per_unit_x: u32,
+    pub pixels_per_unit_y: u32,
+    pub unit: PhysUnit,
+}
+
+impl Phys {
+    pub fn from_dpi(dpi: u32) -> Self {
+        let pixels_per_meter = (dpi as f64 / 0.0254).round() as u32;
+        Self {
+            pixels_per_unit_x: pixels_per_meter,
+            pixels_per_unit_y: pixels_per_meter,
+            unit: PhysUnit::Meter,
+        }
+    }
+
+    pub fn dpi(&self) -> Option<(f64, f64)> {
+        match self.unit {
+            PhysUnit::Meter => Some((
+                self.pixels_per_unit_x as f64 * 0.0254,
+                self.pixels_per_unit_y as f64 * 0.0254,
+            )),
+            PhysUnit::Unknown => None,
+        }
+    }
+}
+
+impl TypedChunk for Phys {
+    const CHUNK_TYPE: ChunkType = ChunkType::pHYs;
+
+    fn from_data(data: &[u8]) -> Result<Self> {
+        let bytes: [u8; 9] = fixed("pHYs", data)?;
+        let unit = match bytes[8] {
+            0 => PhysUnit::Unknown,
+            1 => PhysUnit::Meter,
+            found => {
+                return Err(AncillaryError::InvalidValue {
+                    chunk_type: "pHYs",
+                    found,
+                }
+                .into())
+            }
+        };
+
+        Ok(Self {
+            pixels_per_unit_x: u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            pixels_per_unit_y: u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+            unit,
+        })
+    }
+
+    fn to_data(&self) -> Vec<u8> {
+        self.pixels_per_unit_x
+            .to_be_bytes()
+            .into_iter()
+            .chain(self.pixels_per_unit_y.to_be_bytes())
+            .chain([self.unit as u8])
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gamma(pub u32);
+
+impl Gamma {
+    pub fn from_f64(gamma: f64) -> Self {
+        Self((gamma * 100_000.0).round() as u32)
+    }
+
+    pub fn as_f64(&self) -> f64 {
+        self.0 as f64 / 100_000.0
+    }
+}
+
+impl TypedChunk for Gamma {
+    const CHUNK_TYPE: ChunkType = ChunkType::gAMA;
+
+    fn from_data(data: &[u8]) -> Result<Self> {
+        Ok(Self(u32::from_be_bytes(fixed("gAMA", data)?)))
+    }
+
+    fn to_data(&self) -> Vec<u8> {
+        self.0.to_be_bytes().to_vec()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderingIntent {
+    Perceptual = 0,
+    RelativeColorimetric = 1,
+    Saturation = 2,
+    AbsoluteColorimetric = 3,
+}
+
+impl TypedChunk for RenderingIntent {
+    const CHUNK_TYPE: ChunkType = ChunkType::sRGB;
+
+    fn from_data(data: &[u8]) -> Result<Self> {
+        match fixed::<1>("sRGB", data)?[0] {
+            0 => Ok(Self::Perceptual),
+            1 => Ok(Self::RelativeColorimetric),
+            2 => Ok(Self::Saturation),
+            3 => Ok(Self::AbsoluteColorimetric),
+            found => Err(AncillaryError::InvalidValue {
+                chunk_type: "sRGB",
+                found,
+            }
+            .into()),
+        }
+    }
+
+    fn to_data(&self) -> Vec<u8> {
+        vec![*self as u8]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Background {
+    PaletteIndex(u8),
+    Gray(u16),
+    Rgb(u16, u16, u16),
+}
+
+impl TypedChunk for Background {
+    const CHUNK_TYPE: ChunkType = ChunkType::bKGD;
+
+    fn from_data(data: &[u8]) -> Result<Self> {
+        match *data {
+            [index] => Ok(Self::PaletteIndex(index)),
+            [g0, g1] => Ok(Self::Gray(u16::from_be_bytes([g0, g1]))),
+            [r0, r1, g0, g1, b0, b1] => Ok(Self::Rgb(
+                u16::from_be_bytes([r0, r1]),
+                u16::from_be_bytes([g0, g1]),
+                u16::from_be_bytes([b0, b1]),
+            )),
+            _ => Err(AncillaryError::InvalidLength {
+                chunk_type: "bKGD",
+                found: data.len(),
+            }
+            .into()),
+        }
+    }
+
+    fn to_data(&self) -> Vec<u8> {
+        match *self {
+            Self::PaletteIndex(index) => vec![index],
+            Self::Gray(gray) => gray.to_be_bytes().to_vec(),
+            Self::Rgb(r, g, b) => [r, g, b].iter().flat_map(|c| c.to_be_bytes()).collect(),
+        }
+    }
+}
+
+impl Png {
+    pub fn typed_chunk<T: TypedChunk>(&self) -> Result<Option<T>> {
+        self.chunks()
+            .iter()
+            .find(|c| *c.chunk_type() == T::CHUNK_TYPE)
+            .map(|c| T::from_data(c.chunk_data()))
+            .transpose()
+    }
+
+    pub fn set_typed_chunk<T: TypedChunk>(&mut self, value: &T) {
+        let chunk = value.to_chunk();
+        match self
+            .chunks_mut()
+            .iter_mut()
+            .find(|c| *c.chunk_type() == T::CHUNK_TYPE)
+        {
+            Some(existing) => *existing = chunk,
+            None => self.insert_chunk_ordered(chunk),
+        }
+    }
+
+    pub fn physical_dimensions(&self) -> Result<Option<Phys>> {
+        self.typed_chunk()
+    }
+
+    pub fn set_physical_dimensions(&mut self, phys: Phys) {
+        self.set_typed_chunk(&phys)
+    }
+
+    pub fn gamma(&self) -> Result<Option<Gamma>> {
+        self.typed_chunk()
+    }
+
+    pub fn set_gamma(&mut self, gamma: Gamma) {
+        self.set_typed_chunk(&gamma)
+    }
+
+    pub fn rendering_intent(&self) -> Result<Option<RenderingIntent>> {
+        self.typed_chunk()
+    }
+
+    pub fn set_rendering_intent(&mut self, intent: RenderingIntent) {
+        // sRGB and iCCP are mutually exclusive.
+        let _ = self.remove_all_chunks("iCCP");
+        self.set_typed_chunk(&intent)
+    }
+
+    pub fn background(&self) -> Result<Option<Background>> {
+        self.typed_chunk()
+    }
+
+    pub fn set_background(&mut self, background: Background) {
+        self.set_typed_chunk(&background)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::PngBuilder;
+
+    fn testing_png() -> Png {
+        PngBuilder::new()
+            .chunk(Chunk::new(ChunkType::IHDR, vec![0; 13]))
+            .chunk(Chunk::new(ChunkType::sRGB, vec![0]))
+            .chunk(Chunk::new(ChunkType::gAMA, vec![0, 0, 177, 143]))
+            .chunk(Chunk::new(
+                ChunkType::pHYs,
+                vec![0, 0, 14, 195, 0, 0, 14, 195, 1],
+            ))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_read_standard_chunks() {
+        let png = testing_png();
+
+        assert_eq!(
+            png.rendering_intent().unwrap(),
+            Some(RenderingIntent::Perceptual)
+        );
+        assert_eq!(png.gamma().unwrap(), Some(Gamma(45455)));
+        assert!((png.gamma().unwrap().unwrap().as_f64() - 0.45455).abs() < 1e-9);
+
+        let phys = png.physical_dimensions().unwrap().unwrap();
+        assert_eq!(phys.pixels_per_unit_x, 3779);
+        assert_eq!(phys.unit, PhysUnit::Meter);
+        assert_eq!(phys.dpi().unwrap().0.round(), 96.0);
+        assert_eq!(png.background().unwrap(), None);
+    }
+
+    #[test]
+    fn test_set_chunks() {
+        let mut png = testing_png();
+        png.set_physical_dimensions(Phys::from_dpi(300));
+        png.set_gamma(Gamma::from_f64(1.0 / 2.2));
+        png.set_background(Background::Rgb(255, 0, 65535));
+
+        let png = Png::try_from(png.as_bytes().as_slice()).unwrap();
+        assert_eq!(
+            png.physical_dimensions()
+                .unwrap()
+                .unwrap()
+                .pixels_per_unit_x,
+            11811
+        );
+        assert_eq!(png.gamma().unwrap(), Some(Gamma(45455)));
+        assert_eq!(
+            png.background().unwrap(),
+            Some(Background::Rgb(255, 0, 65535))
+        );
+        assert_eq!(png.chunks().len(), 6);
+        assert_eq!(png.chunks()[4].chunk_type(), &ChunkType::bKGD);
+    }
+
+    #[test]
+    fn test_set_rendering_intent_removes_iccp() {
+        let mut png = testing_png();
+        png.set_icc_profile("Profile", vec![1, 2, 3]).unwrap();
+        assert!(png.rendering_intent().unwrap().is_none());
+
+        png.set_rendering_intent(RenderingIntent::Saturation);
+        assert!(png.icc_profile().unwrap().is_none());
+        assert_eq!(
+            png.rendering_intent().unwrap(),
+            Some(RenderingIntent::Saturation)
+        );
+    }
+
+    #[test]
+    fn test_background_variants() {
+        for background in [
+            Background::PaletteIndex(3),
+            Background::Gray(1000),
+            Background::Rgb(1, 2, 3),
+        ] {
+            assert_eq!(
+                Background::from_data(&background.to_data()).unwrap(),
+                background
+            );
+        }
+        assert!(Background::from_data(&[0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_invalid_data() {
+        assert_eq!(
+            Phys::from_data(&[0; 8])
+                .err()
+                .unwrap()
+                .downcast::<AncillaryError>()
+                .unwrap(),
+            AncillaryError::InvalidLength {
+                chunk_type: "pHYs",
+                found: 8
+            }
+        );
+        assert_eq!(
+            RenderingIntent::from_data(&[4])
+                .err()
+                .unwrap()
+                .downcast::<AncillaryError>()
+                .unwrap(),
+            AncillaryError::InvalidValue {
+                chunk_type: "sRGB",
+                found: 4
+            }
+        );
+    }
+}
@@ -0,0 +1,453 @@
+use crate::{
+    ancillary::{fixed, AncillaryError, TypedChunk},
+    builder::{canonical_rank, PngBuilder},
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    ihdr::Ihdr,
+    png::Png,
+};
+use anyhow::Result;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ApngError {
+    #[error("Expected {expected:?} frames, found {found:?}.")]
+    FrameCountMismatch { expected: u32, found: u32 },
+    #[error("Expected sequence number {expected:?}, got {found:?}.")]
+    SequenceOutOfOrder { expected: u32, found: u32 },
+    #[error("fdAT chunk without a preceding fcTL chunk.")]
+    MissingFrameControl,
+    #[error("The image has no acTL chunk.")]
+    NotAnimated,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnimationControl {
+    pub num_frames: u32,
+    pub num_plays: u32,
+}
+
+impl TypedChunk for AnimationControl {
+    const CHUNK_TYPE: ChunkType = ChunkType::acTL;
+
+    fn from_data(data: &[u8]) -> Result<Self> {
+        let bytes: [u8; 8] = fixed("acTL", data)?;
+
+        Ok(Self {
+            num_frames: u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            num_plays: u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+        })
+    }
+
+    fn to_data(&self) -> Vec<u8> {
+        self.num_frames
+            .to_be_bytes()
+            .into_iter()
+            .chain(self.num_plays.to_be_bytes())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisposeOp {
+    None = 0,
+    Background = 1,
+    Previous = 2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendOp {
+    Source = 0,
+    Over = 1,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameControl {
+    pub sequence_number: u32,
+    pub width: u32,
+    pub height: u32,
+    pub x_offset: u32,
+    pub y_offset: u32,
+    pub delay_num: u16,
+    pub delay_den: u16,
+    pub dispose_op: DisposeOp,
+    pub blend_op: BlendOp,
+}
+
+impl FrameControl {
+    pub fn delay(&self) -> Duration {
+        // A zero denominator means the delay is given in hundredths of a second.
+        let den = match self.delay_den {
+            0 => 100,
+            den => den,
+        };
+
+        Duration::from_secs_f64(self.delay_num as f64 / den as f64)
+    }
+}
+
+impl TypedChunk for FrameControl {
+    const CHUNK_TYPE: ChunkType = ChunkType::fcTL;
+
+    fn from_data(data: &[u8]) -> Result<Self> {
+        let bytes: [u8; 26] = fixed("fcTL", data)?;
+        let u32_at =
+            |i: usize| u32::from_be_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+
+        let dispose_op = match bytes[24] {
+            0 => DisposeOp::None,
+            1 => DisposeOp::Background,
+            2 => DisposeOp::Previous,
+            found => {
+                return Err(AncillaryError::InvalidValue {
+                    chunk_type: "fcTL",
+                    found,
+                }
+                .into())
+            }
+        };
+        let blend_op = match bytes[25] {
+            0 => BlendOp::Source,
+            1 => BlendOp::Over,
+            found => {
+                return Err(AncillaryError::InvalidValue {
+                    chunk_type: "fcTL",
+                    found,
+                }
+                .into())
+            }
+        };
+
+        Ok(Self {
+            sequence_number: u32_at(0),
+            width: u32_at(4),
+            height: u32_at(8),
+            x_offset: u32_at(12),
+            y_offset: u32_at(16),
+            delay_num: u16::from_be_bytes([bytes[20], bytes[21]]),
+            delay_den: u16::from_be_bytes([bytes[22], bytes[23]]),
+            dispose_op,
+            blend_op,
+        })
+    }
+
+    fn to_data(&self) -> Vec<u8> {
+        [
+            self.sequence_number,
+            self.width,
+            self.height,
+            self.x_offset,
+            self.y_offset,
+        ]
+        .iter()
+        .flat_map(|v| v.to_be_bytes())
+        .chain(self.delay_num.to_be_bytes())
+        .chain(self.delay_den.to_be_bytes())
+        .chain([self.dispose_op as u8, self.blend_op as u8])
+        .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame<'a> {
+    pub control: FrameControl,
+    pub data: Vec<&'a [u8]>,
+    pub is_default_image: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Animation<'a> {
+    pub num_plays: u32,
+    pub frames: Vec<Frame<'a>>,
+}
+
+impl Animation<'_> {
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn loops_forever(&self) -> bool {
+        self.num_plays == 0
+    }
+
+    pub fn delays(&self) -> Vec<Duration> {
+        self.frames.iter().map(|f| f.control.delay()).collect()
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.delays().into_iter().sum()
+    }
+}
+
+impl Png {
+    pub fn is_animated(&self) -> bool {
+        self.chunks()
+            .iter()
+            .any(|c| *c.chunk_type() == ChunkType::acTL)
+    }
+
+    pub fn animation(&self) -> Result<Option<Animation<'_>>> {
+        let Some(control) = self.typed_chunk::<AnimationControl>()? else {
+            return Ok(None);
+        };
+
+        let mut frames: Vec<Frame> = Vec::new();
+        let mut sequence_number = 0;
+        let mut seen_idat = false;
+
+        for chunk in self.chunks() {
+            let data = chunk.chunk_data();
+            match *chunk.chunk_type() {
+                ChunkType::fcTL => {
+                    let frame_control = FrameControl::from_data(data)?;
+                    check_sequence(sequence_number, frame_control.sequence_number)?;
+                    sequence_number += 1;
+
+                    frames.push(Frame {
+                        control: frame_control,
+                        data: Vec::new(),
+                        is_default_image: !seen_idat,
+                    });
+                }
+                ChunkType::IDAT => {
+                    // The default image is only part of the animation when an fcTL precedes it.
+                    seen_idat = true;
+                    if let Some(frame) = frames.last_mut().filter(|f| f.is_default_image) {
+                        frame.data.push(data);
+                    }
+                }
+                ChunkType::fdAT => {
+                    let found = u32::from_be_bytes(fixed("fdAT", &data[..data.len().min(4)])?);
+                    check_sequence(sequence_number, found)?;
+                    sequence_number += 1;
+
+                    let Some(frame) = frames.last_mut() else {
+                        return Err(ApngError::MissingFrameControl.into());
+                    };
+                    frame.data.push(&data[4..]);
+                }
+                _ => {}
+            }
+        }
+
+        if frames.len() as u32 != control.num_frames {
+            return Err(ApngError::FrameCountMismatch {
+                expected: control.num_frames,
+                found: frames.len() as u32,
+            }
+            .into());
+        }
+
+        Ok(Some(Animation {
+            num_plays: control.num_plays,
+            frames,
+        }))
+    }
+
+    pub fn extract_frames(&self) -> Result<Vec<Png>> {
+        let header = self.header()?;
+        let Some(animation) = self.animation()? else {
+            return Err(ApngError::NotAnimated.into());
+        };
+
+        // Palette, colour space and other ancillary chunks apply to every frame.
+        let shared: Vec<Chunk> = self
+            .chunks()
+            .iter()
+            .filter(|c| (1..=3).contains(&canonical_rank(c.chunk_type())))
+            .filter(|c| *c.chunk_type() != ChunkType::acTL)
+            .cloned()
+            .collect();
+
+        // Frames are exported as stored, without compositing them onto earlier frames.
+        animation
+            .frames
+            .iter()
+            .map(|frame| {
+                let ihdr = Ihdr {
+                    width: frame.control.width,
+                    height: frame.control.height,
+                    ..header
+                };
+                ihdr.validate()?;
+
+                PngBuilder::new()
+                    .chunk(ihdr.into())
+                    .chunks(shared.iter().cloned())
+                    .chunk(Chunk::new(ChunkType::IDAT, frame.data.concat()))
+                    .build()
+            })
+            .collect()
+    }
+}
+
+fn check_sequence(expected: u32, found: u32) -> Result<()> {
+    if expected != found {
+        return Err(ApngError::SequenceOutOfOrder { expected, found }.into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ihdr::ColorType;
+
+    fn frame_control(sequence_number: u32, delay_num: u16) -> Chunk {
+        FrameControl {
+            sequence_number,
+            width: 4,
+            height: 4,
+            x_offset: 0,
+            y_offset: 0,
+            delay_num,
+            delay_den: 0,
+            dispose_op: DisposeOp::None,
+            blend_op: BlendOp::Source,
+        }
+        .to_chunk()
+    }
+
+    fn frame_data(sequence_number: u32, data: &[u8]) -> Chunk {
+        let mut bytes = sequence_number.to_be_bytes().to_vec();
+        bytes.extend(data);
+        Chunk::new(ChunkType::fdAT, bytes)
+    }
+
+    fn testing_png() -> Png {
+        let control = AnimationControl {
+            num_frames: 3,
+            num_plays: 0,
+        };
+
+        Png::from_chunks(vec![
+            Ihdr::new(4, 4, 8, ColorType::Rgb).unwrap().into(),
+            Chunk::new(ChunkType::gAMA, vec![0, 0, 177, 143]),
+            control.to_chunk(),
+            frame_control(0, 10),
+            Chunk::new(ChunkType::IDAT, vec![1, 2]),
+            Chunk::new(ChunkType::IDAT, vec![3]),
+            frame_control(1, 20),
+            frame_data(2, &[4, 5]),
+            frame_control(3, 50),
+            frame_data(4, &[6]),
+            frame_data(5, &[7]),
+            Chunk::new(ChunkType::IEND, vec![]),
+        ])
+    }
+
+    #[test]
+    fn test_frame_control_round_trip() {
+        let chunk = frame_control(7, 3);
+        assert_eq!(chunk.length(), 26);
+
+        let control = FrameControl::from_data(chunk.chunk_data()).unwrap();
+        assert_eq!(control.sequence_number, 7);
+        assert_eq!(control.delay(), Duration::from_millis(30));
+        assert_eq!(control.to_data(), chunk.chunk_data());
+    }
+
+    #[test]
+    fn test_animation() {
+        let png = testing_png();
+        assert!(png.is_animated());
+
+        let animation = png.animation().unwrap().unwrap();
+        assert_eq!(animation.frame_count(), 3);
+        assert!(animation.loops_forever());
+        assert_eq!(animation.duration(), Duration::from_millis(800));
+
+        assert!(animation.frames[0].is_default_image);
+        assert_eq!(animation.frames[0].data, vec![&[1, 2][..], &[3]]);
+        assert!(!animation.frames[1].is_default_image);
+        assert_eq!(animation.frames[1].data, vec![&[4, 5][..]]);
+        assert_eq!(animation.frames[2].data, vec![&[6][..], &[7]]);
+    }
+
+    #[test]
+    fn test_not_animated() {
+        let png = PngBuilder::new()
+            .chunk(Chunk::new(ChunkType::IHDR, vec![0; 13]))
+            .build()
+            .unwrap();
+
+        assert!(!png.is_animated());
+        assert!(png.animation().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_animation_out_of_order() {
+        let mut png = testing_png();
+        png.remove_chunk_at(6).unwrap();
+
+        assert_eq!(
+            png.animation()
+                .err()
+                .unwrap()
+                .downcast::<ApngError>()
+                .unwrap(),
+            ApngError::SequenceOutOfOrder {
+                expected: 1,
+                found: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_animation_frame_count_mismatch() {
+        let mut png = testing_png();
+        png.chunks_mut()[2] = AnimationControl {
+            num_frames: 2,
+            num_plays: 1,
+        }
+        .to_chunk();
+
+        assert_eq!(
+            png.animation()
+                .err()
+                .unwrap()
+                .downcast::<ApngError>()
+                .unwrap(),
+            ApngError::FrameCountMismatch {
+                expected: 2,
+                found: 3
+            }
+        );
+    }
+
+    #[test]
+    fn test_extract_frames() {
+        let frames = testing_png().extract_frames().unwrap();
+        assert_eq!(frames.len(), 3);
+
+        for (frame, data) in frames.iter().zip([vec![1, 2, 3], vec![4, 5], vec![6, 7]]) {
+            let types: Vec<String> = frame
+                .chunks()
+                .iter()
+                .map(|c| c.chunk_type().to_string())
+                .collect();
+            assert_eq!(types, vec!["IHDR", "gAMA", "IDAT", "IEND"]);
+            assert_eq!(frame.header().unwrap().width, 4);
+            assert_eq!(frame.chunk_by_type("IDAT").unwrap().chunk_data(), data);
+        }
+    }
+
+    #[test]
+    fn test_extract_frames_not_animated() {
+        let png = PngBuilder::new()
+            .chunk(Ihdr::new(4, 4, 8, ColorType::Rgb).unwrap().into())
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            png.extract_frames()
+                .err()
+                .unwrap()
+                .downcast::<ApngError>()
+                .unwrap(),
+            ApngError::NotAnimated
+        );
+    }
+}
@@ -0,0 +1,1153 @@
+use crate::manifest::Step;
+use crate::output::{ColorChoice, ListFormat, ReportFormat};
+use crate::status::EXIT_STATUS_HELP;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use pngme::{
+    chunk_type::ChunkType, compression::Compression, cover::CoverStyle, render::DataFormat,
+    stego::StegoMethod,
+};
+use std::{path::PathBuf, str::FromStr};
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "pngme",
+    version,
+    about = "Hide messages and files inside PNG images",
+    after_help = EXIT_STATUS_HELP
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+
+    #[command(flatten)]
+    pub global: GlobalArgs,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct GlobalArgs {
+    /// Log what pngme is doing to standard error, -vv for every chunk; RUST_LOG takes precedence
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+    /// Print nothing but warnings and errors, for scripts that only check the exit status
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+    /// Update the tIME chunk whenever a command adds or removes chunks
+    #[arg(long, global = true)]
+    pub touch_time: bool,
+    /// Skip safety checks, such as refusing to write binary data to a terminal or to merge chunks
+    /// that are not safe to copy
+    #[arg(long, global = true)]
+    pub force: bool,
+    /// Report what would change without writing anything
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+    /// Record each change to a file in its history chunk, shown by pngme history
+    #[arg(long, global = true)]
+    pub audit: bool,
+    /// Name recorded as the author of changes with --audit
+    #[arg(long, global = true, value_name = "NAME", env = "PNGME_AUDIT_SIGNER")]
+    pub audit_signer: Option<String>,
+    /// Keep a copy of a file before overwriting it, named with this suffix
+    #[arg(long, global = true, value_name = "SUFFIX", num_args = 0..=1, default_missing_value = ".bak")]
+    pub backup: Option<String>,
+    /// Files to process at once in batches, 0 for one per core (the default for scan)
+    #[arg(long, global = true, value_name = "N")]
+    pub jobs: Option<usize>,
+    /// Never show progress bars, even on a terminal
+    #[arg(long, global = true)]
+    pub no_progress: bool,
+    /// Fail rather than buffer more than this many bytes reading a PNG, for untrusted input
+    #[arg(long, global = true, value_name = "BYTES")]
+    pub max_memory: Option<usize>,
+    /// Read defaults from this file instead of ~/.config/pngme/config.toml
+    #[arg(long, global = true, value_name = "PATH", env = "PNGME_CONFIG")]
+    pub config: Option<PathBuf>,
+    // The command being run, such as "text set", recorded by --audit.
+    #[arg(skip)]
+    pub operation: String,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Hide a message or file in a PNG, or in APP15 segments of a JPEG
+    Encode(EncodeArgs),
+    /// Reveal a hidden message or file
+    Decode(DecodeArgs),
+    /// Describe a hidden payload and check its integrity without revealing it
+    Report(ReportArgs),
+    /// Check that a hidden payload matches a reference file or digest, for round-trip tests
+    VerifyPayload(VerifyPayloadArgs),
+    /// Rewrite payloads stored as raw bytes, from before envelopes existed, into envelopes
+    Migrate(MigrateArgs),
+    /// Remove the first chunk of a type
+    Remove(RemoveArgs),
+    /// Remove ancillary chunks such as text and timestamps, keeping the image
+    Strip(StripArgs),
+    /// Remove repeated copies of identical ancillary and private chunks
+    Dedupe(DedupeArgs),
+    /// Reorder chunks into the order the PNG specification recommends
+    Canonicalize(CanonicalizeArgs),
+    /// Remove unregistered chunks and data after IEND, destroying hidden payloads
+    Sanitize(SanitizeArgs),
+    /// Summarize the image, its metadata and its chunks
+    Info(InfoArgs),
+    /// List the chunks of a PNG
+    Print(PrintArgs),
+    /// Exit with status 0 if a hidden chunk is present and 2 if not, printing nothing
+    Check(CheckArgs),
+    /// Count chunks and their bytes by type, totalled across every matching file
+    Stats(StatsArgs),
+    /// Search the data of every chunk for a regular expression or byte pattern
+    Grep(GrepArgs),
+    /// Dump the data of a chunk as hex and ASCII
+    Hexdump(HexdumpArgs),
+    /// Show how many payload bytes each embedding method can hold, after the envelope around them
+    Capacity(CapacityArgs),
+    /// Generate a cover image for a payload, reporting how much it can hold
+    InitCarrier(InitCarrierArgs),
+    /// Look for signs of hidden data
+    Detect(DetectArgs),
+    /// List, read and edit text metadata such as Title, Author and Comment
+    Text(TextArgs),
+    /// List, read and edit key-value metadata kept together in one private chunk
+    Kv(KvArgs),
+    /// Show, export, import or remove EXIF metadata
+    Exif(ExifArgs),
+    /// Export, import or remove the embedded ICC color profile
+    Icc(IccArgs),
+    /// Write the data of a chunk to a file, for editing with other tools
+    Extract(ExtractArgs),
+    /// Add a chunk whose data is read from a file
+    Inject(InjectArgs),
+    /// Copy chunks of some types from another PNG
+    Merge(MergeArgs),
+    /// Show the chunks added, removed or modified between two PNGs
+    Diff(DiffArgs),
+    /// Check a PNG against the specification, for gating image assets in CI
+    Lint(LintArgs),
+    /// Show the changes recorded with --audit
+    History(HistoryArgs),
+    /// Fix bad CRCs and lengths, trailing data and a missing IEND
+    Repair(RepairArgs),
+    /// Search a directory tree for PNGs with matching or suspicious chunks
+    Scan(ScanArgs),
+    /// Run a command on every PNG that appears or changes in a directory
+    Watch(WatchArgs),
+    /// Spread one payload across several carrier PNGs or JPEGs
+    SplitEncode(SplitEncodeArgs),
+    /// Reassemble a payload spread across carrier PNGs or JPEGs
+    JoinDecode(JoinDecodeArgs),
+    /// Show animation details or export the frames of an animated PNG
+    Apng(ApngArgs),
+    /// Export the frames of an animated PNG as standalone PNGs, same as apng frames
+    #[command(hide = true)]
+    Frames(FramesArgs),
+    /// Generate an Ed25519 key pair for signing
+    Keygen(KeygenArgs),
+    /// Sign every chunk of a PNG with an Ed25519 key
+    Sign(SignArgs),
+    /// Check the whole-file signature of a PNG
+    Verify(VerifyArgs),
+    /// Serve encode and decode over HTTP
+    #[cfg(feature = "server")]
+    Serve(ServeArgs),
+    /// Run the edits listed in a manifest, writing each file only if all its steps succeed
+    Apply(ApplyArgs),
+    /// Run a sequence of edits on a file, reading and writing it only once
+    Pipe(PipeArgs),
+    /// Print a shell completion script
+    Completions(CompletionsArgs),
+    /// Print the man page, or write pages for every command to a directory
+    Man(ManArgs),
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct EncodeArgs {
+    /// PNG to hide the payload in, a glob pattern, or - for standard input
+    pub file: PathBuf,
+    /// Chunk type to store the payload in (ignored with --obfuscate)
+    pub chunk_type: ChunkType,
+    /// Message to hide, or OUTPUT when --file or --from-clipboard is given
+    #[cfg_attr(
+        feature = "clipboard",
+        arg(required_unless_present_any = ["payload_file", "from_clipboard"])
+    )]
+    #[cfg_attr(
+        not(feature = "clipboard"),
+        arg(required_unless_present = "payload_file")
+    )]
+    pub message: Option<String>,
+    /// Where to write the result, defaults to overwriting FILE, - for standard output
+    pub output: Option<PathBuf>,
+
+    /// Hide the contents of a file instead of a message, - for standard input
+    #[arg(long = "file", value_name = "PATH")]
+    pub payload_file: Option<PathBuf>,
+    /// Hide the text on the clipboard, keeping it out of shell history and temporary files
+    #[cfg(feature = "clipboard")]
+    #[arg(long, conflicts_with = "payload_file")]
+    pub from_clipboard: bool,
+    /// How the message or file is encoded: utf8, hex, base64 or ascii escapes
+    #[arg(long, value_name = "ENCODING", default_value = "utf8")]
+    pub input_encoding: DataFormat,
+    /// Embedding method: chunk, lsb, alpha or alpha-opaque
+    #[arg(long, default_value = "chunk")]
+    pub method: StegoMethod,
+    /// Scatter pixel embedding with a permutation keyed by the passphrase
+    #[arg(long)]
+    pub scatter: bool,
+    /// Derive the chunk type and position from the passphrase
+    #[arg(long, conflicts_with = "method")]
+    pub obfuscate: bool,
+    /// Add Reed-Solomon parity so the payload survives minor corruption
+    #[arg(long)]
+    pub fec: bool,
+    /// Replace an existing chunk of the type in place instead of adding another
+    #[arg(long)]
+    pub replace: bool,
+    /// What to do when the chunk type is already present
+    #[arg(
+        long,
+        value_name = "POLICY",
+        env = "PNGME_ON_DUPLICATE",
+        default_value = "append"
+    )]
+    pub on_duplicate: DuplicatePolicy,
+    /// Hide the payload in a deniable container opened by the passphrase
+    #[arg(long, conflicts_with_all = ["encrypt", "recipients"])]
+    pub deniable: bool,
+    /// Harmless message revealed by a second, decoy passphrase
+    #[arg(long, value_name = "MESSAGE", requires = "deniable")]
+    pub decoy: Option<String>,
+    /// Harmless file revealed by a second, decoy passphrase
+    #[arg(
+        long,
+        value_name = "PATH",
+        requires = "deniable",
+        conflicts_with = "decoy"
+    )]
+    pub decoy_file: Option<PathBuf>,
+    /// Read the decoy passphrase from the first line of this file instead of asking for it
+    #[arg(long, value_name = "PATH", requires = "deniable")]
+    pub decoy_passphrase_file: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub envelope: EnvelopeArgs,
+    #[command(flatten)]
+    pub passphrase: PassphraseArgs,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DuplicatePolicy {
+    /// Add another chunk after warning about the duplicate
+    Append,
+    /// Update the existing chunk in place
+    Replace,
+    /// Refuse to encode
+    Error,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct EnvelopeArgs {
+    /// Compress the payload: none, deflate or zstd
+    #[arg(long, value_name = "ALGORITHM", default_missing_value = "deflate", num_args = 0..=1)]
+    pub compress: Option<Compression>,
+    /// Encrypt the payload with a passphrase (Argon2id + AES-256-GCM)
+    #[arg(long, conflicts_with = "recipients")]
+    pub encrypt: bool,
+    /// Encrypt to an age or SSH public key, may be repeated
+    #[arg(long = "recipient", value_name = "KEY")]
+    pub recipients: Vec<String>,
+    /// Authenticate the payload with an HMAC keyed by the passphrase
+    #[arg(long, conflicts_with = "sign")]
+    pub hmac: bool,
+    /// Sign the payload with an Ed25519 secret key file
+    #[arg(long, value_name = "KEY_FILE")]
+    pub sign: Option<PathBuf>,
+    /// Encrypt to a GPG recipient, may be repeated
+    #[cfg(feature = "gpg")]
+    #[arg(long = "gpg-recipient", value_name = "USER_ID", conflicts_with_all = ["encrypt", "recipients"])]
+    pub gpg_recipients: Vec<String>,
+    /// Sign the payload with GPG, optionally with a specific key
+    #[cfg(feature = "gpg")]
+    #[arg(long, value_name = "USER_ID", num_args = 0..=1, default_missing_value = "")]
+    pub gpg_sign: Option<String>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct DecodeArgs {
+    /// PNG holding the payload, a glob pattern, or - for standard input
+    pub file: PathBuf,
+    /// Chunk type the payload is stored in (ignored with --obfuscate)
+    pub chunk_type: ChunkType,
+
+    /// Embedding method: chunk, lsb, alpha or alpha-opaque
+    #[arg(long, default_value = "chunk")]
+    pub method: StegoMethod,
+    /// The payload was scattered with --scatter
+    #[arg(long)]
+    pub scatter: bool,
+    /// The chunk type and position were derived from the passphrase
+    #[arg(long, conflicts_with = "method")]
+    pub obfuscate: bool,
+    /// The payload is in a deniable container
+    #[arg(long)]
+    pub deniable: bool,
+    /// Decode every chunk of the type, prefixed with its index and offset
+    #[arg(long, conflicts_with_all = ["method", "scatter", "obfuscate"])]
+    pub all: bool,
+    /// Decode the Nth chunk of the type, counting from 0, instead of the first
+    #[arg(long, value_name = "N", conflicts_with_all = ["method", "scatter", "obfuscate", "all"])]
+    pub index: Option<usize>,
+    /// Copy the payload to the clipboard instead of printing it, it must be text
+    #[cfg(feature = "clipboard")]
+    #[arg(long, conflicts_with_all = ["all", "output_dir", "format"])]
+    pub to_clipboard: bool,
+
+    #[command(flatten)]
+    pub output: OutputArgs,
+    #[command(flatten)]
+    pub passphrase: PassphraseArgs,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ReportArgs {
+    /// PNG or JPEG holding the payload, a glob pattern, or - for standard input
+    pub file: PathBuf,
+    /// Chunk type the payload is stored in
+    pub chunk_type: ChunkType,
+
+    /// Embedding method: chunk, lsb, alpha or alpha-opaque
+    #[arg(long, default_value = "chunk")]
+    pub method: StegoMethod,
+    /// Identity file for payloads encrypted to recipients, may be repeated
+    #[arg(long = "identity", value_name = "PATH")]
+    pub identities: Vec<PathBuf>,
+    /// Check an Ed25519 signature against this public key file
+    #[arg(long, value_name = "KEY_FILE")]
+    pub verify: Option<PathBuf>,
+    #[arg(long, value_enum, default_value_t)]
+    pub format: ReportFormat,
+
+    #[command(flatten)]
+    pub passphrase: PassphraseArgs,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct VerifyPayloadArgs {
+    /// PNG or JPEG holding the payload, a glob pattern, or - for standard input
+    pub file: PathBuf,
+    /// Chunk type the payload is stored in (ignored with --obfuscate)
+    pub chunk_type: ChunkType,
+    /// File the payload must match byte for byte, - for standard input
+    #[arg(long, value_name = "PATH", required_unless_present = "sha256")]
+    pub against: Option<PathBuf>,
+    /// SHA-256 digest in hex the payload must have
+    #[arg(long, value_name = "HEX", conflicts_with = "against")]
+    pub sha256: Option<String>,
+
+    /// Embedding method: chunk, lsb, alpha or alpha-opaque
+    #[arg(long, default_value = "chunk")]
+    pub method: StegoMethod,
+    /// The payload was scattered with --scatter
+    #[arg(long)]
+    pub scatter: bool,
+    /// The chunk type and position were derived from the passphrase
+    #[arg(long, conflicts_with = "method")]
+    pub obfuscate: bool,
+    /// The payload is in a deniable container
+    #[arg(long)]
+    pub deniable: bool,
+    /// Check the Nth chunk of the type, counting from 0, instead of the first
+    #[arg(long, value_name = "N", conflicts_with_all = ["method", "scatter", "obfuscate"])]
+    pub index: Option<usize>,
+    /// Identity file for payloads encrypted to recipients, may be repeated
+    #[arg(long = "identity", value_name = "PATH")]
+    pub identities: Vec<PathBuf>,
+    /// Refuse the payload unless it is signed by this Ed25519 public key file
+    #[arg(long, value_name = "KEY_FILE")]
+    pub verify: Option<PathBuf>,
+    #[arg(long, value_enum, default_value_t)]
+    pub format: ReportFormat,
+
+    #[command(flatten)]
+    pub passphrase: PassphraseArgs,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct MigrateArgs {
+    /// PNG holding the payloads, a glob pattern, or - for standard input
+    pub file: PathBuf,
+    /// Chunk type the payloads are stored in
+    pub chunk_type: ChunkType,
+    /// Where to write the result, defaults to overwriting FILE, - for standard output
+    pub output: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub envelope: EnvelopeArgs,
+    #[command(flatten)]
+    pub passphrase: PassphraseArgs,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct OutputArgs {
+    /// Write the payload to this directory under its recorded filename
+    #[arg(long, value_name = "DIR")]
+    pub output_dir: Option<PathBuf>,
+    /// How to print the payload: utf8, hex, base64, ascii or json
+    #[arg(long, visible_alias = "output-encoding", default_value = "utf8")]
+    pub format: PayloadFormat,
+    /// Identity file for payloads encrypted to recipients, may be repeated
+    #[arg(long = "identity", value_name = "PATH")]
+    pub identities: Vec<PathBuf>,
+    /// Refuse the payload unless it is signed by this Ed25519 public key file
+    #[arg(long, value_name = "KEY_FILE")]
+    pub verify: Option<PathBuf>,
+    #[command(flatten)]
+    pub preview: PreviewArgs,
+}
+
+// Payloads print through a DataFormat, or as JSON along with their metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadFormat {
+    Data(DataFormat),
+    Json,
+}
+
+impl FromStr for PayloadFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.eq_ignore_ascii_case("json") {
+            true => Ok(Self::Json),
+            false => DataFormat::from_str(s).map(Self::Data),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct PassphraseArgs {
+    /// Read the passphrase from the first line of this file, such as /dev/fd/3
+    #[arg(long, value_name = "PATH")]
+    pub passphrase_file: Option<PathBuf>,
+    /// Store and look up the passphrase in the OS keyring under this profile
+    #[cfg(feature = "keyring")]
+    #[arg(long, value_name = "PROFILE", num_args = 0..=1, default_missing_value = pngme::keyring::DEFAULT_PROFILE)]
+    pub use_keyring: Option<String>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct RemoveArgs {
+    pub file: PathBuf,
+    pub chunk_type: ChunkType,
+    /// Remove the Nth chunk of the type, counting from 0, instead of the first
+    #[arg(long, value_name = "N")]
+    pub index: Option<usize>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct StripArgs {
+    pub file: PathBuf,
+    /// Keep ancillary chunks of these types, comma separated
+    #[arg(long, value_name = "TYPES", value_delimiter = ',')]
+    pub keep: Vec<ChunkType>,
+    /// Where to write the result, defaults to overwriting FILE, - for standard output
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct DedupeArgs {
+    pub file: PathBuf,
+    /// Where to write the result, defaults to overwriting FILE, - for standard output
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct CanonicalizeArgs {
+    pub file: PathBuf,
+    /// Where to write the result, defaults to overwriting FILE, - for standard output
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct SanitizeArgs {
+    pub file: PathBuf,
+    /// Also randomize the low bit of every pixel sample, which changes the image imperceptibly
+    #[arg(long)]
+    pub randomize_lsb: bool,
+    /// Where to write the result, defaults to overwriting FILE, - for standard output
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct InfoArgs {
+    pub file: PathBuf,
+    #[arg(long, value_enum, default_value_t)]
+    pub format: ReportFormat,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct PrintArgs {
+    pub file: PathBuf,
+    #[arg(long, value_enum, default_value_t)]
+    pub format: ListFormat,
+    /// Color chunk types by whether they are critical, ancillary or private
+    #[arg(long, value_enum, default_value_t)]
+    pub color: ColorChoice,
+    #[command(flatten)]
+    pub preview: PreviewArgs,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct PreviewArgs {
+    /// Summarize data longer than this as its first and last bytes, half each
+    #[arg(long, value_name = "BYTES")]
+    pub max_preview: Option<usize>,
+    /// Show data in full, however long
+    #[arg(long, conflicts_with = "max_preview")]
+    pub full: bool,
+}
+
+impl PreviewArgs {
+    // How many bytes of data to show, None for all of it.
+    pub fn limit(&self, default: usize) -> Option<usize> {
+        match self.full {
+            true => None,
+            false => Some(self.max_preview.unwrap_or(default)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct CheckArgs {
+    pub file: PathBuf,
+    /// Look for a chunk of this type
+    #[arg(
+        long = "type",
+        value_name = "TYPE",
+        required_unless_present = "any_hidden"
+    )]
+    pub chunk_type: Option<ChunkType>,
+    /// Look for any chunk whose type is not defined by the PNG specification
+    #[arg(long, conflicts_with = "chunk_type")]
+    pub any_hidden: bool,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct GrepArgs {
+    /// Regular expression, matched against the raw bytes so binary data can be searched too
+    pub pattern: String,
+    #[arg(required = true)]
+    pub files: Vec<PathBuf>,
+    /// Read PATTERN as hex bytes, such as "89 50 4e 47", instead of a regular expression
+    #[arg(long)]
+    pub hex: bool,
+    /// Only search chunks of these types, comma separated
+    #[arg(long, value_name = "TYPES", value_delimiter = ',')]
+    pub types: Vec<ChunkType>,
+    #[arg(long, value_enum, default_value_t)]
+    pub format: ListFormat,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct StatsArgs {
+    pub file: PathBuf,
+    #[arg(long, value_enum, default_value_t)]
+    pub format: ListFormat,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct HexdumpArgs {
+    pub file: PathBuf,
+    pub chunk_type: ChunkType,
+    /// Dump the Nth chunk of the type, counting from 0, instead of the first
+    #[arg(long, value_name = "N")]
+    pub index: Option<usize>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct CapacityArgs {
+    pub file: PathBuf,
+    /// Only report this method
+    #[arg(long)]
+    pub method: Option<StegoMethod>,
+    /// Leave room for this file's name and MIME type rather than a message's
+    #[arg(long = "file", value_name = "PATH")]
+    pub payload_file: Option<PathBuf>,
+    #[arg(long, value_enum, default_value_t)]
+    pub format: ListFormat,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct InitCarrierArgs {
+    /// Width and height in pixels
+    #[arg(long, value_name = "WIDTHxHEIGHT", default_value = "1920x1080")]
+    pub size: Size,
+    /// noise, gradient or solid; noise hides pixel embedding, the others compress better
+    #[arg(long, default_value = "noise")]
+    pub style: CoverStyle,
+    /// Generate the same image every time for the same seed
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// Where to write the image, - for standard output
+    #[arg(short, long)]
+    pub output: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Size {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for Size {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let parsed = s
+            .split_once(['x', 'X'])
+            .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)));
+        match parsed {
+            Some((width, height)) => Ok(Self { width, height }),
+            None => anyhow::bail!("Expected a size such as 1920x1080, got {:?}", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct DetectArgs {
+    pub file: PathBuf,
+    #[arg(long, value_enum, default_value_t)]
+    pub format: ReportFormat,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct TextArgs {
+    #[command(subcommand)]
+    pub command: TextCommand,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum TextCommand {
+    /// List the text entries of a PNG
+    List(TextListArgs),
+    /// Print the text stored under a keyword
+    Get(TextGetArgs),
+    /// Store text under a keyword, choosing tEXt, zTXt or iTXt to fit it
+    Set(TextSetArgs),
+    /// Remove every entry with a keyword
+    Remove(TextRemoveArgs),
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct TextListArgs {
+    pub file: PathBuf,
+    #[arg(long, value_enum, default_value_t)]
+    pub format: ListFormat,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct TextGetArgs {
+    pub file: PathBuf,
+    pub keyword: String,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct TextSetArgs {
+    pub file: PathBuf,
+    pub keyword: String,
+    pub value: String,
+    /// Where to write the result, defaults to overwriting FILE, - for standard output
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct TextRemoveArgs {
+    pub file: PathBuf,
+    pub keyword: String,
+    /// Where to write the result, defaults to overwriting FILE, - for standard output
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct KvArgs {
+    #[command(subcommand)]
+    pub command: KvCommand,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum KvCommand {
+    /// List the keys and values in the order they were first set
+    List(KvListArgs),
+    /// Print the value stored under a key
+    Get(KvGetArgs),
+    /// Store a value under a key, replacing any value it had
+    Set(KvSetArgs),
+    /// Remove a key
+    Remove(KvRemoveArgs),
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct KvListArgs {
+    pub file: PathBuf,
+    #[arg(long, value_enum, default_value_t)]
+    pub format: ListFormat,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct KvGetArgs {
+    pub file: PathBuf,
+    pub key: String,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct KvSetArgs {
+    pub file: PathBuf,
+    pub key: String,
+    pub value: String,
+    /// Where to write the result, defaults to overwriting FILE, - for standard output
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct KvRemoveArgs {
+    pub file: PathBuf,
+    pub key: String,
+    /// Where to write the result, defaults to overwriting FILE, - for standard output
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ExifArgs {
+    #[command(subcommand)]
+    pub command: ExifCommand,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum ExifCommand {
+    /// Decode common tags such as the camera, orientation and timestamps
+    Show(ExifShowArgs),
+    /// Write the EXIF data as a TIFF file
+    Export(ExifExportArgs),
+    /// Replace the EXIF data with a TIFF file
+    Import(ExifImportArgs),
+    /// Remove the EXIF data
+    Remove(ExifRemoveArgs),
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ExifShowArgs {
+    pub file: PathBuf,
+    #[arg(long, value_enum, default_value_t)]
+    pub format: ListFormat,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ExifExportArgs {
+    pub file: PathBuf,
+    /// Where to write the EXIF data, defaults to standard output
+    #[arg(short, long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ExifImportArgs {
+    pub file: PathBuf,
+    /// TIFF file holding the EXIF data, - for standard input
+    pub exif_file: PathBuf,
+    /// Where to write the result, defaults to overwriting FILE, - for standard output
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ExifRemoveArgs {
+    pub file: PathBuf,
+    /// Where to write the result, defaults to overwriting FILE, - for standard output
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct IccArgs {
+    #[command(subcommand)]
+    pub command: IccCommand,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum IccCommand {
+    /// Write the color profile to an .icc file
+    Export(IccExportArgs),
+    /// Embed a color profile, replacing any sRGB chunk
+    Import(IccImportArgs),
+    /// Remove the color profile
+    Remove(IccRemoveArgs),
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct IccExportArgs {
+    pub file: PathBuf,
+    /// Where to write the profile, defaults to standard output
+    #[arg(short, long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct IccImportArgs {
+    pub file: PathBuf,
+    /// ICC profile file, - for standard input
+    pub profile: PathBuf,
+    /// Name stored with the profile
+    #[arg(long, default_value = "ICC Profile")]
+    pub name: String,
+    /// Where to write the result, defaults to overwriting FILE, - for standard output
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct IccRemoveArgs {
+    pub file: PathBuf,
+    /// Where to write the result, defaults to overwriting FILE, - for standard output
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ExtractArgs {
+    pub file: PathBuf,
+    pub chunk_type: ChunkType,
+    /// Extract the Nth chunk of the type, counting from 0, instead of the first
+    #[arg(long, value_name = "N")]
+    pub index: Option<usize>,
+    /// Write the whole chunk, with its length, type and CRC, instead of only its data
+    #[arg(long)]
+    pub with_header: bool,
+    /// Where to write the chunk, defaults to standard output
+    #[arg(short, long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct InjectArgs {
+    pub file: PathBuf,
+    pub chunk_type: ChunkType,
+    /// Read the chunk data from this file, - for standard input
+    #[arg(long, value_name = "PATH")]
+    pub from: PathBuf,
+    /// The file holds a whole chunk as written by extract --with-header
+    #[arg(long)]
+    pub with_header: bool,
+    /// Replace any chunks of the type instead of adding another
+    #[arg(long)]
+    pub replace: bool,
+    /// Where to write the result, defaults to overwriting FILE, - for standard output
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct MergeArgs {
+    /// The PNG to copy chunks into
+    pub file: PathBuf,
+    /// The PNG to copy chunks from
+    #[arg(long, value_name = "PATH")]
+    pub from: PathBuf,
+    /// Copy chunks of these types, comma separated
+    #[arg(long, value_name = "TYPES", value_delimiter = ',', required = true)]
+    pub types: Vec<ChunkType>,
+    /// Where to write the result, defaults to overwriting FILE, - for standard output
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct DiffArgs {
+    pub before: PathBuf,
+    pub after: PathBuf,
+    /// Also show a hexdump of the lines that changed in modified chunks
+    #[arg(long)]
+    pub bytes: bool,
+    #[arg(long, value_enum, default_value_t)]
+    pub format: ReportFormat,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct LintArgs {
+    pub file: PathBuf,
+    #[arg(long, value_enum, default_value_t)]
+    pub format: ReportFormat,
+    /// Fail on warnings too
+    #[arg(long)]
+    pub strict: bool,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct HistoryArgs {
+    pub file: PathBuf,
+    #[arg(long, value_enum, default_value_t)]
+    pub format: ListFormat,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ScanArgs {
+    pub dir: PathBuf,
+    /// Report chunks of these types, comma separated
+    #[arg(long, value_name = "TYPES", value_delimiter = ',')]
+    pub types: Vec<ChunkType>,
+    /// Report chunks whose type is not defined by the PNG specification
+    #[arg(long)]
+    pub non_standard: bool,
+    #[arg(long, value_enum, default_value_t)]
+    pub format: ListFormat,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct WatchArgs {
+    pub dir: PathBuf,
+    /// Watch subdirectories too
+    #[arg(long)]
+    pub recursive: bool,
+    /// Command to run, taking the rest of the line; the file replaces {} or follows the command
+    #[arg(
+        long,
+        value_name = "COMMAND",
+        required = true,
+        num_args = 1..,
+        allow_hyphen_values = true
+    )]
+    pub on_new: Vec<String>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct SplitEncodeArgs {
+    /// File to hide
+    pub payload: PathBuf,
+    /// Carrier PNGs, each receives one shard
+    #[arg(long, required = true, num_args = 1..)]
+    pub carriers: Vec<PathBuf>,
+    /// Chunk type to store the shards in
+    #[arg(long, default_value = "ruSt")]
+    pub chunk_type: ChunkType,
+    /// Embedding method: chunk, lsb, alpha or alpha-opaque
+    #[arg(long, default_value = "chunk")]
+    pub method: StegoMethod,
+    /// Write the carriers to this directory instead of overwriting them
+    #[arg(long, value_name = "DIR")]
+    pub output_dir: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub envelope: EnvelopeArgs,
+    #[command(flatten)]
+    pub passphrase: PassphraseArgs,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct JoinDecodeArgs {
+    /// Carrier PNGs, in any order
+    #[arg(required = true)]
+    pub carriers: Vec<PathBuf>,
+    /// Chunk type the shards are stored in
+    #[arg(long, default_value = "ruSt")]
+    pub chunk_type: ChunkType,
+    /// Embedding method: chunk, lsb, alpha or alpha-opaque
+    #[arg(long, default_value = "chunk")]
+    pub method: StegoMethod,
+
+    #[command(flatten)]
+    pub output: OutputArgs,
+    #[command(flatten)]
+    pub passphrase: PassphraseArgs,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ApngArgs {
+    #[command(subcommand)]
+    pub command: ApngCommand,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum ApngCommand {
+    /// Show the frame count, loops and each frame's delay, dispose and blend operations
+    Info(ApngInfoArgs),
+    /// Export the frames as standalone PNGs
+    Frames(ApngFramesArgs),
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ApngInfoArgs {
+    pub file: PathBuf,
+    #[arg(long, value_enum, default_value_t)]
+    pub format: ListFormat,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ApngFramesArgs {
+    pub file: PathBuf,
+    /// Directory to write frame-000.png, frame-001.png, ... into
+    #[arg(short, long = "output-dir", value_name = "DIR")]
+    pub output_dir: PathBuf,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct FramesArgs {
+    pub file: PathBuf,
+    /// Directory to write frame-000.png, frame-001.png, ... into
+    pub output_dir: PathBuf,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct KeygenArgs {
+    /// Secret key file to create, the public key goes next to it with a .pub extension
+    pub key_file: PathBuf,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct RepairArgs {
+    pub file: PathBuf,
+    /// Where to write the result, defaults to overwriting FILE, - for standard output
+    pub output: Option<PathBuf>,
+}
+
+#[cfg(feature = "server")]
+#[derive(Debug, Clone, Args)]
+pub struct ServeArgs {
+    /// Address to listen on
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:8080")]
+    pub listen: String,
+    /// Largest request body accepted, in bytes
+    #[arg(long, value_name = "BYTES", default_value_t = 16 * 1024 * 1024)]
+    pub max_size: usize,
+    /// Require this bearer token in the Authorization header
+    #[arg(long, env = "PNGME_SERVE_TOKEN", hide_env_values = true)]
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ApplyArgs {
+    /// TOML manifest listing files and the steps to run on each
+    pub manifest: PathBuf,
+    #[arg(long, value_enum, default_value_t)]
+    pub format: ReportFormat,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct PipeArgs {
+    /// PNG to edit, a glob pattern, or - for standard input
+    pub file: PathBuf,
+    /// Edit to run, such as strip:keep=tEXt or encode:type=ruSt,file=msg.bin, separated by ; or
+    /// repeated. Takes the ops and settings of manifest steps
+    #[arg(
+        long = "op",
+        visible_alias = "ops",
+        value_name = "OP",
+        value_delimiter = ';',
+        required = true
+    )]
+    pub ops: Vec<Step>,
+    /// Where to write the result, defaults to overwriting FILE, - for standard output
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct SignArgs {
+    pub file: PathBuf,
+    /// Ed25519 secret key file
+    pub key_file: PathBuf,
+    /// Where to write the result, defaults to overwriting FILE
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct VerifyArgs {
+    pub file: PathBuf,
+    /// Ed25519 public key file
+    pub key_file: PathBuf,
+    #[arg(long, value_enum, default_value_t)]
+    pub format: ReportFormat,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct CompletionsArgs {
+    pub shell: clap_complete::Shell,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ManArgs {
+    /// Write pngme.1 and a page per command to this directory
+    #[arg(long, value_name = "DIR")]
+    pub output_dir: Option<PathBuf>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_encode_args() {
+        let cli =
+            Cli::try_parse_from(["pngme", "encode", "a.png", "ruSt", "hello", "b.png"]).unwrap();
+        let Command::Encode(args) = cli.command else {
+            panic!("expected encode")
+        };
+        assert_eq!(args.message.as_deref(), Some("hello"));
+        assert_eq!(args.output, Some(PathBuf::from("b.png")));
+        assert_eq!(args.method, StegoMethod::Chunk);
+        assert_eq!(args.on_duplicate, DuplicatePolicy::Append);
+
+        let cli = Cli::try_parse_from([
+            "pngme",
+            "encode",
+            "a.png",
+            "ruSt",
+            "--file",
+            "secret.pdf",
+            "--compress",
+        ])
+        .unwrap();
+        let Command::Encode(args) = cli.command else {
+            panic!("expected encode")
+        };
+        assert_eq!(args.message, None);
+        assert_eq!(args.envelope.compress, Some(Compression::Deflate));
+    }
+
+    #[test]
+    fn test_verbose() {
+        let cli = Cli::try_parse_from(["pngme", "print", "a.png"]).unwrap();
+        assert_eq!(cli.global.verbose, 0);
+        let cli = Cli::try_parse_from(["pngme", "-vv", "print", "a.png"]).unwrap();
+        assert_eq!(cli.global.verbose, 2);
+        let cli = Cli::try_parse_from(["pngme", "print", "a.png", "--verbose"]).unwrap();
+        assert_eq!(cli.global.verbose, 1);
+    }
+
+    #[test]
+    fn test_invalid_args() {
+        for args in [
+            vec!["pngme", "encode", "a.png", "ruSt"],
+            vec!["pngme", "encode", "a.png", "ru5t", "hello"],
+            vec![
+                "pngme", "encode", "a.png", "ruSt", "hello", "--method", "dct",
+            ],
+            vec![
+                "pngme",
+                "encode",
+                "a.png",
+                "ruSt",
+                "hello",
+                "--encrypt",
+                "--recipient",
+                "age1",
+            ],
+            vec!["pngme", "encode", "a.png", "ruSt", "hello", "--decoy", "hi"],
+            vec!["pngme", "decode", "a.png"],
+            vec!["pngme", "decode", "a.png", "ruSt", "--format", "xml"],
+            vec!["pngme", "print", "a.png", "--format", "hex"],
+            vec!["pngme", "split-encode", "secret.bin"],
+        ] {
+            assert!(Cli::try_parse_from(&args).is_err(), "{:?}", args);
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use crate::{chunk::Chunk, chunk_type::ChunkType, png::Png, text::Text};
+use anyhow::Result;
+use std::str::FromStr;
+
+#[derive(Debug, Default)]
+pub struct PngBuilder {
+    chunks: Vec<Chunk>,
+    error: Option<anyhow::Error>,
+}
+
+impl PngBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn chunk(mut self, chunk: Chunk) -> Self {
+        self.chunks.push(chunk);
+        self
+    }
+
+    pub fn chunks(mut self, chunks: impl IntoIterator<Item = Chunk>) -> Self {
+        self.chunks.extend(chunks);
+        self
+    }
+
+    pub fn text(mut self, keyword: &str, text: &str) -> Self {
+        match Text::new(keyword, text) {
+            Ok(text) => self.chunks.push(text.into()),
+            Err(error) => {
+                self.error.get_or_insert(error);
+            }
+        }
+
+        self
+    }
+
+    pub fn message(self, chunk_type: &str, message: impl Into<Vec<u8>>) -> Self {
+        self.typed_chunk(chunk_type, message.into())
+    }
+
+    pub fn build(self) -> Result<Png> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+
+        let mut chunks = self.chunks;
+        if !chunks.iter().any(|c| *c.chunk_type() == ChunkType::IEND) {
+            chunks.push(Chunk::new(ChunkType::IEND, Vec::new()));
+        }
+        chunks.sort_by_key(|c| canonical_rank(c.chunk_type()));
+
+        Ok(Png::from_chunks(chunks))
+    }
+
+    fn typed_chunk(mut self, chunk_type: &str, data: Vec<u8>) -> Self {
+        match ChunkType::from_str(chunk_type) {
+            Ok(chunk_type) => self.chunks.push(Chunk::new(chunk_type, data)),
+            Err(error) => {
+                self.error.get_or_insert(error);
+            }
+        }
+
+        self
+    }
+}
+
+pub(crate) fn canonical_rank(chunk_type: &ChunkType) -> u8 {
+    match *chunk_type {
+        ChunkType::IHDR => 0,
+        ChunkType::cHRM
+        | ChunkType::gAMA
+        | ChunkType::iCCP
+        | ChunkType::sBIT
+        | ChunkType::sRGB
+        | ChunkType::cICP
+        | ChunkType::mDCV
+        | ChunkType::cLLI => 1,
+        ChunkType::PLTE => 2,
+        ChunkType::bKGD
+        | ChunkType::hIST
+        | ChunkType::tRNS
+        | ChunkType::pHYs
+        | ChunkType::sPLT
+        | ChunkType::eXIf
+        | ChunkType::acTL
+        | ChunkType::tEXt
+        | ChunkType::zTXt
+        | ChunkType::iTXt => 3,
+        ChunkType::IDAT | ChunkType::fcTL | ChunkType::fdAT => 4,
+        ChunkType::IEND => 6,
+        _ => 5,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(chunk_type: &str, data: &str) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.into())
+    }
+
+    fn types(png: &Png) -> Vec<String> {
+        png.chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_build_orders_chunks() {
+        let png = PngBuilder::new()
+            .message("ruSt", "hidden")
+            .chunks(vec![chunk("IDAT", "one"), chunk("IDAT", "two")])
+            .text("Author", "me")
+            .chunk(chunk("IHDR", "header"))
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            types(&png),
+            vec!["IHDR", "tEXt", "IDAT", "IDAT", "ruSt", "IEND"]
+        );
+        assert_eq!(&png.chunks()[2].to_string(), "one");
+        assert_eq!(&png.chunks()[3].to_string(), "two");
+    }
+
+    #[test]
+    fn test_build_text_chunk() {
+        let png = PngBuilder::new().text("Author", "me").build().unwrap();
+        let chunk = png.chunk_by_type("tEXt").unwrap();
+        assert_eq!(chunk.chunk_data(), b"Author\0me");
+    }
+
+    #[test]
+    fn test_build_keeps_single_iend() {
+        let png = PngBuilder::new()
+            .chunk(chunk("IEND", ""))
+            .chunk(chunk("IHDR", "header"))
+            .build()
+            .unwrap();
+
+        assert_eq!(types(&png), vec!["IHDR", "IEND"]);
+    }
+
+    #[test]
+    fn test_build_invalid_text_keyword() {
+        let png = PngBuilder::new().text("", "me").build();
+        assert!(png.is_err());
+    }
+
+    #[test]
+    fn test_build_invalid_chunk_type() {
+        let png = PngBuilder::new().message("ruStt", "hidden").build();
+        assert!(png.is_err());
+    }
+}
@@ -0,0 +1,242 @@
+#[cfg(feature = "gpg")]
+use crate::{
+    crypto::{Encryption, Signature},
+    gpg::Gpg,
+};
+use crate::{
+    envelope::{
+        join_segments, split_segments, Envelope, DEFAULT_SEGMENT_LENGTH, SEGMENT_HEADER_LENGTH,
+        SEGMENT_MAGIC,
+    },
+    png::Png,
+    stego::{StegoError, StegoMethod},
+};
+use anyhow::Result;
+
+// What the envelope, crypto and splitting machinery needs from a container format. A slot names a
+// place inside the container (a chunk type for PNG), and StegoMethod::Chunk stands for the
+// container's own metadata blocks, which every format has; pixel methods are up to the format.
+pub trait Carrier {
+    // Short name of the format, for messages.
+    fn format_name(&self) -> &'static str;
+
+    // Slots already holding something that is not part of the format itself.
+    fn slots(&self) -> Vec<String>;
+
+    fn capacity(&self, method: StegoMethod) -> Result<usize>;
+
+    // Each call adds another block to the slot, which is how segmented envelopes are stored.
+    fn embed(&mut self, method: StegoMethod, slot: &str, payload: &[u8]) -> Result<()>;
+
+    // Every block in the slot, in file order. Fails with StegoError::NoPayload if there is none.
+    fn extract_all(&self, method: StegoMethod, slot: &str) -> Result<Vec<Vec<u8>>>;
+
+    fn to_bytes(&self) -> Vec<u8>;
+
+    fn extract(&self, method: StegoMethod, slot: &str) -> Result<Vec<u8>> {
+        self.extract_all(method, slot)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| StegoError::NoPayload.into())
+    }
+
+    fn embed_envelope(
+        &mut self,
+        method: StegoMethod,
+        slot: &str,
+        envelope: &Envelope,
+    ) -> Result<()> {
+        // A segment must fit in one block, which some formats keep well below the default.
+        let segment_length = match method {
+            StegoMethod::Chunk => DEFAULT_SEGMENT_LENGTH
+                .min(self.capacity(method)?.saturating_sub(SEGMENT_HEADER_LENGTH)),
+            _ => DEFAULT_SEGMENT_LENGTH,
+        };
+        self.embed_envelope_with_segment_length(method, slot, envelope, segment_length)
+    }
+
+    // Only chunk embedding is segmented, pixel methods store the envelope as one stream.
+    fn embed_envelope_with_segment_length(
+        &mut self,
+        method: StegoMethod,
+        slot: &str,
+        envelope: &Envelope,
+        segment_length: usize,
+    ) -> Result<()> {
+        let bytes = envelope.to_bytes()?;
+        if method != StegoMethod::Chunk || bytes.len() <= segment_length {
+            return self.embed(method, slot, &bytes);
+        }
+
+        for segment in split_segments(&bytes, segment_length)? {
+            self.embed(method, slot, &segment)?;
+        }
+
+        Ok(())
+    }
+
+    // Compressed payloads come back decompressed, encrypted ones as stored.
+    fn extract_envelope(&self, method: StegoMethod, slot: &str) -> Result<Envelope> {
+        let envelope = self.extract_stored_envelope(method, slot)?;
+        if envelope.encryption.is_some() {
+            return Ok(envelope);
+        }
+
+        envelope.decompressed()
+    }
+
+    fn extract_envelope_with_passphrase(
+        &self,
+        method: StegoMethod,
+        slot: &str,
+        passphrase: &str,
+    ) -> Result<Envelope> {
+        self.extract_stored_envelope(method, slot)?
+            .decrypted(passphrase)?
+            .decompressed()
+    }
+
+    fn extract_envelope_with_identities(
+        &self,
+        method: StegoMethod,
+        slot: &str,
+        identities: &str,
+    ) -> Result<Envelope> {
+        self.extract_stored_envelope(method, slot)?
+            .decrypted_with_identities(identities)?
+            .decompressed()
+    }
+
+    // Decrypts and verifies whatever GPG layers the envelope carries, a bad signature is an error.
+    #[cfg(feature = "gpg")]
+    fn extract_envelope_with_gpg(
+        &self,
+        method: StegoMethod,
+        slot: &str,
+        gpg: &Gpg,
+    ) -> Result<Envelope> {
+        let mut envelope = self.extract_stored_envelope(method, slot)?;
+        if envelope.encryption == Some(Encryption::Gpg) {
+            envelope = envelope.gpg_decrypted(gpg)?;
+        }
+        if envelope.signature == Some(Signature::Gpg) {
+            envelope = envelope.gpg_verified(gpg)?.0;
+        }
+
+        envelope.decompressed()
+    }
+
+    fn extract_stored_envelope(&self, method: StegoMethod, slot: &str) -> Result<Envelope> {
+        let blocks = self.extract_all(method, slot)?;
+        if method != StegoMethod::Chunk || !blocks[0].starts_with(&SEGMENT_MAGIC) {
+            return Envelope::from_bytes(&blocks[0]);
+        }
+
+        let segments = blocks
+            .iter()
+            .map(Vec::as_slice)
+            .filter(|data| data.starts_with(&SEGMENT_MAGIC));
+
+        Envelope::from_bytes(&join_segments(segments)?)
+    }
+}
+
+impl Carrier for Png {
+    fn format_name(&self) -> &'static str {
+        "PNG"
+    }
+
+    fn slots(&self) -> Vec<String> {
+        let mut slots: Vec<String> = Vec::new();
+        for chunk in self.chunks() {
+            let chunk_type = chunk.chunk_type().to_string();
+            if !chunk.chunk_type().is_standard() && !slots.contains(&chunk_type) {
+                slots.push(chunk_type);
+            }
+        }
+        slots
+    }
+
+    fn capacity(&self, method: StegoMethod) -> Result<usize> {
+        Png::capacity(self, method)
+    }
+
+    fn embed(&mut self, method: StegoMethod, slot: &str, payload: &[u8]) -> Result<()> {
+        Png::embed(self, method, slot, payload)
+    }
+
+    fn extract_all(&self, method: StegoMethod, slot: &str) -> Result<Vec<Vec<u8>>> {
+        if method != StegoMethod::Chunk {
+            return Ok(vec![Png::extract(self, method, slot)?]);
+        }
+
+        let blocks: Vec<_> = self
+            .chunks()
+            .iter()
+            .filter(|c| c.chunk_type().to_string() == slot)
+            .map(|c| c.chunk_data().to_vec())
+            .collect();
+        if blocks.is_empty() {
+            return Err(StegoError::NoPayload.into());
+        }
+        Ok(blocks)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.as_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chunk::Chunk, chunk_type::ChunkType};
+    use std::str::FromStr;
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::IHDR, vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+            Chunk::new(ChunkType::tEXt, b"Comment\0hello".to_vec()),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ])
+    }
+
+    // Exercises the provided methods the way another format would, through the trait alone.
+    fn round_trip(carrier: &mut dyn Carrier) {
+        let envelope = Envelope::new(vec![7; 1000]);
+        carrier
+            .embed_envelope_with_segment_length(StegoMethod::Chunk, "ruSt", &envelope, 300)
+            .unwrap();
+        assert_eq!(
+            carrier
+                .extract_all(StegoMethod::Chunk, "ruSt")
+                .unwrap()
+                .len(),
+            4
+        );
+        assert_eq!(
+            carrier
+                .extract_envelope(StegoMethod::Chunk, "ruSt")
+                .unwrap(),
+            envelope
+        );
+    }
+
+    #[test]
+    fn test_png_carrier() {
+        let mut png = testing_png();
+        assert!(Carrier::slots(&png).is_empty());
+        assert!(matches!(
+            Carrier::extract(&png, StegoMethod::Chunk, "ruSt")
+                .unwrap_err()
+                .downcast::<StegoError>(),
+            Ok(StegoError::NoPayload)
+        ));
+
+        round_trip(&mut png);
+        png.insert_chunk_ordered(Chunk::new(ChunkType::from_str("abCd").unwrap(), vec![1]));
+        assert_eq!(Carrier::slots(&png), ["ruSt", "abCd"]);
+        assert_eq!(Carrier::to_bytes(&png), png.as_bytes());
+        assert_eq!(png.format_name(), "PNG");
+    }
+}
@@ -1,13 +1,20 @@
-use crate::chunk_type::ChunkType;
+use crate::{
+    chunk_type::ChunkType,
+    options::{MemoryBudget, ParseOptions},
+    render::DataFormat,
+};
 use anyhow::{Error, Result};
-use crc::Crc;
+use crc::{Crc, Table};
 use std::{
     fmt::Display,
-    io::{BufReader, Read},
+    io::{Read, Write},
 };
 use thiserror::Error;
 
-const ALG: Crc<u32> = Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
+// Slice-by-16 tables, which check the many short chunks of a typical file about twice as fast.
+#[cfg_attr(feature = "simd-crc", allow(dead_code))]
+const ALG: Crc<u32, Table<16>> = Crc::<u32, Table<16>>::new(&crc::CRC_32_ISO_HDLC);
+const PREVIEW_LENGTH: usize = 32;
 
 #[derive(Error, Debug, PartialEq, Eq)]
 pub enum ChunkError {
@@ -15,6 +22,10 @@ pub enum ChunkError {
     InvalidLength { actual: u32, found: u32 },
     #[error("Expected crc {actual:?}, got crc {found:?}.")]
     InvalidCrc { actual: u32, found: u32 },
+    #[error("Chunk length {length:?} exceeds the maximum of {max:?}.")]
+    ChunkTooLarge { length: u32, max: u32 },
+    #[error("Chunk is truncated, expected {needed:?} bytes but only {found:?} remain.")]
+    Truncated { needed: usize, found: usize },
 }
 
 #[derive(Debug, Clone)]
@@ -29,74 +40,307 @@ impl TryFrom<&[u8]> for Chunk {
     type Error = Error;
 
     fn try_from(value: &[u8]) -> Result<Self> {
-        let mut reader = BufReader::new(value);
-        let mut buffer: [u8; 4] = [0, 0, 0, 0];
+        let (chunk, _) = Self::from_slice_with_options(value, &ParseOptions::default())?;
+        Ok(chunk)
+    }
+}
 
-        reader.read_exact(&mut buffer)?;
-        let length = u32::from_be_bytes(buffer);
+impl Display for Chunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if f.alternate() {
+            return self.fmt_summary(f);
+        }
 
-        reader.read_exact(&mut buffer)?;
-        let chunk_type = ChunkType::try_from(buffer)?;
+        let string = match std::str::from_utf8(self.chunk_data()) {
+            Ok(value) => value.to_string(),
+            Err(_) => "\u{FFFD}".repeat(self.length() as usize),
+        };
+
+        write!(f, "{}", string)
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SerdeChunk {
+    chunk_type: ChunkType,
+    #[serde(default, skip_deserializing)]
+    length: u32,
+    #[serde(default)]
+    crc: Option<u32>,
+    data: String,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Chunk {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use base64::Engine;
+
+        SerdeChunk {
+            chunk_type: self.chunk_type,
+            length: self.length,
+            crc: Some(self.crc),
+            data: base64::engine::general_purpose::STANDARD.encode(&self.chunk_data),
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Chunk {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use base64::Engine;
+        use serde::de::Error;
+
+        let raw = SerdeChunk::deserialize(deserializer)?;
+        let chunk_data = base64::engine::general_purpose::STANDARD
+            .decode(raw.data)
+            .map_err(D::Error::custom)?;
+        let chunk = Self::new(raw.chunk_type, chunk_data);
+
+        match raw.crc {
+            Some(crc) if crc != chunk.crc() => Err(D::Error::custom(ChunkError::InvalidCrc {
+                actual: chunk.crc(),
+                found: crc,
+            })),
+            _ => Ok(chunk),
+        }
+    }
+}
+
+impl Chunk {
+    fn fmt_summary(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let chunk_type = self.chunk_type();
+        let mut flags = vec![
+            if chunk_type.is_critical() {
+                "critical"
+            } else {
+                "ancillary"
+            },
+            if chunk_type.is_public() {
+                "public"
+            } else {
+                "private"
+            },
+            if chunk_type.is_safe_to_copy() {
+                "safe-to-copy"
+            } else {
+                "unsafe-to-copy"
+            },
+        ];
+        if !chunk_type.is_reserved_bit_valid() {
+            flags.push("reserved-bit-set");
+        }
+
+        let preview_length = self.chunk_data.len().min(PREVIEW_LENGTH);
+        let preview = String::from_utf8_lossy(&self.chunk_data[..preview_length]);
+        let ellipsis = if self.chunk_data.len() > PREVIEW_LENGTH {
+            "…"
+        } else {
+            ""
+        };
+
+        write!(
+            f,
+            "{} length={} crc={:#010x} [{}] \"{}{}\"",
+            chunk_type,
+            self.length,
+            self.crc,
+            flags.join(", "),
+            preview.escape_debug(),
+            ellipsis
+        )
+    }
+
+    pub fn new(chunk_type: ChunkType, chunk_data: Vec<u8>) -> Self {
+        let length = chunk_data.len() as u32;
+        let crc = Self::compute_crc(&chunk_type, &chunk_data);
+
+        Self {
+            length,
+            chunk_type,
+            chunk_data,
+            crc,
+        }
+    }
+
+    #[cfg(feature = "simd-crc")]
+    fn compute_crc(chunk_type: &ChunkType, chunk_data: &[u8]) -> u32 {
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&chunk_type.bytes());
+        hasher.update(chunk_data);
+        hasher.finalize()
+    }
+
+    #[cfg(not(feature = "simd-crc"))]
+    fn compute_crc(chunk_type: &ChunkType, chunk_data: &[u8]) -> u32 {
+        let mut digest = ALG.digest();
+        digest.update(&chunk_type.bytes());
+        digest.update(chunk_data);
+        digest.finalize()
+    }
+
+    // For a CRC already computed over this type and data, so it is not computed again.
+    pub(crate) fn with_crc(chunk_type: ChunkType, chunk_data: Vec<u8>, crc: u32) -> Self {
+        Self {
+            length: chunk_data.len() as u32,
+            chunk_type,
+            chunk_data,
+            crc,
+        }
+    }
+
+    // The CRC of the type and data as they lie together in a file.
+    #[cfg(feature = "simd-crc")]
+    pub(crate) fn compute_crc_contiguous(type_and_data: &[u8]) -> u32 {
+        crc32fast::hash(type_and_data)
+    }
+
+    #[cfg(not(feature = "simd-crc"))]
+    pub(crate) fn compute_crc_contiguous(type_and_data: &[u8]) -> u32 {
+        ALG.checksum(type_and_data)
+    }
+
+    // Parses straight from the slice, so the only copy made is the chunk's own data. Returns the
+    // chunk and how many bytes it took up.
+    pub fn from_slice_with_options(bytes: &[u8], options: &ParseOptions) -> Result<(Self, usize)> {
+        Self::from_slice_within(bytes, options, &mut MemoryBudget::new(options))
+    }
+
+    pub(crate) fn from_slice_within(
+        bytes: &[u8],
+        options: &ParseOptions,
+        budget: &mut MemoryBudget,
+    ) -> Result<(Self, usize)> {
+        let truncated = |needed: usize| ChunkError::Truncated {
+            needed,
+            found: bytes.len(),
+        };
+        let header: &[u8; 8] = bytes
+            .get(..8)
+            .and_then(|header| header.try_into().ok())
+            .ok_or_else(|| truncated(12))?;
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+
+        let max = options.effective_max_chunk_length();
+        if length > max {
+            return Err(ChunkError::ChunkTooLarge { length, max }.into());
+        }
+        budget.charge(Self::buffered_length(length))?;
+
+        let type_bytes = [header[4], header[5], header[6], header[7]];
+        let chunk_type = if options.lenient_chunk_types {
+            ChunkType::new_unchecked(type_bytes)
+        } else {
+            ChunkType::try_from(type_bytes)?
+        };
+
+        let end = 8 + length as usize;
+        let total = end + 4;
+        let crc_bytes = bytes.get(end..total).ok_or_else(|| truncated(total))?;
+        let crc = u32::from_be_bytes([crc_bytes[0], crc_bytes[1], crc_bytes[2], crc_bytes[3]]);
+
+        let expected_crc = Self::compute_crc_contiguous(&bytes[4..end]);
+        if expected_crc != crc {
+            return Err(ChunkError::InvalidCrc {
+                actual: expected_crc,
+                found: crc,
+            }
+            .into());
+        }
+
+        let chunk = Self {
+            length,
+            chunk_type,
+            chunk_data: bytes[8..end].to_vec(),
+            crc,
+        };
+
+        Ok((chunk, total))
+    }
+
+    pub fn read_from(reader: impl Read) -> Result<(Self, usize)> {
+        Self::read_from_with_options(reader, &ParseOptions::default())
+    }
+
+    pub fn read_from_with_options(
+        reader: impl Read,
+        options: &ParseOptions,
+    ) -> Result<(Self, usize)> {
+        Self::read_from_within(reader, options, &mut MemoryBudget::new(options))
+    }
+
+    pub(crate) fn read_from_within(
+        mut reader: impl Read,
+        options: &ParseOptions,
+        budget: &mut MemoryBudget,
+    ) -> Result<(Self, usize)> {
+        let mut header = [0; 8];
+        reader.read_exact(&mut header)?;
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+
+        let max = options.effective_max_chunk_length();
+        if length > max {
+            return Err(ChunkError::ChunkTooLarge { length, max }.into());
+        }
+        budget.charge(Self::buffered_length(length))?;
+
+        let type_bytes = [header[4], header[5], header[6], header[7]];
+        let chunk_type = if options.lenient_chunk_types {
+            ChunkType::new_unchecked(type_bytes)
+        } else {
+            ChunkType::try_from(type_bytes)?
+        };
 
         let mut chunk_data = vec![0_u8; length as usize];
         reader.read_exact(&mut chunk_data)?;
 
+        let mut buffer = [0; 4];
         reader.read_exact(&mut buffer)?;
         let crc = u32::from_be_bytes(buffer);
 
-        let chunk = Self::new(chunk_type.clone(), chunk_data.clone());
-        if chunk.length() != length {
-            return Err(ChunkError::InvalidLength {
-                actual: chunk.length(),
-                found: length,
-            }
-            .into());
-        } else if chunk.crc() != crc {
+        let expected_crc = Self::compute_crc(&chunk_type, &chunk_data);
+        if expected_crc != crc {
             return Err(ChunkError::InvalidCrc {
-                actual: chunk.crc(),
+                actual: expected_crc,
                 found: crc,
             }
             .into());
         }
 
-        Ok(Self {
+        let chunk = Self {
             length,
             chunk_type,
             chunk_data,
             crc,
-        })
+        };
+
+        Ok((chunk, length as usize + 12))
     }
-}
 
-impl Display for Chunk {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let string = match std::str::from_utf8(self.chunk_data()) {
-            Ok(value) => value.to_string(),
-            Err(_) => "\u{FFFD}".repeat(self.length() as usize),
-        };
+    // The data plus the chunk itself, as held in a parsed PNG.
+    fn buffered_length(length: u32) -> usize {
+        length as usize + std::mem::size_of::<Self>()
+    }
 
-        write!(f, "{}", string)
+    fn refresh(&mut self) {
+        self.length = self.chunk_data.len() as u32;
+        self.crc = Self::compute_crc(&self.chunk_type, &self.chunk_data);
     }
-}
 
-impl Chunk {
-    pub fn new(chunk_type: ChunkType, chunk_data: Vec<u8>) -> Self {
-        let length = chunk_data.len() as u32;
-        let crc = ALG.checksum(
-            &chunk_type
-                .bytes()
-                .iter()
-                .chain(&chunk_data)
-                .copied()
-                .collect::<Vec<u8>>(),
-        ) as u32;
+    pub fn set_data(&mut self, chunk_data: Vec<u8>) {
+        self.chunk_data = chunk_data;
+        self.refresh();
+    }
 
-        Self {
-            length,
-            chunk_type,
-            chunk_data,
-            crc,
-        }
+    pub fn set_chunk_type(&mut self, chunk_type: ChunkType) {
+        self.chunk_type = chunk_type;
+        self.refresh();
+    }
+
+    pub fn append_data(&mut self, data: &[u8]) {
+        self.chunk_data.extend_from_slice(data);
+        self.refresh();
     }
 
     pub fn length(&self) -> u32 {
@@ -115,22 +359,38 @@ impl Chunk {
         self.crc
     }
 
+    pub fn render(&self, format: DataFormat) -> String {
+        format.render(&self.chunk_data)
+    }
+
+    pub fn into_data(self) -> Vec<u8> {
+        self.chunk_data
+    }
+
+    pub fn into_parts(self) -> (ChunkType, Vec<u8>) {
+        (self.chunk_type, self.chunk_data)
+    }
+
     pub fn as_bytes(&self) -> Vec<u8> {
-        self.length
-            .to_be_bytes()
-            .iter()
-            .chain(&self.chunk_type.bytes())
-            .chain(&self.chunk_data)
-            .chain(&self.crc.to_be_bytes())
-            .copied()
-            .collect()
+        let mut bytes = Vec::with_capacity(self.chunk_data.len() + 12);
+        self.write_to(&mut bytes)
+            .expect("writing to a Vec cannot fail");
+        bytes
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> Result<()> {
+        writer.write_all(&self.length.to_be_bytes())?;
+        writer.write_all(&self.chunk_type.bytes())?;
+        writer.write_all(&self.chunk_data)?;
+        writer.write_all(&self.crc.to_be_bytes())?;
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::chunk_type::ChunkType;
+    use crate::{chunk_type::ChunkType, options::ParseOptions, render::DataFormat};
     use std::str::FromStr;
 
     fn testing_chunk() -> Chunk {
@@ -234,6 +494,13 @@ mod tests {
         let chunk = Chunk::try_from(chunk_data.as_ref());
 
         assert!(chunk.is_err());
+        assert_eq!(
+            chunk.err().unwrap().downcast::<ChunkError>().unwrap(),
+            ChunkError::InvalidCrc {
+                actual: 2882656334,
+                found: 2882656333
+            }
+        );
     }
 
     #[test]
@@ -256,4 +523,277 @@ mod tests {
 
         let _chunk_string = format!("{}", chunk);
     }
+
+    #[test]
+    fn test_read_from_consecutive_chunks() {
+        let first = testing_chunk();
+        let second = Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"second".to_vec());
+        let bytes: Vec<u8> = first
+            .as_bytes()
+            .into_iter()
+            .chain(second.as_bytes())
+            .collect();
+
+        let mut reader = bytes.as_slice();
+        let (chunk, consumed) = Chunk::read_from(&mut reader).unwrap();
+        assert_eq!(consumed, 54);
+        assert_eq!(chunk.as_bytes(), first.as_bytes());
+        assert_eq!(reader.len(), 18);
+
+        let (chunk, consumed) = Chunk::read_from(&mut reader).unwrap();
+        assert_eq!(consumed, 18);
+        assert_eq!(chunk.to_string(), "second");
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn test_read_from_too_large() {
+        let bytes: Vec<u8> = u32::MAX
+            .to_be_bytes()
+            .iter()
+            .chain(b"IDAT")
+            .copied()
+            .collect();
+
+        let chunk = Chunk::read_from(bytes.as_slice());
+        assert_eq!(
+            chunk.err().unwrap().downcast::<ChunkError>().unwrap(),
+            ChunkError::ChunkTooLarge {
+                length: u32::MAX,
+                max: crate::options::DEFAULT_MAX_CHUNK_LENGTH
+            }
+        );
+    }
+
+    #[test]
+    fn test_read_from_custom_max_length() {
+        let bytes = testing_chunk().as_bytes();
+        let options = ParseOptions {
+            max_chunk_length: 41,
+            ..Default::default()
+        };
+
+        let chunk = Chunk::read_from_with_options(bytes.as_slice(), &options);
+        assert_eq!(
+            chunk.err().unwrap().downcast::<ChunkError>().unwrap(),
+            ChunkError::ChunkTooLarge {
+                length: 42,
+                max: 41
+            }
+        );
+
+        let options = ParseOptions {
+            max_chunk_length: 42,
+            ..Default::default()
+        };
+        assert!(Chunk::read_from_with_options(bytes.as_slice(), &options).is_ok());
+    }
+
+    #[test]
+    fn test_read_from_spec_max_length() {
+        let bytes: Vec<u8> = (1_u32 << 31)
+            .to_be_bytes()
+            .iter()
+            .chain(b"IDAT")
+            .copied()
+            .collect();
+
+        let chunk = Chunk::read_from_with_options(bytes.as_slice(), &ParseOptions::unlimited());
+        assert_eq!(
+            chunk.err().unwrap().downcast::<ChunkError>().unwrap(),
+            ChunkError::ChunkTooLarge {
+                length: 1 << 31,
+                max: crate::options::SPEC_MAX_CHUNK_LENGTH
+            }
+        );
+    }
+
+    #[test]
+    fn test_read_from_lenient_chunk_type() {
+        let chunk = Chunk::new(ChunkType::new_unchecked(*b"Ru t"), b"odd".to_vec());
+        let bytes = chunk.as_bytes();
+
+        assert!(Chunk::read_from(bytes.as_slice()).is_err());
+
+        let options = ParseOptions {
+            lenient_chunk_types: true,
+            ..Default::default()
+        };
+        let (parsed, _) = Chunk::read_from_with_options(bytes.as_slice(), &options).unwrap();
+        assert_eq!(parsed.chunk_type().bytes(), *b"Ru t");
+    }
+
+    #[test]
+    fn test_read_from_truncated() {
+        let bytes = testing_chunk().as_bytes();
+        assert!(Chunk::read_from(&bytes[..30]).is_err());
+    }
+
+    #[test]
+    fn test_from_slice() {
+        let mut bytes = testing_chunk().as_bytes();
+        bytes.extend(b"trailing");
+        let options = ParseOptions::default();
+        let (chunk, length) = Chunk::from_slice_with_options(&bytes, &options).unwrap();
+        assert_eq!(length, 54);
+        assert_eq!(chunk.as_bytes(), testing_chunk().as_bytes());
+
+        let error = |bytes: &[u8]| {
+            Chunk::from_slice_with_options(bytes, &options)
+                .unwrap_err()
+                .downcast::<ChunkError>()
+                .unwrap()
+        };
+        assert_eq!(
+            error(&bytes[..4]),
+            ChunkError::Truncated {
+                needed: 12,
+                found: 4
+            }
+        );
+        assert_eq!(
+            error(&bytes[..30]),
+            ChunkError::Truncated {
+                needed: 54,
+                found: 30
+            }
+        );
+        bytes[20] ^= 1;
+        assert!(matches!(error(&bytes), ChunkError::InvalidCrc { .. }));
+    }
+
+    #[test]
+    fn test_write_to() {
+        let chunk = testing_chunk();
+        let mut written = Vec::new();
+        chunk.write_to(&mut written).unwrap();
+
+        assert_eq!(written.len(), 54);
+        assert_eq!(written, chunk.as_bytes());
+        assert_eq!(&written[..4], &42_u32.to_be_bytes());
+        assert_eq!(&written[50..], &2882656334_u32.to_be_bytes());
+    }
+
+    #[cfg(feature = "simd-crc")]
+    #[test]
+    fn test_simd_crc_matches_iso_hdlc() {
+        let chunk_type = ChunkType::from_str("IDAT").unwrap();
+        for length in [0, 1, 15, 16, 17, 63, 64, 65, 1000, 65537] {
+            let data: Vec<u8> = (0..length).map(|i| (i * 31 % 251) as u8).collect();
+            let mut digest = ALG.digest();
+            digest.update(&chunk_type.bytes());
+            digest.update(&data);
+
+            assert_eq!(Chunk::compute_crc(&chunk_type, &data), digest.finalize());
+        }
+    }
+
+    #[test]
+    fn test_into_data() {
+        let chunk = testing_chunk();
+        let data = chunk.into_data();
+        assert_eq!(data, b"This is where your secret message will be!");
+    }
+
+    #[test]
+    fn test_into_parts() {
+        let chunk = testing_chunk();
+        let (chunk_type, data) = chunk.into_parts();
+        assert_eq!(chunk_type.to_string(), "RuSt");
+        assert_eq!(data, b"This is where your secret message will be!");
+    }
+
+    #[test]
+    fn test_chunk_alternate_display() {
+        let chunk = testing_chunk();
+        assert_eq!(
+            format!("{:#}", chunk),
+            "RuSt length=42 crc=0xabd1d84e [critical, private, safe-to-copy] \
+             \"This is where your secret messag…\""
+        );
+    }
+
+    #[test]
+    fn test_chunk_alternate_display_binary() {
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), vec![0, 159, 10]);
+        assert_eq!(
+            format!("{:#}", chunk),
+            "ruSt length=3 crc=0x30967f15 [ancillary, private, safe-to-copy] \"\\0�\\n\""
+        );
+    }
+
+    #[test]
+    fn test_chunk_render() {
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), vec![0, 159, 10]);
+        assert_eq!(chunk.render(DataFormat::Hex), "009f0a");
+        assert_eq!(chunk.render(DataFormat::Base64), "AJ8K");
+        assert_eq!(chunk.render(DataFormat::EscapedAscii), "\\x00\\x9f\\n");
+    }
+
+    #[test]
+    fn test_set_data() {
+        let mut chunk = testing_chunk();
+        chunk.set_data(b"Updated".to_vec());
+
+        let expected = Chunk::new(ChunkType::from_str("RuSt").unwrap(), b"Updated".to_vec());
+        assert_eq!(chunk.length(), 7);
+        assert_eq!(chunk.crc(), expected.crc());
+        assert_eq!(chunk.to_string(), "Updated");
+    }
+
+    #[test]
+    fn test_set_chunk_type() {
+        let mut chunk = testing_chunk();
+        chunk.set_chunk_type(ChunkType::from_str("ruSt").unwrap());
+
+        let expected = Chunk::new(
+            ChunkType::from_str("ruSt").unwrap(),
+            chunk.chunk_data().to_vec(),
+        );
+        assert_eq!(chunk.chunk_type().to_string(), "ruSt");
+        assert_eq!(chunk.crc(), expected.crc());
+    }
+
+    #[test]
+    fn test_append_data() {
+        let mut chunk = Chunk::new(ChunkType::from_str("RuSt").unwrap(), b"This is".to_vec());
+        chunk.append_data(b" where your secret message will be!");
+
+        assert_eq!(chunk.length(), 42);
+        assert_eq!(chunk.crc(), 2882656334);
+        assert!(Chunk::try_from(chunk.as_bytes().as_ref()).is_ok());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_chunk_serde_round_trip() {
+        let chunk = testing_chunk();
+        let json = serde_json::to_value(&chunk).unwrap();
+
+        assert_eq!(json["chunk_type"], "RuSt");
+        assert_eq!(json["length"], 42);
+        assert_eq!(json["crc"], 2882656334_u32);
+        assert_eq!(
+            json["data"],
+            "VGhpcyBpcyB3aGVyZSB5b3VyIHNlY3JldCBtZXNzYWdlIHdpbGwgYmUh"
+        );
+
+        let parsed: Chunk = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.as_bytes(), chunk.as_bytes());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_chunk_serde_without_crc() {
+        let json = r#"{"chunk_type": "RuSt", "data": "aGk="}"#;
+        let chunk: Chunk = serde_json::from_str(json).unwrap();
+        assert_eq!(chunk.chunk_data(), b"hi");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_chunk_serde_bad_crc() {
+        let json = r#"{"chunk_type": "RuSt", "crc": 1, "data": "aGk="}"#;
+        assert!(serde_json::from_str::<Chunk>(json).is_err());
+    }
 }
@@ -7,11 +7,14 @@ pub enum ChunkTypeError {
     #[error("Bytes have to be within the ASCII range.")]
     InvalidEncoding,
 
+    #[error("Bytes have to be ASCII letters (A-Z or a-z), got {found:?}.")]
+    NonAlphabetic { found: u8 },
+
     #[error("Expected 4 bytes, got {found:?} bytes.")]
     InvalidLength { found: String },
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct ChunkType {
     bytes: [u8; 4],
 }
@@ -20,11 +23,15 @@ impl TryFrom<[u8; 4]> for ChunkType {
     type Error = Error;
 
     fn try_from(value: [u8; 4]) -> Result<Self> {
-        if value.is_ascii() {
-            Ok(Self { bytes: value })
-        } else {
-            Err(ChunkTypeError::InvalidEncoding.into())
+        if !value.is_ascii() {
+            return Err(ChunkTypeError::InvalidEncoding.into());
+        }
+
+        if let Some(&found) = value.iter().find(|b| !b.is_ascii_alphabetic()) {
+            return Err(ChunkTypeError::NonAlphabetic { found }.into());
         }
+
+        Ok(Self { bytes: value })
     }
 }
 
@@ -34,12 +41,12 @@ impl FromStr for ChunkType {
     fn from_str(s: &str) -> Result<Self> {
         let Ok(bytes) = <[u8; 4]>::try_from(s.as_bytes()) else {
             return Err(ChunkTypeError::InvalidLength {
-                found: s.as_bytes().len().to_string(),
+                found: s.len().to_string(),
             }
             .into());
         };
 
-        Ok(Self::try_from(bytes)?)
+        Self::try_from(bytes)
     }
 }
 
@@ -54,11 +61,192 @@ impl Display for ChunkType {
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for ChunkType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ChunkType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        Self::from_str(&string).map_err(serde::de::Error::custom)
+    }
+}
+
+#[allow(non_upper_case_globals)]
 impl ChunkType {
+    pub const IHDR: ChunkType = ChunkType::from_bytes_const(*b"IHDR");
+    pub const PLTE: ChunkType = ChunkType::from_bytes_const(*b"PLTE");
+    pub const IDAT: ChunkType = ChunkType::from_bytes_const(*b"IDAT");
+    pub const IEND: ChunkType = ChunkType::from_bytes_const(*b"IEND");
+    pub const tRNS: ChunkType = ChunkType::from_bytes_const(*b"tRNS");
+    pub const cHRM: ChunkType = ChunkType::from_bytes_const(*b"cHRM");
+    pub const gAMA: ChunkType = ChunkType::from_bytes_const(*b"gAMA");
+    pub const iCCP: ChunkType = ChunkType::from_bytes_const(*b"iCCP");
+    pub const sBIT: ChunkType = ChunkType::from_bytes_const(*b"sBIT");
+    pub const sRGB: ChunkType = ChunkType::from_bytes_const(*b"sRGB");
+    pub const cICP: ChunkType = ChunkType::from_bytes_const(*b"cICP");
+    pub const mDCV: ChunkType = ChunkType::from_bytes_const(*b"mDCV");
+    pub const cLLI: ChunkType = ChunkType::from_bytes_const(*b"cLLI");
+    pub const tEXt: ChunkType = ChunkType::from_bytes_const(*b"tEXt");
+    pub const zTXt: ChunkType = ChunkType::from_bytes_const(*b"zTXt");
+    pub const iTXt: ChunkType = ChunkType::from_bytes_const(*b"iTXt");
+    pub const bKGD: ChunkType = ChunkType::from_bytes_const(*b"bKGD");
+    pub const hIST: ChunkType = ChunkType::from_bytes_const(*b"hIST");
+    pub const pHYs: ChunkType = ChunkType::from_bytes_const(*b"pHYs");
+    pub const sPLT: ChunkType = ChunkType::from_bytes_const(*b"sPLT");
+    pub const eXIf: ChunkType = ChunkType::from_bytes_const(*b"eXIf");
+    pub const tIME: ChunkType = ChunkType::from_bytes_const(*b"tIME");
+    pub const acTL: ChunkType = ChunkType::from_bytes_const(*b"acTL");
+    pub const fcTL: ChunkType = ChunkType::from_bytes_const(*b"fcTL");
+    pub const fdAT: ChunkType = ChunkType::from_bytes_const(*b"fdAT");
+
+    pub const STANDARD: [ChunkType; 25] = [
+        Self::IHDR,
+        Self::PLTE,
+        Self::IDAT,
+        Self::IEND,
+        Self::tRNS,
+        Self::cHRM,
+        Self::gAMA,
+        Self::iCCP,
+        Self::sBIT,
+        Self::sRGB,
+        Self::cICP,
+        Self::mDCV,
+        Self::cLLI,
+        Self::tEXt,
+        Self::zTXt,
+        Self::iTXt,
+        Self::bKGD,
+        Self::hIST,
+        Self::pHYs,
+        Self::sPLT,
+        Self::eXIf,
+        Self::tIME,
+        Self::acTL,
+        Self::fcTL,
+        Self::fdAT,
+    ];
+
+    pub const fn new_unchecked(bytes: [u8; 4]) -> Self {
+        Self { bytes }
+    }
+
+    pub const fn from_bytes_const(bytes: [u8; 4]) -> Self {
+        let mut i = 0;
+        while i < bytes.len() {
+            assert!(
+                bytes[i].is_ascii_alphabetic(),
+                "chunk type bytes have to be ASCII letters"
+            );
+            i += 1;
+        }
+
+        Self { bytes }
+    }
+
+    pub fn random_private(rng: &mut impl rand::Rng) -> Self {
+        loop {
+            let mut bytes = [0u8; 4];
+            for byte in &mut bytes {
+                *byte = rng.gen_range(b'a'..=b'z');
+            }
+
+            let chunk_type = Self { bytes }
+                .with_critical(false)
+                .with_public(false)
+                .with_reserved_bit_valid(true)
+                .with_safe_to_copy(true);
+
+            if !chunk_type.is_registered() {
+                return chunk_type;
+            }
+        }
+    }
+
     pub fn bytes(&self) -> [u8; 4] {
         self.bytes
     }
 
+    pub fn is_standard(&self) -> bool {
+        Self::STANDARD.contains(self)
+    }
+
+    pub fn description(&self) -> Option<&'static str> {
+        let description = match &self.bytes {
+            b"IHDR" => "image header",
+            b"PLTE" => "palette",
+            b"IDAT" => "image data",
+            b"IEND" => "image trailer",
+            b"tRNS" => "transparency",
+            b"cHRM" => "primary chromaticities and white point",
+            b"gAMA" => "image gamma",
+            b"iCCP" => "embedded ICC profile",
+            b"sBIT" => "significant bits",
+            b"sRGB" => "standard RGB colour space",
+            b"cICP" => "coding-independent code points",
+            b"mDCV" => "mastering display colour volume",
+            b"cLLI" => "content light level information",
+            b"tEXt" => "textual data",
+            b"zTXt" => "compressed textual data",
+            b"iTXt" => "international textual data",
+            b"bKGD" => "background colour",
+            b"hIST" => "image histogram",
+            b"pHYs" => "physical pixel dimensions",
+            b"sPLT" => "suggested palette",
+            b"eXIf" => "exchangeable image file profile",
+            b"tIME" => "image last-modification time",
+            b"acTL" => "animation control",
+            b"fcTL" => "frame control",
+            b"fdAT" => "frame data",
+            b"oFFs" => "image offset",
+            b"pCAL" => "calibration of pixel values",
+            b"sCAL" => "physical scale of image subject",
+            b"gIFg" => "GIF graphic control extension",
+            b"gIFx" => "GIF application extension",
+            b"gIFt" => "GIF plain text extension (deprecated)",
+            b"sTER" => "stereo image indicator",
+            b"dSIG" => "digital signature",
+            b"fRAc" => "fractal image parameters",
+            _ => return None,
+        };
+
+        Some(description)
+    }
+
+    // Standard chunks the specification allows at most once per file.
+    pub fn is_single_instance(&self) -> bool {
+        matches!(
+            &self.bytes,
+            b"IHDR"
+                | b"PLTE"
+                | b"IEND"
+                | b"tRNS"
+                | b"cHRM"
+                | b"gAMA"
+                | b"iCCP"
+                | b"sBIT"
+                | b"sRGB"
+                | b"cICP"
+                | b"mDCV"
+                | b"cLLI"
+                | b"bKGD"
+                | b"hIST"
+                | b"pHYs"
+                | b"eXIf"
+                | b"tIME"
+                | b"acTL"
+        )
+    }
+
+    pub fn is_registered(&self) -> bool {
+        self.description().is_some()
+    }
+
     pub fn is_critical(&self) -> bool {
         self.bytes[0].is_ascii_uppercase()
     }
@@ -74,6 +262,31 @@ impl ChunkType {
     pub fn is_safe_to_copy(&self) -> bool {
         self.bytes[3].is_ascii_lowercase()
     }
+
+    pub fn with_critical(self, critical: bool) -> Self {
+        self.with_case(0, critical)
+    }
+
+    pub fn with_public(self, public: bool) -> Self {
+        self.with_case(1, public)
+    }
+
+    pub fn with_reserved_bit_valid(self, valid: bool) -> Self {
+        self.with_case(2, valid)
+    }
+
+    pub fn with_safe_to_copy(self, safe_to_copy: bool) -> Self {
+        self.with_case(3, !safe_to_copy)
+    }
+
+    fn with_case(mut self, index: usize, uppercase: bool) -> Self {
+        self.bytes[index] = if uppercase {
+            self.bytes[index].to_ascii_uppercase()
+        } else {
+            self.bytes[index].to_ascii_lowercase()
+        };
+        self
+    }
 }
 
 #[cfg(test)]
@@ -116,6 +329,140 @@ mod tests {
         );
     }
 
+    #[test]
+    pub fn is_non_alphabetic() {
+        let chunk = ChunkType::from_str("Ru t");
+
+        assert_eq!(
+            chunk.err().unwrap().downcast::<ChunkTypeError>().unwrap(),
+            ChunkTypeError::NonAlphabetic { found: b' ' }
+        );
+        assert!(ChunkType::try_from([82, 117, 0, 116]).is_err());
+        assert!(ChunkType::from_str("Ru5t").is_err());
+    }
+
+    #[test]
+    pub fn new_unchecked_accepts_anything() {
+        let chunk = ChunkType::new_unchecked([82, 117, 0, 255]);
+        assert_eq!(chunk.bytes(), [82, 117, 0, 255]);
+    }
+
+    #[test]
+    pub fn standard_constants() {
+        assert_eq!(ChunkType::IHDR, ChunkType::from_str("IHDR").unwrap());
+        assert_eq!(ChunkType::tEXt.to_string(), "tEXt");
+        assert_eq!(ChunkType::fdAT.bytes(), *b"fdAT");
+
+        for chunk_type in ChunkType::STANDARD {
+            assert!(ChunkType::try_from(chunk_type.bytes()).is_ok());
+            assert!(chunk_type.is_reserved_bit_valid());
+        }
+    }
+
+    #[test]
+    pub fn is_standard() {
+        assert!(ChunkType::IDAT.is_standard());
+        assert!(ChunkType::from_str("eXIf").unwrap().is_standard());
+        assert!(!ChunkType::from_str("RuSt").unwrap().is_standard());
+        assert!(!ChunkType::from_str("ihdr").unwrap().is_standard());
+    }
+
+    #[test]
+    pub fn description() {
+        assert_eq!(ChunkType::tEXt.description(), Some("textual data"));
+        assert_eq!(ChunkType::IHDR.description(), Some("image header"));
+        assert_eq!(
+            ChunkType::from_str("sCAL").unwrap().description(),
+            Some("physical scale of image subject")
+        );
+        assert_eq!(ChunkType::from_str("ruSt").unwrap().description(), None);
+    }
+
+    #[test]
+    pub fn is_single_instance() {
+        assert!(ChunkType::IHDR.is_single_instance());
+        assert!(ChunkType::iCCP.is_single_instance());
+        assert!(!ChunkType::IDAT.is_single_instance());
+        assert!(!ChunkType::tEXt.is_single_instance());
+        assert!(!ChunkType::from_str("ruSt").unwrap().is_single_instance());
+    }
+
+    #[test]
+    pub fn is_registered() {
+        for chunk_type in ChunkType::STANDARD {
+            assert!(chunk_type.is_registered());
+        }
+        assert!(ChunkType::from_str("oFFs").unwrap().is_registered());
+        assert!(!ChunkType::from_str("ruSt").unwrap().is_registered());
+    }
+
+    #[test]
+    pub fn from_bytes_const() {
+        const CUSTOM: ChunkType = ChunkType::from_bytes_const(*b"ruSt");
+        assert_eq!(CUSTOM, ChunkType::from_str("ruSt").unwrap());
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn from_bytes_const_invalid() {
+        ChunkType::from_bytes_const(*b"ru t");
+    }
+
+    #[test]
+    pub fn usable_as_map_key() {
+        use std::collections::{BTreeMap, HashMap};
+
+        let mut counts = HashMap::new();
+        *counts.entry(ChunkType::IDAT).or_insert(0) += 1;
+        *counts.entry(ChunkType::IDAT).or_insert(0) += 1;
+        assert_eq!(counts[&ChunkType::IDAT], 2);
+
+        let ordered: BTreeMap<_, _> = [(ChunkType::tEXt, 1), (ChunkType::IDAT, 2)].into();
+        let keys: Vec<_> = ordered.keys().map(|t| t.to_string()).collect();
+        assert_eq!(keys, vec!["IDAT", "tEXt"]);
+    }
+
+    #[test]
+    pub fn property_setters() {
+        let chunk = ChunkType::from_str("rust")
+            .unwrap()
+            .with_critical(false)
+            .with_public(false)
+            .with_reserved_bit_valid(true)
+            .with_safe_to_copy(true);
+
+        assert_eq!(chunk.to_string(), "ruSt");
+        assert!(!chunk.is_critical());
+        assert!(!chunk.is_public());
+        assert!(chunk.is_reserved_bit_valid());
+        assert!(chunk.is_safe_to_copy());
+
+        let chunk = chunk
+            .with_critical(true)
+            .with_public(true)
+            .with_safe_to_copy(false);
+        assert_eq!(chunk.to_string(), "RUST");
+        assert!(chunk.is_critical());
+        assert!(chunk.is_public());
+        assert!(!chunk.is_safe_to_copy());
+    }
+
+    #[test]
+    pub fn random_private() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..1000 {
+            let chunk = ChunkType::random_private(&mut rng);
+            assert!(ChunkType::try_from(chunk.bytes()).is_ok());
+            assert!(!chunk.is_critical());
+            assert!(!chunk.is_public());
+            assert!(chunk.is_reserved_bit_valid());
+            assert!(chunk.is_safe_to_copy());
+            assert!(!chunk.is_registered());
+        }
+    }
+
     #[test]
     pub fn is_critical() {
         let chunk = ChunkType::from_str("RuSt").unwrap();
@@ -163,4 +510,19 @@ mod tests {
         let chunk = ChunkType::from_str("RuST").unwrap();
         assert!(!chunk.is_safe_to_copy());
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    pub fn serde_round_trip() {
+        let chunk = ChunkType::from_str("RuSt").unwrap();
+        let json = serde_json::to_string(&chunk).unwrap();
+        assert_eq!(json, "\"RuSt\"");
+        assert_eq!(serde_json::from_str::<ChunkType>(&json).unwrap(), chunk);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    pub fn serde_invalid_length() {
+        assert!(serde_json::from_str::<ChunkType>("\"RuStt\"").is_err());
+    }
 }
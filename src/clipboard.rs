@@ -0,0 +1,53 @@
+use anyhow::Result;
+use arboard::Clipboard;
+use thiserror::Error;
+
+// On X11 and Wayland the clipboard empties once the process that copied exits, so a copied
+// payload is served for this long and then, like a password manager would, let go.
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+))]
+pub const HOLD: std::time::Duration = std::time::Duration::from_secs(45);
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ClipboardError {
+    #[error("The clipboard holds no text.")]
+    NoText,
+}
+
+pub fn read_text() -> Result<String> {
+    match Clipboard::new()?.get_text() {
+        Ok(text) => Ok(text),
+        Err(arboard::Error::ContentNotAvailable) => Err(ClipboardError::NoText.into()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Kept out of clipboard manager histories wherever the platform allows it.
+pub fn write_text(text: &str) -> Result<()> {
+    let mut clipboard = Clipboard::new()?;
+    let set = clipboard.set();
+
+    #[cfg(all(
+        unix,
+        not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+    ))]
+    let set = {
+        use arboard::SetExtLinux;
+        set.exclude_from_history()
+            .wait_until(std::time::Instant::now() + HOLD)
+    };
+    #[cfg(windows)]
+    let set = {
+        use arboard::SetExtWindows;
+        set.exclude_from_history()
+    };
+    #[cfg(target_os = "macos")]
+    let set = {
+        use arboard::SetExtApple;
+        set.exclude_from_history()
+    };
+
+    Ok(set.text(text)?)
+}
@@ -0,0 +1,153 @@
+use crate::{chunk::Chunk, chunk_type::ChunkType, png::Png};
+use anyhow::{Context, Result};
+use std::io::Cursor;
+
+// Hands pixel work to the png crate, which handles every bit depth, interlacing and palette
+// expansion, while pngme keeps owning the chunks around the image data.
+
+// Samples exactly as stored, one row after another without filter bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pixels {
+    pub width: u32,
+    pub height: u32,
+    pub color_type: ::png::ColorType,
+    pub bit_depth: ::png::BitDepth,
+    pub palette: Option<Vec<u8>>,
+    pub data: Vec<u8>,
+}
+
+impl Png {
+    // Only the chunks the decoder needs are passed, so private chunks cannot upset it.
+    pub fn decode_pixels(&self) -> Result<Pixels> {
+        let image = Png::from_chunks(
+            self.chunks()
+                .iter()
+                .filter(|c| c.chunk_type().is_critical() || *c.chunk_type() == ChunkType::tRNS)
+                .cloned()
+                .collect(),
+        );
+        let mut decoder = ::png::Decoder::new(Cursor::new(image.as_bytes()));
+        decoder.set_transformations(::png::Transformations::IDENTITY);
+        let mut reader = decoder
+            .read_info()
+            .context("Could not read the image header")?;
+        let length = reader
+            .output_buffer_size()
+            .context("The image is too large to decode")?;
+        let mut data = vec![0; length];
+        let info = reader
+            .next_frame(&mut data)
+            .context("Could not decode the image data")?;
+        data.truncate(info.buffer_size());
+
+        Ok(Pixels {
+            width: info.width,
+            height: info.height,
+            color_type: info.color_type,
+            bit_depth: info.bit_depth,
+            palette: reader.info().palette.as_ref().map(|p| p.to_vec()),
+            data,
+        })
+    }
+
+    // Encodes with the png crate and takes its IHDR, PLTE and IDAT chunks in place of the current
+    // ones, leaving every other chunk where it was.
+    pub fn encode_pixels(&mut self, pixels: &Pixels) -> Result<()> {
+        let mut bytes = Vec::new();
+        let mut encoder = ::png::Encoder::new(&mut bytes, pixels.width, pixels.height);
+        encoder.set_color(pixels.color_type);
+        encoder.set_depth(pixels.bit_depth);
+        if let Some(palette) = &pixels.palette {
+            encoder.set_palette(palette.as_slice());
+        }
+        let mut writer = encoder.write_header()?;
+        writer
+            .write_image_data(&pixels.data)
+            .context("Could not encode the pixels")?;
+        writer.finish()?;
+
+        self.replace_image_chunks(&Png::try_from(bytes.as_slice())?)
+    }
+
+    // Re-chunks image data encoded elsewhere into this PNG.
+    pub fn replace_image_chunks(&mut self, encoded: &Png) -> Result<()> {
+        self.set_header(encoded.header()?)?;
+        self.copy_chunks_from(encoded, &[ChunkType::PLTE]);
+        if !self
+            .chunks()
+            .iter()
+            .any(|c| *c.chunk_type() == ChunkType::IDAT)
+        {
+            self.insert_chunk_ordered(Chunk::new(ChunkType::IDAT, Vec::new()));
+        }
+
+        let max_len = encoded
+            .chunks()
+            .iter()
+            .filter(|c| *c.chunk_type() == ChunkType::IDAT)
+            .map(|c| c.chunk_data().len())
+            .max()
+            .unwrap_or(usize::MAX);
+        self.rechunk_idat(encoded.idat_data(), max_len.max(1));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builder::PngBuilder,
+        ihdr::{ColorType, Ihdr},
+        imagedata::ImageData,
+    };
+
+    fn testing_png() -> Png {
+        let header = Ihdr::new(3, 2, 8, ColorType::Rgb).unwrap();
+        let pixels = (0..18).collect();
+        let mut png = PngBuilder::new()
+            .chunk(Chunk::from(header))
+            .chunk(Chunk::new(ChunkType::tEXt, b"Comment\0hello".to_vec()))
+            .chunk(Chunk::new("ruSt".parse().unwrap(), b"hidden".to_vec()))
+            .build()
+            .unwrap();
+        png.set_image_data(&ImageData::new(header, pixels).unwrap())
+            .unwrap();
+        png
+    }
+
+    #[test]
+    fn test_decode_pixels() {
+        let pixels = testing_png().decode_pixels().unwrap();
+        assert_eq!((pixels.width, pixels.height), (3, 2));
+        assert_eq!(pixels.color_type, ::png::ColorType::Rgb);
+        assert_eq!(pixels.data, (0..18).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn test_encode_pixels() {
+        let mut png = testing_png();
+        let mut pixels = png.decode_pixels().unwrap();
+        pixels.data.iter_mut().for_each(|b| *b = 255 - *b);
+        png.encode_pixels(&pixels).unwrap();
+
+        assert_eq!(png.decode_pixels().unwrap(), pixels);
+        assert_eq!(png.image_data().unwrap().pixels(), pixels.data.as_slice());
+        // The other chunks are untouched.
+        assert!(png.chunk_by_type("ruSt").is_some());
+        assert!(png.chunk_by_type("tEXt").is_some());
+        assert_eq!(*png.chunks().last().unwrap().chunk_type(), ChunkType::IEND);
+
+        let palette = Pixels {
+            width: 2,
+            height: 1,
+            color_type: ::png::ColorType::Indexed,
+            bit_depth: ::png::BitDepth::Eight,
+            palette: Some(vec![0, 0, 0, 255, 255, 255]),
+            data: vec![1, 0],
+        };
+        png.encode_pixels(&palette).unwrap();
+        assert!(png.chunk_by_type("PLTE").is_some());
+        assert_eq!(png.decode_pixels().unwrap(), palette);
+    }
+}
@@ -0,0 +1,5475 @@
+use crate::args::{
+    ApngArgs, ApngCommand, ApngInfoArgs, ApplyArgs, CanonicalizeArgs, CapacityArgs, CheckArgs, Cli,
+    Command, CompletionsArgs, DecodeArgs, DedupeArgs, DetectArgs, DiffArgs, DuplicatePolicy,
+    EncodeArgs, EnvelopeArgs, ExifArgs, ExifCommand, ExifExportArgs, ExifImportArgs,
+    ExifRemoveArgs, ExifShowArgs, ExtractArgs, FramesArgs, GlobalArgs, GrepArgs, HexdumpArgs,
+    HistoryArgs, IccArgs, IccCommand, IccExportArgs, IccImportArgs, IccRemoveArgs, InfoArgs,
+    InitCarrierArgs, InjectArgs, JoinDecodeArgs, KeygenArgs, KvArgs, KvCommand, KvGetArgs,
+    KvListArgs, KvRemoveArgs, KvSetArgs, LintArgs, ManArgs, MergeArgs, MigrateArgs, OutputArgs,
+    PassphraseArgs, PayloadFormat, PipeArgs, PreviewArgs, PrintArgs, RemoveArgs, RepairArgs,
+    ReportArgs, SanitizeArgs, ScanArgs, SignArgs, Size, SplitEncodeArgs, StatsArgs, StripArgs,
+    TextArgs, TextCommand, TextGetArgs, TextListArgs, TextRemoveArgs, TextSetArgs, VerifyArgs,
+    VerifyPayloadArgs, WatchArgs,
+};
+use crate::config;
+use crate::manifest::{Entry, Manifest, Step};
+use crate::output::{
+    self, noteln, out, outln, print_json, print_listing, Column, ListFormat, ReportFormat,
+};
+use crate::status::ExitStatus;
+use anyhow::{bail, Context, Result};
+use base64::Engine;
+use clap::CommandFactory;
+use indicatif::{ProgressBar, ProgressStyle};
+use notify::{
+    event::{EventKind, ModifyKind},
+    Event, RecursiveMode, Watcher,
+};
+use pngme::{
+    carrier::Carrier,
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    compression::Compression,
+    cover::generate_cover,
+    crypto::{ed25519_public_key, parse_ed25519_key, Encryption, Signature, ED25519_KEY_LENGTH},
+    decoy,
+    detect::{detect_bytes, DetectionReport},
+    diff::ChunkDiff,
+    envelope::{join_from_carriers, split_across_carriers, Envelope, EnvelopeError, SEGMENT_MAGIC},
+    fec,
+    history::HistoryEntry,
+    ihdr::ColorType,
+    jpeg::Jpeg,
+    lint::{lint_bytes_with_options, Severity},
+    obfuscation,
+    options::{ParseError, ParseOptions, VerifyOptions},
+    png::Png,
+    render::DataFormat,
+    repair::repair_bytes,
+    sanitize::sanitize_bytes,
+    stego::{StegoError, StegoMethod},
+    text::Text,
+};
+use rayon::prelude::*;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeSet, HashMap},
+    ffi::{OsStr, OsString},
+    fs,
+    io::{self, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc, Mutex,
+    },
+    time::{Duration, SystemTime},
+};
+
+// Set once from --no-progress, so reading a file does not need the global arguments threaded in.
+static SHOW_PROGRESS: AtomicBool = AtomicBool::new(true);
+
+// Set once from --max-memory, usize::MAX when there is no budget.
+static MAX_MEMORY: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+// Random temporary names only collide by accident, so a few retries are plenty.
+const TEMP_ATTEMPTS: usize = 8;
+
+// Files at least this large show a progress bar while they are read.
+const LARGE_FILE_LENGTH: u64 = 64 * 1024 * 1024;
+
+// Downloads are cut off here, so a misbehaving server cannot exhaust memory.
+#[cfg(feature = "http")]
+const MAX_DOWNLOAD_LENGTH: u64 = 256 * 1024 * 1024;
+
+pub fn run(cli: Cli) -> Result<()> {
+    let global = &cli.global;
+    SHOW_PROGRESS.store(!global.no_progress && !global.quiet, Ordering::Relaxed);
+    MAX_MEMORY.store(global.max_memory.unwrap_or(usize::MAX), Ordering::Relaxed);
+    output::set_quiet(global.quiet);
+
+    match cli.command {
+        Command::Encode(args) => {
+            // With --file the message slot holds the output path.
+            let output =
+                args.output.is_some() || (payload_elsewhere(&args) && args.message.is_some());
+            let files = expand_files(&args.file, output)?;
+            let passphrase = Passphrase::new(&args.passphrase)?;
+            let decoy_passphrase = Passphrase::decoy(args.decoy_passphrase_file.clone());
+            for_each_file(files, global.jobs, |file| {
+                encode(
+                    EncodeArgs {
+                        file,
+                        ..args.clone()
+                    },
+                    global,
+                    &passphrase,
+                    &decoy_passphrase,
+                )
+            })
+        }
+        Command::Decode(args) => {
+            let passphrase = Passphrase::new(&args.passphrase)?;
+            for_each_file(expand_files(&args.file, false)?, global.jobs, |file| {
+                decode(
+                    DecodeArgs {
+                        file,
+                        ..args.clone()
+                    },
+                    &passphrase,
+                )
+            })
+        }
+        Command::Report(args) => {
+            let passphrase = Passphrase::new(&args.passphrase)?;
+            for_each_file(expand_files(&args.file, false)?, global.jobs, |file| {
+                report(
+                    ReportArgs {
+                        file,
+                        ..args.clone()
+                    },
+                    &passphrase,
+                )
+            })
+        }
+        Command::VerifyPayload(args) => {
+            let passphrase = Passphrase::new(&args.passphrase)?;
+            for_each_file(expand_files(&args.file, false)?, global.jobs, |file| {
+                verify_payload(
+                    VerifyPayloadArgs {
+                        file,
+                        ..args.clone()
+                    },
+                    &passphrase,
+                )
+            })
+        }
+        Command::Migrate(args) => {
+            let files = expand_files(&args.file, args.output.is_some())?;
+            let passphrase = Passphrase::new(&args.passphrase)?;
+            for_each_file(files, global.jobs, |file| {
+                migrate(
+                    MigrateArgs {
+                        file,
+                        ..args.clone()
+                    },
+                    global,
+                    &passphrase,
+                )
+            })
+        }
+        Command::Remove(args) => {
+            for_each_file(expand_files(&args.file, false)?, global.jobs, |file| {
+                remove(
+                    RemoveArgs {
+                        file,
+                        ..args.clone()
+                    },
+                    global,
+                )
+            })
+        }
+        Command::Strip(args) => {
+            let files = expand_files(&args.file, args.output.is_some())?;
+            for_each_file(files, global.jobs, |file| {
+                strip(
+                    StripArgs {
+                        file,
+                        ..args.clone()
+                    },
+                    global,
+                )
+            })
+        }
+        Command::Dedupe(args) => {
+            let files = expand_files(&args.file, args.output.is_some())?;
+            for_each_file(files, global.jobs, |file| {
+                dedupe(
+                    DedupeArgs {
+                        file,
+                        ..args.clone()
+                    },
+                    global,
+                )
+            })
+        }
+        Command::Canonicalize(args) => {
+            let files = expand_files(&args.file, args.output.is_some())?;
+            for_each_file(files, global.jobs, |file| {
+                canonicalize(
+                    CanonicalizeArgs {
+                        file,
+                        ..args.clone()
+                    },
+                    global,
+                )
+            })
+        }
+        Command::Sanitize(args) => {
+            let files = expand_files(&args.file, args.output.is_some())?;
+            for_each_file(files, global.jobs, |file| {
+                sanitize(
+                    SanitizeArgs {
+                        file,
+                        ..args.clone()
+                    },
+                    global,
+                )
+            })
+        }
+        Command::Info(args) => {
+            for_each_file(expand_files(&args.file, false)?, global.jobs, |file| {
+                info(InfoArgs {
+                    file,
+                    ..args.clone()
+                })
+            })
+        }
+        Command::Print(args) => {
+            for_each_file(expand_files(&args.file, false)?, global.jobs, |file| {
+                print(PrintArgs {
+                    file,
+                    ..args.clone()
+                })
+            })
+        }
+        Command::Hexdump(args) => {
+            for_each_file(expand_files(&args.file, false)?, global.jobs, |file| {
+                hexdump(HexdumpArgs {
+                    file,
+                    ..args.clone()
+                })
+            })
+        }
+        Command::Capacity(args) => {
+            for_each_file(expand_files(&args.file, false)?, global.jobs, |file| {
+                capacity(CapacityArgs {
+                    file,
+                    ..args.clone()
+                })
+            })
+        }
+        Command::InitCarrier(args) => init_carrier(args, global),
+        Command::Detect(args) => {
+            for_each_file(expand_files(&args.file, false)?, global.jobs, |file| {
+                detect(DetectArgs {
+                    file,
+                    ..args.clone()
+                })
+            })
+        }
+        Command::Text(args) => text(args, global),
+        Command::Kv(args) => kv(args, global),
+        Command::Exif(args) => exif(args, global),
+        Command::Icc(args) => icc(args, global),
+        Command::Extract(args) => extract(args, global),
+        Command::Inject(args) => {
+            let files = expand_files(&args.file, args.output.is_some())?;
+            for_each_file(files, global.jobs, |file| {
+                inject(
+                    InjectArgs {
+                        file,
+                        ..args.clone()
+                    },
+                    global,
+                )
+            })
+        }
+        Command::Merge(args) => {
+            let files = expand_files(&args.file, args.output.is_some())?;
+            for_each_file(files, global.jobs, |file| {
+                merge(
+                    MergeArgs {
+                        file,
+                        ..args.clone()
+                    },
+                    global,
+                )
+            })
+        }
+        Command::Diff(args) => diff(args),
+        Command::Lint(args) => {
+            for_each_file(expand_files(&args.file, false)?, global.jobs, |file| {
+                lint(
+                    LintArgs {
+                        file,
+                        ..args.clone()
+                    },
+                    global,
+                )
+            })
+        }
+        Command::History(args) => {
+            for_each_file(expand_files(&args.file, false)?, global.jobs, |file| {
+                history(HistoryArgs {
+                    file,
+                    ..args.clone()
+                })
+            })
+        }
+        Command::Repair(args) => {
+            let files = expand_files(&args.file, args.output.is_some())?;
+            for_each_file(files, global.jobs, |file| {
+                repair(
+                    RepairArgs {
+                        file,
+                        ..args.clone()
+                    },
+                    global,
+                )
+            })
+        }
+        Command::Scan(args) => scan(args, global.jobs),
+        Command::Stats(args) => stats(args),
+        Command::Check(args) => check(args),
+        Command::Grep(args) => grep(args),
+        Command::Watch(args) => watch(args),
+        Command::SplitEncode(args) => split_encode(args, global),
+        Command::JoinDecode(args) => join_decode(args),
+        Command::Apng(args) => apng(args, global),
+        Command::Frames(args) => frames(args),
+        Command::Keygen(args) => keygen(args),
+        Command::Apply(args) => apply(args, global),
+        Command::Pipe(args) => {
+            let files = expand_files(&args.file, args.output.is_some())?;
+            for_each_file(files, global.jobs, |file| {
+                pipe(
+                    PipeArgs {
+                        file,
+                        ..args.clone()
+                    },
+                    global,
+                )
+            })
+        }
+        #[cfg(feature = "server")]
+        Command::Serve(args) => crate::server::serve(
+            &args.listen,
+            &crate::server::ServerOptions {
+                max_size: args.max_size,
+                token: args.token,
+            },
+        ),
+        Command::Sign(args) => {
+            let files = expand_files(&args.file, args.output.is_some())?;
+            for_each_file(files, global.jobs, |file| {
+                sign(
+                    SignArgs {
+                        file,
+                        ..args.clone()
+                    },
+                    global,
+                )
+            })
+        }
+        Command::Verify(args) => {
+            for_each_file(expand_files(&args.file, false)?, global.jobs, |file| {
+                verify(VerifyArgs {
+                    file,
+                    ..args.clone()
+                })
+            })
+        }
+        Command::Completions(args) => completions(args),
+        Command::Man(args) => man(args),
+    }
+}
+
+// A FILE argument that does not exist and holds glob characters is expanded, so commands can be
+// run over many files with a quoted pattern such as "assets/**/*.png".
+fn expand_files(pattern: &Path, has_output: bool) -> Result<Vec<PathBuf>> {
+    let pattern_str = pattern.to_string_lossy();
+    if is_stdio(pattern)
+        || is_url(pattern)
+        || pattern.exists()
+        || !pattern_str.contains(['*', '?', '['])
+    {
+        return Ok(vec![pattern.to_path_buf()]);
+    }
+
+    let files = glob::glob(&pattern_str)
+        .with_context(|| format!("{} is not a valid pattern", pattern_str))?
+        .filter_map(|entry| entry.ok())
+        .filter(|path| path.is_file())
+        .collect::<Vec<_>>();
+    if files.is_empty() {
+        bail!("No files match {}", pattern_str)
+    }
+    if files.len() > 1 && has_output {
+        bail!("OUTPUT can only be given for a single file")
+    }
+
+    Ok(files)
+}
+
+// Runs a command over each file, carrying on past failures and summarizing them at the end. With
+// --jobs files run in parallel, and each failure names its file since their output interleaves.
+fn for_each_file(
+    files: Vec<PathBuf>,
+    jobs: Option<usize>,
+    command: impl Fn(PathBuf) -> Result<()> + Sync,
+) -> Result<()> {
+    if files.len() == 1 {
+        return command(files.into_iter().next().unwrap());
+    }
+
+    let total = files.len();
+    let bar = progress_bar(total as u64, "{bar:40} {pos}/{len} files {elapsed}");
+    // Output is written with the bar suspended, so it is not drawn over.
+    let failures: Vec<ExitStatus> = match jobs {
+        None | Some(1) => files
+            .into_iter()
+            .filter_map(|file| {
+                let failure = bar.suspend(|| {
+                    noteln!("==> {} <==", file.display());
+                    command(file).err().map(|e| {
+                        eprintln!("Error: {:#}", e);
+                        ExitStatus::of(&e)
+                    })
+                });
+                bar.inc(1);
+                failure
+            })
+            .collect(),
+        Some(_) => thread_pool(jobs)?.install(|| {
+            files
+                .into_par_iter()
+                .filter_map(|file| {
+                    let failure = command(file.clone()).err().map(|e| {
+                        bar.suspend(|| eprintln!("Error: {}: {:#}", file.display(), e));
+                        ExitStatus::of(&e)
+                    });
+                    bar.inc(1);
+                    failure
+                })
+                .collect()
+        }),
+    };
+    bar.finish_and_clear();
+
+    let failed = failures.len();
+    noteln!("Processed {} files, {} failed", total, failed);
+    // The batch exits with the status its failures share, or a plain failure when they differ.
+    match failures.first() {
+        None => Ok(()),
+        Some(&status) => {
+            let status = match failures.iter().all(|&s| s == status) {
+                true => status,
+                false => ExitStatus::Failure,
+            };
+            Err(status
+                .error(format!("{} of {} files failed", failed, total))
+                .into())
+        }
+    }
+}
+
+// Drawn on standard error, and hidden when that is not a terminal or with --no-progress.
+fn progress_bar(length: u64, template: &str) -> ProgressBar {
+    if !SHOW_PROGRESS.load(Ordering::Relaxed) {
+        return ProgressBar::hidden();
+    }
+
+    let style = ProgressStyle::with_template(template).expect("progress templates are valid");
+    ProgressBar::new(length).with_style(style)
+}
+
+// Zero or no --jobs at all means one thread per core.
+fn thread_pool(jobs: Option<usize>) -> Result<rayon::ThreadPool> {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs.unwrap_or(0))
+        .build()
+        .context("Could not start worker threads")
+}
+
+// A path of - stands for standard input or output, so pngme can sit in a pipeline.
+fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+fn is_url(path: &Path) -> bool {
+    let path = path.to_string_lossy();
+    path.starts_with("https://") || path.starts_with("http://")
+}
+
+fn read_png(path: &Path, touch_time: bool) -> Result<Png> {
+    let _span = tracing::debug_span!("read", path = %path.display()).entered();
+    if is_url(path) {
+        let mut png = read_url(&path.to_string_lossy())?;
+        png.set_touch_time(touch_time);
+        return Ok(png);
+    }
+    let options = parse_options();
+    let bytes = read_bytes_within(path, options.max_total_memory)?;
+    // Running out of budget says nothing about whether the file is a valid PNG.
+    let mut png =
+        Png::from_bytes_with_options(&bytes, &options).map_err(|e| match e.is::<ParseError>() {
+            true => e.context(format!("Could not read {}", path.display())),
+            false => e.context(
+                ExitStatus::InvalidPng.error(format!("{} is not a valid PNG", path.display())),
+            ),
+        })?;
+    png.set_touch_time(touch_time);
+
+    Ok(png)
+}
+
+// JPEG carriers are recognised by their signature. Standard input and URLs are always read as PNG,
+// since sniffing would consume them.
+fn read_jpeg(path: &Path) -> Result<Option<Jpeg>> {
+    if is_stdio(path) || is_url(path) {
+        return Ok(None);
+    }
+    let mut signature = [0; Jpeg::SIGNATURE.len()];
+    let sniffed = fs::File::open(path).and_then(|mut file| file.read_exact(&mut signature));
+    if sniffed.is_err() || !Jpeg::is_jpeg(&signature) {
+        return Ok(None);
+    }
+
+    let bytes = read_bytes_within(path, parse_options().max_total_memory)?;
+    let jpeg = Jpeg::try_from(bytes.as_slice()).with_context(|| {
+        ExitStatus::InvalidPng.error(format!("{} is not a valid JPEG", path.display()))
+    })?;
+    Ok(Some(jpeg))
+}
+
+// The download is parsed as it arrives, up to MAX_DOWNLOAD_LENGTH.
+#[cfg(feature = "http")]
+fn read_url(url: &str) -> Result<Png> {
+    let response = ureq::get(url)
+        .call()
+        .with_context(|| format!("Could not download {}", url))?;
+    let reader = io::BufReader::new(response.into_reader().take(MAX_DOWNLOAD_LENGTH));
+    Png::read_from_with_options(reader, &parse_options())
+        .with_context(|| ExitStatus::InvalidPng.error(format!("{} is not a valid PNG", url)))
+}
+
+#[cfg(not(feature = "http"))]
+fn read_url(url: &str) -> Result<Png> {
+    bail!(
+        "Cannot read {}, pngme was built without the http feature",
+        url
+    )
+}
+
+// A file, or standard input for -.
+fn read_bytes(path: &Path) -> Result<Vec<u8>> {
+    read_bytes_within(path, None)
+}
+
+// The input counts against the memory budget too, so it is refused before being read.
+fn read_bytes_within(path: &Path, budget: Option<usize>) -> Result<Vec<u8>> {
+    let over_budget = |needed: u64| match budget {
+        Some(budget) if needed > budget as u64 => Err(ParseError::MemoryBudgetExceeded {
+            needed: needed.try_into().unwrap_or(usize::MAX),
+            budget,
+        })
+        .with_context(|| format!("Could not read {}", path.display())),
+        _ => Ok(()),
+    };
+
+    if !is_stdio(path) {
+        if let Ok(metadata) = fs::metadata(path) {
+            over_budget(metadata.len())?;
+        }
+        return read_file(path).with_context(|| format!("Could not read {}", path.display()));
+    }
+
+    let mut bytes = Vec::new();
+    let limit = budget.map_or(u64::MAX, |budget| budget as u64 + 1);
+    io::stdin()
+        .take(limit)
+        .read_to_end(&mut bytes)
+        .context("Could not read standard input")?;
+    over_budget(bytes.len() as u64)?;
+    Ok(bytes)
+}
+
+fn parse_options() -> ParseOptions {
+    ParseOptions {
+        max_total_memory: match MAX_MEMORY.load(Ordering::Relaxed) {
+            usize::MAX => None,
+            budget => Some(budget),
+        },
+        ..ParseOptions::default()
+    }
+}
+
+// Like fs::read, with a progress bar for large files.
+fn read_file(path: &Path) -> io::Result<Vec<u8>> {
+    let length = fs::metadata(path)?.len();
+    if length < LARGE_FILE_LENGTH {
+        return fs::read(path);
+    }
+
+    let bar = progress_bar(length, "{bar:40} {bytes}/{total_bytes} {msg}")
+        .with_message(path.display().to_string());
+    let mut bytes = Vec::with_capacity(length as usize);
+    bar.wrap_read(fs::File::open(path)?)
+        .read_to_end(&mut bytes)?;
+    bar.finish_and_clear();
+
+    Ok(bytes)
+}
+
+fn write_png(png: &Png, path: &Path) -> Result<()> {
+    write_atomic(&png.as_bytes(), path)
+}
+
+// Writes a temporary file next to the target and renames it over the target, so a crash leaves
+// either the old file or the new one and never half of each.
+fn write_atomic(bytes: &[u8], path: &Path) -> Result<()> {
+    let _span = tracing::debug_span!("write", path = %path.display()).entered();
+    let name = path
+        .file_name()
+        .with_context(|| format!("{} is not a file path", path.display()))?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let (temp, mut file) =
+        create_temp(dir, name).with_context(|| format!("Could not write {}", path.display()))?;
+
+    let result = (|| -> io::Result<()> {
+        if let Ok(metadata) = fs::metadata(path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        fs::rename(&temp, path)
+    })();
+    if let Err(e) = result {
+        let _ = fs::remove_file(&temp);
+        return Err(e).with_context(|| format!("Could not write {}", path.display()));
+    }
+
+    // Persist the rename itself.
+    #[cfg(unix)]
+    if let Ok(dir) = fs::File::open(dir) {
+        let _ = dir.sync_all();
+    }
+
+    Ok(())
+}
+
+// A fresh file under an unpredictable name, never one that is already there, so a symlink
+// planted beside the output cannot redirect the write.
+fn create_temp(dir: &Path, name: &OsStr) -> io::Result<(PathBuf, fs::File)> {
+    let mut attempts = 0;
+    loop {
+        let suffix: u64 = rand::random();
+        let temp = dir.join(format!(".{}.{:016x}.tmp", name.to_string_lossy(), suffix));
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp)
+        {
+            Ok(file) => return Ok((temp, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempts < TEMP_ATTEMPTS => {
+                attempts += 1
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn report_changes(original: &Png, png: &Png, path: &Path) {
+    // Chunks are matched by their bytes, so an edited chunk shows as removed and added.
+    let mut unmatched: Vec<&Chunk> = original.chunks().iter().collect();
+    let mut added = Vec::new();
+    for chunk in png.chunks() {
+        match unmatched
+            .iter()
+            .position(|c| c.as_bytes() == chunk.as_bytes())
+        {
+            Some(index) => {
+                unmatched.remove(index);
+            }
+            None => added.push(chunk),
+        }
+    }
+
+    outln!("Would write {}", path.display());
+    for chunk in &unmatched {
+        outln!("  - {:#}", chunk);
+    }
+    for chunk in &added {
+        outln!("  + {:#}", chunk);
+    }
+    let (before, after) = (original.as_bytes().len(), png.as_bytes().len());
+    outln!(
+        "  {} bytes -> {} bytes ({:+})",
+        before,
+        after,
+        after as i64 - before as i64
+    );
+}
+
+fn backup_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(suffix);
+    PathBuf::from(backup)
+}
+
+// Like write_png, but - writes to standard output unless that is a terminal, and an existing file
+// is copied aside first with --backup. With --dry-run the changes from the original are reported
+// instead.
+fn write_output(original: &Png, png: &Png, path: &Path, global: &GlobalArgs) -> Result<()> {
+    if global.dry_run {
+        report_changes(original, png, path);
+        return Ok(());
+    }
+
+    if global.audit {
+        let mut png = png.clone();
+        record_history(&mut png, global)?;
+        return write_output_bytes(&png.as_bytes(), path, global);
+    }
+    write_output_bytes(&png.as_bytes(), path, global)
+}
+
+fn record_history(png: &mut Png, global: &GlobalArgs) -> Result<()> {
+    let entry = HistoryEntry::new(&global.operation, global.audit_signer.as_deref())?;
+    png.record_history(entry)
+        .context("Could not record the change with --audit")
+}
+
+fn write_output_bytes(bytes: &[u8], path: &Path, global: &GlobalArgs) -> Result<()> {
+    if !is_stdio(path) {
+        if let Some(suffix) = &global.backup {
+            if path.exists() {
+                let backup = backup_path(path, suffix);
+                fs::copy(path, &backup)
+                    .with_context(|| format!("Could not back up to {}", backup.display()))?;
+            }
+        }
+        return write_atomic(bytes, path);
+    }
+
+    let mut stdout = io::stdout().lock();
+    if stdout.is_terminal() && !global.force {
+        bail!("Refusing to write an image to a terminal, redirect the output or pass --force")
+    }
+    stdout
+        .write_all(bytes)
+        .and_then(|_| stdout.flush())
+        .context("Could not write standard output")
+}
+
+fn read_key(path: &Path) -> Result<[u8; ED25519_KEY_LENGTH]> {
+    let bytes = fs::read(path).with_context(|| format!("Could not read {}", path.display()))?;
+    parse_ed25519_key(&bytes).with_context(|| format!("{} is not an Ed25519 key", path.display()))
+}
+
+// Lets scripts and CI supply the passphrases without a terminal.
+const PASSPHRASE_ENV: &str = "PNGME_PASSPHRASE";
+const DECOY_PASSPHRASE_ENV: &str = "PNGME_DECOY_PASSPHRASE";
+
+// Asks for the passphrase at most once per command, or takes it from --passphrase-file,
+// PNGME_PASSPHRASE or the OS keyring. One is shared by every file of a batch, and the lock is
+// held while prompting, so parallel jobs do not ask over each other.
+struct Passphrase {
+    label: &'static str,
+    env: &'static str,
+    file: Option<PathBuf>,
+    #[cfg(feature = "keyring")]
+    keyring: Option<pngme::keyring::Keyring>,
+    value: Mutex<Option<String>>,
+}
+
+impl Passphrase {
+    fn new(args: &PassphraseArgs) -> Result<Self> {
+        Ok(Self {
+            label: "Passphrase",
+            env: PASSPHRASE_ENV,
+            file: args.passphrase_file.clone(),
+            #[cfg(feature = "keyring")]
+            keyring: args
+                .use_keyring
+                .as_deref()
+                .map(pngme::keyring::Keyring::new)
+                .transpose()?,
+            value: Mutex::new(None),
+        })
+    }
+
+    // The second passphrase of a deniable container, from --decoy-passphrase-file or
+    // PNGME_DECOY_PASSPHRASE; never kept in the keyring.
+    fn decoy(file: Option<PathBuf>) -> Self {
+        Self {
+            label: "Decoy passphrase",
+            env: DECOY_PASSPHRASE_ENV,
+            file,
+            #[cfg(feature = "keyring")]
+            keyring: None,
+            value: Mutex::new(None),
+        }
+    }
+
+    // New passphrases are asked for twice, and stored when a keyring profile has none yet.
+    fn get(&self, new: bool) -> Result<String> {
+        let mut stored = self.value.lock().unwrap();
+        if let Some(value) = &*stored {
+            return Ok(value.clone());
+        }
+
+        let value = match &self.file {
+            Some(path) => Some(read_passphrase_file(path)?),
+            None => std::env::var(self.env).ok(),
+        };
+        if let Some(value) = value {
+            if value.is_empty() {
+                bail!("{} must not be empty", self.label)
+            }
+            *stored = Some(value.clone());
+            return Ok(value);
+        }
+
+        #[cfg(feature = "keyring")]
+        if let Some(keyring) = &self.keyring {
+            match keyring.load() {
+                Ok(value) => {
+                    *stored = Some(value.clone());
+                    return Ok(value);
+                }
+                Err(e) if !new => return Err(e),
+                Err(_) => {}
+            }
+        }
+
+        let value = prompt(self.label, new)?;
+
+        #[cfg(feature = "keyring")]
+        if let Some(keyring) = &self.keyring {
+            keyring.store(&value)?;
+            noteln!("Stored passphrase for profile {:?}", keyring.profile());
+        }
+
+        *stored = Some(value.clone());
+        Ok(value)
+    }
+}
+
+// Only the first line counts, so a trailing newline is not part of the passphrase.
+fn read_passphrase_file(path: &Path) -> Result<String> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Could not read a passphrase from {}", path.display()))?;
+    Ok(text.lines().next().unwrap_or_default().to_string())
+}
+
+fn prompt(label: &str, confirm: bool) -> Result<String> {
+    let value = rpassword::prompt_password(format!("{}: ", label))?;
+    if value.is_empty() {
+        bail!("{} must not be empty", label)
+    }
+    if confirm
+        && rpassword::prompt_password(format!("Confirm {}: ", label.to_lowercase()))? != value
+    {
+        bail!("Passphrases do not match")
+    }
+
+    Ok(value)
+}
+
+fn payload_envelope(message: Option<String>, file: Option<&Path>) -> Result<Envelope> {
+    match (message, file) {
+        (_, Some(path)) if is_stdio(path) => Ok(Envelope::new(read_bytes(path)?)),
+        (_, Some(path)) => {
+            Envelope::from_file(path).with_context(|| format!("Could not read {}", path.display()))
+        }
+        (Some(message), None) => {
+            Ok(Envelope::new(message.into_bytes()).with_mime_type("text/plain"))
+        }
+        (None, None) => bail!("Nothing to encode, pass a message or --file"),
+    }
+}
+
+// Binary payloads can be passed as hex or base64 text, which survives shells and terminals.
+fn decode_payload(mut envelope: Envelope, encoding: DataFormat) -> Result<Envelope> {
+    if encoding == DataFormat::LossyUtf8 {
+        return Ok(envelope);
+    }
+
+    let text = String::from_utf8(envelope.payload).context("An encoded payload must be text")?;
+    envelope.payload = encoding
+        .parse(&text)
+        .context("Could not decode the payload")?;
+    envelope.mime_type = None;
+    Ok(envelope)
+}
+
+// Compress, sign with GPG, encrypt, then authenticate, mirroring what decoding undoes.
+fn seal_envelope(
+    mut envelope: Envelope,
+    args: &EnvelopeArgs,
+    passphrase: &Passphrase,
+) -> Result<Envelope> {
+    if let Some(compression) = args.compress {
+        envelope = envelope.compressed(compression);
+    }
+
+    #[cfg(feature = "gpg")]
+    {
+        let gpg = pngme::gpg::Gpg::new();
+        if let Some(key) = &args.gpg_sign {
+            envelope = envelope.gpg_signed(&gpg, Some(key.as_str()).filter(|k| !k.is_empty()))?;
+        }
+        if !args.gpg_recipients.is_empty() {
+            let recipients: Vec<&str> = args.gpg_recipients.iter().map(String::as_str).collect();
+            envelope = envelope.gpg_encrypted(&gpg, &recipients)?;
+        }
+    }
+
+    if args.encrypt {
+        envelope = envelope.encrypted(&passphrase.get(true)?)?;
+    }
+    if !args.recipients.is_empty() {
+        let recipients: Vec<&str> = args.recipients.iter().map(String::as_str).collect();
+        envelope = envelope.encrypted_to(&recipients)?;
+    }
+
+    if args.hmac {
+        envelope = envelope.authenticated(&passphrase.get(true)?)?;
+    }
+    if let Some(path) = &args.sign {
+        envelope = envelope.signed_ed25519(&read_key(path)?)?;
+    }
+
+    Ok(envelope)
+}
+
+fn open_envelope(
+    mut envelope: Envelope,
+    args: &OutputArgs,
+    passphrase: &Passphrase,
+) -> Result<Envelope> {
+    match (&args.verify, &envelope.signature) {
+        (Some(path), _) => envelope
+            .verify_ed25519(&read_key(path)?)
+            .context("Refusing to decode the payload")?,
+        (None, Some(Signature::Ed25519 { .. })) => {
+            eprintln!("Warning: payload is signed but was not verified, pass --verify")
+        }
+        (None, Some(Signature::Hmac { .. })) => envelope
+            .verify_hmac(&passphrase.get(false)?)
+            .context("Refusing to decode the payload")?,
+        _ => {}
+    }
+
+    envelope = match &envelope.encryption {
+        None => envelope,
+        Some(Encryption::Passphrase(_)) => envelope.decrypted(&passphrase.get(false)?)?,
+        Some(Encryption::Recipients) => decrypt_with_identity_files(envelope, &args.identities)?,
+        #[cfg(feature = "gpg")]
+        Some(Encryption::Gpg) => envelope.gpg_decrypted(&pngme::gpg::Gpg::new())?,
+        #[cfg(not(feature = "gpg"))]
+        Some(Encryption::Gpg) => {
+            bail!("Payload is encrypted with GPG, rebuild with the gpg feature")
+        }
+    };
+
+    if envelope.signature == Some(Signature::Gpg) {
+        #[cfg(feature = "gpg")]
+        {
+            let (verified, signer) = envelope.gpg_verified(&pngme::gpg::Gpg::new())?;
+            eprintln!("Good GPG signature from {}", signer);
+            envelope = verified;
+        }
+        #[cfg(not(feature = "gpg"))]
+        bail!("Payload is signed with GPG, rebuild with the gpg feature")
+    }
+
+    envelope.decompressed()
+}
+
+fn decrypt_with_identity_files(envelope: Envelope, paths: &[PathBuf]) -> Result<Envelope> {
+    if paths.is_empty() {
+        bail!("Payload is encrypted to recipients, pass --identity")
+    }
+
+    let mut error = None;
+    for path in paths {
+        let identities = fs::read_to_string(path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+        match envelope.clone().decrypted_with_identities(&identities) {
+            Ok(envelope) => return Ok(envelope),
+            Err(e) => error = Some(e),
+        }
+    }
+
+    Err(error.unwrap())
+}
+
+// How much of a binary payload decode shows on a terminal without --full.
+const PAYLOAD_PREVIEW_LENGTH: usize = 256;
+
+// Payloads written before envelopes existed, or by another tool, come back as raw bytes.
+enum Decoded {
+    Envelope(Envelope),
+    Raw(Vec<u8>),
+}
+
+impl Decoded {
+    fn payload(&self) -> &[u8] {
+        match self {
+            Self::Envelope(envelope) => &envelope.payload,
+            Self::Raw(bytes) => bytes,
+        }
+    }
+}
+
+fn output_decoded(decoded: &Decoded, args: &OutputArgs) -> Result<()> {
+    let format = match args.format {
+        PayloadFormat::Json => {
+            print_json(&decoded_json(decoded, args)?);
+            return Ok(());
+        }
+        PayloadFormat::Data(format) => format,
+    };
+
+    let payload = match (decoded, &args.output_dir) {
+        (Decoded::Envelope(envelope), Some(dir)) => {
+            fs::create_dir_all(dir)?;
+            let path = envelope.write_to_dir(dir)?;
+            outln!("Wrote {}", path.display());
+            return Ok(());
+        }
+        (decoded, _) => decoded.payload(),
+    };
+
+    // Text is always printed whole. Binary payloads are summarized on a terminal, and anywhere
+    // --max-preview is given.
+    let limit = match (args.preview.max_preview, io::stdout().is_terminal()) {
+        (None, false) => None,
+        _ => args.preview.limit(PAYLOAD_PREVIEW_LENGTH),
+    };
+    let summary = match std::str::from_utf8(payload) {
+        Ok(_) => None,
+        Err(_) => output::preview_ends(payload, limit),
+    };
+    match summary {
+        Some((head, tail)) => {
+            outln!("{}…{}", format.render(head), format.render(tail));
+            noteln!(
+                "Showing {} of {} bytes, pass --full for all of them",
+                head.len() + tail.len(),
+                payload.len()
+            );
+        }
+        None => outln!("{}", format.render(payload)),
+    }
+
+    Ok(())
+}
+
+// A payload written to --output-dir is reported by its path rather than inlined.
+fn decoded_json(decoded: &Decoded, args: &OutputArgs) -> Result<Value> {
+    let envelope = match decoded {
+        Decoded::Envelope(envelope) => envelope,
+        Decoded::Raw(bytes) => return Ok(output::raw_json(bytes)),
+    };
+
+    let mut value = output::envelope_json(envelope);
+    if let Some(dir) = &args.output_dir {
+        fs::create_dir_all(dir)?;
+        let path = envelope.write_to_dir(dir)?;
+        value["path"] = json!(path);
+        if let Some(object) = value.as_object_mut() {
+            object.remove("payload");
+        }
+    }
+
+    Ok(value)
+}
+
+fn encode(
+    mut args: EncodeArgs,
+    global: &GlobalArgs,
+    passphrase: &Passphrase,
+    decoy_passphrase: &Passphrase,
+) -> Result<()> {
+    // With --file the message slot holds the output path.
+    let output = match (
+        payload_elsewhere(&args),
+        args.message.take(),
+        args.output.take(),
+    ) {
+        (true, Some(_), Some(_)) => bail!("Pass either a message or --file, not both"),
+        (true, Some(output), None) => Some(PathBuf::from(output)),
+        (_, message, output) => {
+            args.message = message;
+            output
+        }
+    };
+    let output = output.unwrap_or_else(|| args.file.clone());
+    if let Some(jpeg) = read_jpeg(&args.file)? {
+        return encode_jpeg(jpeg, &args, &output, global, passphrase, decoy_passphrase);
+    }
+
+    let mut png = read_png(&args.file, global.touch_time)?;
+    let original = png.clone();
+    let chunk_type = args.chunk_type.to_string();
+    let (envelope, bytes) = encode_payload(&args, passphrase, decoy_passphrase)?;
+
+    // Pixel methods overwrite whatever was there, only chunk storage can pile up duplicates.
+    let target = match args.obfuscate {
+        true => obfuscation::derive_chunk_type(&passphrase.get(true)?)?,
+        false => args.chunk_type,
+    };
+    let duplicate = (args.obfuscate || args.method == StegoMethod::Chunk)
+        && png.chunks().iter().any(|c| *c.chunk_type() == target);
+    let previous = match (duplicate, duplicate_policy(&args)) {
+        (false, _) => None,
+        (true, DuplicatePolicy::Append) => {
+            eprintln!(
+                "Warning: {} already has a {} chunk, adding another (use --replace to update it)",
+                args.file.display(),
+                target
+            );
+            None
+        }
+        (true, DuplicatePolicy::Error) => {
+            bail!("{} already has a {} chunk", args.file.display(), target)
+        }
+        (true, DuplicatePolicy::Replace) => {
+            let mut without = png.clone();
+            without.remove_all_chunks(&target.to_string())?;
+            Some(std::mem::replace(&mut png, without))
+        }
+    };
+
+    if args.obfuscate {
+        let chunk_type = png.embed_obfuscated(&passphrase.get(true)?, &bytes)?;
+        noteln!("Stored payload in a {} chunk", chunk_type);
+    } else if args.scatter {
+        png.embed_keyed(args.method, &chunk_type, &bytes, &passphrase.get(true)?)?;
+    } else if args.method == StegoMethod::Chunk && !args.deniable && !args.fec {
+        png.embed_envelope(args.method, &chunk_type, &envelope)?;
+    } else {
+        png.embed(args.method, &chunk_type, &bytes)?;
+    }
+
+    if let Some(mut previous) = previous {
+        let added = png
+            .chunks()
+            .iter()
+            .filter(|c| *c.chunk_type() == target)
+            .cloned()
+            .collect();
+        previous.replace_chunks_of_type(&target.to_string(), added)?;
+        png = previous;
+    }
+
+    write_output(&original, &png, &output, global)
+}
+
+// JPEG payloads go in APP15 segments, so only the options that shape the payload itself apply.
+fn encode_jpeg(
+    mut jpeg: Jpeg,
+    args: &EncodeArgs,
+    output: &Path,
+    global: &GlobalArgs,
+    passphrase: &Passphrase,
+    decoy_passphrase: &Passphrase,
+) -> Result<()> {
+    if args.obfuscate || args.scatter || args.method != StegoMethod::Chunk {
+        bail!("JPEG carriers only support chunk embedding, without --obfuscate or --scatter")
+    }
+    let slot = args.chunk_type.to_string();
+    let (envelope, bytes) = encode_payload(args, passphrase, decoy_passphrase)?;
+
+    if jpeg.slots().contains(&slot) {
+        match duplicate_policy(args) {
+            DuplicatePolicy::Append => eprintln!(
+                "Warning: {} already has a {} payload, adding another (use --replace to update it)",
+                args.file.display(),
+                slot
+            ),
+            DuplicatePolicy::Error => {
+                bail!("{} already has a {} payload", args.file.display(), slot)
+            }
+            DuplicatePolicy::Replace => {
+                jpeg.remove_slot(&slot);
+            }
+        }
+    }
+    match args.deniable || args.fec {
+        true => jpeg.embed(args.method, &slot, &bytes)?,
+        false => jpeg.embed_envelope(args.method, &slot, &envelope)?,
+    }
+
+    if global.dry_run {
+        noteln!("Would add a {} payload to {}", slot, output.display());
+        return Ok(());
+    }
+    write_output_bytes(&jpeg.as_bytes(), output, global)
+}
+
+// Whether the payload comes from somewhere other than the message argument.
+fn payload_elsewhere(args: &EncodeArgs) -> bool {
+    #[cfg(feature = "clipboard")]
+    if args.from_clipboard {
+        return true;
+    }
+    args.payload_file.is_some()
+}
+
+// The clipboard is only read once the message is needed.
+fn message(args: &EncodeArgs) -> Result<Option<String>> {
+    #[cfg(feature = "clipboard")]
+    if args.from_clipboard {
+        return pngme::clipboard::read_text().map(Some);
+    }
+    Ok(args.message.clone())
+}
+
+fn duplicate_policy(args: &EncodeArgs) -> DuplicatePolicy {
+    match args.replace {
+        true => DuplicatePolicy::Replace,
+        false => args.on_duplicate,
+    }
+}
+
+// The sealed envelope, and the bytes to store when --deniable or --fec wrap it further.
+fn encode_payload(
+    args: &EncodeArgs,
+    passphrase: &Passphrase,
+    decoy_passphrase: &Passphrase,
+) -> Result<(Envelope, Vec<u8>)> {
+    let envelope = payload_envelope(message(args)?, args.payload_file.as_deref())?;
+    let envelope = decode_payload(envelope, args.input_encoding)?;
+    let envelope = seal_envelope(envelope, &args.envelope, passphrase)?;
+    let mut bytes = envelope.to_bytes()?;
+
+    if args.deniable {
+        let decoy = match (args.decoy.clone(), &args.decoy_file) {
+            (None, None) => None,
+            (message, file) => Some(payload_envelope(message, file.as_deref())?),
+        };
+        bytes = match decoy {
+            Some(decoy) => {
+                let decoy_passphrase = decoy_passphrase.get(true)?;
+                let passphrase = passphrase.get(true)?;
+                if decoy_passphrase == passphrase {
+                    bail!("The decoy passphrase must differ from the real one")
+                }
+                decoy::seal_pair(
+                    (&decoy_passphrase, &decoy.to_bytes()?),
+                    (&passphrase, &bytes),
+                )?
+            }
+            None => decoy::seal_single(&passphrase.get(true)?, &bytes)?,
+        };
+    }
+    if args.fec {
+        bytes = fec::protect(&bytes);
+    }
+
+    Ok((envelope, bytes))
+}
+
+fn decode(args: DecodeArgs, passphrase: &Passphrase) -> Result<()> {
+    let decoded = match read_jpeg(&args.file)? {
+        Some(jpeg) => decode_jpeg(&jpeg, &args, passphrase)?,
+        None => {
+            let png = read_png(&args.file, false)?;
+            if args.all {
+                return decode_all(&png, &args, passphrase);
+            }
+            decode_png(&png, &args, passphrase)?
+        }
+    };
+
+    #[cfg(feature = "clipboard")]
+    if args.to_clipboard {
+        return copy_decoded(&decoded);
+    }
+    output_decoded(&decoded, &args.output)
+}
+
+fn decode_png(png: &Png, args: &DecodeArgs, passphrase: &Passphrase) -> Result<Decoded> {
+    let chunk_type = args.chunk_type.to_string();
+    let bytes = if let Some(index) = args.index {
+        let chunk = png.nth_chunk_by_type(&chunk_type, index).with_context(|| {
+            ExitStatus::NotFound.error(format!(
+                "{} has no {} chunk at index {}",
+                args.file.display(),
+                chunk_type,
+                index
+            ))
+        })?;
+        if chunk.chunk_data().starts_with(&SEGMENT_MAGIC) {
+            bail!(
+                "Chunk {} is a segment of a split payload, decode without --index to join it",
+                index
+            );
+        }
+        chunk.chunk_data().to_vec()
+    } else if args.obfuscate {
+        png.extract_obfuscated(&passphrase.get(false)?)?
+    } else if args.scatter {
+        png.extract_keyed(args.method, &chunk_type, &passphrase.get(false)?)?
+    } else {
+        png.extract(args.method, &chunk_type)?
+    };
+    decode_extracted(png, bytes, args, passphrase)
+}
+
+fn decode_jpeg(jpeg: &Jpeg, args: &DecodeArgs, passphrase: &Passphrase) -> Result<Decoded> {
+    if args.all || args.index.is_some() || args.obfuscate || args.scatter {
+        bail!("JPEG carriers do not support --all, --index, --obfuscate or --scatter")
+    }
+    let bytes = jpeg.extract(args.method, &args.chunk_type.to_string())?;
+    decode_extracted(jpeg, bytes, args, passphrase)
+}
+
+// Segmented envelopes are joined from every block in the carrier's slot.
+fn decode_extracted(
+    carrier: &dyn Carrier,
+    bytes: Vec<u8>,
+    args: &DecodeArgs,
+    passphrase: &Passphrase,
+) -> Result<Decoded> {
+    let bytes = unwrap_payload(bytes, args.deniable, passphrase)?;
+    if args.method == StegoMethod::Chunk && bytes.starts_with(&SEGMENT_MAGIC) {
+        let envelope =
+            carrier.extract_stored_envelope(args.method, &args.chunk_type.to_string())?;
+        return Ok(Decoded::Envelope(open_envelope(
+            envelope,
+            &args.output,
+            passphrase,
+        )?));
+    }
+    open_bytes(bytes, &args.output, passphrase)
+}
+
+#[cfg(feature = "clipboard")]
+fn copy_decoded(decoded: &Decoded) -> Result<()> {
+    let text = std::str::from_utf8(decoded.payload())
+        .context("Only text payloads can be copied to the clipboard")?;
+
+    pngme::clipboard::write_text(text)?;
+    noteln!("Copied the payload to the clipboard");
+    Ok(())
+}
+
+// Chunks of the type with their offsets, numbered among that type as --index counts them.
+fn numbered_chunks(png: &Png, chunk_type: ChunkType) -> Vec<(usize, (usize, &Chunk))> {
+    png.chunks_with_offsets()
+        .filter(|(_, chunk)| *chunk.chunk_type() == chunk_type)
+        .enumerate()
+        .collect()
+}
+
+fn decode_all(png: &Png, args: &DecodeArgs, passphrase: &Passphrase) -> Result<()> {
+    let matches = numbered_chunks(png, args.chunk_type);
+    if matches.is_empty() {
+        return Err(StegoError::NoPayload.into());
+    }
+
+    let json = args.output.format == PayloadFormat::Json;
+    let mut listing = Vec::new();
+    for (index, (offset, chunk)) in matches {
+        if chunk.chunk_data().starts_with(&SEGMENT_MAGIC) {
+            match json {
+                true => listing.push(json!({"index": index, "offset": offset, "segment": true})),
+                false => outln!(
+                    "[{}] offset {}: segment of a split payload, decode without --all to join it",
+                    index,
+                    offset
+                ),
+            }
+            continue;
+        }
+
+        let bytes = unwrap_payload(chunk.chunk_data().to_vec(), args.deniable, passphrase)?;
+        let decoded = open_bytes(bytes, &args.output, passphrase)?;
+        if json {
+            let mut value = decoded_json(&decoded, &args.output)?;
+            value["index"] = json!(index);
+            value["offset"] = json!(offset);
+            listing.push(value);
+        } else {
+            out!("[{}] offset {}: ", index, offset);
+            output_decoded(&decoded, &args.output)?;
+        }
+    }
+
+    if json {
+        print_json(&Value::Array(listing));
+    }
+    Ok(())
+}
+
+// Undoes the error correction and deniable container layers around the envelope.
+fn unwrap_payload(mut bytes: Vec<u8>, deniable: bool, passphrase: &Passphrase) -> Result<Vec<u8>> {
+    if fec::is_protected(&bytes) {
+        let recovered = fec::recover(&bytes)?;
+        if recovered.corrected > 0 {
+            noteln!("Corrected {} damaged bytes", recovered.corrected);
+        }
+        bytes = recovered.data;
+    }
+    if deniable {
+        bytes = decoy::open(&passphrase.get(false)?, &bytes)?;
+    }
+
+    Ok(bytes)
+}
+
+fn open_bytes(bytes: Vec<u8>, args: &OutputArgs, passphrase: &Passphrase) -> Result<Decoded> {
+    if !Envelope::is_envelope(&bytes) {
+        return Ok(Decoded::Raw(bytes));
+    }
+
+    let envelope = Envelope::from_bytes(&bytes)?;
+    Ok(Decoded::Envelope(open_envelope(
+        envelope, args, passphrase,
+    )?))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CheckStatus {
+    Pass,
+    Fail,
+    Skipped,
+}
+
+impl CheckStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pass => "pass",
+            Self::Fail => "fail",
+            Self::Skipped => "skipped",
+        }
+    }
+}
+
+struct PayloadCheck {
+    name: &'static str,
+    status: CheckStatus,
+    detail: String,
+}
+
+impl PayloadCheck {
+    fn of(name: &'static str, result: Result<()>) -> Self {
+        let (status, detail) = match result {
+            Ok(()) => (CheckStatus::Pass, String::new()),
+            Err(e) => (CheckStatus::Fail, format!("{:#}", e)),
+        };
+        Self {
+            name,
+            status,
+            detail,
+        }
+    }
+
+    fn skipped(name: &'static str, detail: &str) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Skipped,
+            detail: detail.to_string(),
+        }
+    }
+}
+
+// Opens the payload as far as the given keys allow to run every check it can, but only ever
+// prints a digest of it. The digest is of the payload decode would reveal, or of the stored
+// ciphertext when there is no key to decrypt it with.
+fn report(args: ReportArgs, passphrase: &Passphrase) -> Result<()> {
+    let carrier: Box<dyn Carrier> = match read_jpeg(&args.file)? {
+        Some(jpeg) => Box::new(jpeg),
+        None => Box::new(read_png(&args.file, false)?),
+    };
+    let slot = args.chunk_type.to_string();
+    let mut checks = Vec::new();
+
+    let mut bytes = carrier.extract(args.method, &slot)?;
+    if fec::is_protected(&bytes) {
+        match fec::recover(&bytes) {
+            Ok(recovered) => {
+                checks.push(PayloadCheck {
+                    name: "error_correction",
+                    status: CheckStatus::Pass,
+                    detail: format!("{} damaged bytes corrected", recovered.corrected),
+                });
+                bytes = recovered.data;
+            }
+            Err(e) => checks.push(PayloadCheck::of("error_correction", Err(e))),
+        }
+    }
+
+    let segmented = args.method == StegoMethod::Chunk && bytes.starts_with(&SEGMENT_MAGIC);
+    let segments = match segmented {
+        true => Some(
+            carrier
+                .extract_all(args.method, &slot)?
+                .iter()
+                .filter(|block| block.starts_with(&SEGMENT_MAGIC))
+                .count(),
+        ),
+        false => None,
+    };
+    let stored = match segmented {
+        true => Some(carrier.extract_stored_envelope(args.method, &slot)),
+        false if Envelope::is_envelope(&bytes) => Some(Envelope::from_bytes(&bytes)),
+        false => None,
+    };
+    let stored = match stored {
+        Some(Ok(envelope)) => {
+            checks.push(PayloadCheck::of("checksum", Ok(())));
+            Some(envelope)
+        }
+        Some(Err(e)) if e.downcast_ref() == Some(&EnvelopeError::ChecksumMismatch) => {
+            checks.push(PayloadCheck::of("checksum", Err(e)));
+            None
+        }
+        Some(Err(e)) => return Err(e),
+        None => None,
+    };
+
+    let (payload, sealed) = match &stored {
+        Some(envelope) => {
+            let opened = check_envelope(envelope, &args, passphrase, &mut checks)?;
+            let sealed = opened.is_none();
+            (opened.unwrap_or_else(|| envelope.payload.clone()), sealed)
+        }
+        None => (bytes, false),
+    };
+
+    let engine = base64::engine::general_purpose::STANDARD;
+    let signer = match stored.as_ref().and_then(|e| e.signature.as_ref()) {
+        Some(Signature::Ed25519 { public_key, .. }) => Some(engine.encode(public_key)),
+        _ => None,
+    };
+    let sha256 = DataFormat::Hex.render(&Sha256::digest(&payload));
+    let failed = checks
+        .iter()
+        .filter(|check| check.status == CheckStatus::Fail)
+        .count();
+
+    if args.format == ReportFormat::Json {
+        let check_values: Vec<Value> = checks
+            .iter()
+            .map(|check| {
+                json!({
+                    "name": check.name,
+                    "status": check.status.as_str(),
+                    "detail": Some(&check.detail).filter(|detail| !detail.is_empty()),
+                })
+            })
+            .collect();
+        print_json(&json!({
+            "length": payload.len(),
+            "sha256": sha256,
+            "sealed": sealed,
+            "envelope": stored.is_some(),
+            "segments": segments,
+            "mime_type": stored.as_ref().and_then(|e| e.mime_type.as_deref()),
+            "filename": stored.as_ref().and_then(|e| e.filename.as_deref()),
+            "compression": stored.as_ref().map(|e| compression_name(e.compression)),
+            "encryption": stored.as_ref().and_then(|e| e.encryption.as_ref()).map(encryption_name),
+            "signature": stored.as_ref().and_then(|e| e.signature.as_ref()).map(signature_name),
+            "signer": signer,
+            "status": if failed > 0 { "fail" } else { "pass" },
+            "checks": check_values,
+        }));
+    } else {
+        match sealed {
+            true => outln!("Payload: {} bytes, still encrypted", payload.len()),
+            false => outln!("Payload: {} bytes", payload.len()),
+        }
+        outln!("SHA-256: {}", sha256);
+        match &stored {
+            Some(envelope) => {
+                if let Some(mime_type) = &envelope.mime_type {
+                    outln!("MIME type: {}", mime_type);
+                }
+                if let Some(filename) = &envelope.filename {
+                    outln!("Filename: {}", filename);
+                }
+                outln!("Compression: {}", compression_name(envelope.compression));
+                outln!(
+                    "Encryption: {}",
+                    envelope.encryption.as_ref().map_or("none", encryption_name)
+                );
+                match (&envelope.signature, &signer) {
+                    (Some(signature), Some(signer)) => {
+                        outln!("Signature: {} by {}", signature_name(signature), signer)
+                    }
+                    (Some(signature), None) => outln!("Signature: {}", signature_name(signature)),
+                    (None, _) => outln!("Signature: none"),
+                }
+            }
+            None => outln!("Envelope: none, the payload is stored as raw bytes"),
+        }
+        if let Some(segments) = segments {
+            outln!("Segments: {}", segments);
+        }
+        for check in &checks {
+            match check.detail.is_empty() {
+                true => outln!("Check {}: {}", check.name, check.status.as_str()),
+                false => outln!(
+                    "Check {}: {} ({})",
+                    check.name,
+                    check.status.as_str(),
+                    check.detail
+                ),
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(ExitStatus::VerificationFailed
+            .error(format!(
+                "The payload in {} failed {} of its checks",
+                args.file.display(),
+                failed
+            ))
+            .into());
+    }
+    Ok(())
+}
+
+// Runs the signature, decryption and decompression checks, returning the payload decode would
+// reveal if they got that far.
+fn check_envelope(
+    envelope: &Envelope,
+    args: &ReportArgs,
+    passphrase: &Passphrase,
+    checks: &mut Vec<PayloadCheck>,
+) -> Result<Option<Vec<u8>>> {
+    match (&envelope.signature, &args.verify) {
+        (Some(Signature::Ed25519 { .. }), Some(path)) => checks.push(PayloadCheck::of(
+            "signature",
+            envelope.verify_ed25519(&read_key(path)?),
+        )),
+        (Some(Signature::Ed25519 { .. }), None) => checks.push(PayloadCheck::skipped(
+            "signature",
+            "pass --verify with the signer's public key",
+        )),
+        (Some(Signature::Hmac { .. }), _) => checks.push(PayloadCheck::of(
+            "authentication",
+            envelope.verify_hmac(&passphrase.get(false)?),
+        )),
+        (Some(Signature::Gpg), _) => checks.push(PayloadCheck::skipped(
+            "signature",
+            "GPG signatures are only checked by decode",
+        )),
+        (None, _) => {}
+    }
+
+    let decrypted = match &envelope.encryption {
+        None => envelope.clone(),
+        Some(Encryption::Passphrase(_)) => {
+            match envelope.clone().decrypted(&passphrase.get(false)?) {
+                Ok(decrypted) => {
+                    checks.push(PayloadCheck::of("decryption", Ok(())));
+                    decrypted
+                }
+                Err(e) => {
+                    checks.push(PayloadCheck::of("decryption", Err(e)));
+                    return Ok(None);
+                }
+            }
+        }
+        Some(Encryption::Recipients) if args.identities.is_empty() => {
+            checks.push(PayloadCheck::skipped("decryption", "pass --identity"));
+            return Ok(None);
+        }
+        Some(Encryption::Recipients) => {
+            match decrypt_with_identity_files(envelope.clone(), &args.identities) {
+                Ok(decrypted) => {
+                    checks.push(PayloadCheck::of("decryption", Ok(())));
+                    decrypted
+                }
+                Err(e) => {
+                    checks.push(PayloadCheck::of("decryption", Err(e)));
+                    return Ok(None);
+                }
+            }
+        }
+        Some(Encryption::Gpg) => {
+            checks.push(PayloadCheck::skipped(
+                "decryption",
+                "GPG payloads are only opened by decode",
+            ));
+            return Ok(None);
+        }
+    };
+
+    // A GPG signed payload is still wrapped in its signature here.
+    if decrypted.compression == Compression::None || envelope.signature == Some(Signature::Gpg) {
+        return Ok(Some(decrypted.payload));
+    }
+    match decrypted.decompressed() {
+        Ok(decompressed) => {
+            checks.push(PayloadCheck::of("decompression", Ok(())));
+            Ok(Some(decompressed.payload))
+        }
+        Err(e) => {
+            checks.push(PayloadCheck::of("decompression", Err(e)));
+            Ok(None)
+        }
+    }
+}
+
+fn compression_name(compression: Compression) -> &'static str {
+    match compression {
+        Compression::None => "none",
+        Compression::Deflate => "deflate",
+        Compression::Zstd => "zstd",
+    }
+}
+
+fn encryption_name(encryption: &Encryption) -> &'static str {
+    match encryption {
+        Encryption::Passphrase(_) => "passphrase",
+        Encryption::Recipients => "recipients",
+        Encryption::Gpg => "gpg",
+    }
+}
+
+fn signature_name(signature: &Signature) -> &'static str {
+    match signature {
+        Signature::Gpg => "gpg",
+        Signature::Ed25519 { .. } => "ed25519",
+        Signature::Hmac { .. } => "hmac",
+    }
+}
+
+// Before envelopes, a payload chunk held the message bytes and nothing else. Anything already in
+// an envelope, split into segments or wrapped in error correction is current and left alone.
+// Deniable containers look like random bytes by design, so they cannot be told apart from a raw
+// payload and must not be migrated.
+fn is_legacy_payload(data: &[u8]) -> bool {
+    !Envelope::is_envelope(data) && !data.starts_with(&SEGMENT_MAGIC) && !fec::is_protected(data)
+}
+
+fn migrate(args: MigrateArgs, global: &GlobalArgs, passphrase: &Passphrase) -> Result<()> {
+    let mut png = read_png(&args.file, global.touch_time)?;
+    let original = png.clone();
+    let chunk_type = args.chunk_type.to_string();
+
+    if png.chunk_by_type(&chunk_type).is_none() {
+        return Err(ExitStatus::NotFound
+            .error(format!(
+                "{} has no {} chunk",
+                args.file.display(),
+                chunk_type
+            ))
+            .into());
+    }
+
+    // Rewritten in place, so the payloads keep their position and order.
+    let mut migrated = 0;
+    for chunk in png.chunks_mut() {
+        if *chunk.chunk_type() != args.chunk_type || !is_legacy_payload(chunk.chunk_data()) {
+            continue;
+        }
+        let payload = chunk.chunk_data().to_vec();
+        // Messages were all old releases could encode, so text stays text.
+        let envelope = match std::str::from_utf8(&payload).is_ok() {
+            true => Envelope::new(payload).with_mime_type("text/plain"),
+            false => Envelope::new(payload),
+        };
+        let envelope = seal_envelope(envelope, &args.envelope, passphrase)?;
+        chunk.set_data(envelope.to_bytes()?);
+        migrated += 1;
+    }
+    if migrated == 0 {
+        noteln!(
+            "{} has no {} payloads to migrate",
+            args.file.display(),
+            chunk_type
+        );
+        return Ok(());
+    }
+    if global.touch_time {
+        png.touch();
+    }
+    noteln!(
+        "Migrated {} {} payloads in {}",
+        migrated,
+        chunk_type,
+        args.file.display()
+    );
+
+    write_output(
+        &original,
+        &png,
+        args.output.as_deref().unwrap_or(&args.file),
+        global,
+    )
+}
+
+// Decodes the payload as decode would and compares it with the reference, reporting only where
+// they differ so the payload itself is never printed.
+fn verify_payload(args: VerifyPayloadArgs, passphrase: &Passphrase) -> Result<()> {
+    let decode_args = DecodeArgs {
+        file: args.file.clone(),
+        chunk_type: args.chunk_type,
+        method: args.method,
+        scatter: args.scatter,
+        obfuscate: args.obfuscate,
+        deniable: args.deniable,
+        all: false,
+        index: args.index,
+        #[cfg(feature = "clipboard")]
+        to_clipboard: false,
+        output: OutputArgs {
+            output_dir: None,
+            format: PayloadFormat::Data(DataFormat::LossyUtf8),
+            identities: args.identities.clone(),
+            verify: args.verify.clone(),
+            preview: PreviewArgs {
+                max_preview: None,
+                full: false,
+            },
+        },
+        passphrase: args.passphrase.clone(),
+    };
+    let decoded = match read_jpeg(&args.file)? {
+        Some(jpeg) => decode_jpeg(&jpeg, &decode_args, passphrase)?,
+        None => decode_png(&read_png(&args.file, false)?, &decode_args, passphrase)?,
+    };
+    let payload = decoded.payload();
+    let sha256 = Sha256::digest(payload).to_vec();
+
+    let (reference, expected_sha256, difference) = match (&args.against, &args.sha256) {
+        (Some(path), _) => {
+            let reference = read_bytes(path)?;
+            let difference = first_difference(payload, &reference).map(|index| {
+                match payload.len() == reference.len() {
+                    true => format!("first differs at byte {}", index),
+                    false => format!(
+                        "first differs at byte {}, and is {} bytes where the reference is {}",
+                        index,
+                        payload.len(),
+                        reference.len()
+                    ),
+                }
+            });
+            (
+                path.display().to_string(),
+                Sha256::digest(&reference).to_vec(),
+                difference,
+            )
+        }
+        (None, Some(hex)) => {
+            let expected = DataFormat::Hex
+                .parse(hex)
+                .ok()
+                .filter(|digest| digest.len() == sha256.len())
+                .with_context(|| {
+                    ExitStatus::Usage.error(format!("{:?} is not a SHA-256 digest in hex", hex))
+                })?;
+            let difference = (expected != sha256).then(|| "its SHA-256 differs".to_string());
+            (format!("SHA-256 {}", hex), expected, difference)
+        }
+        (None, None) => bail!("Pass --against or --sha256"),
+    };
+
+    if args.format == ReportFormat::Json {
+        print_json(&json!({
+            "matches": difference.is_none(),
+            "length": payload.len(),
+            "sha256": DataFormat::Hex.render(&sha256),
+            "expected_sha256": DataFormat::Hex.render(&expected_sha256),
+            "difference": difference,
+        }));
+    }
+    if let Some(difference) = difference {
+        return Err(ExitStatus::VerificationFailed
+            .error(format!(
+                "The {} payload in {} does not match {}, it {}",
+                args.chunk_type,
+                args.file.display(),
+                reference,
+                difference
+            ))
+            .into());
+    }
+
+    if args.format == ReportFormat::Text {
+        outln!("Payload matches {}", reference);
+    }
+    Ok(())
+}
+
+// Where two buffers stop agreeing, which is the end of the shorter one if it is a prefix.
+fn first_difference(a: &[u8], b: &[u8]) -> Option<usize> {
+    a.iter()
+        .zip(b)
+        .position(|(x, y)| x != y)
+        .or_else(|| (a.len() != b.len()).then(|| a.len().min(b.len())))
+}
+
+fn remove(args: RemoveArgs, global: &GlobalArgs) -> Result<()> {
+    let mut png = read_png(&args.file, global.touch_time)?;
+    let original = png.clone();
+    let chunk_type = args.chunk_type.to_string();
+    let chunk = match args.index {
+        Some(index) => png.remove_nth_chunk(&chunk_type, index).with_context(|| {
+            ExitStatus::NotFound.error(format!(
+                "{} has no {} chunk at index {}",
+                args.file.display(),
+                chunk_type,
+                index
+            ))
+        })?,
+        None => png.remove_first_chunk(&chunk_type).with_context(|| {
+            ExitStatus::NotFound.error(format!(
+                "{} has no {} chunk",
+                args.file.display(),
+                chunk_type
+            ))
+        })?,
+    };
+    write_output(&original, &png, &args.file, global)?;
+    if global.dry_run {
+        return Ok(());
+    }
+
+    // Standard output may be carrying the PNG.
+    match is_stdio(&args.file) {
+        true => noteln!("Removed {:#}", chunk),
+        false => outln!("Removed {:#}", chunk),
+    }
+    Ok(())
+}
+
+fn strip(args: StripArgs, global: &GlobalArgs) -> Result<()> {
+    let mut png = read_png(&args.file, global.touch_time)?;
+    let original = png.clone();
+    let removed = png.strip_ancillary(&args.keep);
+    let output = args.output.as_deref().unwrap_or(&args.file);
+    if removed.is_empty() && args.output.is_none() {
+        noteln!("Nothing to strip");
+        return Ok(());
+    }
+
+    write_output(&original, &png, output, global)?;
+    if global.dry_run {
+        return Ok(());
+    }
+    match is_stdio(output) {
+        true => noteln!("Stripped {} chunks", removed.len()),
+        false => outln!("Stripped {} chunks", removed.len()),
+    }
+    Ok(())
+}
+
+fn dedupe(args: DedupeArgs, global: &GlobalArgs) -> Result<()> {
+    let mut png = read_png(&args.file, global.touch_time)?;
+    let original = png.clone();
+    let removed = png.dedupe();
+    let output = args.output.as_deref().unwrap_or(&args.file);
+    if removed.is_empty() && args.output.is_none() {
+        noteln!("No duplicate chunks");
+        return Ok(());
+    }
+
+    write_output(&original, &png, output, global)?;
+    if global.dry_run {
+        return Ok(());
+    }
+    let saved: usize = removed.iter().map(|c| c.as_bytes().len()).sum();
+    match is_stdio(output) {
+        true => noteln!(
+            "Removed {} duplicate chunks, {} bytes",
+            removed.len(),
+            saved
+        ),
+        false => outln!(
+            "Removed {} duplicate chunks, {} bytes",
+            removed.len(),
+            saved
+        ),
+    }
+    Ok(())
+}
+
+fn canonicalize(args: CanonicalizeArgs, global: &GlobalArgs) -> Result<()> {
+    let mut png = read_png(&args.file, false)?;
+    let original = png.clone();
+    if !png.canonicalize() && args.output.is_none() {
+        noteln!("Already in canonical order");
+        return Ok(());
+    }
+
+    write_output(
+        &original,
+        &png,
+        args.output.as_deref().unwrap_or(&args.file),
+        global,
+    )
+}
+
+fn sanitize(args: SanitizeArgs, global: &GlobalArgs) -> Result<()> {
+    let bytes =
+        fs::read(&args.file).with_context(|| format!("Could not read {}", args.file.display()))?;
+    let (png, changes) = sanitize_bytes(&bytes, args.randomize_lsb).with_context(|| {
+        ExitStatus::InvalidPng.error(format!("{} is not a valid PNG", args.file.display()))
+    })?;
+    let output = args.output.as_deref().unwrap_or(&args.file);
+
+    // Standard output may be carrying the PNG.
+    let report = |line: &dyn std::fmt::Display| match is_stdio(output) {
+        true => noteln!("{}", line),
+        false => outln!("{}", line),
+    };
+    if changes.is_empty() {
+        report(&"Nothing to sanitize");
+        if args.output.is_none() {
+            return Ok(());
+        }
+    }
+    for change in &changes {
+        report(&format_args!("Sanitized: {}", change));
+    }
+
+    if global.dry_run {
+        outln!("Would write {}", output.display());
+        return Ok(());
+    }
+    write_output(&png, &png, output, global)
+}
+
+fn info(args: InfoArgs) -> Result<()> {
+    let png = read_png(&args.file, false)?;
+    let header = png
+        .header()
+        .with_context(|| format!("{} has an invalid IHDR chunk", args.file.display()))?;
+    let chunks = png.chunks();
+    let has = |chunk_type: ChunkType| chunks.iter().any(|c| *c.chunk_type() == chunk_type);
+    let private: BTreeSet<String> = chunks
+        .iter()
+        .filter(|c| !c.chunk_type().is_public())
+        .map(|c| c.chunk_type().to_string())
+        .collect();
+    let color_type = match header.color_type {
+        ColorType::Grayscale => "grayscale",
+        ColorType::Rgb => "rgb",
+        ColorType::Indexed => "indexed",
+        ColorType::GrayscaleAlpha => "grayscale-alpha",
+        ColorType::Rgba => "rgba",
+    };
+    let icc = png.icc_profile().ok().flatten();
+    let frames = png
+        .animation()
+        .ok()
+        .flatten()
+        .map(|animation| animation.frame_count());
+
+    let value = json!({
+        "width": header.width,
+        "height": header.height,
+        "bit_depth": header.bit_depth,
+        "color_type": color_type,
+        "interlaced": header.is_interlaced(),
+        "transparency": header.color_type.has_alpha() || has(ChunkType::tRNS),
+        "icc_profile": icc.as_ref().map(|icc| icc.name()),
+        "srgb": has(ChunkType::sRGB),
+        "exif_bytes": png.exif().map(<[u8]>::len),
+        "text_entries": png.texts().count(),
+        "frames": frames,
+        "chunks": chunks.len(),
+        "ancillary_chunks": chunks.iter().filter(|c| !c.chunk_type().is_critical()).count(),
+        "private_chunks": private,
+        "bytes": png.as_bytes().len(),
+    });
+    if args.format == ReportFormat::Json {
+        print_json(&value);
+        return Ok(());
+    }
+
+    let yes_no = |yes: bool| if yes { "yes" } else { "no" };
+    outln!("Dimensions: {}x{}", header.width, header.height);
+    outln!("Bit depth: {}", header.bit_depth);
+    outln!("Color type: {}", color_type);
+    outln!("Interlaced: {}", yes_no(header.is_interlaced()));
+    outln!("Transparency: {}", yes_no(value["transparency"] == true));
+    match (&icc, has(ChunkType::sRGB)) {
+        (Some(icc), _) => outln!("Color profile: ICC {:?}", icc.name()),
+        (None, true) => outln!("Color profile: sRGB"),
+        (None, false) => outln!("Color profile: none"),
+    }
+    match png.exif() {
+        Some(exif) => outln!("EXIF: {} bytes", exif.len()),
+        None => outln!("EXIF: none"),
+    }
+    outln!("Text entries: {}", value["text_entries"]);
+    match frames {
+        Some(frames) => outln!("Animation: {} frames", frames),
+        None => outln!("Animation: no"),
+    }
+    outln!(
+        "Chunks: {} ({} ancillary)",
+        chunks.len(),
+        value["ancillary_chunks"]
+    );
+    match private.is_empty() {
+        true => outln!("Private chunks: none"),
+        false => outln!(
+            "Private chunks: {}",
+            private.into_iter().collect::<Vec<_>>().join(", ")
+        ),
+    }
+    Ok(())
+}
+
+fn print(args: PrintArgs) -> Result<()> {
+    let png = read_png(&args.file, false)?;
+    if args.format != ListFormat::Text {
+        const COLUMNS: &[Column] = &[
+            ("index", "/index"),
+            ("offset", "/offset"),
+            ("type", "/type"),
+            ("length", "/length"),
+            ("crc", "/crc"),
+            ("critical", "/flags/critical"),
+            ("public", "/flags/public"),
+            ("safe_to_copy", "/flags/safe_to_copy"),
+            ("description", "/description"),
+        ];
+        let chunks = png
+            .chunks_with_offsets()
+            .enumerate()
+            .map(|(index, (offset, chunk))| output::chunk_json(index, offset, chunk))
+            .collect();
+        print_listing(args.format, COLUMNS, chunks);
+        return Ok(());
+    }
+
+    let offsets: Vec<usize> = png
+        .chunks_with_offsets()
+        .map(|(offset, _)| offset)
+        .collect();
+    out!(
+        "{}",
+        output::chunk_table(
+            png.chunks(),
+            &offsets,
+            args.preview.limit(output::DATA_PREVIEW_LENGTH),
+            args.color.enabled()
+        )
+    );
+    Ok(())
+}
+
+// Meant for shell conditionals, so the answer is only the exit status. A file that cannot be read
+// still reports why, and exits with its own status rather than 1.
+fn check(args: CheckArgs) -> Result<()> {
+    let png = read_png(&args.file, false)?;
+    let found = png.chunks().iter().any(|chunk| match args.chunk_type {
+        Some(chunk_type) => *chunk.chunk_type() == chunk_type,
+        None => !chunk.chunk_type().is_standard(),
+    });
+    match found {
+        true => Ok(()),
+        false => Err(ExitStatus::NotFound.silent().into()),
+    }
+}
+
+// A hex pattern becomes a regular expression of escaped bytes, so both kinds are searched alike.
+fn grep_regex(pattern: &str, hex: bool) -> Result<regex::bytes::Regex> {
+    let pattern = match hex {
+        true => {
+            let bytes = DataFormat::Hex
+                .parse(pattern)
+                .ok()
+                .filter(|bytes| !bytes.is_empty())
+                .with_context(|| {
+                    ExitStatus::Usage.error(format!("{:?} is not a sequence of hex bytes", pattern))
+                })?;
+            let escaped: String = bytes.iter().map(|b| format!("\\x{:02x}", b)).collect();
+            format!("(?-u){}", escaped)
+        }
+        false => pattern.to_string(),
+    };
+    regex::bytes::Regex::new(&pattern)
+        .with_context(|| ExitStatus::Usage.error(format!("{:?} is not a valid pattern", pattern)))
+}
+
+fn grep(args: GrepArgs) -> Result<()> {
+    let regex = grep_regex(&args.pattern, args.hex)?;
+    let mut files = Vec::new();
+    for file in &args.files {
+        files.extend(expand_files(file, false)?);
+    }
+
+    let mut rows = Vec::new();
+    for file in &files {
+        let png = match read_png(file, false) {
+            Ok(png) => png,
+            Err(e) if files.len() > 1 => {
+                eprintln!("Warning: skipping {}: {:#}", file.display(), e);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        for (index, (chunk_offset, chunk)) in png.chunks_with_offsets().enumerate() {
+            if !args.types.is_empty() && !args.types.contains(chunk.chunk_type()) {
+                continue;
+            }
+            rows.extend(regex.find_iter(chunk.chunk_data()).map(|m| {
+                json!({
+                    "path": file,
+                    "index": index,
+                    "type": chunk.chunk_type().to_string(),
+                    // The offset is within the chunk data, the file offset of the chunk is
+                    // reported separately.
+                    "offset": m.start(),
+                    "chunk_offset": chunk_offset,
+                    "match": m.as_bytes().escape_ascii().to_string(),
+                })
+            }));
+        }
+    }
+
+    if rows.is_empty() {
+        return Err(ExitStatus::NotFound
+            .error(format!("No chunk data matches {:?}", args.pattern))
+            .into());
+    }
+    if args.format != ListFormat::Text {
+        const COLUMNS: &[Column] = &[
+            ("path", "/path"),
+            ("index", "/index"),
+            ("type", "/type"),
+            ("offset", "/offset"),
+            ("match", "/match"),
+        ];
+        print_listing(args.format, COLUMNS, rows);
+        return Ok(());
+    }
+
+    for row in &rows {
+        outln!(
+            "{}: [{}] {} offset {}: {}",
+            row["path"].as_str().unwrap_or_default(),
+            row["index"],
+            row["type"].as_str().unwrap_or_default(),
+            row["offset"],
+            row["match"].as_str().unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+// Bytes are counted as stored, so the length, type and CRC fields are included and the totals add
+// up to the file sizes less their signatures.
+fn stats(args: StatsArgs) -> Result<()> {
+    let files = expand_files(&args.file, false)?;
+    let mut types: Vec<(ChunkType, usize, usize)> = Vec::new();
+    let mut read = 0;
+    for file in &files {
+        let png = match read_png(file, false) {
+            Ok(png) => png,
+            Err(e) if files.len() > 1 => {
+                eprintln!("Warning: skipping {}: {:#}", file.display(), e);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        read += 1;
+        for chunk in png.chunks() {
+            let size = chunk.length() as usize + 12;
+            match types.iter_mut().find(|(t, ..)| t == chunk.chunk_type()) {
+                Some((_, count, bytes)) => {
+                    *count += 1;
+                    *bytes += size;
+                }
+                None => types.push((*chunk.chunk_type(), 1, size)),
+            }
+        }
+    }
+    types.sort_by_key(|&(.., bytes)| std::cmp::Reverse(bytes));
+
+    let total: usize = types.iter().map(|(.., bytes)| bytes).sum();
+    let percent = |bytes: usize| match total {
+        0 => 0.0,
+        _ => (bytes * 1000 / total) as f64 / 10.0,
+    };
+    if args.format != ListFormat::Text {
+        const COLUMNS: &[Column] = &[
+            ("type", "/type"),
+            ("count", "/count"),
+            ("bytes", "/bytes"),
+            ("percent", "/percent"),
+        ];
+        let rows = types
+            .iter()
+            .map(|(chunk_type, count, bytes)| {
+                json!({
+                    "type": chunk_type.to_string(),
+                    "count": count,
+                    "bytes": bytes,
+                    "percent": percent(*bytes),
+                    "critical": chunk_type.is_critical(),
+                    "public": chunk_type.is_public(),
+                })
+            })
+            .collect();
+        print_listing(args.format, COLUMNS, rows);
+        return Ok(());
+    }
+
+    let sum = |include: fn(&ChunkType) -> bool| -> usize {
+        types
+            .iter()
+            .filter(|(chunk_type, ..)| include(chunk_type))
+            .map(|(.., bytes)| bytes)
+            .sum()
+    };
+    for (chunk_type, count, bytes) in &types {
+        outln!(
+            "{}  {:>6} chunks  {:>10} bytes  {:>5.1}%",
+            chunk_type,
+            count,
+            bytes,
+            percent(*bytes)
+        );
+    }
+    outln!(
+        "Total: {} bytes in {} files; critical {}, ancillary {}, private {}",
+        total,
+        read,
+        sum(ChunkType::is_critical),
+        sum(|t| !t.is_critical()),
+        sum(|t| !t.is_public())
+    );
+    Ok(())
+}
+
+fn hexdump(args: HexdumpArgs) -> Result<()> {
+    let png = read_png(&args.file, false)?;
+    let chunk_type = args.chunk_type.to_string();
+    let index = args.index.unwrap_or(0);
+    let chunk = png.nth_chunk_by_type(&chunk_type, index).with_context(|| {
+        ExitStatus::NotFound.error(format!(
+            "{} has no {} chunk at index {}",
+            args.file.display(),
+            chunk_type,
+            index
+        ))
+    })?;
+
+    out!("{}", output::hexdump(chunk.chunk_data()));
+    Ok(())
+}
+
+fn method_name(method: StegoMethod) -> &'static str {
+    match method {
+        StegoMethod::Chunk => "chunk",
+        StegoMethod::Lsb => "lsb",
+        StegoMethod::Alpha { opaque_only: false } => "alpha",
+        StegoMethod::Alpha { opaque_only: true } => "alpha-opaque",
+    }
+}
+
+fn capacity(args: CapacityArgs) -> Result<()> {
+    let png = read_png(&args.file, false)?;
+    let envelope = capacity_envelope(args.payload_file.as_deref())?;
+    let methods = match args.method {
+        Some(method) => vec![method],
+        None => vec![
+            StegoMethod::Chunk,
+            StegoMethod::Lsb,
+            StegoMethod::Alpha { opaque_only: false },
+            StegoMethod::Alpha { opaque_only: true },
+        ],
+    };
+
+    if args.format != ListFormat::Text {
+        const COLUMNS: &[Column] = &[
+            ("method", "/method"),
+            ("bytes", "/bytes"),
+            ("error", "/error"),
+        ];
+        let capacities = methods
+            .into_iter()
+            .map(|method| match payload_capacity(&png, method, &envelope) {
+                Ok(bytes) => json!({"method": method_name(method), "bytes": bytes}),
+                Err(e) => json!({"method": method_name(method), "error": e.to_string()}),
+            })
+            .collect();
+        print_listing(args.format, COLUMNS, capacities);
+        return Ok(());
+    }
+
+    for method in methods {
+        match payload_capacity(&png, method, &envelope) {
+            Ok(bytes) => outln!("{:<13} {} bytes", method_name(method), bytes),
+            Err(e) => outln!("{:<13} unavailable: {}", method_name(method), e),
+        }
+    }
+
+    Ok(())
+}
+
+// The envelope the payload is wrapped in, without its payload; messages are recorded as text.
+fn capacity_envelope(file: Option<&Path>) -> Result<Envelope> {
+    let mut envelope = match file {
+        Some(path) if !is_stdio(path) => Envelope::from_file(path)
+            .with_context(|| format!("Could not read {}", path.display()))?,
+        Some(_) => Envelope::default(),
+        None => Envelope::default().with_mime_type("text/plain"),
+    };
+    envelope.payload.clear();
+    Ok(envelope)
+}
+
+// The carrier also holds the envelope, so its bytes are not available to the payload.
+fn payload_capacity(png: &Png, method: StegoMethod, envelope: &Envelope) -> Result<usize> {
+    Ok(png.capacity(method)?.saturating_sub(envelope.overhead()))
+}
+
+// The summary goes to standard error, standard output may be the image.
+fn init_carrier(args: InitCarrierArgs, global: &GlobalArgs) -> Result<()> {
+    let Size { width, height } = args.size;
+    let png = generate_cover(width, height, args.style, args.seed)
+        .with_context(|| ExitStatus::Usage.error("Could not generate a cover image"))?;
+    let capacity = payload_capacity(&png, StegoMethod::Lsb, &capacity_envelope(None)?)?;
+
+    if global.dry_run {
+        outln!("Would write {}", args.output.display());
+        return Ok(());
+    }
+    write_output_bytes(&png.as_bytes(), &args.output, global)?;
+    noteln!(
+        "Wrote a {}x{} cover image to {}, holding a {} byte message with lsb embedding",
+        width,
+        height,
+        args.output.display(),
+        capacity
+    );
+    Ok(())
+}
+
+fn detect(args: DetectArgs) -> Result<()> {
+    let bytes =
+        fs::read(&args.file).with_context(|| format!("Could not read {}", args.file.display()))?;
+    let report = detect_bytes(&bytes).with_context(|| {
+        ExitStatus::InvalidPng.error(format!("{} is not a valid PNG", args.file.display()))
+    })?;
+
+    match args.format {
+        ReportFormat::Text => outln!("{}", report),
+        ReportFormat::Json => print_json(&output::detection_json(&report)),
+    }
+    Ok(())
+}
+
+fn text(args: TextArgs, global: &GlobalArgs) -> Result<()> {
+    match args.command {
+        TextCommand::List(args) => {
+            for_each_file(expand_files(&args.file, false)?, global.jobs, |file| {
+                text_list(TextListArgs {
+                    file,
+                    ..args.clone()
+                })
+            })
+        }
+        TextCommand::Get(args) => {
+            for_each_file(expand_files(&args.file, false)?, global.jobs, |file| {
+                text_get(TextGetArgs {
+                    file,
+                    ..args.clone()
+                })
+            })
+        }
+        TextCommand::Set(args) => {
+            let files = expand_files(&args.file, args.output.is_some())?;
+            for_each_file(files, global.jobs, |file| {
+                text_set(
+                    TextSetArgs {
+                        file,
+                        ..args.clone()
+                    },
+                    global,
+                )
+            })
+        }
+        TextCommand::Remove(args) => {
+            let files = expand_files(&args.file, args.output.is_some())?;
+            for_each_file(files, global.jobs, |file| {
+                text_remove(
+                    TextRemoveArgs {
+                        file,
+                        ..args.clone()
+                    },
+                    global,
+                )
+            })
+        }
+    }
+}
+
+fn text_kind(text: &Text) -> &'static str {
+    match (text.is_international(), text.is_compressed()) {
+        (true, _) => "iTXt",
+        (false, true) => "zTXt",
+        (false, false) => "tEXt",
+    }
+}
+
+fn text_list(args: TextListArgs) -> Result<()> {
+    let png = read_png(&args.file, false)?;
+    if args.format != ListFormat::Text {
+        const COLUMNS: &[Column] = &[
+            ("keyword", "/keyword"),
+            ("type", "/type"),
+            ("language", "/language"),
+            ("text", "/text"),
+        ];
+        let texts = png
+            .texts()
+            .map(|text| {
+                json!({
+                    "keyword": text.keyword(),
+                    "type": text_kind(&text),
+                    "language": text.language_tag(),
+                    "translated_keyword": text.translated_keyword(),
+                    "text": text.text(),
+                })
+            })
+            .collect();
+        print_listing(args.format, COLUMNS, texts);
+        return Ok(());
+    }
+
+    for text in png.texts() {
+        outln!("{}: {}", text.keyword(), text.text());
+    }
+    Ok(())
+}
+
+fn text_get(args: TextGetArgs) -> Result<()> {
+    let png = read_png(&args.file, false)?;
+    let text = png.get_text(&args.keyword).with_context(|| {
+        ExitStatus::NotFound.error(format!(
+            "{} has no {:?} text",
+            args.file.display(),
+            args.keyword
+        ))
+    })?;
+
+    outln!("{}", text);
+    Ok(())
+}
+
+fn text_set(args: TextSetArgs, global: &GlobalArgs) -> Result<()> {
+    let mut png = read_png(&args.file, global.touch_time)?;
+    let original = png.clone();
+    png.put_text(Text::auto(&args.keyword, &args.value)?)?;
+
+    write_output(
+        &original,
+        &png,
+        args.output.as_deref().unwrap_or(&args.file),
+        global,
+    )
+}
+
+fn text_remove(args: TextRemoveArgs, global: &GlobalArgs) -> Result<()> {
+    let mut png = read_png(&args.file, global.touch_time)?;
+    let original = png.clone();
+    if png.remove_text(&args.keyword).is_none() {
+        return Err(ExitStatus::NotFound
+            .error(format!(
+                "{} has no {:?} text",
+                args.file.display(),
+                args.keyword
+            ))
+            .into());
+    }
+    while png.remove_text(&args.keyword).is_some() {}
+
+    write_output(
+        &original,
+        &png,
+        args.output.as_deref().unwrap_or(&args.file),
+        global,
+    )
+}
+
+fn kv(args: KvArgs, global: &GlobalArgs) -> Result<()> {
+    match args.command {
+        KvCommand::List(args) => {
+            for_each_file(expand_files(&args.file, false)?, global.jobs, |file| {
+                kv_list(KvListArgs {
+                    file,
+                    ..args.clone()
+                })
+            })
+        }
+        KvCommand::Get(args) => {
+            for_each_file(expand_files(&args.file, false)?, global.jobs, |file| {
+                kv_get(KvGetArgs {
+                    file,
+                    ..args.clone()
+                })
+            })
+        }
+        KvCommand::Set(args) => {
+            let files = expand_files(&args.file, args.output.is_some())?;
+            for_each_file(files, global.jobs, |file| {
+                kv_set(
+                    KvSetArgs {
+                        file,
+                        ..args.clone()
+                    },
+                    global,
+                )
+            })
+        }
+        KvCommand::Remove(args) => {
+            let files = expand_files(&args.file, args.output.is_some())?;
+            for_each_file(files, global.jobs, |file| {
+                kv_remove(
+                    KvRemoveArgs {
+                        file,
+                        ..args.clone()
+                    },
+                    global,
+                )
+            })
+        }
+    }
+}
+
+fn kv_list(args: KvListArgs) -> Result<()> {
+    let png = read_png(&args.file, false)?;
+    if args.format != ListFormat::Text {
+        const COLUMNS: &[Column] = &[("key", "/key"), ("value", "/value")];
+        let entries = png
+            .kv_iter()?
+            .map(|(key, value)| json!({"key": key, "value": value}))
+            .collect();
+        print_listing(args.format, COLUMNS, entries);
+        return Ok(());
+    }
+
+    for (key, value) in png.kv_iter()? {
+        outln!("{}: {}", key, value);
+    }
+    Ok(())
+}
+
+fn kv_get(args: KvGetArgs) -> Result<()> {
+    let png = read_png(&args.file, false)?;
+    let value = png.kv_get(&args.key)?.with_context(|| {
+        ExitStatus::NotFound.error(format!("{} has no {:?} key", args.file.display(), args.key))
+    })?;
+
+    outln!("{}", value);
+    Ok(())
+}
+
+fn kv_set(args: KvSetArgs, global: &GlobalArgs) -> Result<()> {
+    let mut png = read_png(&args.file, global.touch_time)?;
+    let original = png.clone();
+    png.kv_set(&args.key, &args.value)?;
+
+    write_output(
+        &original,
+        &png,
+        args.output.as_deref().unwrap_or(&args.file),
+        global,
+    )
+}
+
+fn kv_remove(args: KvRemoveArgs, global: &GlobalArgs) -> Result<()> {
+    let mut png = read_png(&args.file, global.touch_time)?;
+    let original = png.clone();
+    if png.kv_remove(&args.key)?.is_none() {
+        return Err(ExitStatus::NotFound
+            .error(format!("{} has no {:?} key", args.file.display(), args.key))
+            .into());
+    }
+
+    write_output(
+        &original,
+        &png,
+        args.output.as_deref().unwrap_or(&args.file),
+        global,
+    )
+}
+
+fn exif(args: ExifArgs, global: &GlobalArgs) -> Result<()> {
+    match args.command {
+        ExifCommand::Show(args) => {
+            for_each_file(expand_files(&args.file, false)?, global.jobs, |file| {
+                exif_show(ExifShowArgs {
+                    file,
+                    ..args.clone()
+                })
+            })
+        }
+        ExifCommand::Export(args) => exif_export(args, global),
+        ExifCommand::Import(args) => {
+            let files = expand_files(&args.file, args.output.is_some())?;
+            for_each_file(files, global.jobs, |file| {
+                exif_import(
+                    ExifImportArgs {
+                        file,
+                        ..args.clone()
+                    },
+                    global,
+                )
+            })
+        }
+        ExifCommand::Remove(args) => {
+            let files = expand_files(&args.file, args.output.is_some())?;
+            for_each_file(files, global.jobs, |file| {
+                exif_remove(
+                    ExifRemoveArgs {
+                        file,
+                        ..args.clone()
+                    },
+                    global,
+                )
+            })
+        }
+    }
+}
+
+fn read_exif(png: &Png, path: &Path) -> Result<Vec<u8>> {
+    png.exif()
+        .map(<[u8]>::to_vec)
+        .with_context(|| ExitStatus::NotFound.error(format!("{} has no EXIF data", path.display())))
+}
+
+fn exif_show(args: ExifShowArgs) -> Result<()> {
+    let png = read_png(&args.file, false)?;
+    let tags = pngme::exif::parse_exif(&read_exif(&png, &args.file)?)
+        .with_context(|| format!("{} has invalid EXIF data", args.file.display()))?;
+    if args.format != ListFormat::Text {
+        const COLUMNS: &[Column] = &[("tag", "/tag"), ("name", "/name"), ("value", "/value")];
+        let rows = tags
+            .iter()
+            .map(|tag| {
+                json!({
+                    "tag": tag.id,
+                    "name": tag.name(),
+                    "value": tag.value.to_string(),
+                })
+            })
+            .collect();
+        print_listing(args.format, COLUMNS, rows);
+        return Ok(());
+    }
+
+    for tag in &tags {
+        outln!("{}", tag);
+    }
+    Ok(())
+}
+
+fn exif_export(args: ExifExportArgs, global: &GlobalArgs) -> Result<()> {
+    let png = read_png(&args.file, false)?;
+    write_bytes(
+        &read_exif(&png, &args.file)?,
+        args.output.as_deref(),
+        global,
+    )
+}
+
+fn exif_import(args: ExifImportArgs, global: &GlobalArgs) -> Result<()> {
+    let exif = read_bytes(&args.exif_file)?;
+    let mut png = read_png(&args.file, global.touch_time)?;
+    let original = png.clone();
+    png.set_exif(exif)
+        .with_context(|| format!("{} is not EXIF data", args.exif_file.display()))?;
+
+    write_output(
+        &original,
+        &png,
+        args.output.as_deref().unwrap_or(&args.file),
+        global,
+    )
+}
+
+fn exif_remove(args: ExifRemoveArgs, global: &GlobalArgs) -> Result<()> {
+    let mut png = read_png(&args.file, global.touch_time)?;
+    let original = png.clone();
+    if png.remove_exif().is_none() {
+        return Err(ExitStatus::NotFound
+            .error(format!("{} has no EXIF data", args.file.display()))
+            .into());
+    }
+
+    write_output(
+        &original,
+        &png,
+        args.output.as_deref().unwrap_or(&args.file),
+        global,
+    )
+}
+
+fn icc(args: IccArgs, global: &GlobalArgs) -> Result<()> {
+    match args.command {
+        IccCommand::Export(args) => icc_export(args, global),
+        IccCommand::Import(args) => {
+            let files = expand_files(&args.file, args.output.is_some())?;
+            for_each_file(files, global.jobs, |file| {
+                icc_import(
+                    IccImportArgs {
+                        file,
+                        ..args.clone()
+                    },
+                    global,
+                )
+            })
+        }
+        IccCommand::Remove(args) => {
+            let files = expand_files(&args.file, args.output.is_some())?;
+            for_each_file(files, global.jobs, |file| {
+                icc_remove(
+                    IccRemoveArgs {
+                        file,
+                        ..args.clone()
+                    },
+                    global,
+                )
+            })
+        }
+    }
+}
+
+fn icc_export(args: IccExportArgs, global: &GlobalArgs) -> Result<()> {
+    let png = read_png(&args.file, false)?;
+    let profile = png
+        .icc_profile()
+        .with_context(|| format!("{} has an invalid iCCP chunk", args.file.display()))?
+        .with_context(|| {
+            ExitStatus::NotFound.error(format!("{} has no ICC profile", args.file.display()))
+        })?;
+
+    noteln!(
+        "Profile {:?}, {} bytes",
+        profile.name(),
+        profile.profile().len()
+    );
+    write_bytes(profile.profile(), args.output.as_deref(), global)
+}
+
+// ICC profiles have a 128 byte header with the signature acsp at offset 36.
+fn is_icc_profile(bytes: &[u8]) -> bool {
+    bytes.len() >= 128 && &bytes[36..40] == b"acsp"
+}
+
+fn icc_import(args: IccImportArgs, global: &GlobalArgs) -> Result<()> {
+    let profile = read_bytes(&args.profile)?;
+    if !is_icc_profile(&profile) && !global.force {
+        bail!(
+            "{} does not look like an ICC profile, pass --force to embed it anyway",
+            args.profile.display()
+        )
+    }
+    let mut png = read_png(&args.file, global.touch_time)?;
+    let original = png.clone();
+    png.set_icc_profile(&args.name, profile)?;
+
+    write_output(
+        &original,
+        &png,
+        args.output.as_deref().unwrap_or(&args.file),
+        global,
+    )
+}
+
+fn icc_remove(args: IccRemoveArgs, global: &GlobalArgs) -> Result<()> {
+    let mut png = read_png(&args.file, global.touch_time)?;
+    let original = png.clone();
+    if png.chunk_by_type("iCCP").is_none() {
+        return Err(ExitStatus::NotFound
+            .error(format!("{} has no ICC profile", args.file.display()))
+            .into());
+    }
+    let _ = png.remove_icc_profile();
+
+    write_output(
+        &original,
+        &png,
+        args.output.as_deref().unwrap_or(&args.file),
+        global,
+    )
+}
+
+fn extract(args: ExtractArgs, global: &GlobalArgs) -> Result<()> {
+    let png = read_png(&args.file, false)?;
+    let chunk_type = args.chunk_type.to_string();
+    let index = args.index.unwrap_or(0);
+    let chunk = png.nth_chunk_by_type(&chunk_type, index).with_context(|| {
+        ExitStatus::NotFound.error(format!(
+            "{} has no {} chunk at index {}",
+            args.file.display(),
+            chunk_type,
+            index
+        ))
+    })?;
+    let bytes = match args.with_header {
+        true => chunk.as_bytes(),
+        false => chunk.chunk_data().to_vec(),
+    };
+
+    write_bytes(&bytes, args.output.as_deref(), global)
+}
+
+// Writes binary data to a file, or to standard output unless that is a terminal.
+fn write_bytes(bytes: &[u8], path: Option<&Path>, global: &GlobalArgs) -> Result<()> {
+    match path.filter(|path| !is_stdio(path)) {
+        Some(path) => {
+            fs::write(path, bytes).with_context(|| format!("Could not write {}", path.display()))
+        }
+        None => {
+            let mut stdout = io::stdout().lock();
+            if stdout.is_terminal() && !global.force {
+                bail!("Refusing to write binary data to a terminal, pass --output or --force")
+            }
+            stdout
+                .write_all(bytes)
+                .and_then(|_| stdout.flush())
+                .context("Could not write standard output")
+        }
+    }
+}
+
+fn inject(args: InjectArgs, global: &GlobalArgs) -> Result<()> {
+    let bytes = read_bytes(&args.from)?;
+    let chunk = match args.with_header {
+        true => {
+            let (chunk, length) = Chunk::read_from(bytes.as_slice())
+                .with_context(|| format!("{} does not hold a chunk", args.from.display()))?;
+            if length != bytes.len() {
+                bail!("{} holds more than one chunk", args.from.display())
+            }
+            if *chunk.chunk_type() != args.chunk_type {
+                bail!(
+                    "{} holds a {} chunk, not {}",
+                    args.from.display(),
+                    chunk.chunk_type(),
+                    args.chunk_type
+                )
+            }
+            chunk
+        }
+        false => Chunk::new(args.chunk_type, bytes),
+    };
+
+    let mut png = read_png(&args.file, global.touch_time)?;
+    let original = png.clone();
+    if args.replace {
+        let _ = png.remove_all_chunks(&args.chunk_type.to_string());
+    }
+    png.insert_chunk_ordered(chunk);
+
+    write_output(
+        &original,
+        &png,
+        args.output.as_deref().unwrap_or(&args.file),
+        global,
+    )
+}
+
+fn merge(args: MergeArgs, global: &GlobalArgs) -> Result<()> {
+    let source = read_png(&args.from, false)?;
+    let mut png = read_png(&args.file, global.touch_time)?;
+    let original = png.clone();
+
+    // Chunks that are not safe to copy describe the source image's pixels.
+    let types: Vec<ChunkType> = args
+        .types
+        .iter()
+        .copied()
+        .filter(|chunk_type| {
+            let safe = chunk_type.is_safe_to_copy() || global.force;
+            if !safe {
+                eprintln!(
+                    "Warning: skipping {}, which is not safe to copy, pass --force to copy it anyway",
+                    chunk_type
+                );
+            }
+            safe
+        })
+        .collect();
+    let copied = png.copy_chunks_from(&source, &types);
+    if copied == 0 {
+        return Err(ExitStatus::NotFound
+            .error(format!("{} has no chunks to copy", args.from.display()))
+            .into());
+    }
+
+    let output = args.output.as_deref().unwrap_or(&args.file);
+    write_output(&original, &png, output, global)?;
+    if global.dry_run {
+        return Ok(());
+    }
+    match is_stdio(output) {
+        true => noteln!("Copied {} chunks", copied),
+        false => outln!("Copied {} chunks", copied),
+    }
+    Ok(())
+}
+
+fn diff(args: DiffArgs) -> Result<()> {
+    let before = read_png(&args.before, false)?;
+    let after = read_png(&args.after, false)?;
+    let diffs = pngme::diff::diff(&before, &after);
+    if args.format == ReportFormat::Json {
+        print_json(&output::diff_json(&diffs));
+        return Ok(());
+    }
+
+    if diffs.is_empty() {
+        outln!("No differences");
+    }
+    for diff in &diffs {
+        let name = format!("{}[{}]", diff.chunk_type(), diff.index());
+        match diff {
+            ChunkDiff::Added { chunk, .. } => {
+                outln!(
+                    "+ {} {} bytes, crc {:#010x}",
+                    name,
+                    chunk.length(),
+                    chunk.crc()
+                )
+            }
+            ChunkDiff::Removed { chunk, .. } => {
+                outln!(
+                    "- {} {} bytes, crc {:#010x}",
+                    name,
+                    chunk.length(),
+                    chunk.crc()
+                )
+            }
+            ChunkDiff::Modified { before, after, .. } => {
+                outln!(
+                    "~ {} {} -> {} bytes, crc {:#010x} -> {:#010x}",
+                    name,
+                    before.length(),
+                    after.length(),
+                    before.crc(),
+                    after.crc()
+                );
+                if args.bytes {
+                    out!(
+                        "{}",
+                        output::hexdump_diff(before.chunk_data(), after.chunk_data())
+                    );
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn lint(args: LintArgs, global: &GlobalArgs) -> Result<()> {
+    let bytes =
+        fs::read(&args.file).with_context(|| format!("Could not read {}", args.file.display()))?;
+    // --jobs 1 keeps everything on one thread.
+    let options = VerifyOptions {
+        parallel: global.jobs != Some(1),
+    };
+    let report = lint_bytes_with_options(&bytes, &options);
+
+    match args.format {
+        ReportFormat::Text => out!("{}", report),
+        ReportFormat::Json => print_json(&output::lint_json(&report)),
+    }
+    let failing = match args.strict {
+        true => Severity::Warn,
+        false => Severity::Fail,
+    };
+    if report.status() >= failing {
+        return Err(ExitStatus::InvalidPng
+            .error(format!("{} does not conform", args.file.display()))
+            .into());
+    }
+    Ok(())
+}
+
+fn repair(args: RepairArgs, global: &GlobalArgs) -> Result<()> {
+    let bytes =
+        fs::read(&args.file).with_context(|| format!("Could not read {}", args.file.display()))?;
+    let (png, repairs) = repair_bytes(&bytes).with_context(|| {
+        ExitStatus::InvalidPng.error(format!("{} is not a valid PNG", args.file.display()))
+    })?;
+    let output = args.output.as_deref().unwrap_or(&args.file);
+
+    // Standard output may be carrying the PNG.
+    let report = |line: &dyn std::fmt::Display| match is_stdio(output) {
+        true => noteln!("{}", line),
+        false => outln!("{}", line),
+    };
+    if repairs.is_empty() {
+        report(&"Nothing to repair");
+        if args.output.is_none() {
+            return Ok(());
+        }
+    }
+    for repair in &repairs {
+        report(&format_args!("Fixed: {}", repair));
+    }
+
+    if global.dry_run {
+        outln!("Would write {}", output.display());
+        return Ok(());
+    }
+    write_output(&png, &png, output, global)
+}
+
+// Every file under dir that starts with the PNG signature, in a stable order.
+fn find_pngs(dir: &Path, pngs: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries = fs::read_dir(dir)
+        .with_context(|| format!("Could not read {}", dir.display()))?
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            find_pngs(&path, pngs)?;
+            continue;
+        }
+
+        if has_png_signature(&path) {
+            pngs.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn has_png_signature(path: &Path) -> bool {
+    let mut header = [0; 8];
+    fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok_and(|_| header == Png::STANDARD_HEADER)
+}
+
+// Without --types or --non-standard every file is run through detect instead.
+fn scan(args: ScanArgs, jobs: Option<usize>) -> Result<()> {
+    let mut paths = Vec::new();
+    find_pngs(&args.dir, &mut paths)?;
+    let by_type = !args.types.is_empty() || args.non_standard;
+
+    let bar = progress_bar(paths.len() as u64, "{bar:40} {pos}/{len} PNGs {elapsed}");
+    let results: Vec<Result<Vec<Value>>> = thread_pool(jobs)?.install(|| {
+        paths
+            .par_iter()
+            .map(|path| {
+                let bytes = fs::read(path)?;
+                let rows = match by_type {
+                    true => {
+                        Png::try_from(bytes.as_slice()).map(|png| scan_chunks(&png, path, &args))
+                    }
+                    false => detect_bytes(&bytes).map(|report| scan_findings(&report, path)),
+                };
+                bar.inc(1);
+                rows
+            })
+            .collect()
+    });
+    bar.finish_and_clear();
+
+    let mut rows = Vec::new();
+    let (mut reported, mut failed) = (0, 0);
+    for (path, file_rows) in paths.iter().zip(results) {
+        match file_rows {
+            Ok(file_rows) if file_rows.is_empty() => {}
+            Ok(file_rows) => {
+                reported += 1;
+                rows.extend(file_rows);
+            }
+            Err(e) => {
+                failed += 1;
+                eprintln!("Warning: skipping {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    if args.format != ListFormat::Text {
+        const CHUNK_COLUMNS: &[Column] = &[
+            ("path", "/path"),
+            ("index", "/index"),
+            ("offset", "/offset"),
+            ("type", "/type"),
+            ("length", "/length"),
+        ];
+        const FINDING_COLUMNS: &[Column] = &[
+            ("path", "/path"),
+            ("score", "/score"),
+            ("kind", "/kind"),
+            ("offset", "/offset"),
+            ("description", "/description"),
+        ];
+        let columns = match by_type {
+            true => CHUNK_COLUMNS,
+            false => FINDING_COLUMNS,
+        };
+        print_listing(args.format, columns, rows);
+    } else {
+        for row in &rows {
+            match by_type {
+                true => outln!(
+                    "{}: [{}] offset {} {} ({} bytes)",
+                    row["path"].as_str().unwrap_or_default(),
+                    row["index"],
+                    row["offset"],
+                    row["type"].as_str().unwrap_or_default(),
+                    row["length"]
+                ),
+                false => outln!(
+                    "{}: score {}, {}",
+                    row["path"].as_str().unwrap_or_default(),
+                    row["score"],
+                    row["description"].as_str().unwrap_or_default()
+                ),
+            }
+        }
+    }
+
+    noteln!(
+        "Scanned {} PNGs, {} reported, {} skipped",
+        paths.len(),
+        reported,
+        failed
+    );
+    Ok(())
+}
+
+// Writers rarely finish a file in one event, so changes are gathered until the directory is quiet.
+const WATCH_SETTLE_TIME: Duration = Duration::from_millis(500);
+
+fn watch(args: WatchArgs) -> Result<()> {
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)?;
+    let mode = match args.recursive {
+        true => RecursiveMode::Recursive,
+        false => RecursiveMode::NonRecursive,
+    };
+    watcher
+        .watch(&args.dir, mode)
+        .with_context(|| format!("Could not watch {}", args.dir.display()))?;
+    noteln!("Watching {}", args.dir.display());
+
+    // Modification times after the command last ran, so its own writes do not trigger it again.
+    let mut handled: HashMap<PathBuf, SystemTime> = HashMap::new();
+    loop {
+        let mut paths = BTreeSet::new();
+        watched_paths(receiver.recv()?, &mut paths);
+        while let Ok(event) = receiver.recv_timeout(WATCH_SETTLE_TIME) {
+            watched_paths(event, &mut paths);
+        }
+
+        for path in paths {
+            let modified = || fs::metadata(&path).and_then(|m| m.modified()).ok();
+            if !has_png_signature(&path)
+                || modified().is_some_and(|m| handled.get(&path) == Some(&m))
+            {
+                continue;
+            }
+
+            noteln!("==> {} <==", path.display());
+            if let Err(e) = Cli::command()
+                .try_get_matches_from(watch_command(&args.on_new, &path))
+                .map_err(anyhow::Error::from)
+                .and_then(|matches| config::cli_from_matches(&matches))
+                .and_then(run)
+            {
+                eprintln!("Error: {:#}", e);
+            }
+            if let Some(modified) = modified() {
+                handled.insert(path, modified);
+            }
+        }
+    }
+}
+
+// Files that were created, written or renamed into place, leaving out hidden files such as the
+// temporary ones write_png renames over its target.
+fn watched_paths(event: notify::Result<Event>, paths: &mut BTreeSet<PathBuf>) {
+    let event = match event {
+        Ok(event) => event,
+        Err(e) => return eprintln!("Warning: {}", e),
+    };
+    if !matches!(
+        event.kind,
+        EventKind::Create(_)
+            | EventKind::Modify(ModifyKind::Any | ModifyKind::Data(_) | ModifyKind::Name(_))
+    ) {
+        return;
+    }
+
+    paths.extend(event.paths.into_iter().filter(|path| {
+        path.file_name()
+            .is_some_and(|name| !name.to_string_lossy().starts_with('.'))
+    }));
+}
+
+fn watch_command(template: &[String], path: &Path) -> Vec<OsString> {
+    let mut argv = vec![OsString::from("pngme")];
+    if template.iter().any(|arg| arg == "{}") {
+        argv.extend(template.iter().map(|arg| match arg == "{}" {
+            true => path.as_os_str().to_owned(),
+            false => OsString::from(arg),
+        }));
+    } else if let Some((command, rest)) = template.split_first() {
+        argv.push(command.into());
+        argv.push(path.as_os_str().to_owned());
+        argv.extend(rest.iter().map(OsString::from));
+    }
+
+    argv
+}
+
+fn scan_chunks(png: &Png, path: &Path, args: &ScanArgs) -> Vec<Value> {
+    png.chunks_with_offsets()
+        .enumerate()
+        .filter(|(_, (_, chunk))| {
+            let chunk_type = chunk.chunk_type();
+            args.types.contains(chunk_type) || (args.non_standard && !chunk_type.is_standard())
+        })
+        .map(|(index, (offset, chunk))| {
+            json!({
+                "path": path,
+                "index": index,
+                "offset": offset,
+                "type": chunk.chunk_type().to_string(),
+                "length": chunk.length(),
+            })
+        })
+        .collect()
+}
+
+fn scan_findings(report: &DetectionReport, path: &Path) -> Vec<Value> {
+    output::detection_json(report)["findings"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|finding| {
+            let mut row = finding.clone();
+            row["path"] = json!(path);
+            row["score"] = json!(report.score());
+            row
+        })
+        .collect()
+}
+
+// The carriers must all be PNGs or all be JPEGs. Only PNG carriers keep a history for --audit.
+fn split_encode(args: SplitEncodeArgs, global: &GlobalArgs) -> Result<()> {
+    let jpegs = args
+        .carriers
+        .iter()
+        .map(|path| read_jpeg(path))
+        .collect::<Result<Option<Vec<_>>>>()?;
+    match jpegs {
+        Some(jpegs) => split_encode_into(jpegs, &args),
+        None => {
+            let mut pngs = args
+                .carriers
+                .iter()
+                .map(|path| read_png(path, global.touch_time))
+                .collect::<Result<Vec<_>>>()?;
+            if global.audit {
+                for png in &mut pngs {
+                    record_history(png, global)?;
+                }
+            }
+            split_encode_into(pngs, &args)
+        }
+    }
+}
+
+fn split_encode_into<C: Carrier>(mut carriers: Vec<C>, args: &SplitEncodeArgs) -> Result<()> {
+    let passphrase = Passphrase::new(&args.passphrase)?;
+
+    let envelope = payload_envelope(None, Some(&args.payload))?;
+    let envelope = seal_envelope(envelope, &args.envelope, &passphrase)?;
+    split_across_carriers(
+        &mut carriers,
+        args.method,
+        &args.chunk_type.to_string(),
+        &envelope,
+    )?;
+
+    if let Some(dir) = &args.output_dir {
+        fs::create_dir_all(dir)?;
+    }
+    for (path, carrier) in args.carriers.iter().zip(&carriers) {
+        let output = match &args.output_dir {
+            Some(dir) => dir.join(path.file_name().unwrap_or_default()),
+            None => path.clone(),
+        };
+        write_atomic(&carrier.to_bytes(), &output)?;
+        outln!("Wrote {}", output.display());
+    }
+
+    Ok(())
+}
+
+fn join_decode(args: JoinDecodeArgs) -> Result<()> {
+    let jpegs = args
+        .carriers
+        .iter()
+        .map(|path| read_jpeg(path))
+        .collect::<Result<Option<Vec<_>>>>()?;
+    let slot = args.chunk_type.to_string();
+    let envelope = match jpegs {
+        Some(jpegs) => join_from_carriers(&jpegs, args.method, &slot)?,
+        None => {
+            let pngs = args
+                .carriers
+                .iter()
+                .map(|path| read_png(path, false))
+                .collect::<Result<Vec<_>>>()?;
+            join_from_carriers(&pngs, args.method, &slot)?
+        }
+    };
+    let passphrase = Passphrase::new(&args.passphrase)?;
+    let envelope = open_envelope(envelope, &args.output, &passphrase)?;
+    output_decoded(&Decoded::Envelope(envelope), &args.output)
+}
+
+fn apng(args: ApngArgs, global: &GlobalArgs) -> Result<()> {
+    match args.command {
+        ApngCommand::Info(args) => {
+            for_each_file(expand_files(&args.file, false)?, global.jobs, |file| {
+                apng_info(ApngInfoArgs {
+                    file,
+                    ..args.clone()
+                })
+            })
+        }
+        ApngCommand::Frames(args) => frames(FramesArgs {
+            file: args.file,
+            output_dir: args.output_dir,
+        }),
+    }
+}
+
+fn apng_info(args: ApngInfoArgs) -> Result<()> {
+    let png = read_png(&args.file, false)?;
+    let animation = png
+        .animation()
+        .with_context(|| format!("{} has an invalid animation", args.file.display()))?
+        .with_context(|| {
+            ExitStatus::NotFound.error(format!("{} is not animated", args.file.display()))
+        })?;
+
+    let rows: Vec<Value> = animation
+        .frames
+        .iter()
+        .enumerate()
+        .map(|(index, frame)| {
+            let control = &frame.control;
+            json!({
+                "index": index,
+                "width": control.width,
+                "height": control.height,
+                "x_offset": control.x_offset,
+                "y_offset": control.y_offset,
+                "delay_ms": control.delay().as_millis() as u64,
+                "dispose": format!("{:?}", control.dispose_op).to_lowercase(),
+                "blend": format!("{:?}", control.blend_op).to_lowercase(),
+                "default_image": frame.is_default_image,
+            })
+        })
+        .collect();
+    if args.format != ListFormat::Text {
+        const COLUMNS: &[Column] = &[
+            ("index", "/index"),
+            ("width", "/width"),
+            ("height", "/height"),
+            ("x_offset", "/x_offset"),
+            ("y_offset", "/y_offset"),
+            ("delay_ms", "/delay_ms"),
+            ("dispose", "/dispose"),
+            ("blend", "/blend"),
+            ("default_image", "/default_image"),
+        ];
+        print_listing(args.format, COLUMNS, rows);
+        return Ok(());
+    }
+
+    outln!("Frames: {}", animation.frame_count());
+    match animation.loops_forever() {
+        true => outln!("Plays: forever"),
+        false => outln!("Plays: {}", animation.num_plays),
+    }
+    outln!("Duration: {:?}", animation.duration());
+    for row in &rows {
+        outln!(
+            "  {:>3}  {}x{} at {},{}  {} ms  dispose {}  blend {}{}",
+            row["index"],
+            row["width"],
+            row["height"],
+            row["x_offset"],
+            row["y_offset"],
+            row["delay_ms"],
+            row["dispose"].as_str().unwrap_or_default(),
+            row["blend"].as_str().unwrap_or_default(),
+            match row["default_image"].as_bool() {
+                Some(true) => "  (default image)",
+                _ => "",
+            }
+        );
+    }
+    Ok(())
+}
+
+fn frames(args: FramesArgs) -> Result<()> {
+    let png = read_png(&args.file, false)?;
+    let frames = png.extract_frames()?;
+
+    fs::create_dir_all(&args.output_dir)?;
+    for (index, frame) in frames.iter().enumerate() {
+        write_png(
+            frame,
+            &args.output_dir.join(format!("frame-{:03}.png", index)),
+        )?;
+    }
+
+    outln!(
+        "Wrote {} frames to {}",
+        frames.len(),
+        args.output_dir.display()
+    );
+    Ok(())
+}
+
+fn keygen(args: KeygenArgs) -> Result<()> {
+    let secret_key: [u8; ED25519_KEY_LENGTH] = rand::random();
+    let public_key = ed25519_public_key(&secret_key);
+    let engine = base64::engine::general_purpose::STANDARD;
+
+    let mut public_path = args.key_file.clone().into_os_string();
+    public_path.push(".pub");
+    let public_path = PathBuf::from(public_path);
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options
+        .open(&args.key_file)
+        .and_then(|mut file| writeln!(file, "{}", engine.encode(secret_key)))
+        .with_context(|| format!("Could not create {}", args.key_file.display()))?;
+    fs::write(&public_path, format!("{}\n", engine.encode(public_key)))
+        .with_context(|| format!("Could not write {}", public_path.display()))?;
+
+    outln!("Secret key: {}", args.key_file.display());
+    outln!("Public key: {}", public_path.display());
+    Ok(())
+}
+
+fn sign(args: SignArgs, global: &GlobalArgs) -> Result<()> {
+    let mut png = read_png(&args.file, global.touch_time)?;
+    let original = png.clone();
+    // The history entry is recorded first so the signature covers it.
+    if global.audit && !global.dry_run {
+        record_history(&mut png, global)?;
+    }
+    png.sign(&read_key(&args.key_file)?)?;
+
+    write_output(
+        &original,
+        &png,
+        args.output.as_deref().unwrap_or(&args.file),
+        &GlobalArgs {
+            audit: false,
+            ..global.clone()
+        },
+    )
+}
+
+// Each file's steps run on a copy in memory, so a failing step leaves the file untouched and the
+// rest of the manifest still runs.
+fn apply(args: ApplyArgs, global: &GlobalArgs) -> Result<()> {
+    let manifest = Manifest::load(&args.manifest)?;
+    let results: Vec<Value> = manifest
+        .files
+        .iter()
+        .map(|entry| {
+            let result = apply_entry(entry, global);
+            json!({
+                "path": entry.path,
+                "ok": result.is_ok(),
+                "steps": entry.steps.len(),
+                "error": result.as_ref().err().map(|e| format!("{:#}", e)),
+            })
+        })
+        .collect();
+    let (total, failed) = (
+        results.len(),
+        results.iter().filter(|r| r["ok"] == false).count(),
+    );
+
+    match args.format {
+        ReportFormat::Json => print_json(&Value::Array(results)),
+        ReportFormat::Text => {
+            for result in &results {
+                let path = result["path"].as_str().unwrap_or_default();
+                match result["error"].as_str() {
+                    None => outln!("OK {} ({} steps)", path, result["steps"]),
+                    Some(error) => outln!("FAILED {}: {}", path, error),
+                }
+            }
+            noteln!("Applied {} of {} files", total - failed, total);
+        }
+    }
+    if failed > 0 {
+        bail!("{} of {} files failed", failed, total)
+    }
+    Ok(())
+}
+
+fn apply_entry(entry: &Entry, global: &GlobalArgs) -> Result<()> {
+    let mut png = read_png(&entry.path, global.touch_time)?;
+    let original = png.clone();
+    // As with sign, the history entry goes in just before the last sign step so the signature
+    // covers it, and at the end when nothing is signed.
+    let last_sign = entry
+        .steps
+        .iter()
+        .rposition(|step| matches!(step, Step::Sign { .. }));
+    for (index, step) in entry.steps.iter().enumerate() {
+        if Some(index) == last_sign && global.audit && !global.dry_run {
+            record_history(&mut png, global)?;
+        }
+        apply_step(&mut png, step)
+            .with_context(|| format!("step {} ({}) failed", index + 1, step.name()))?;
+    }
+
+    write_output(
+        &original,
+        &png,
+        entry.output.as_deref().unwrap_or(&entry.path),
+        &GlobalArgs {
+            audit: global.audit && last_sign.is_none(),
+            ..global.clone()
+        },
+    )
+}
+
+// The steps of a manifest entry given on the command line, run on one read of the file.
+fn pipe(args: PipeArgs, global: &GlobalArgs) -> Result<()> {
+    apply_entry(
+        &Entry {
+            path: args.file,
+            output: args.output,
+            steps: args.ops,
+        },
+        global,
+    )
+}
+
+fn apply_step(png: &mut Png, step: &Step) -> Result<()> {
+    match step {
+        Step::Encode {
+            chunk_type,
+            message,
+            file,
+            method,
+            compress,
+            replace,
+        } => {
+            let mut envelope = payload_envelope(message.clone(), file.as_deref())?;
+            if let Some(compression) = compress {
+                envelope = envelope.compressed(*compression);
+            }
+            let chunk_type = chunk_type.to_string();
+            if *replace && *method == StegoMethod::Chunk {
+                // Nothing to replace is fine, the payload is simply added.
+                let _ = png.remove_all_chunks(&chunk_type);
+            }
+            match method {
+                StegoMethod::Chunk => png.embed_envelope(*method, &chunk_type, &envelope)?,
+                _ => png.embed(*method, &chunk_type, &envelope.to_bytes()?)?,
+            }
+        }
+        Step::Remove { chunk_type } => {
+            png.remove_all_chunks(&chunk_type.to_string())?;
+        }
+        Step::Strip { keep } => {
+            png.strip_ancillary(keep);
+        }
+        Step::SetText { keyword, value } => png.put_text(Text::auto(keyword, value)?)?,
+        Step::RemoveText { keyword } => while png.remove_text(keyword).is_some() {},
+        Step::Sign { key } => png.sign(&read_key(key)?)?,
+    }
+    Ok(())
+}
+
+fn verify(args: VerifyArgs) -> Result<()> {
+    let png = read_png(&args.file, false)?;
+    let result = png.verify(&read_key(&args.key_file)?);
+    if args.format == ReportFormat::Json {
+        print_json(&json!({
+            "valid": result.is_ok(),
+            "error": result.as_ref().err().map(|e| e.to_string()),
+        }));
+    }
+    result.with_context(|| {
+        ExitStatus::VerificationFailed.error(format!("{} failed verification", args.file.display()))
+    })?;
+
+    if args.format == ReportFormat::Text {
+        outln!("Signature OK");
+    }
+    Ok(())
+}
+
+fn history(args: HistoryArgs) -> Result<()> {
+    let png = read_png(&args.file, false)?;
+    let history = png.history()?.with_context(|| {
+        ExitStatus::NotFound.error(format!(
+            "{} has no recorded history, changes are only recorded with --audit",
+            args.file.display()
+        ))
+    })?;
+
+    if args.format != ListFormat::Text {
+        const COLUMNS: &[Column] = &[
+            ("time", "/time"),
+            ("version", "/version"),
+            ("operation", "/operation"),
+            ("signer", "/signer"),
+        ];
+        let rows = history
+            .entries
+            .iter()
+            .map(|entry| {
+                json!({
+                    "time": entry.time().to_string(),
+                    "timestamp": entry.timestamp,
+                    "version": entry.version,
+                    "operation": entry.operation,
+                    "signer": entry.signer,
+                })
+            })
+            .collect();
+        print_listing(args.format, COLUMNS, rows);
+        return Ok(());
+    }
+
+    for entry in &history.entries {
+        outln!("{}", entry);
+    }
+    Ok(())
+}
+
+fn completions(args: CompletionsArgs) -> Result<()> {
+    clap_complete::generate(args.shell, &mut Cli::command(), "pngme", &mut io::stdout());
+    Ok(())
+}
+
+fn man(args: ManArgs) -> Result<()> {
+    let command = Cli::command();
+    match &args.output_dir {
+        Some(dir) => {
+            fs::create_dir_all(dir)?;
+            clap_mangen::generate_to(command, dir)
+                .with_context(|| format!("Could not write man pages to {}", dir.display()))?;
+            outln!("Wrote man pages to {}", dir.display());
+        }
+        None => clap_mangen::Man::new(command).render(&mut io::stdout())?,
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::status::StatusError;
+    use clap::Parser;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pngme-cli-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_carrier(path: &Path) {
+        let png = pngme::builder::PngBuilder::new()
+            .chunk(pngme::chunk::Chunk::new(
+                pngme::chunk_type::ChunkType::IHDR,
+                vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0],
+            ))
+            .build()
+            .unwrap();
+        write_png(&png, path).unwrap();
+    }
+
+    fn run_args(args: &[&str]) -> Result<()> {
+        run(Cli::try_parse_from(args)?)
+    }
+
+    // Through read_bytes_within rather than run, as --max-memory is process wide and would reach
+    // tests running alongside.
+    #[test]
+    fn test_max_memory() {
+        let dir = temp_dir("max-memory");
+        let file = dir.join("cover.png");
+        write_carrier(&file);
+        let length = fs::metadata(&file).unwrap().len() as usize;
+
+        assert!(read_bytes_within(&file, Some(length)).is_ok());
+        let error = read_bytes_within(&file, Some(length - 1)).unwrap_err();
+        assert!(error.is::<ParseError>());
+        assert_eq!(ExitStatus::of(&error), ExitStatus::Failure);
+
+        let cli = Cli::try_parse_from(["pngme", "--max-memory", "4096", "print", "a.png"]).unwrap();
+        assert_eq!(cli.global.max_memory, Some(4096));
+    }
+
+    #[test]
+    fn test_jpeg_carrier() {
+        let dir = temp_dir("jpeg");
+        let file = dir.join("cover.jpg");
+        let mut jpeg = vec![0xff, 0xd8, 0xff, 0xe0, 0, 7];
+        jpeg.extend(b"JFIF\0");
+        jpeg.extend([0xff, 0xda, 0, 2, 0x12, 0x34, 0xff, 0xd9]);
+        fs::write(&file, &jpeg).unwrap();
+        let file = file.to_str().unwrap();
+
+        run_args(&["pngme", "encode", file, "ruSt", "hello", "--replace"]).unwrap();
+        run_args(&["pngme", "encode", file, "ruSt", "there", "--replace"]).unwrap();
+        let encoded = Jpeg::try_from(fs::read(file).unwrap().as_slice()).unwrap();
+        assert_eq!(encoded.slots(), ["ruSt"]);
+        let envelope = encoded
+            .extract_envelope(StegoMethod::Chunk, "ruSt")
+            .unwrap();
+        assert_eq!(envelope.payload, b"there");
+        assert!(fs::read(file).unwrap().ends_with(&jpeg[12..]));
+
+        run_args(&["pngme", "decode", file, "ruSt"]).unwrap();
+        assert!(run_args(&["pngme", "decode", file, "abCd"]).is_err());
+        assert!(run_args(&["pngme", "encode", file, "ruSt", "x", "--method", "lsb"]).is_err());
+    }
+
+    #[test]
+    fn test_encode_remove() {
+        let dir = temp_dir("encode");
+        let file = dir.join("cover.png");
+        let output = dir.join("out.png");
+        write_carrier(&file);
+        let (file, output) = (file.to_str().unwrap(), output.to_str().unwrap());
+
+        run_args(&[
+            "pngme",
+            "encode",
+            file,
+            "ruSt",
+            "hello",
+            output,
+            "--compress",
+            "zstd",
+        ])
+        .unwrap();
+        let png = read_png(Path::new(output), false).unwrap();
+        let envelope = png.extract_envelope(StegoMethod::Chunk, "ruSt").unwrap();
+        assert_eq!(envelope.payload, b"hello");
+        assert_eq!(envelope.mime_type.as_deref(), Some("text/plain"));
+        assert!(read_png(Path::new(file), false)
+            .unwrap()
+            .chunk_by_type("ruSt")
+            .is_none());
+
+        run_args(&["pngme", "decode", output, "ruSt"]).unwrap();
+        run_args(&["pngme", "remove", output, "ruSt"]).unwrap();
+        assert!(read_png(Path::new(output), false)
+            .unwrap()
+            .chunk_by_type("ruSt")
+            .is_none());
+        assert!(run_args(&["pngme", "remove", output, "ruSt"]).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_file_round_trip() {
+        let dir = temp_dir("file");
+        let file = dir.join("cover.png");
+        let secret = dir.join("secret.pdf");
+        let out_dir = dir.join("out");
+        write_carrier(&file);
+        fs::write(&secret, b"%PDF-1.7 secret").unwrap();
+        let output = dir.join("out.png");
+        let (file, output) = (file.to_str().unwrap(), output.to_str().unwrap());
+
+        run_args(&[
+            "pngme",
+            "encode",
+            file,
+            "ruSt",
+            output,
+            "--file",
+            secret.to_str().unwrap(),
+            "--fec",
+        ])
+        .unwrap();
+        run_args(&[
+            "pngme",
+            "decode",
+            output,
+            "ruSt",
+            "--output-dir",
+            out_dir.to_str().unwrap(),
+        ])
+        .unwrap();
+        assert_eq!(
+            fs::read(out_dir.join("secret.pdf")).unwrap(),
+            b"%PDF-1.7 secret"
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_decoy_passphrase_file() {
+        let dir = temp_dir("decoy");
+        let file = dir.join("cover.png");
+        write_carrier(&file);
+        let file = file.to_str().unwrap();
+        let passphrase_file = |name: &str, passphrase: &str| {
+            let path = dir.join(name);
+            fs::write(&path, passphrase).unwrap();
+            path.to_str().unwrap().to_string()
+        };
+        let real = passphrase_file("real", "correct horse");
+        let decoy = passphrase_file("decoy", "battery staple");
+
+        let encode = |decoy: &str| {
+            run_args(&[
+                "pngme",
+                "encode",
+                file,
+                "ruSt",
+                "secret",
+                "--deniable",
+                "--decoy",
+                "harmless",
+                "--passphrase-file",
+                &real,
+                "--decoy-passphrase-file",
+                decoy,
+                "--replace",
+            ])
+        };
+        assert!(encode(&real).is_err());
+        encode(&decoy).unwrap();
+
+        for (passphrase, expected) in [(&real, "secret"), (&decoy, "harmless")] {
+            let out_dir = dir.join(expected);
+            run_args(&[
+                "pngme",
+                "decode",
+                file,
+                "ruSt",
+                "--deniable",
+                "--passphrase-file",
+                passphrase,
+                "--output-dir",
+                out_dir.to_str().unwrap(),
+            ])
+            .unwrap();
+            let payload = fs::read(out_dir.join("payload.bin")).unwrap();
+            assert_eq!(payload, expected.as_bytes());
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_passphrase_file() {
+        let dir = temp_dir("passphrase");
+        let file = dir.join("cover.png");
+        let passphrase = dir.join("passphrase");
+        write_carrier(&file);
+        fs::write(&passphrase, "correct horse\nignored\n").unwrap();
+        let (file, passphrase) = (file.to_str().unwrap(), passphrase.to_str().unwrap());
+
+        assert_eq!(
+            read_passphrase_file(Path::new(passphrase)).unwrap(),
+            "correct horse"
+        );
+
+        run_args(&[
+            "pngme",
+            "encode",
+            file,
+            "ruSt",
+            "hello",
+            "--encrypt",
+            "--passphrase-file",
+            passphrase,
+        ])
+        .unwrap();
+        run_args(&[
+            "pngme",
+            "decode",
+            file,
+            "ruSt",
+            "--passphrase-file",
+            passphrase,
+        ])
+        .unwrap();
+
+        let wrong = dir.join("wrong");
+        fs::write(&wrong, "battery staple").unwrap();
+        let wrong = wrong.to_str().unwrap();
+        assert!(run_args(&["pngme", "decode", file, "ruSt", "--passphrase-file", wrong]).is_err());
+
+        let empty = dir.join("empty");
+        fs::write(&empty, "\n").unwrap();
+        let empty = empty.to_str().unwrap();
+        assert!(run_args(&["pngme", "decode", file, "ruSt", "--passphrase-file", empty]).is_err());
+
+        // Resolved once and shared by every job of a batch.
+        let once = dir.join("once");
+        fs::write(&once, "correct horse").unwrap();
+        let shared = Passphrase::new(&PassphraseArgs {
+            passphrase_file: Some(once.clone()),
+            #[cfg(feature = "keyring")]
+            use_keyring: None,
+        })
+        .unwrap();
+        assert_eq!(shared.get(false).unwrap(), "correct horse");
+        fs::remove_file(&once).unwrap();
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| assert_eq!(shared.get(false).unwrap(), "correct horse"));
+            }
+        });
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_report() {
+        let dir = temp_dir("report");
+        let file = dir.join("cover.png");
+        let key = dir.join("key.ed25519");
+        let passphrase = dir.join("passphrase");
+        let wrong = dir.join("wrong");
+        write_carrier(&file);
+        fs::write(&passphrase, "correct horse").unwrap();
+        fs::write(&wrong, "battery staple").unwrap();
+        let (file, key) = (file.to_str().unwrap(), key.to_str().unwrap());
+        let (passphrase, wrong) = (passphrase.to_str().unwrap(), wrong.to_str().unwrap());
+        let public_key = format!("{}.pub", key);
+        let status = |args: &[&str]| ExitStatus::of(&run_args(args).unwrap_err());
+
+        assert_eq!(
+            status(&["pngme", "report", file, "ruSt"]),
+            ExitStatus::NotFound
+        );
+        run_args(&["pngme", "keygen", key]).unwrap();
+        run_args(&[
+            "pngme",
+            "encode",
+            file,
+            "ruSt",
+            "hello",
+            "--compress",
+            "--encrypt",
+            "--sign",
+            key,
+            "--passphrase-file",
+            passphrase,
+        ])
+        .unwrap();
+
+        let report = ["pngme", "report", file, "ruSt", "--format", "json"];
+        run_args(&[&report[..], &["--passphrase-file", passphrase]].concat()).unwrap();
+        run_args(
+            &[
+                &report[..],
+                &["--passphrase-file", passphrase, "--verify", &public_key],
+            ]
+            .concat(),
+        )
+        .unwrap();
+        assert_eq!(
+            status(&[&report[..], &["--passphrase-file", wrong]].concat()),
+            ExitStatus::VerificationFailed
+        );
+
+        // A flipped byte in the stored payload fails the envelope checksum.
+        let mut png = read_png(Path::new(file), false).unwrap();
+        let index = png.chunks().len() - 2;
+        let mut data = png.chunks()[index].chunk_data().to_vec();
+        *data.last_mut().unwrap() ^= 1;
+        png.chunks_mut()[index].set_data(data);
+        write_png(&png, Path::new(file)).unwrap();
+        assert_eq!(status(&report), ExitStatus::VerificationFailed);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_migrate() {
+        let dir = temp_dir("migrate");
+        let file = dir.join("cover.png");
+        write_carrier(&file);
+        let slot = ChunkType::from_bytes_const(*b"ruSt");
+        let mut png = read_png(&file, false).unwrap();
+        png.append_chunk(Chunk::new(slot, b"old message".to_vec()));
+        png.append_chunk(Chunk::new(ChunkType::tEXt, b"Title\0Dice".to_vec()));
+        png.append_chunk(Chunk::new(slot, vec![0xff, 0xfe]));
+        write_png(&png, &file).unwrap();
+        let path = file.to_str().unwrap();
+        let payloads = || {
+            read_png(&file, false)
+                .unwrap()
+                .chunks()
+                .iter()
+                .filter(|c| *c.chunk_type() == slot)
+                .map(|c| Envelope::from_bytes(c.chunk_data()))
+                .collect::<Result<Vec<_>>>()
+        };
+        assert!(payloads().is_err());
+
+        run_args(&["pngme", "migrate", path, "ruSt", "--compress"]).unwrap();
+        let envelopes = payloads().unwrap();
+        assert_eq!(envelopes[0].mime_type.as_deref(), Some("text/plain"));
+        assert_eq!(envelopes[1].mime_type, None);
+        let opened = envelopes[0].clone().decompressed().unwrap();
+        assert_eq!(opened.payload, b"old message");
+        let types: Vec<String> = read_png(&file, false)
+            .unwrap()
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types[types.len() - 3..], ["ruSt", "tEXt", "ruSt"]);
+
+        // Payloads already in envelopes are left alone.
+        let migrated = fs::read(&file).unwrap();
+        run_args(&["pngme", "migrate", path, "ruSt"]).unwrap();
+        assert_eq!(fs::read(&file).unwrap(), migrated);
+        let error = run_args(&["pngme", "migrate", path, "abCd"]).unwrap_err();
+        assert_eq!(ExitStatus::of(&error), ExitStatus::NotFound);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_init_carrier() {
+        let dir = temp_dir("init-carrier");
+        let file = dir.join("cover.png");
+        let path = file.to_str().unwrap();
+        let init =
+            |args: &[&str]| run_args(&[&["pngme", "init-carrier", "-o", path], args].concat());
+
+        init(&["--size", "40x30", "--style", "gradient", "--seed", "1"]).unwrap();
+        let png = read_png(&file, false).unwrap();
+        assert_eq!(png.header().unwrap().width, 40);
+        assert_eq!(
+            png.capacity(StegoMethod::Lsb).unwrap(),
+            (40 * 30 * 3 - 32) / 8
+        );
+
+        init(&["--size", "64x64"]).unwrap();
+        run_args(&["pngme", "encode", path, "ruSt", "hidden", "--method", "lsb"]).unwrap();
+        let png = read_png(&file, false).unwrap();
+        let payload = png.extract(StegoMethod::Lsb, "ruSt").unwrap();
+        assert!(payload.ends_with(b"hidden"));
+
+        // A file as large as the reported capacity fits, envelope and all.
+        let secret = dir.join("secret.bin");
+        fs::write(&secret, b"").unwrap();
+        let envelope = capacity_envelope(Some(&secret)).unwrap();
+        let fits = payload_capacity(&png, StegoMethod::Lsb, &envelope).unwrap();
+        let encode_file = |length: usize| {
+            init(&["--size", "64x64"]).unwrap();
+            fs::write(&secret, vec![7; length]).unwrap();
+            let secret = secret.to_str().unwrap();
+            run_args(&[
+                "pngme", "encode", path, "ruSt", "--file", secret, "--method", "lsb",
+            ])
+        };
+        encode_file(fits).unwrap();
+        assert!(encode_file(fits + 1).is_err());
+
+        assert!(init(&["--size", "40"]).is_err());
+        assert!(init(&["--style", "plaid"]).is_err());
+        let error = init(&["--size", "0x10"]).unwrap_err();
+        assert_eq!(ExitStatus::of(&error), ExitStatus::Usage);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_verify_payload() {
+        let dir = temp_dir("verify-payload");
+        let (file, original, other) = (
+            dir.join("cover.png"),
+            dir.join("original.bin"),
+            dir.join("other.bin"),
+        );
+        write_carrier(&file);
+        fs::write(&original, [0, 1, 2, 3, 4]).unwrap();
+        fs::write(&other, [0, 1, 2, 3]).unwrap();
+        let [path, original_path, other_path] =
+            [&file, &original, &other].map(|path| path.to_str().unwrap());
+        run_args(&[
+            "pngme",
+            "encode",
+            path,
+            "ruSt",
+            "--file",
+            original_path,
+            "--compress",
+        ])
+        .unwrap();
+        let verify =
+            |args: &[&str]| run_args(&[&["pngme", "verify-payload", path, "ruSt"], args].concat());
+
+        verify(&["--against", original_path]).unwrap();
+        verify(&["--against", original_path, "--format", "json"]).unwrap();
+        let error = verify(&["--against", other_path]).unwrap_err();
+        assert_eq!(ExitStatus::of(&error), ExitStatus::VerificationFailed);
+        assert!(format!("{:#}", error).contains("first differs at byte 4"));
+
+        let digest = DataFormat::Hex.render(&Sha256::digest([0, 1, 2, 3, 4]));
+        verify(&["--sha256", &digest]).unwrap();
+        let error = verify(&["--sha256", &"00".repeat(32)]).unwrap_err();
+        assert_eq!(ExitStatus::of(&error), ExitStatus::VerificationFailed);
+        let error = verify(&["--sha256", "abc"]).unwrap_err();
+        assert_eq!(ExitStatus::of(&error), ExitStatus::Usage);
+        assert!(verify(&[]).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_history() {
+        let dir = temp_dir("history");
+        let file = dir.join("cover.png");
+        let key = dir.join("key.ed25519");
+        let config = dir.join("config.toml");
+        write_carrier(&file);
+        fs::write(&config, "").unwrap();
+        let (file, key, config) = (
+            file.to_str().unwrap(),
+            key.to_str().unwrap(),
+            config.to_str().unwrap(),
+        );
+        // Through the config like main, which is where the operation name comes from.
+        let run_audited = |args: &[&str]| {
+            let args = [args, &["--config", config]].concat();
+            run(config::cli_from_matches(
+                &Cli::command().try_get_matches_from(args)?,
+            )?)
+        };
+
+        run_args(&["pngme", "encode", file, "ruSt", "unrecorded"]).unwrap();
+        let error = run_args(&["pngme", "history", file]).unwrap_err();
+        assert_eq!(ExitStatus::of(&error), ExitStatus::NotFound);
+
+        run_audited(&[
+            "pngme",
+            "encode",
+            file,
+            "ruSt",
+            "hi",
+            "--replace",
+            "--audit",
+        ])
+        .unwrap();
+        run_audited(&["pngme", "text", "set", file, "Title", "Hi", "--audit"]).unwrap();
+        run_audited(&["pngme", "keygen", key]).unwrap();
+        run_audited(&[
+            "pngme",
+            "sign",
+            file,
+            key,
+            "--audit",
+            "--audit-signer",
+            "alice",
+        ])
+        .unwrap();
+        run_args(&["pngme", "verify", file, &format!("{}.pub", key)]).unwrap();
+        run_args(&["pngme", "history", file, "--format", "json"]).unwrap();
+
+        let history = read_png(Path::new(file), false)
+            .unwrap()
+            .history()
+            .unwrap()
+            .unwrap();
+        let operations: Vec<&str> = history
+            .entries
+            .iter()
+            .map(|entry| entry.operation.as_str())
+            .collect();
+        assert_eq!(operations, ["encode", "text set", "sign"]);
+        assert_eq!(history.entries[0].signer, None);
+        assert_eq!(history.entries[2].signer.as_deref(), Some("alice"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let dir = temp_dir("sign");
+        let file = dir.join("cover.png");
+        let key = dir.join("key.ed25519");
+        write_carrier(&file);
+        let (file, key) = (file.to_str().unwrap(), key.to_str().unwrap());
+        let public_key = format!("{}.pub", key);
+
+        run_args(&["pngme", "keygen", key]).unwrap();
+        assert!(run_args(&["pngme", "keygen", key]).is_err());
+
+        run_args(&["pngme", "encode", file, "ruSt", "hello", "--sign", key]).unwrap();
+        run_args(&["pngme", "decode", file, "ruSt", "--verify", &public_key]).unwrap();
+
+        run_args(&["pngme", "sign", file, key]).unwrap();
+        run_args(&["pngme", "verify", file, &public_key]).unwrap();
+        run_args(&["pngme", "encode", file, "teSt", "tamper"]).unwrap();
+        assert!(run_args(&["pngme", "verify", file, &public_key]).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_decode_all() {
+        let dir = temp_dir("all");
+        let file = dir.join("cover.png");
+        write_carrier(&file);
+        let file = file.to_str().unwrap();
+
+        assert!(run_args(&["pngme", "decode", file, "ruSt", "--all"]).is_err());
+        run_args(&["pngme", "encode", file, "ruSt", "first"]).unwrap();
+        run_args(&["pngme", "encode", file, "ruSt", "second", "--fec"]).unwrap();
+        run_args(&["pngme", "decode", file, "ruSt", "--all"]).unwrap();
+        assert!(run_args(&["pngme", "decode", file, "ruSt", "--all", "--method", "lsb"]).is_err());
+
+        let png = read_png(Path::new(file), false).unwrap();
+        let offsets: Vec<usize> = png
+            .chunks_with_offsets()
+            .map(|(offset, _)| offset)
+            .collect();
+        assert_eq!(offsets[0], 8);
+        assert_eq!(offsets[1], 8 + 13 + 12);
+        assert_eq!(png.as_bytes()[offsets[2] + 4..offsets[2] + 8], *b"ruSt");
+
+        let matches = numbered_chunks(&png, "ruSt".parse().unwrap());
+        assert_eq!(matches.len(), 2);
+        for (index, (offset, chunk)) in matches {
+            let nth = png.nth_chunk_by_type("ruSt", index).unwrap();
+            assert!(std::ptr::eq(nth, chunk));
+            assert_eq!(png.as_bytes()[offset + 4..offset + 8], *b"ruSt");
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_input_encoding() {
+        let dir = temp_dir("encoding");
+        let file = dir.join("cover.png");
+        write_carrier(&file);
+        let file = file.to_str().unwrap();
+
+        let args = [
+            "pngme",
+            "encode",
+            file,
+            "ruSt",
+            "00ff 0a",
+            "--input-encoding",
+            "hex",
+        ];
+        run_args(&args).unwrap();
+        run_args(&[
+            "pngme",
+            "decode",
+            file,
+            "ruSt",
+            "--output-encoding",
+            "base64",
+        ])
+        .unwrap();
+        let png = read_png(Path::new(file), false).unwrap();
+        let data = png.chunk_by_type("ruSt").unwrap().chunk_data();
+        let envelope = Envelope::from_bytes(data).unwrap();
+        assert_eq!(envelope.payload, b"\0\xff\n");
+        assert_eq!(envelope.mime_type, None);
+
+        let args = [
+            "pngme",
+            "encode",
+            file,
+            "ruSt",
+            "AP8",
+            "--input-encoding",
+            "base64",
+        ];
+        assert!(run_args(&args).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_index() {
+        let dir = temp_dir("index");
+        let file = dir.join("cover.png");
+        write_carrier(&file);
+        let file = file.to_str().unwrap();
+
+        for message in ["first", "second", "third"] {
+            run_args(&["pngme", "encode", file, "ruSt", message, "--fec"]).unwrap();
+        }
+        run_args(&["pngme", "decode", file, "ruSt", "--index", "2"]).unwrap();
+        assert!(run_args(&["pngme", "decode", file, "ruSt", "--index", "3"]).is_err());
+        run_args(&["pngme", "hexdump", file, "ruSt", "--index", "2"]).unwrap();
+        assert!(run_args(&["pngme", "hexdump", file, "ruSt", "--index", "3"]).is_err());
+
+        run_args(&["pngme", "remove", file, "ruSt", "--index", "1"]).unwrap();
+        let png = read_png(Path::new(file), false).unwrap();
+        let remaining: Vec<Vec<u8>> = (0..2)
+            .map(|n| {
+                let data = png.nth_chunk_by_type("ruSt", n).unwrap().chunk_data();
+                fec::recover(data).unwrap().data
+            })
+            .map(|bytes| Envelope::from_bytes(&bytes).unwrap().payload)
+            .collect();
+        assert_eq!(remaining, [b"first".to_vec(), b"third".to_vec()]);
+        assert!(run_args(&["pngme", "remove", file, "ruSt", "--index", "2"]).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_replace() {
+        let dir = temp_dir("replace");
+        let file = dir.join("cover.png");
+        write_carrier(&file);
+        let file = file.to_str().unwrap();
+
+        let stored = |file: &str| {
+            let png = read_png(Path::new(file), false).unwrap();
+            let index = png
+                .chunks()
+                .iter()
+                .position(|c| c.chunk_type().to_string() == "ruSt")
+                .unwrap();
+            let count = png
+                .chunks()
+                .iter()
+                .filter(|c| c.chunk_type().to_string() == "ruSt")
+                .count();
+            let envelope = png.extract_envelope(StegoMethod::Chunk, "ruSt").unwrap();
+            (index, count, envelope.payload)
+        };
+
+        run_args(&["pngme", "encode", file, "ruSt", "first"]).unwrap();
+        let (index, _, _) = stored(file);
+        run_args(&["pngme", "encode", file, "ruSt", "second", "--replace"]).unwrap();
+        assert_eq!(stored(file), (index, 1, b"second".to_vec()));
+
+        run_args(&[
+            "pngme",
+            "encode",
+            file,
+            "ruSt",
+            "third",
+            "--on-duplicate",
+            "replace",
+        ])
+        .unwrap();
+        assert_eq!(stored(file), (index, 1, b"third".to_vec()));
+
+        assert!(run_args(&[
+            "pngme",
+            "encode",
+            file,
+            "ruSt",
+            "fourth",
+            "--on-duplicate",
+            "error"
+        ])
+        .is_err());
+        run_args(&["pngme", "encode", file, "ruSt", "fourth"]).unwrap();
+        assert_eq!(stored(file), (index, 2, b"third".to_vec()));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_atomic_write_and_backup() {
+        let dir = temp_dir("backup");
+        let file = dir.join("cover.png");
+        write_carrier(&file);
+        let original = fs::read(&file).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&file, fs::Permissions::from_mode(0o640)).unwrap();
+        }
+        let file_str = file.to_str().unwrap();
+
+        run_args(&["pngme", "encode", file_str, "ruSt", "hello", "--backup"]).unwrap();
+        assert_eq!(fs::read(dir.join("cover.png.bak")).unwrap(), original);
+        assert_ne!(fs::read(&file).unwrap(), original);
+        run_args(&["pngme", "remove", file_str, "ruSt", "--backup", "~"]).unwrap();
+        assert!(dir.join("cover.png~").exists());
+
+        // Only the image and its two backups, no temporary files left behind.
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 3);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&file).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o640);
+        }
+
+        // Temporary files are never opened through a link planted beside the output.
+        #[cfg(unix)]
+        {
+            let victim = dir.join("victim");
+            fs::write(&victim, "untouched").unwrap();
+            let planted = dir.join(format!(".cover.png.{}.tmp", std::process::id()));
+            std::os::unix::fs::symlink(&victim, &planted).unwrap();
+            write_atomic(b"written", &file).unwrap();
+            assert_eq!(fs::read(&file).unwrap(), b"written");
+            assert_eq!(fs::read(&victim).unwrap(), b"untouched");
+
+            let (temp, _) = create_temp(&dir, OsStr::new("cover.png")).unwrap();
+            assert_ne!(create_temp(&dir, OsStr::new("cover.png")).unwrap().0, temp);
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let dir = temp_dir("dry-run");
+        let file = dir.join("cover.png");
+        write_carrier(&file);
+        let original = fs::read(&file).unwrap();
+        let file_str = file.to_str().unwrap();
+
+        run_args(&["pngme", "encode", file_str, "ruSt", "hello", "--dry-run"]).unwrap();
+        run_args(&[
+            "pngme",
+            "--dry-run",
+            "encode",
+            file_str,
+            "ruSt",
+            "hello",
+            "--backup",
+        ])
+        .unwrap();
+        assert_eq!(fs::read(&file).unwrap(), original);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        run_args(&["pngme", "encode", file_str, "ruSt", "hello"]).unwrap();
+        let encoded = fs::read(&file).unwrap();
+        run_args(&["pngme", "remove", file_str, "ruSt", "--dry-run"]).unwrap();
+        assert_eq!(fs::read(&file).unwrap(), encoded);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_preview() {
+        let dir = temp_dir("preview");
+        let file = dir.join("cover.png");
+        write_carrier(&file);
+        let file = file.to_str().unwrap();
+        let binary = "ff".repeat(1000);
+        run_args(&[
+            "pngme",
+            "encode",
+            file,
+            "ruSt",
+            &binary,
+            "--input-encoding",
+            "hex",
+        ])
+        .unwrap();
+
+        for args in [
+            vec!["pngme", "print", file, "--max-preview", "4"],
+            vec!["pngme", "print", file, "--full"],
+            vec!["pngme", "decode", file, "ruSt", "--format", "hex"],
+            vec!["pngme", "decode", file, "ruSt", "--max-preview", "8"],
+            vec!["pngme", "decode", file, "ruSt", "--full", "--all"],
+        ] {
+            run_args(&args).unwrap();
+        }
+        assert!(run_args(&["pngme", "print", file, "--full", "--max-preview", "4"]).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_json_format() {
+        let dir = temp_dir("json");
+        let file = dir.join("cover.png");
+        write_carrier(&file);
+        let file = file.to_str().unwrap();
+
+        run_args(&["pngme", "encode", file, "ruSt", "hello"]).unwrap();
+        for args in [
+            vec!["pngme", "print", file, "--format", "json"],
+            vec!["pngme", "decode", file, "ruSt", "--format", "json"],
+            vec!["pngme", "decode", file, "ruSt", "--all", "--format", "json"],
+            vec!["pngme", "print", file, "--format", "table"],
+            vec!["pngme", "print", file, "--format", "csv"],
+            vec!["pngme", "print", file, "--format", "yaml"],
+            vec!["pngme", "capacity", file, "--format", "json"],
+            vec!["pngme", "capacity", file, "--format", "csv"],
+            vec!["pngme", "detect", file, "--format", "json"],
+        ] {
+            run_args(&args).unwrap();
+        }
+
+        let args = OutputArgs {
+            output_dir: Some(dir.join("out")),
+            format: PayloadFormat::Json,
+            identities: Vec::new(),
+            verify: None,
+            preview: PreviewArgs {
+                max_preview: None,
+                full: false,
+            },
+        };
+        let mut envelope = Envelope::new(b"hello".to_vec());
+        envelope.filename = Some("note.txt".to_string());
+        let value = decoded_json(&Decoded::Envelope(envelope), &args).unwrap();
+        assert_eq!(value["filename"], "note.txt");
+        assert_eq!(value.get("payload"), None);
+        assert_eq!(
+            fs::read(dir.join("out").join("note.txt")).unwrap(),
+            b"hello"
+        );
+
+        let value = decoded_json(&Decoded::Raw(b"hi".to_vec()), &args).unwrap();
+        assert_eq!(value["payload"], "aGk=");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_scan() {
+        let dir = temp_dir("scan");
+        let nested = dir.join("nested");
+        fs::create_dir_all(&nested).unwrap();
+        write_carrier(&dir.join("clean.png"));
+        write_carrier(&nested.join("hidden.png"));
+        fs::write(dir.join("notes.txt"), b"not a png").unwrap();
+        let hidden = nested.join("hidden.png");
+        run_args(&["pngme", "encode", hidden.to_str().unwrap(), "ruSt", "hi"]).unwrap();
+
+        let mut paths = Vec::new();
+        find_pngs(&dir, &mut paths).unwrap();
+        assert_eq!(paths, [dir.join("clean.png"), hidden.clone()]);
+
+        let png = read_png(&hidden, false).unwrap();
+        let args = ScanArgs {
+            dir: dir.clone(),
+            types: vec![ChunkType::IHDR],
+            non_standard: true,
+            format: ListFormat::Json,
+        };
+        let rows = scan_chunks(&png, &hidden, &args);
+        let types: Vec<&Value> = rows.iter().map(|row| &row["type"]).collect();
+        assert_eq!(types, ["IHDR", "ruSt"]);
+        assert_eq!(
+            rows[1]["offset"],
+            png.chunks_with_offsets().nth(1).unwrap().0
+        );
+
+        let dir_str = dir.to_str().unwrap();
+        run_args(&["pngme", "scan", dir_str]).unwrap();
+        run_args(&["pngme", "scan", dir_str, "--types", "ruSt,teXt"]).unwrap();
+        run_args(&[
+            "pngme",
+            "scan",
+            dir_str,
+            "--non-standard",
+            "--format",
+            "csv",
+        ])
+        .unwrap();
+        assert!(run_args(&["pngme", "scan", "/nonexistent"]).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_glob_files() {
+        let dir = temp_dir("glob");
+        let nested = dir.join("nested");
+        fs::create_dir_all(&nested).unwrap();
+        for file in [dir.join("a.png"), nested.join("b.png")] {
+            write_carrier(&file);
+        }
+        let pattern = format!("{}/**/*.png", dir.display());
+
+        assert_eq!(
+            expand_files(Path::new(&pattern), false).unwrap(),
+            [dir.join("a.png"), nested.join("b.png")]
+        );
+        assert!(expand_files(Path::new(&pattern), true).is_err());
+        assert!(expand_files(&dir.join("*.jpg"), false).is_err());
+        assert_eq!(
+            expand_files(Path::new("missing.png"), false).unwrap(),
+            [PathBuf::from("missing.png")]
+        );
+
+        run_args(&["pngme", "encode", &pattern, "cOpy", "(c) 2024"]).unwrap();
+        for file in [dir.join("a.png"), nested.join("b.png")] {
+            let png = read_png(&file, false).unwrap();
+            assert!(png.chunk_by_type("cOpy").is_some());
+        }
+        run_args(&["pngme", "print", &pattern, "--no-progress"]).unwrap();
+        assert!(run_args(&["pngme", "encode", &pattern, "cOpy", "x", "out.png"]).is_err());
+
+        // One file without the chunk fails, the other is still processed.
+        run_args(&[
+            "pngme",
+            "remove",
+            nested.join("b.png").to_str().unwrap(),
+            "cOpy",
+        ])
+        .unwrap();
+        assert!(run_args(&["pngme", "remove", &pattern, "cOpy"]).is_err());
+        assert!(read_png(&dir.join("a.png"), false)
+            .unwrap()
+            .chunk_by_type("cOpy")
+            .is_none());
+
+        run_args(&["pngme", "--jobs", "2", "encode", &pattern, "cOpy", "x"]).unwrap();
+        run_args(&["pngme", "--jobs", "0", "remove", &pattern, "cOpy"]).unwrap();
+        assert!(read_png(&dir.join("a.png"), false)
+            .unwrap()
+            .chunk_by_type("cOpy")
+            .is_none());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_watch_command() {
+        let template = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let path = Path::new("drop/new.png");
+
+        assert_eq!(
+            watch_command(&template(&["decode", "ruSt", "--all"]), path),
+            ["pngme", "decode", "drop/new.png", "ruSt", "--all"]
+        );
+        assert_eq!(
+            watch_command(&template(&["--dry-run", "remove", "{}", "ruSt"]), path),
+            ["pngme", "--dry-run", "remove", "drop/new.png", "ruSt"]
+        );
+
+        let cli = Cli::try_parse_from([
+            "pngme", "watch", "drop", "--on-new", "decode", "ruSt", "--all",
+        ])
+        .unwrap();
+        let Command::Watch(args) = cli.command else {
+            panic!("expected watch")
+        };
+        assert_eq!(args.on_new, ["decode", "ruSt", "--all"]);
+    }
+
+    #[test]
+    fn test_man_pages() {
+        let dir = temp_dir("man");
+        let dir_str = dir.to_str().unwrap();
+
+        run_args(&["pngme", "man", "--output-dir", dir_str]).unwrap();
+        assert!(dir.join("pngme.1").exists());
+        assert!(dir.join("pngme-encode.1").exists());
+        run_args(&["pngme", "completions", "bash"]).unwrap();
+        assert!(run_args(&["pngme", "completions", "tcsh"]).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_exit_status() {
+        let dir = temp_dir("status");
+        let file = dir.join("cover.png");
+        let broken = dir.join("broken.png");
+        write_carrier(&file);
+        fs::write(&broken, [&Png::STANDARD_HEADER[..], b"\0\0"].concat()).unwrap();
+        let (file, broken) = (file.to_str().unwrap(), broken.to_str().unwrap());
+        let status = |args: &[&str]| ExitStatus::of(&run_args(args).unwrap_err());
+
+        run_args(&["pngme", "--quiet", "print", file]).unwrap();
+        assert_eq!(
+            status(&["pngme", "remove", file, "ruSt"]),
+            ExitStatus::NotFound
+        );
+        assert_eq!(
+            status(&["pngme", "decode", file, "ruSt"]),
+            ExitStatus::NotFound
+        );
+        assert_eq!(status(&["pngme", "print", broken]), ExitStatus::InvalidPng);
+
+        let pattern = format!("{}/*.png", dir.display());
+        assert_eq!(
+            status(&["pngme", "hexdump", &pattern, "ruSt"]),
+            ExitStatus::Failure
+        );
+        assert_eq!(
+            status(&["pngme", "print", &pattern]),
+            ExitStatus::InvalidPng
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_repair() {
+        let dir = temp_dir("repair");
+        let file = dir.join("cover.png");
+        write_carrier(&file);
+        let intact = fs::read(&file).unwrap();
+        let mut bytes = intact.clone();
+        bytes.truncate(bytes.len() - 12);
+        bytes.extend_from_slice(b"junk");
+        fs::write(&file, &bytes).unwrap();
+        let path = file.to_str().unwrap();
+
+        assert!(run_args(&["pngme", "print", path]).is_err());
+        run_args(&["pngme", "--dry-run", "repair", path]).unwrap();
+        assert_eq!(fs::read(&file).unwrap(), bytes);
+        run_args(&["pngme", "repair", path]).unwrap();
+        assert_eq!(fs::read(&file).unwrap(), intact);
+        run_args(&["pngme", "print", path]).unwrap();
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_strip() {
+        let dir = temp_dir("strip");
+        let file = dir.join("cover.png");
+        write_carrier(&file);
+        let mut png = Png::try_from(fs::read(&file).unwrap().as_slice()).unwrap();
+        let critical = png.chunks().len();
+        png.insert_chunk_ordered(Chunk::new(ChunkType::tEXt, b"Author\0someone".to_vec()));
+        png.insert_chunk_ordered(Chunk::new(ChunkType::pHYs, vec![0; 9]));
+        png.insert_chunk_ordered(Chunk::new("ruSt".parse().unwrap(), b"hi".to_vec()));
+        fs::write(&file, png.as_bytes()).unwrap();
+        let path = file.to_str().unwrap();
+
+        run_args(&["pngme", "strip", path, "--keep", "pHYs"]).unwrap();
+        let png = Png::try_from(fs::read(&file).unwrap().as_slice()).unwrap();
+        assert_eq!(png.chunks().len(), critical + 1);
+        assert!(png.chunk_by_type("pHYs").is_some());
+        assert!(png.chunk_by_type("tEXt").is_none());
+        assert!(png.chunk_by_type("ruSt").is_none());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_sanitize() {
+        let dir = temp_dir("sanitize");
+        let file = dir.join("cover.png");
+        write_carrier(&file);
+        let path = file.to_str().unwrap();
+        run_args(&["pngme", "encode", path, "ruSt", "hidden"]).unwrap();
+        let mut bytes = fs::read(&file).unwrap();
+        bytes.extend_from_slice(b"more");
+        fs::write(&file, &bytes).unwrap();
+
+        run_args(&["pngme", "sanitize", path]).unwrap();
+        let png = Png::try_from(fs::read(&file).unwrap().as_slice()).unwrap();
+        assert!(png.chunk_by_type("ruSt").is_none());
+        // The carrier has no image data to randomize.
+        assert!(run_args(&["pngme", "sanitize", path, "--randomize-lsb"]).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_lint() {
+        let dir = temp_dir("lint");
+        let file = dir.join("cover.png");
+        write_carrier(&file);
+        let path = file.to_str().unwrap();
+
+        // The carrier has no image data.
+        let error = run_args(&["pngme", "lint", path]).unwrap_err();
+        assert_eq!(ExitStatus::of(&error), ExitStatus::InvalidPng);
+
+        let mut png = Png::try_from(fs::read(&file).unwrap().as_slice()).unwrap();
+        png.insert_chunk_ordered(Chunk::new("IDAT".parse().unwrap(), vec![0; 4]));
+        let mut bytes = png.as_bytes();
+        fs::write(&file, &bytes).unwrap();
+        run_args(&["pngme", "lint", path, "--format", "json"]).unwrap();
+
+        bytes.extend_from_slice(b"junk");
+        fs::write(&file, &bytes).unwrap();
+        run_args(&["pngme", "lint", path]).unwrap();
+        assert!(run_args(&["pngme", "lint", path, "--strict"]).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_diff() {
+        let dir = temp_dir("diff");
+        let (before, after) = (dir.join("before.png"), dir.join("after.png"));
+        write_carrier(&before);
+        write_carrier(&after);
+        let (before, after) = (before.to_str().unwrap(), after.to_str().unwrap());
+        run_args(&["pngme", "encode", after, "ruSt", "hidden"]).unwrap();
+
+        run_args(&["pngme", "diff", before, before]).unwrap();
+        run_args(&["pngme", "diff", before, after, "--bytes"]).unwrap();
+        run_args(&["pngme", "diff", before, after, "--format", "json"]).unwrap();
+        assert!(run_args(&["pngme", "diff", before, "/nonexistent/after.png"]).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_merge() {
+        let dir = temp_dir("merge");
+        let (source, file) = (dir.join("source.png"), dir.join("cover.png"));
+        write_carrier(&source);
+        write_carrier(&file);
+        let mut png = Png::try_from(fs::read(&source).unwrap().as_slice()).unwrap();
+        png.insert_chunk_ordered(Chunk::new(ChunkType::tEXt, b"Author\0someone".to_vec()));
+        png.insert_chunk_ordered(Chunk::new(ChunkType::sBIT, vec![8]));
+        fs::write(&source, png.as_bytes()).unwrap();
+        let (source, path) = (source.to_str().unwrap(), file.to_str().unwrap());
+
+        run_args(&[
+            "pngme",
+            "merge",
+            path,
+            "--from",
+            source,
+            "--types",
+            "tEXt,sBIT",
+        ])
+        .unwrap();
+        let png = Png::try_from(fs::read(&file).unwrap().as_slice()).unwrap();
+        assert!(png.chunk_by_type("tEXt").is_some());
+        assert!(png.chunk_by_type("sBIT").is_none());
+
+        let error =
+            run_args(&["pngme", "merge", path, "--from", source, "--types", "sBIT"]).unwrap_err();
+        assert_eq!(ExitStatus::of(&error), ExitStatus::NotFound);
+        run_args(&[
+            "pngme", "--force", "merge", path, "--from", source, "--types", "sBIT",
+        ])
+        .unwrap();
+        let png = Png::try_from(fs::read(&file).unwrap().as_slice()).unwrap();
+        assert!(png.chunk_by_type("sBIT").is_some());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_extract_inject() {
+        let dir = temp_dir("extract");
+        let (file, data, chunk) = (
+            dir.join("cover.png"),
+            dir.join("data.bin"),
+            dir.join("chunk.bin"),
+        );
+        write_carrier(&file);
+        let [path, data_path, chunk_path] =
+            [&file, &data, &chunk].map(|path| path.to_str().unwrap());
+        fs::write(&data, b"\x00\x01 raw bytes").unwrap();
+
+        run_args(&["pngme", "inject", path, "ruSt", "--from", data_path]).unwrap();
+        run_args(&[
+            "pngme",
+            "extract",
+            path,
+            "ruSt",
+            "--with-header",
+            "-o",
+            chunk_path,
+        ])
+        .unwrap();
+        let png = Png::try_from(fs::read(&file).unwrap().as_slice()).unwrap();
+        assert_eq!(
+            fs::read(&chunk).unwrap(),
+            png.chunk_by_type("ruSt").unwrap().as_bytes()
+        );
+
+        fs::remove_file(&data).unwrap();
+        run_args(&["pngme", "extract", path, "ruSt", "-o", data_path]).unwrap();
+        assert_eq!(fs::read(&data).unwrap(), b"\x00\x01 raw bytes");
+
+        run_args(&[
+            "pngme",
+            "inject",
+            path,
+            "ruSt",
+            "--from",
+            chunk_path,
+            "--with-header",
+            "--replace",
+        ])
+        .unwrap();
+        let png = Png::try_from(fs::read(&file).unwrap().as_slice()).unwrap();
+        assert_eq!(
+            png.chunks()
+                .iter()
+                .filter(|c| c.chunk_type().to_string() == "ruSt")
+                .count(),
+            1
+        );
+        assert!(run_args(&[
+            "pngme",
+            "inject",
+            path,
+            "teSt",
+            "--from",
+            chunk_path,
+            "--with-header"
+        ])
+        .is_err());
+
+        let error = run_args(&["pngme", "extract", path, "teSt", "-o", data_path]).unwrap_err();
+        assert_eq!(ExitStatus::of(&error), ExitStatus::NotFound);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_dedupe() {
+        let dir = temp_dir("dedupe");
+        let file = dir.join("cover.png");
+        write_carrier(&file);
+        let path = file.to_str().unwrap();
+        let mut png = Png::try_from(fs::read(&file).unwrap().as_slice()).unwrap();
+        let length = png.chunks().len();
+        for _ in 0..3 {
+            png.insert_chunk_ordered(Chunk::new(ChunkType::tEXt, b"Author\0someone".to_vec()));
+        }
+        fs::write(&file, png.as_bytes()).unwrap();
+
+        run_args(&["pngme", "dedupe", path]).unwrap();
+        let png = Png::try_from(fs::read(&file).unwrap().as_slice()).unwrap();
+        assert_eq!(png.chunks().len(), length + 1);
+        run_args(&["pngme", "dedupe", path]).unwrap();
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let dir = temp_dir("canonicalize");
+        let file = dir.join("cover.png");
+        write_carrier(&file);
+        let mut png = Png::try_from(fs::read(&file).unwrap().as_slice()).unwrap();
+        png.append_chunk(Chunk::new(ChunkType::gAMA, vec![0, 0, 0xb1, 0x8f]));
+        fs::write(&file, png.as_bytes()).unwrap();
+        let path = file.to_str().unwrap();
+
+        run_args(&["pngme", "canonicalize", path]).unwrap();
+        let png = Png::try_from(fs::read(&file).unwrap().as_slice()).unwrap();
+        assert_eq!(*png.chunks()[1].chunk_type(), ChunkType::gAMA);
+        assert_eq!(*png.chunks().last().unwrap().chunk_type(), ChunkType::IEND);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_text() {
+        let dir = temp_dir("text");
+        let file = dir.join("cover.png");
+        write_carrier(&file);
+        let path = file.to_str().unwrap();
+        let read = || Png::try_from(fs::read(&file).unwrap().as_slice()).unwrap();
+
+        run_args(&["pngme", "text", "set", path, "Title", "Dice"]).unwrap();
+        run_args(&["pngme", "text", "set", path, "Author", "サイコロ"]).unwrap();
+        run_args(&["pngme", "text", "set", path, "Title", "Würfel"]).unwrap();
+        let png = read();
+        assert_eq!(png.get_text("Title").as_deref(), Some("Würfel"));
+        assert!(png.chunk_by_type("iTXt").is_some());
+        assert_eq!(png.texts().count(), 2);
+
+        run_args(&["pngme", "text", "get", path, "Author"]).unwrap();
+        run_args(&["pngme", "text", "list", path, "--format", "csv"]).unwrap();
+        run_args(&["pngme", "text", "remove", path, "Title"]).unwrap();
+        assert_eq!(read().texts().count(), 1);
+
+        let error = run_args(&["pngme", "text", "get", path, "Title"]).unwrap_err();
+        assert_eq!(ExitStatus::of(&error), ExitStatus::NotFound);
+        let error = run_args(&["pngme", "text", "remove", path, "Title"]).unwrap_err();
+        assert_eq!(ExitStatus::of(&error), ExitStatus::NotFound);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_kv() {
+        let dir = temp_dir("kv");
+        let file = dir.join("cover.png");
+        write_carrier(&file);
+        let path = file.to_str().unwrap();
+        let read = || Png::try_from(fs::read(&file).unwrap().as_slice()).unwrap();
+
+        run_args(&["pngme", "kv", "set", path, "build", "1.2.3"]).unwrap();
+        run_args(&["pngme", "kv", "set", path, "commit", "abc123"]).unwrap();
+        run_args(&["pngme", "kv", "set", path, "build", "1.2.4"]).unwrap();
+        let png = read();
+        assert_eq!(png.kv_get("build").unwrap().as_deref(), Some("1.2.4"));
+        assert_eq!(png.kv_iter().unwrap().count(), 2);
+
+        run_args(&["pngme", "kv", "get", path, "commit"]).unwrap();
+        run_args(&["pngme", "kv", "list", path, "--format", "json"]).unwrap();
+        run_args(&["pngme", "kv", "remove", path, "build"]).unwrap();
+        assert_eq!(read().kv_iter().unwrap().count(), 1);
+        assert!(run_args(&["pngme", "kv", "set", path, "", "x"]).is_err());
+
+        let error = run_args(&["pngme", "kv", "get", path, "build"]).unwrap_err();
+        assert_eq!(ExitStatus::of(&error), ExitStatus::NotFound);
+        let error = run_args(&["pngme", "kv", "remove", path, "build"]).unwrap_err();
+        assert_eq!(ExitStatus::of(&error), ExitStatus::NotFound);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_exif() {
+        let dir = temp_dir("exif");
+        let (file, tiff, export) = (
+            dir.join("cover.png"),
+            dir.join("in.tiff"),
+            dir.join("out.tiff"),
+        );
+        write_carrier(&file);
+        // Orientation 6, little endian.
+        let exif = b"II*\0\x08\0\0\0\x01\0\x12\x01\x03\0\x01\0\0\0\x06\0\0\0\0\0\0\0";
+        fs::write(&tiff, exif).unwrap();
+        let [path, tiff_path, export_path] =
+            [&file, &tiff, &export].map(|path| path.to_str().unwrap());
+
+        run_args(&["pngme", "exif", "import", path, tiff_path]).unwrap();
+        run_args(&["pngme", "exif", "show", path]).unwrap();
+        run_args(&["pngme", "exif", "export", path, "-o", export_path]).unwrap();
+        assert_eq!(fs::read(&export).unwrap(), exif);
+        assert!(run_args(&["pngme", "exif", "import", path, path]).is_err());
+
+        run_args(&["pngme", "exif", "remove", path]).unwrap();
+        let error = run_args(&["pngme", "exif", "show", path]).unwrap_err();
+        assert_eq!(ExitStatus::of(&error), ExitStatus::NotFound);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_icc() {
+        let dir = temp_dir("icc");
+        let (file, input, export) = (
+            dir.join("cover.png"),
+            dir.join("in.icc"),
+            dir.join("out.icc"),
+        );
+        write_carrier(&file);
+        let mut profile = vec![0; 132];
+        profile[36..40].copy_from_slice(b"acsp");
+        fs::write(&input, &profile).unwrap();
+        let [path, input_path, export_path] =
+            [&file, &input, &export].map(|path| path.to_str().unwrap());
+
+        run_args(&[
+            "pngme",
+            "icc",
+            "import",
+            path,
+            input_path,
+            "--name",
+            "Display P3",
+        ])
+        .unwrap();
+        let png = Png::try_from(fs::read(&file).unwrap().as_slice()).unwrap();
+        assert_eq!(png.icc_profile().unwrap().unwrap().name(), "Display P3");
+        run_args(&["pngme", "icc", "export", path, "-o", export_path]).unwrap();
+        assert_eq!(fs::read(&export).unwrap(), profile);
+        assert!(run_args(&["pngme", "icc", "import", path, path]).is_err());
+
+        run_args(&["pngme", "icc", "remove", path]).unwrap();
+        let error = run_args(&["pngme", "icc", "export", path, "-o", export_path]).unwrap_err();
+        assert_eq!(ExitStatus::of(&error), ExitStatus::NotFound);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_apng_info() {
+        use pngme::ancillary::TypedChunk;
+        use pngme::apng::{AnimationControl, BlendOp, DisposeOp, FrameControl};
+
+        let dir = temp_dir("apng");
+        let file = dir.join("animated.png");
+        let path = file.to_str().unwrap();
+        let control = |sequence_number| FrameControl {
+            sequence_number,
+            width: 1,
+            height: 1,
+            x_offset: 0,
+            y_offset: 0,
+            delay_num: 1,
+            delay_den: 10,
+            dispose_op: DisposeOp::None,
+            blend_op: BlendOp::Source,
+        };
+        let png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::IHDR, vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+            AnimationControl {
+                num_frames: 2,
+                num_plays: 0,
+            }
+            .to_chunk(),
+            control(0).to_chunk(),
+            Chunk::new(ChunkType::IDAT, vec![1, 2]),
+            control(1).to_chunk(),
+            Chunk::new(ChunkType::fdAT, vec![0, 0, 0, 2, 1, 2]),
+            Chunk::new(ChunkType::IEND, vec![]),
+        ]);
+        write_png(&png, &file).unwrap();
+
+        run_args(&["pngme", "apng", "info", path]).unwrap();
+        run_args(&["pngme", "apng", "info", path, "--format", "json"]).unwrap();
+
+        write_carrier(&file);
+        let error = run_args(&["pngme", "apng", "info", path]).unwrap_err();
+        assert_eq!(ExitStatus::of(&error), ExitStatus::NotFound);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_apply() {
+        let dir = temp_dir("apply");
+        for name in ["a.png", "b.png"] {
+            write_carrier(&dir.join(name));
+        }
+        let manifest = dir.join("manifest.toml");
+        fs::write(
+            &manifest,
+            r#"
+            [[file]]
+            path = "a.png"
+            output = "a-out.png"
+
+            [[file.step]]
+            op = "encode"
+            chunk_type = "ruSt"
+            message = "hidden"
+
+            [[file.step]]
+            op = "set-text"
+            keyword = "Author"
+            value = "Ferris"
+
+            [[file]]
+            path = "b.png"
+
+            [[file.step]]
+            op = "set-text"
+            keyword = "Author"
+            value = "Ferris"
+
+            [[file.step]]
+            op = "remove"
+            chunk_type = "ruSt"
+            "#,
+        )
+        .unwrap();
+
+        let error = run_args(&["pngme", "apply", manifest.to_str().unwrap()]).unwrap_err();
+        assert_eq!(ExitStatus::of(&error), ExitStatus::Failure);
+
+        let png = read_png(&dir.join("a-out.png"), false).unwrap();
+        assert!(png.chunk_by_type("ruSt").is_some());
+        assert_eq!(png.get_text("Author").as_deref(), Some("Ferris"));
+        // The second file failed at its last step, so none of its steps were written.
+        let png = read_png(&dir.join("b.png"), false).unwrap();
+        assert!(png.get_text("Author").is_none());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_pipe() {
+        let dir = temp_dir("pipe");
+        let (file, output, message, key) = (
+            dir.join("cover.png"),
+            dir.join("out.png"),
+            dir.join("msg.bin"),
+            dir.join("k.ed25519"),
+        );
+        write_carrier(&file);
+        fs::write(&message, b"hidden").unwrap();
+        let [path, output_path, message_path, key_path] =
+            [&file, &output, &message, &key].map(|path| path.to_str().unwrap());
+        run_args(&["pngme", "text", "set", path, "Title", "Dice"]).unwrap();
+        run_args(&["pngme", "encode", path, "abCd", "old"]).unwrap();
+        run_args(&["pngme", "keygen", key_path]).unwrap();
+
+        let ops = format!(
+            "strip:keep=tEXt;encode:type=ruSt,file={};sign:key={}",
+            message_path, key_path
+        );
+        run_args(&[
+            "pngme",
+            "pipe",
+            path,
+            "--ops",
+            &ops,
+            "-o",
+            output_path,
+            "--audit",
+        ])
+        .unwrap();
+        let png = read_png(&output, false).unwrap();
+        assert!(png.chunk_by_type("abCd").is_none());
+        assert_eq!(png.get_text("Title").as_deref(), Some("Dice"));
+        assert!(png.chunk_by_type("ruSt").is_some());
+        // The audit entry is recorded before the sign op, so the signature still holds.
+        assert!(png.history().unwrap().is_some());
+        run_args(&["pngme", "verify", output_path, &format!("{}.pub", key_path)]).unwrap();
+
+        // A failing op leaves the file untouched.
+        let before = fs::read(&file).unwrap();
+        let error = run_args(&[
+            "pngme",
+            "pipe",
+            path,
+            "--op",
+            "strip",
+            "--op",
+            "remove:type=xyZw",
+        ]);
+        assert!(error.is_err());
+        assert_eq!(fs::read(&file).unwrap(), before);
+        assert!(run_args(&["pngme", "pipe", path, "--op", "paint"]).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_grep() {
+        let dir = temp_dir("grep");
+        let file = dir.join("cover.png");
+        write_carrier(&file);
+        let path = file.to_str().unwrap();
+        run_args(&["pngme", "encode", path, "ruSt", "marker-1234"]).unwrap();
+
+        run_args(&["pngme", "grep", "marker-[0-9]+", path]).unwrap();
+        run_args(&[
+            "pngme", "grep", "--hex", "6d 61 72", path, "--types", "ruSt",
+        ])
+        .unwrap();
+        let error = run_args(&["pngme", "grep", "absent", path]).unwrap_err();
+        assert_eq!(ExitStatus::of(&error), ExitStatus::NotFound);
+
+        let regex = grep_regex("2a00FF", true).unwrap();
+        assert!(regex.is_match(b"x*\0\xff"));
+        assert!(grep_regex("2a0", true).is_err());
+        assert!(grep_regex(" ", true).is_err());
+        let error = run_args(&["pngme", "grep", "--hex", "aé0", path]).unwrap_err();
+        assert_eq!(ExitStatus::of(&error), ExitStatus::Usage);
+        assert!(grep_regex("(", false).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_check() {
+        let dir = temp_dir("check");
+        let file = dir.join("cover.png");
+        write_carrier(&file);
+        let path = file.to_str().unwrap();
+
+        let error = run_args(&["pngme", "check", path, "--any-hidden"]).unwrap_err();
+        assert_eq!(ExitStatus::of(&error), ExitStatus::NotFound);
+        assert!(StatusError::is_silent(&error));
+        run_args(&["pngme", "check", path, "--type", "IHDR"]).unwrap();
+
+        run_args(&["pngme", "encode", path, "ruSt", "hidden"]).unwrap();
+        run_args(&["pngme", "check", path, "--any-hidden"]).unwrap();
+        run_args(&["pngme", "check", path, "--type", "ruSt"]).unwrap();
+        let error = run_args(&["pngme", "check", path, "--type", "abCd"]).unwrap_err();
+        assert_eq!(ExitStatus::of(&error), ExitStatus::NotFound);
+
+        // Errors keep their own status, so a script can tell them from a clean image.
+        let missing = dir.join("missing.png");
+        let missing = missing.to_str().unwrap();
+        let error = run_args(&["pngme", "check", missing, "--any-hidden"]).unwrap_err();
+        assert_eq!(ExitStatus::of(&error), ExitStatus::Failure);
+        assert!(!StatusError::is_silent(&error));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_stats() {
+        let dir = temp_dir("stats");
+        for name in ["a.png", "b.png"] {
+            write_carrier(&dir.join(name));
+        }
+        let first = dir.join("a.png");
+        run_args(&["pngme", "encode", first.to_str().unwrap(), "ruSt", "hidden"]).unwrap();
+
+        let pattern = dir.join("*.png");
+        run_args(&["pngme", "stats", pattern.to_str().unwrap()]).unwrap();
+        run_args(&[
+            "pngme",
+            "stats",
+            pattern.to_str().unwrap(),
+            "--format",
+            "csv",
+        ])
+        .unwrap();
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_info() {
+        let dir = temp_dir("info");
+        let file = dir.join("cover.png");
+        write_carrier(&file);
+        let path = file.to_str().unwrap();
+        run_args(&["pngme", "encode", path, "ruSt", "hidden"]).unwrap();
+
+        run_args(&["pngme", "info", path]).unwrap();
+        run_args(&["pngme", "info", path, "--format", "json"]).unwrap();
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_stdio_path() {
+        assert!(is_url(Path::new("https://example.com/a.png")));
+        assert!(!is_url(Path::new("example.com/a.png")));
+        assert!(is_stdio(Path::new("-")));
+        assert!(!is_stdio(Path::new("./-")));
+        assert!(!is_stdio(Path::new("cover.png")));
+    }
+
+    #[test]
+    fn test_missing_file() {
+        let error = run_args(&["pngme", "print", "/nonexistent/cover.png"]).unwrap_err();
+        assert!(error.to_string().contains("/nonexistent/cover.png"));
+    }
+}
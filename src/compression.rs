@@ -0,0 +1,184 @@
+use anyhow::{bail, Error, Result};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder};
+use std::{
+    io::{Read, Write},
+    str::FromStr,
+};
+use thiserror::Error;
+
+const ZSTD_LEVEL: i32 = 19;
+
+// How far data read from a file may expand when nothing tighter is asked for, so a small stream
+// that inflates to gigabytes fails instead of exhausting memory.
+pub const DEFAULT_MAX_DECOMPRESSED_LENGTH: usize = 1024 * 1024 * 1024;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum CompressionError {
+    #[error("Unknown compression algorithm {found:?}.")]
+    UnknownAlgorithm { found: u8 },
+    #[error("Decompressed data exceeds the limit of {limit:?} bytes.")]
+    TooLarge { limit: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub enum Compression {
+    #[default]
+    None = 0,
+    Deflate = 1,
+    Zstd = 2,
+}
+
+impl TryFrom<u8> for Compression {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(Self::None),
+            1 => Ok(Self::Deflate),
+            2 => Ok(Self::Zstd),
+            found => Err(CompressionError::UnknownAlgorithm { found }.into()),
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "deflate" => Ok(Self::Deflate),
+            "zstd" => Ok(Self::Zstd),
+            _ => bail!(
+                "Unknown compression {:?} (expected none, deflate or zstd)",
+                s
+            ),
+        }
+    }
+}
+
+impl Compression {
+    pub fn compress(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Self::None => data.to_vec(),
+            Self::Deflate => deflate(data),
+            Self::Zstd => {
+                zstd::encode_all(data, ZSTD_LEVEL).expect("reading from a slice cannot fail")
+            }
+        }
+    }
+
+    pub fn decompress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        self.decompress_within(data, DEFAULT_MAX_DECOMPRESSED_LENGTH)
+    }
+
+    // Stops reading one byte past the limit, so memory never grows beyond it.
+    pub fn decompress_within(&self, data: &[u8], limit: usize) -> Result<Vec<u8>> {
+        match self {
+            Self::None if data.len() > limit => Err(CompressionError::TooLarge { limit }.into()),
+            Self::None => Ok(data.to_vec()),
+            Self::Deflate => inflate_within(data, limit),
+            Self::Zstd => read_within(zstd::Decoder::new(data)?, limit),
+        }
+    }
+}
+
+pub(crate) fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder
+        .write_all(data)
+        .expect("writing to a Vec cannot fail");
+    encoder.finish().expect("writing to a Vec cannot fail")
+}
+
+pub(crate) fn inflate(data: &[u8]) -> Result<Vec<u8>> {
+    inflate_within(data, DEFAULT_MAX_DECOMPRESSED_LENGTH)
+}
+
+pub(crate) fn inflate_within(data: &[u8], limit: usize) -> Result<Vec<u8>> {
+    read_within(ZlibDecoder::new(data), limit)
+}
+
+fn read_within(reader: impl Read, limit: usize) -> Result<Vec<u8>> {
+    let mut decompressed = Vec::new();
+    reader
+        .take(limit as u64 + 1)
+        .read_to_end(&mut decompressed)?;
+    if decompressed.len() > limit {
+        return Err(CompressionError::TooLarge { limit }.into());
+    }
+    Ok(decompressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let data = b"This is where your secret message will be!".repeat(10);
+        let compressed = deflate(&data);
+
+        assert!(compressed.len() < data.len());
+        assert_eq!(inflate(&compressed).unwrap(), data);
+    }
+
+    #[test]
+    fn test_inflate_invalid() {
+        assert!(inflate(b"not zlib").is_err());
+    }
+
+    #[test]
+    fn test_compression_round_trip() {
+        let data = b"This is where your secret message will be!".repeat(10);
+
+        for compression in [Compression::None, Compression::Deflate, Compression::Zstd] {
+            let compressed = compression.compress(&data);
+            assert_eq!(compression.decompress(&compressed).unwrap(), data);
+            assert_eq!(
+                Compression::try_from(compression as u8).unwrap(),
+                compression
+            );
+        }
+        assert!(Compression::Zstd.compress(&data).len() < data.len() / 5);
+    }
+
+    #[test]
+    fn test_decompress_within() {
+        let data = vec![0; 100_000];
+
+        for compression in [Compression::None, Compression::Deflate, Compression::Zstd] {
+            let compressed = compression.compress(&data);
+            assert_eq!(
+                compression
+                    .decompress_within(&compressed, data.len())
+                    .unwrap(),
+                data
+            );
+            assert_eq!(
+                compression
+                    .decompress_within(&compressed, data.len() - 1)
+                    .unwrap_err()
+                    .downcast::<CompressionError>()
+                    .unwrap(),
+                CompressionError::TooLarge {
+                    limit: data.len() - 1
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn test_compression_from_str() {
+        assert_eq!(Compression::from_str("ZSTD").unwrap(), Compression::Zstd);
+        assert!(Compression::from_str("brotli").is_err());
+        assert_eq!(
+            Compression::try_from(9)
+                .err()
+                .unwrap()
+                .downcast::<CompressionError>()
+                .unwrap(),
+            CompressionError::UnknownAlgorithm { found: 9 }
+        );
+    }
+}
@@ -0,0 +1,352 @@
+use crate::args::{Cli, Command, EnvelopeArgs, PayloadFormat};
+use crate::output::{ColorChoice, ListFormat, ReportFormat};
+use anyhow::{bail, Context, Result};
+use clap::{parser::ValueSource, ArgMatches, FromArgMatches, ValueEnum};
+use pngme::{chunk_type::ChunkType, compression::Compression, stego::StegoMethod};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+// Defaults read from config.toml, each used only where the command line and environment leave an
+// option unset. A format applies to the commands that support it and is ignored by the rest.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    pub chunk_type: Option<ChunkType>,
+    pub method: Option<StegoMethod>,
+    pub format: Option<String>,
+    pub color: Option<ColorChoice>,
+    pub compress: Option<Compression>,
+    pub encrypt: Option<bool>,
+    pub audit: Option<bool>,
+    pub audit_signer: Option<String>,
+}
+
+// Builds the arguments from parsed matches, then fills in defaults from the config file.
+pub fn cli_from_matches(matches: &ArgMatches) -> Result<Cli> {
+    let mut cli = Cli::from_arg_matches(matches)?;
+    cli.global.operation = operation(matches);
+    Config::load(cli.global.config.as_deref())?.apply(&mut cli, matches);
+
+    Ok(cli)
+}
+
+// The subcommand path, such as "text set".
+fn operation(matches: &ArgMatches) -> String {
+    let mut names = Vec::new();
+    let mut matches = matches;
+    while let Some((name, subcommand)) = matches.subcommand() {
+        names.push(name);
+        matches = subcommand;
+    }
+    names.join(" ")
+}
+
+// $XDG_CONFIG_HOME/pngme/config.toml, falling back to ~/.config.
+fn default_path() -> Option<PathBuf> {
+    let dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(dir.join("pngme").join("config.toml"))
+}
+
+impl Config {
+    // A missing default config is fine, a missing --config is not.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let (path, explicit) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+        if !explicit && !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+        Self::from_str(&text).with_context(|| format!("Invalid config in {}", path.display()))
+    }
+
+    fn apply(&self, cli: &mut Cli, matches: &ArgMatches) {
+        let Some((_, matches)) = matches.subcommand() else {
+            return;
+        };
+        let unset = |id: &str| {
+            !matches!(
+                matches.value_source(id),
+                Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+            )
+        };
+        let format = |set: &mut dyn FnMut(&str)| {
+            if let Some(format) = &self.format {
+                if unset("format") {
+                    set(format);
+                }
+            }
+        };
+
+        if let (Some(audit), true) = (self.audit, unset("audit")) {
+            cli.global.audit = audit;
+        }
+        if let (Some(signer), true) = (&self.audit_signer, unset("audit_signer")) {
+            cli.global.audit_signer = Some(signer.clone());
+        }
+
+        match &mut cli.command {
+            Command::Encode(args) => {
+                self.apply_method(&mut args.method, &unset);
+                self.apply_envelope(&mut args.envelope, &unset);
+            }
+            Command::Decode(args) => {
+                self.apply_method(&mut args.method, &unset);
+                format(&mut |s| set_parsed(&mut args.output.format, PayloadFormat::from_str(s)));
+            }
+            Command::Report(args) => {
+                self.apply_method(&mut args.method, &unset);
+                format(&mut |s| set_parsed(&mut args.format, ReportFormat::from_str(s, true)));
+            }
+            Command::VerifyPayload(args) => {
+                self.apply_method(&mut args.method, &unset);
+                format(&mut |s| set_parsed(&mut args.format, ReportFormat::from_str(s, true)));
+            }
+            Command::Migrate(args) => self.apply_envelope(&mut args.envelope, &unset),
+            Command::Print(args) => {
+                format(&mut |s| set_parsed(&mut args.format, ListFormat::from_str(s, true)));
+                if let (Some(color), true) = (self.color, unset("color")) {
+                    args.color = color;
+                }
+            }
+            Command::Capacity(args) => {
+                format(&mut |s| set_parsed(&mut args.format, ListFormat::from_str(s, true)))
+            }
+            Command::Scan(args) => {
+                format(&mut |s| set_parsed(&mut args.format, ListFormat::from_str(s, true)))
+            }
+            Command::Detect(args) => {
+                format(&mut |s| set_parsed(&mut args.format, ReportFormat::from_str(s, true)))
+            }
+            Command::Grep(args) => {
+                format(&mut |s| set_parsed(&mut args.format, ListFormat::from_str(s, true)))
+            }
+            Command::Stats(args) => {
+                format(&mut |s| set_parsed(&mut args.format, ListFormat::from_str(s, true)))
+            }
+            Command::Apply(args) => {
+                format(&mut |s| set_parsed(&mut args.format, ReportFormat::from_str(s, true)))
+            }
+            Command::Info(args) => {
+                format(&mut |s| set_parsed(&mut args.format, ReportFormat::from_str(s, true)))
+            }
+            Command::Diff(args) => {
+                format(&mut |s| set_parsed(&mut args.format, ReportFormat::from_str(s, true)))
+            }
+            Command::Lint(args) => {
+                format(&mut |s| set_parsed(&mut args.format, ReportFormat::from_str(s, true)))
+            }
+            Command::Verify(args) => {
+                format(&mut |s| set_parsed(&mut args.format, ReportFormat::from_str(s, true)))
+            }
+            Command::SplitEncode(args) => {
+                self.apply_chunk_type(&mut args.chunk_type, &unset);
+                self.apply_method(&mut args.method, &unset);
+                self.apply_envelope(&mut args.envelope, &unset);
+            }
+            Command::JoinDecode(args) => {
+                self.apply_chunk_type(&mut args.chunk_type, &unset);
+                self.apply_method(&mut args.method, &unset);
+                format(&mut |s| set_parsed(&mut args.output.format, PayloadFormat::from_str(s)));
+            }
+            _ => {}
+        }
+    }
+
+    fn apply_chunk_type(&self, chunk_type: &mut ChunkType, unset: &impl Fn(&str) -> bool) {
+        if let (Some(default), true) = (self.chunk_type, unset("chunk_type")) {
+            *chunk_type = default;
+        }
+    }
+
+    fn apply_method(&self, method: &mut StegoMethod, unset: &impl Fn(&str) -> bool) {
+        if let (Some(default), true) = (self.method, unset("method")) {
+            *method = default;
+        }
+    }
+
+    // Passphrase encryption is left off when the command line encrypts to recipients instead.
+    fn apply_envelope(&self, envelope: &mut EnvelopeArgs, unset: &impl Fn(&str) -> bool) {
+        if let (Some(compress), true) = (self.compress, unset("compress")) {
+            envelope.compress = Some(compress);
+        }
+        if let (Some(encrypt), true) = (self.encrypt, unset("encrypt")) {
+            envelope.encrypt = encrypt && envelope.recipients.is_empty();
+        }
+    }
+}
+
+fn set_parsed<T, E>(value: &mut T, parsed: std::result::Result<T, E>) {
+    if let Ok(parsed) = parsed {
+        *value = parsed;
+    }
+}
+
+impl FromStr for Config {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let table: toml::Table = s.parse()?;
+        let mut config = Self::default();
+
+        for (key, value) in &table {
+            let string = || {
+                value
+                    .as_str()
+                    .with_context(|| format!("{} must be a string", key))
+            };
+            match key.as_str() {
+                "chunk_type" => config.chunk_type = Some(ChunkType::from_str(string()?)?),
+                "method" => config.method = Some(StegoMethod::from_str(string()?)?),
+                "format" => config.format = Some(string()?.to_string()),
+                "color" => {
+                    config.color =
+                        Some(ColorChoice::from_str(string()?, true).map_err(anyhow::Error::msg)?)
+                }
+                "compress" => config.compress = Some(Compression::from_str(string()?)?),
+                "encrypt" => {
+                    config.encrypt = Some(value.as_bool().context("encrypt must be true or false")?)
+                }
+                "audit" => {
+                    config.audit = Some(value.as_bool().context("audit must be true or false")?)
+                }
+                "audit_signer" => config.audit_signer = Some(string()?.to_string()),
+                _ => bail!("Unknown setting {:?}", key),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    const CONFIG: &str = r#"
+        chunk_type = "teSt"
+        method = "lsb"
+        format = "csv"
+        color = "never"
+        compress = "zstd"
+        encrypt = true
+        audit = true
+        audit_signer = "alice"
+    "#;
+
+    fn parse_with(config: &Config, args: &[&str]) -> Cli {
+        let matches = Cli::command().try_get_matches_from(args).unwrap();
+        let mut cli = Cli::from_arg_matches(&matches).unwrap();
+        config.apply(&mut cli, &matches);
+        cli
+    }
+
+    #[test]
+    fn test_from_str() {
+        let config = Config::from_str(CONFIG).unwrap();
+
+        assert_eq!(
+            config.chunk_type,
+            Some(ChunkType::from_str("teSt").unwrap())
+        );
+        assert_eq!(config.method, Some(StegoMethod::Lsb));
+        assert_eq!(config.format.as_deref(), Some("csv"));
+        assert_eq!(config.color, Some(ColorChoice::Never));
+        assert_eq!(config.compress, Some(Compression::Zstd));
+        assert_eq!(config.encrypt, Some(true));
+        assert_eq!(config.audit, Some(true));
+        assert_eq!(config.audit_signer.as_deref(), Some("alice"));
+
+        assert!(Config::from_str("colour = \"never\"").is_err());
+        assert!(Config::from_str("encrypt = \"yes\"").is_err());
+        assert!(Config::from_str("chunk_type = \"ru5t\"").is_err());
+        assert_eq!(Config::from_str("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_apply() {
+        let config = Config::from_str(CONFIG).unwrap();
+
+        let cli = parse_with(&config, &["pngme", "print", "a.png"]);
+        let Command::Print(args) = cli.command else {
+            panic!("expected print")
+        };
+        assert_eq!(args.format, ListFormat::Csv);
+        assert_eq!(args.color, ColorChoice::Never);
+
+        // The command line wins, and formats a command does not support are skipped.
+        let cli = parse_with(&config, &["pngme", "print", "a.png", "--format", "json"]);
+        let Command::Print(args) = cli.command else {
+            panic!("expected print")
+        };
+        assert_eq!(args.format, ListFormat::Json);
+        let cli = parse_with(&config, &["pngme", "detect", "a.png"]);
+        let Command::Detect(args) = cli.command else {
+            panic!("expected detect")
+        };
+        assert_eq!(args.format, ReportFormat::Text);
+
+        let cli = parse_with(&config, &["pngme", "encode", "a.png", "ruSt", "hi"]);
+        let Command::Encode(args) = cli.command else {
+            panic!("expected encode")
+        };
+        assert_eq!(args.method, StegoMethod::Lsb);
+        assert_eq!(args.envelope.compress, Some(Compression::Zstd));
+        assert!(args.envelope.encrypt);
+
+        let cli = parse_with(
+            &config,
+            &[
+                "pngme",
+                "encode",
+                "a.png",
+                "ruSt",
+                "hi",
+                "--recipient",
+                "age1",
+            ],
+        );
+        let Command::Encode(args) = cli.command else {
+            panic!("expected encode")
+        };
+        assert!(!args.envelope.encrypt);
+
+        let cli = parse_with(&config, &["pngme", "join-decode", "a.png", "b.png"]);
+        let Command::JoinDecode(args) = cli.command else {
+            panic!("expected join-decode")
+        };
+        assert_eq!(args.chunk_type.to_string(), "teSt");
+
+        let cli = parse_with(
+            &config,
+            &["pngme", "strip", "a.png", "--audit-signer", "bob"],
+        );
+        assert!(cli.global.audit);
+        assert_eq!(cli.global.audit_signer.as_deref(), Some("bob"));
+    }
+
+    #[test]
+    fn test_operation() {
+        let matches = Cli::command()
+            .try_get_matches_from(["pngme", "text", "set", "a.png", "Title", "hi"])
+            .unwrap();
+        assert_eq!(operation(&matches), "text set");
+    }
+
+    #[test]
+    fn test_load() {
+        assert!(Config::load(Some(Path::new("/nonexistent/config.toml"))).is_err());
+    }
+}
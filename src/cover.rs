@@ -0,0 +1,189 @@
+use crate::{
+    builder::PngBuilder,
+    ihdr::{ColorType, Ihdr},
+    imagedata::ImageData,
+    png::Png,
+};
+use anyhow::{bail, Error, Result};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::str::FromStr;
+use thiserror::Error;
+
+// Larger covers are possible, but nothing hidden with pngme needs them and the pixels are held in
+// memory while they are generated.
+pub const MAX_COVER_PIXELS: u64 = 100_000_000;
+
+// Spacing of the random colors that noise blends between.
+const NOISE_CELL: usize = 64;
+// How far grain moves a sample either way.
+const NOISE_GRAIN: i16 = 12;
+const GRADIENT_DITHER: i16 = 2;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum CoverError {
+    #[error("Cover images can hold at most {max:?} pixels, {width:?}x{height:?} has more.")]
+    TooLarge { width: u32, height: u32, max: u64 },
+}
+
+// Noise hides pixel embedding best, as its low bits are random to begin with. Gradients and solid
+// colors compress far better, but changes to their low bits stand out, so they suit chunk
+// embedding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CoverStyle {
+    #[default]
+    Noise,
+    Gradient,
+    Solid,
+}
+
+impl FromStr for CoverStyle {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "noise" => Ok(Self::Noise),
+            "gradient" => Ok(Self::Gradient),
+            "solid" => Ok(Self::Solid),
+            _ => bail!(
+                "Unknown cover style {:?} (expected noise, gradient or solid)",
+                s
+            ),
+        }
+    }
+}
+
+// An 8 bit RGB image in the style, the same for the same seed.
+pub fn generate_cover(
+    width: u32,
+    height: u32,
+    style: CoverStyle,
+    seed: Option<u64>,
+) -> Result<Png> {
+    if width as u64 * height as u64 > MAX_COVER_PIXELS {
+        return Err(CoverError::TooLarge {
+            width,
+            height,
+            max: MAX_COVER_PIXELS,
+        }
+        .into());
+    }
+    let header = Ihdr::new(width, height, 8, ColorType::Rgb)?;
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+
+    let (width, height) = (width as usize, height as usize);
+    let pixels = match style {
+        CoverStyle::Noise => noise(width, height, &mut rng),
+        CoverStyle::Gradient => gradient(width, height, &mut rng),
+        CoverStyle::Solid => {
+            let color: [u8; 3] = rng.gen();
+            color.repeat(width * height)
+        }
+    };
+
+    let mut png = PngBuilder::new().chunk(header.into()).build()?;
+    png.set_image_data(&ImageData::new(header, pixels)?)?;
+    Ok(png)
+}
+
+// Random colors on a coarse grid, blended smoothly and covered in grain, which reads as an out of
+// focus photo rather than static.
+fn noise(width: usize, height: usize, rng: &mut StdRng) -> Vec<u8> {
+    let columns = width.div_ceil(NOISE_CELL) + 1;
+    let rows = height.div_ceil(NOISE_CELL) + 1;
+    let grid: Vec<[u8; 3]> = (0..columns * rows).map(|_| rng.gen()).collect();
+
+    let mut pixels = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        let (row, ty) = cell(y);
+        for x in 0..width {
+            let (column, tx) = cell(x);
+            let corner = |dx: usize, dy: usize| grid[(row + dy) * columns + column + dx];
+            for channel in 0..3 {
+                let top = lerp(corner(0, 0)[channel], corner(1, 0)[channel], tx);
+                let bottom = lerp(corner(0, 1)[channel], corner(1, 1)[channel], tx);
+                let grain = rng.gen_range(-NOISE_GRAIN..=NOISE_GRAIN);
+                pixels.push(clamp(top + (bottom - top) * ty, grain));
+            }
+        }
+    }
+    pixels
+}
+
+// A diagonal blend between two random colors, dithered so it does not band.
+fn gradient(width: usize, height: usize, rng: &mut StdRng) -> Vec<u8> {
+    let (from, to): ([u8; 3], [u8; 3]) = (rng.gen(), rng.gen());
+    let span = (width + height).saturating_sub(2).max(1) as f32;
+
+    let mut pixels = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        for x in 0..width {
+            let t = (x + y) as f32 / span;
+            for channel in 0..3 {
+                let dither = rng.gen_range(-GRADIENT_DITHER..=GRADIENT_DITHER);
+                pixels.push(clamp(lerp(from[channel], to[channel], t), dither));
+            }
+        }
+    }
+    pixels
+}
+
+// The grid cell a coordinate falls in and how far across it, eased so cell edges do not show.
+fn cell(position: usize) -> (usize, f32) {
+    let t = (position % NOISE_CELL) as f32 / NOISE_CELL as f32;
+    (position / NOISE_CELL, t * t * (3.0 - 2.0 * t))
+}
+
+fn lerp(from: u8, to: u8, t: f32) -> f32 {
+    from as f32 + (to as f32 - from as f32) * t
+}
+
+fn clamp(value: f32, offset: i16) -> u8 {
+    (value.round() as i16 + offset).clamp(0, u8::MAX as i16) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stego::StegoMethod;
+
+    #[test]
+    fn test_generate_cover() {
+        for style in [CoverStyle::Noise, CoverStyle::Gradient, CoverStyle::Solid] {
+            let png = generate_cover(100, 70, style, Some(7)).unwrap();
+            let png = Png::try_from(png.as_bytes().as_slice()).unwrap();
+            let header = png.header().unwrap();
+            assert_eq!((header.width, header.height), (100, 70));
+            assert_eq!(
+                png.capacity(StegoMethod::Lsb).unwrap(),
+                (100 * 70 * 3 - 32) / 8
+            );
+
+            let again = generate_cover(100, 70, style, Some(7)).unwrap();
+            assert_eq!(again.as_bytes(), png.as_bytes());
+        }
+
+        let solid = generate_cover(5, 5, CoverStyle::Solid, None).unwrap();
+        let pixels = solid.image_data().unwrap().into_pixels();
+        assert!(pixels.chunks_exact(3).all(|pixel| pixel == &pixels[..3]));
+    }
+
+    #[test]
+    fn test_invalid_cover() {
+        assert!(generate_cover(0, 10, CoverStyle::Noise, None).is_err());
+        assert_eq!(
+            generate_cover(100_000, 100_000, CoverStyle::Solid, None)
+                .unwrap_err()
+                .downcast::<CoverError>()
+                .unwrap(),
+            CoverError::TooLarge {
+                width: 100_000,
+                height: 100_000,
+                max: MAX_COVER_PIXELS
+            }
+        );
+        assert!(CoverStyle::from_str("plaid").is_err());
+    }
+}
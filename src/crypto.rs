@@ -0,0 +1,582 @@
+use aes_gcm::{aead::Aead, Aes256Gcm, Key, KeyInit, Nonce};
+use age::{DecryptError, Decryptor, Encryptor, IdentityFile};
+use anyhow::{anyhow, Result};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::Engine;
+use ed25519_dalek::{Signer, SigningKey, Verifier, VerifyingKey};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::io::{Read, Write};
+use thiserror::Error;
+
+pub const SALT_LENGTH: usize = 16;
+pub const NONCE_LENGTH: usize = 12;
+pub const KEY_LENGTH: usize = 32;
+pub const ED25519_KEY_LENGTH: usize = 32;
+pub const ED25519_SIGNATURE_LENGTH: usize = 64;
+pub const TAG_LENGTH: usize = 32;
+
+// Ceilings on the Argon2 parameters read from a file, so a crafted payload cannot make key
+// derivation take unbounded memory or time. Memory is in KiB, 1 GiB here.
+pub const MAX_MEMORY_COST: u32 = 1024 * 1024;
+pub const MAX_TIME_COST: u32 = 10;
+pub const MAX_PARALLELISM: u32 = 16;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum CryptoError {
+    #[error("Decryption failed, wrong key or corrupted payload.")]
+    DecryptionFailed,
+    #[error("Unknown encryption scheme {found:?}.")]
+    UnknownScheme { found: u8 },
+    #[error("Unknown signature scheme {found:?}.")]
+    UnknownSignatureScheme { found: u8 },
+    #[error("Payload is not encrypted.")]
+    NotEncrypted,
+    #[error("Payload is not signed.")]
+    NotSigned,
+    #[error("Signature does not match, the payload was tampered with or signed by another key.")]
+    BadSignature,
+    #[error(
+        "Authentication tag does not match, the payload was modified or the passphrase is wrong."
+    )]
+    AuthenticationFailed,
+    #[error("Invalid Ed25519 key, expected 32 raw or base64 encoded bytes.")]
+    InvalidKey,
+    #[error("Invalid recipient {recipient:?}, expected an age or SSH public key.")]
+    InvalidRecipient { recipient: String },
+    #[error("No recipients given.")]
+    NoRecipients,
+    #[error("No usable identities found.")]
+    NoIdentities,
+    #[error("Argon2 {field} of {found:?} exceeds the limit of {max:?}.")]
+    ParamsOutOfRange {
+        field: &'static str,
+        found: u32,
+        max: u32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PassphraseParams {
+    pub salt: [u8; SALT_LENGTH],
+    pub nonce: [u8; NONCE_LENGTH],
+    pub memory_cost: u32,
+    pub time_cost: u32,
+    pub parallelism: u32,
+}
+
+impl PassphraseParams {
+    pub const ENCODED_LENGTH: usize = SALT_LENGTH + NONCE_LENGTH + 12;
+
+    pub fn random() -> Self {
+        Self {
+            salt: rand::random(),
+            nonce: rand::random(),
+            memory_cost: Params::DEFAULT_M_COST,
+            time_cost: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+
+    // Every set of parameters read from a file is built here, which rejects costs above the limits.
+    pub fn new(
+        salt: [u8; SALT_LENGTH],
+        nonce: [u8; NONCE_LENGTH],
+        memory_cost: u32,
+        time_cost: u32,
+        parallelism: u32,
+    ) -> Result<Self> {
+        let params = Self {
+            salt,
+            nonce,
+            memory_cost,
+            time_cost,
+            parallelism,
+        };
+        params.validate()?;
+        Ok(params)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for (field, found, max) in [
+            ("memory cost", self.memory_cost, MAX_MEMORY_COST),
+            ("time cost", self.time_cost, MAX_TIME_COST),
+            ("parallelism", self.parallelism, MAX_PARALLELISM),
+        ] {
+            if found > max {
+                return Err(CryptoError::ParamsOutOfRange { field, found, max }.into());
+            }
+        }
+        Ok(())
+    }
+
+    // Checked again here, as the fields can be set directly.
+    pub fn derive_key(&self, passphrase: &str) -> Result<[u8; KEY_LENGTH]> {
+        self.validate()?;
+        let params = Params::new(
+            self.memory_cost,
+            self.time_cost,
+            self.parallelism,
+            Some(KEY_LENGTH),
+        )
+        .map_err(|e| anyhow!("Invalid Argon2 parameters: {}", e))?;
+
+        let mut key = [0; KEY_LENGTH];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &self.salt, &mut key)
+            .map_err(|e| anyhow!("Key derivation failed: {}", e))?;
+
+        Ok(key)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.salt
+            .into_iter()
+            .chain(self.nonce)
+            .chain(self.memory_cost.to_be_bytes())
+            .chain(self.time_cost.to_be_bytes())
+            .chain(self.parallelism.to_be_bytes())
+            .collect()
+    }
+
+    pub fn from_bytes(bytes: &[u8; Self::ENCODED_LENGTH]) -> Result<Self> {
+        let u32_at =
+            |i: usize| u32::from_be_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        let offset = SALT_LENGTH + NONCE_LENGTH;
+
+        Self::new(
+            bytes[..SALT_LENGTH].try_into().unwrap(),
+            bytes[SALT_LENGTH..offset].try_into().unwrap(),
+            u32_at(offset),
+            u32_at(offset + 4),
+            u32_at(offset + 8),
+        )
+    }
+}
+
+// For keys that hide where data is rather than encrypt it. There is nowhere to record parameters
+// before the data is found, so the default costs are used with the caller's salt.
+pub fn stretch_passphrase(passphrase: &str, salt: &[u8]) -> Result<[u8; KEY_LENGTH]> {
+    let mut key = [0; KEY_LENGTH];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("Key derivation failed: {}", e))?;
+
+    Ok(key)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Encryption {
+    Passphrase(PassphraseParams),
+    // The age header inside the payload carries everything needed to decrypt.
+    Recipients,
+    // An OpenPGP message, decrypted by gpg with the reader's own keyring.
+    Gpg,
+}
+
+impl Encryption {
+    pub fn scheme(&self) -> u8 {
+        match self {
+            Self::Passphrase(_) => 1,
+            Self::Recipients => 2,
+            Self::Gpg => 3,
+        }
+    }
+
+    pub fn parameters(&self) -> Vec<u8> {
+        match self {
+            Self::Passphrase(params) => params.to_bytes(),
+            Self::Recipients | Self::Gpg => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Signature {
+    // The payload is an OpenPGP signed message wrapping the data.
+    Gpg,
+    // Detached signature, the envelope is left untouched.
+    Ed25519 {
+        public_key: [u8; ED25519_KEY_LENGTH],
+        signature: [u8; ED25519_SIGNATURE_LENGTH],
+    },
+    // HMAC-SHA256 keyed with Argon2id output, the nonce of the parameters is unused.
+    Hmac {
+        params: PassphraseParams,
+        tag: [u8; TAG_LENGTH],
+    },
+}
+
+impl Signature {
+    pub const ED25519_ENCODED_LENGTH: usize = ED25519_KEY_LENGTH + ED25519_SIGNATURE_LENGTH;
+    pub const HMAC_ENCODED_LENGTH: usize = PassphraseParams::ENCODED_LENGTH + TAG_LENGTH;
+
+    pub fn scheme(&self) -> u8 {
+        match self {
+            Self::Gpg => 1,
+            Self::Ed25519 { .. } => 2,
+            Self::Hmac { .. } => 3,
+        }
+    }
+
+    pub fn parameters(&self) -> Vec<u8> {
+        match self {
+            Self::Gpg => Vec::new(),
+            Self::Ed25519 {
+                public_key,
+                signature,
+            } => public_key.iter().chain(signature).copied().collect(),
+            Self::Hmac { params, tag } => params.to_bytes().into_iter().chain(*tag).collect(),
+        }
+    }
+
+    pub fn ed25519_from_bytes(bytes: &[u8; Self::ED25519_ENCODED_LENGTH]) -> Self {
+        Self::Ed25519 {
+            public_key: bytes[..ED25519_KEY_LENGTH].try_into().unwrap(),
+            signature: bytes[ED25519_KEY_LENGTH..].try_into().unwrap(),
+        }
+    }
+
+    pub fn hmac_from_bytes(bytes: &[u8; Self::HMAC_ENCODED_LENGTH]) -> Result<Self> {
+        let (params, tag) = bytes.split_at(PassphraseParams::ENCODED_LENGTH);
+        Ok(Self::Hmac {
+            params: PassphraseParams::from_bytes(params.try_into().unwrap())?,
+            tag: tag.try_into().unwrap(),
+        })
+    }
+}
+
+pub fn seal(key: &[u8; KEY_LENGTH], nonce: &[u8; NONCE_LENGTH], plaintext: &[u8]) -> Vec<u8> {
+    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key))
+        .encrypt(Nonce::from_slice(nonce), plaintext)
+        .expect("encrypting into a Vec cannot fail")
+}
+
+pub fn open(
+    key: &[u8; KEY_LENGTH],
+    nonce: &[u8; NONCE_LENGTH],
+    ciphertext: &[u8],
+) -> Result<Vec<u8>> {
+    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key))
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| CryptoError::DecryptionFailed.into())
+}
+
+pub fn encrypt_with_passphrase(
+    passphrase: &str,
+    plaintext: &[u8],
+) -> Result<(PassphraseParams, Vec<u8>)> {
+    let params = PassphraseParams::random();
+    let key = params.derive_key(passphrase)?;
+
+    Ok((params, seal(&key, &params.nonce, plaintext)))
+}
+
+pub fn decrypt_with_passphrase(
+    params: &PassphraseParams,
+    passphrase: &str,
+    ciphertext: &[u8],
+) -> Result<Vec<u8>> {
+    open(&params.derive_key(passphrase)?, &params.nonce, ciphertext)
+}
+
+// Accepts age X25519 keys ("age1...") and SSH public keys ("ssh-ed25519 ...", "ssh-rsa ...").
+pub fn parse_recipient(recipient: &str) -> Result<Box<dyn age::Recipient + Send>> {
+    let recipient = recipient.trim();
+    if let Ok(key) = recipient.parse::<age::x25519::Recipient>() {
+        return Ok(Box::new(key));
+    }
+    if let Ok(key) = recipient.parse::<age::ssh::Recipient>() {
+        return Ok(Box::new(key));
+    }
+
+    Err(CryptoError::InvalidRecipient {
+        recipient: recipient.to_string(),
+    }
+    .into())
+}
+
+// Accepts age identity files and unencrypted SSH private keys.
+pub fn parse_identities(identities: &str) -> Result<Vec<Box<dyn age::Identity>>> {
+    if let Ok(file) = IdentityFile::from_buffer(identities.as_bytes()) {
+        if let Ok(identities) = file.into_identities() {
+            if !identities.is_empty() {
+                return Ok(identities);
+            }
+        }
+    }
+
+    match age::ssh::Identity::from_buffer(identities.as_bytes(), None) {
+        Ok(identity) => Ok(vec![Box::new(identity)]),
+        Err(_) => Err(CryptoError::NoIdentities.into()),
+    }
+}
+
+pub fn encrypt_to_recipients(recipients: &[&str], plaintext: &[u8]) -> Result<Vec<u8>> {
+    if recipients.is_empty() {
+        return Err(CryptoError::NoRecipients.into());
+    }
+
+    let recipients = recipients
+        .iter()
+        .map(|r| parse_recipient(r))
+        .collect::<Result<Vec<_>>>()?;
+    let encryptor = Encryptor::with_recipients(recipients.iter().map(|r| r.as_ref() as _))?;
+
+    let mut ciphertext = Vec::new();
+    let mut writer = encryptor.wrap_output(&mut ciphertext)?;
+    writer.write_all(plaintext)?;
+    writer.finish()?;
+
+    Ok(ciphertext)
+}
+
+pub fn decrypt_with_identities(identities: &str, ciphertext: &[u8]) -> Result<Vec<u8>> {
+    let identities = parse_identities(identities)?;
+    let decryptor = Decryptor::new_buffered(ciphertext)?;
+
+    let mut reader = match decryptor.decrypt(identities.iter().map(|i| i.as_ref())) {
+        Ok(reader) => reader,
+        Err(DecryptError::NoMatchingKeys | DecryptError::DecryptionFailed) => {
+            return Err(CryptoError::DecryptionFailed.into())
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut plaintext = Vec::new();
+    reader
+        .read_to_end(&mut plaintext)
+        .map_err(|_| CryptoError::DecryptionFailed)?;
+
+    Ok(plaintext)
+}
+
+// Key files hold either the 32 raw key bytes or their base64 encoding.
+pub fn parse_ed25519_key(bytes: &[u8]) -> Result<[u8; ED25519_KEY_LENGTH]> {
+    if let Ok(key) = bytes.try_into() {
+        return Ok(key);
+    }
+
+    let text = std::str::from_utf8(bytes).map_err(|_| CryptoError::InvalidKey)?;
+    base64::engine::general_purpose::STANDARD
+        .decode(text.trim())
+        .ok()
+        .and_then(|key| key.try_into().ok())
+        .ok_or(CryptoError::InvalidKey.into())
+}
+
+pub fn ed25519_public_key(secret_key: &[u8; ED25519_KEY_LENGTH]) -> [u8; ED25519_KEY_LENGTH] {
+    SigningKey::from_bytes(secret_key)
+        .verifying_key()
+        .to_bytes()
+}
+
+pub fn sign_ed25519(
+    secret_key: &[u8; ED25519_KEY_LENGTH],
+    message: &[u8],
+) -> [u8; ED25519_SIGNATURE_LENGTH] {
+    SigningKey::from_bytes(secret_key).sign(message).to_bytes()
+}
+
+pub fn verify_ed25519(
+    public_key: &[u8; ED25519_KEY_LENGTH],
+    message: &[u8],
+    signature: &[u8; ED25519_SIGNATURE_LENGTH],
+) -> Result<()> {
+    let key = VerifyingKey::from_bytes(public_key).map_err(|_| CryptoError::InvalidKey)?;
+    key.verify(message, &ed25519_dalek::Signature::from_bytes(signature))
+        .map_err(|_| CryptoError::BadSignature.into())
+}
+
+fn hmac(key: &[u8; KEY_LENGTH], message: &[u8]) -> Hmac<Sha256> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(message);
+    mac
+}
+
+pub fn hmac_tag(key: &[u8; KEY_LENGTH], message: &[u8]) -> [u8; TAG_LENGTH] {
+    hmac(key, message).finalize().into_bytes().into()
+}
+
+pub fn verify_hmac(key: &[u8; KEY_LENGTH], message: &[u8], tag: &[u8; TAG_LENGTH]) -> Result<()> {
+    hmac(key, message)
+        .verify_slice(tag)
+        .map_err(|_| CryptoError::AuthenticationFailed.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_passphrase_round_trip() {
+        let (params, ciphertext) = encrypt_with_passphrase("hunter2", b"secret").unwrap();
+        assert_eq!(ciphertext.len(), 6 + 16);
+        assert_eq!(
+            decrypt_with_passphrase(&params, "hunter2", &ciphertext).unwrap(),
+            b"secret"
+        );
+
+        assert_eq!(
+            decrypt_with_passphrase(&params, "hunter3", &ciphertext)
+                .err()
+                .unwrap()
+                .downcast::<CryptoError>()
+                .unwrap(),
+            CryptoError::DecryptionFailed
+        );
+    }
+
+    #[test]
+    fn test_params_round_trip() {
+        let params = PassphraseParams::random();
+        let bytes: [u8; PassphraseParams::ENCODED_LENGTH] = params.to_bytes().try_into().unwrap();
+
+        assert_eq!(PassphraseParams::from_bytes(&bytes).unwrap(), params);
+        assert_ne!(PassphraseParams::random().salt, params.salt);
+    }
+
+    #[test]
+    fn test_params_out_of_range() {
+        let params = PassphraseParams::random();
+        let error = |memory_cost, time_cost, parallelism| {
+            PassphraseParams::new(
+                params.salt,
+                params.nonce,
+                memory_cost,
+                time_cost,
+                parallelism,
+            )
+            .unwrap_err()
+            .downcast::<CryptoError>()
+            .unwrap()
+        };
+        assert_eq!(
+            error(u32::MAX, 2, 1),
+            CryptoError::ParamsOutOfRange {
+                field: "memory cost",
+                found: u32::MAX,
+                max: MAX_MEMORY_COST
+            }
+        );
+        assert!(matches!(
+            error(19_456, MAX_TIME_COST + 1, 1),
+            CryptoError::ParamsOutOfRange {
+                field: "time cost",
+                ..
+            }
+        ));
+        assert!(matches!(
+            error(19_456, 2, 64),
+            CryptoError::ParamsOutOfRange {
+                field: "parallelism",
+                ..
+            }
+        ));
+
+        let unchecked = PassphraseParams {
+            memory_cost: u32::MAX,
+            ..params
+        };
+        let bytes: [u8; PassphraseParams::ENCODED_LENGTH] =
+            unchecked.to_bytes().try_into().unwrap();
+        assert!(PassphraseParams::from_bytes(&bytes).is_err());
+        assert!(decrypt_with_passphrase(&unchecked, "hunter2", &[0; 32]).is_err());
+    }
+
+    #[test]
+    fn test_ed25519_sign_and_verify() {
+        let secret_key: [u8; ED25519_KEY_LENGTH] = rand::random();
+        let public_key = ed25519_public_key(&secret_key);
+
+        let signature = sign_ed25519(&secret_key, b"message");
+        assert!(verify_ed25519(&public_key, b"message", &signature).is_ok());
+        assert_eq!(
+            verify_ed25519(&public_key, b"messagf", &signature)
+                .err()
+                .unwrap()
+                .downcast::<CryptoError>()
+                .unwrap(),
+            CryptoError::BadSignature
+        );
+
+        let other_key = ed25519_public_key(&rand::random());
+        assert!(verify_ed25519(&other_key, b"message", &signature).is_err());
+    }
+
+    #[test]
+    fn test_hmac() {
+        let key = [9; KEY_LENGTH];
+        let tag = hmac_tag(&key, b"message");
+
+        assert!(verify_hmac(&key, b"message", &tag).is_ok());
+        assert!(verify_hmac(&key, b"messag", &tag).is_err());
+        assert_eq!(
+            verify_hmac(&[8; KEY_LENGTH], b"message", &tag)
+                .err()
+                .unwrap()
+                .downcast::<CryptoError>()
+                .unwrap(),
+            CryptoError::AuthenticationFailed
+        );
+    }
+
+    #[test]
+    fn test_parse_ed25519_key() {
+        let key = [7; ED25519_KEY_LENGTH];
+        let encoded = base64::engine::general_purpose::STANDARD.encode(key) + "\n";
+
+        assert_eq!(parse_ed25519_key(&key).unwrap(), key);
+        assert_eq!(parse_ed25519_key(encoded.as_bytes()).unwrap(), key);
+        assert!(parse_ed25519_key(b"too short").is_err());
+    }
+
+    #[test]
+    fn test_recipients_round_trip() {
+        use age::secrecy::ExposeSecret;
+
+        let alice = age::x25519::Identity::generate();
+        let bob = age::x25519::Identity::generate();
+        let recipients = [alice.to_public().to_string(), bob.to_public().to_string()];
+        let recipients: Vec<&str> = recipients.iter().map(String::as_str).collect();
+
+        let ciphertext = encrypt_to_recipients(&recipients, b"secret").unwrap();
+        for identity in [&alice, &bob] {
+            let identity = identity.to_string();
+            assert_eq!(
+                decrypt_with_identities(identity.expose_secret(), &ciphertext).unwrap(),
+                b"secret"
+            );
+        }
+
+        let eve = age::x25519::Identity::generate().to_string();
+        assert_eq!(
+            decrypt_with_identities(eve.expose_secret(), &ciphertext)
+                .err()
+                .unwrap()
+                .downcast::<CryptoError>()
+                .unwrap(),
+            CryptoError::DecryptionFailed
+        );
+    }
+
+    #[test]
+    fn test_parse_recipient() {
+        assert!(parse_recipient(
+            "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIHsKLqeplhpW+uObz5dvMgjz1OxfM/XXUB+VHtZ6isGN alice@rust"
+        )
+        .is_ok());
+        assert_eq!(
+            parse_recipient("age1nope")
+                .err()
+                .unwrap()
+                .downcast::<CryptoError>()
+                .unwrap(),
+            CryptoError::InvalidRecipient {
+                recipient: "age1nope".to_string()
+            }
+        );
+        assert!(encrypt_to_recipients(&[], b"secret").is_err());
+        assert!(parse_identities("not a key").is_err());
+    }
+}
@@ -0,0 +1,207 @@
+use crate::{
+    crypto::{decrypt_with_passphrase, encrypt_with_passphrase, CryptoError, PassphraseParams},
+    envelope::Envelope,
+    png::Png,
+    stego::StegoMethod,
+};
+use anyhow::Result;
+use thiserror::Error;
+
+// Each slot holds the passphrase parameters, then the ciphertext of a u32 length, the data and padding.
+const SLOT_OVERHEAD: usize = PassphraseParams::ENCODED_LENGTH + 4 + 16;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum DecoyError {
+    #[error("Deniable container is truncated.")]
+    Truncated,
+}
+
+// Two equally sized slots in random order, interleaved byte by byte. A container holding one
+// payload gets a random slot next to it, so it looks the same as one holding two.
+pub fn seal_pair(first: (&str, &[u8]), second: (&str, &[u8])) -> Result<Vec<u8>> {
+    let length = first.1.len().max(second.1.len());
+    let mut slots = [
+        seal_slot(first.0, first.1, length)?,
+        seal_slot(second.0, second.1, length)?,
+    ];
+    if rand::random() {
+        slots.swap(0, 1);
+    }
+
+    Ok(interleave(&slots[0], &slots[1]))
+}
+
+pub fn seal_single(passphrase: &str, payload: &[u8]) -> Result<Vec<u8>> {
+    let slot = seal_slot(passphrase, payload, payload.len())?;
+    // The filler carries real looking parameters, only its ciphertext is random.
+    let mut filler = PassphraseParams::random().to_bytes();
+    filler.extend((filler.len()..slot.len()).map(|_| rand::random::<u8>()));
+
+    Ok(match rand::random() {
+        true => interleave(&slot, &filler),
+        false => interleave(&filler, &slot),
+    })
+}
+
+pub fn open(passphrase: &str, container: &[u8]) -> Result<Vec<u8>> {
+    if !container.len().is_multiple_of(2) || container.len() < SLOT_OVERHEAD * 2 {
+        return Err(DecoyError::Truncated.into());
+    }
+
+    let slots = [
+        container.iter().step_by(2).copied().collect::<Vec<_>>(),
+        container
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .copied()
+            .collect::<Vec<_>>(),
+    ];
+    for slot in slots {
+        if let Ok(payload) = open_slot(passphrase, &slot) {
+            return Ok(payload);
+        }
+    }
+
+    Err(CryptoError::DecryptionFailed.into())
+}
+
+fn seal_slot(passphrase: &str, payload: &[u8], length: usize) -> Result<Vec<u8>> {
+    let mut plaintext = (payload.len() as u32).to_be_bytes().to_vec();
+    plaintext.extend(payload);
+    plaintext.extend((payload.len()..length).map(|_| rand::random::<u8>()));
+
+    let (params, ciphertext) = encrypt_with_passphrase(passphrase, &plaintext)?;
+    let mut slot = params.to_bytes();
+    slot.extend(ciphertext);
+
+    Ok(slot)
+}
+
+fn open_slot(passphrase: &str, slot: &[u8]) -> Result<Vec<u8>> {
+    let (params, ciphertext) = slot.split_at(PassphraseParams::ENCODED_LENGTH);
+    let params = PassphraseParams::from_bytes(params.try_into().unwrap())?;
+    let plaintext = decrypt_with_passphrase(&params, passphrase, ciphertext)?;
+
+    let length = u32::from_be_bytes(plaintext[..4].try_into().unwrap()) as usize;
+    match plaintext.get(4..4 + length) {
+        Some(payload) => Ok(payload.to_vec()),
+        None => Err(DecoyError::Truncated.into()),
+    }
+}
+
+fn interleave(a: &[u8], b: &[u8]) -> Vec<u8> {
+    a.iter().zip(b).flat_map(|(&a, &b)| [a, b]).collect()
+}
+
+impl Png {
+    // The decoy passphrase reveals the decoy envelope, the real one the real envelope.
+    pub fn embed_with_decoy(
+        &mut self,
+        method: StegoMethod,
+        chunk_type: &str,
+        decoy: (&str, &Envelope),
+        real: (&str, &Envelope),
+    ) -> Result<()> {
+        let container = seal_pair(
+            (decoy.0, &decoy.1.to_bytes()?),
+            (real.0, &real.1.to_bytes()?),
+        )?;
+        self.embed(method, chunk_type, &container)
+    }
+
+    pub fn embed_deniable(
+        &mut self,
+        method: StegoMethod,
+        chunk_type: &str,
+        passphrase: &str,
+        envelope: &Envelope,
+    ) -> Result<()> {
+        let container = seal_single(passphrase, &envelope.to_bytes()?)?;
+        self.embed(method, chunk_type, &container)
+    }
+
+    pub fn extract_deniable(
+        &self,
+        method: StegoMethod,
+        chunk_type: &str,
+        passphrase: &str,
+    ) -> Result<Envelope> {
+        let container = self.extract(method, chunk_type)?;
+        Envelope::from_bytes(&open(passphrase, &container)?)?.decompressed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{builder::PngBuilder, chunk::Chunk, chunk_type::ChunkType};
+
+    #[test]
+    fn test_pair_round_trip() {
+        let container = seal_pair(("decoy", b"shopping list"), ("real", b"meet at dawn")).unwrap();
+        assert_eq!(container.len(), 2 * (SLOT_OVERHEAD + 13));
+
+        assert_eq!(open("decoy", &container).unwrap(), b"shopping list");
+        assert_eq!(open("real", &container).unwrap(), b"meet at dawn");
+        assert_eq!(
+            open("other", &container)
+                .err()
+                .unwrap()
+                .downcast::<CryptoError>()
+                .unwrap(),
+            CryptoError::DecryptionFailed
+        );
+    }
+
+    #[test]
+    fn test_single_matches_pair_length() {
+        let single = seal_single("real", b"meet at dawn!").unwrap();
+        let pair = seal_pair(("decoy", b"shopping list"), ("real", b"meet at dawn!")).unwrap();
+
+        assert_eq!(single.len(), pair.len());
+        assert_eq!(open("real", &single).unwrap(), b"meet at dawn!");
+        assert!(open("decoy", &single).is_err());
+    }
+
+    #[test]
+    fn test_truncated() {
+        assert_eq!(
+            open("real", &[0; 7])
+                .err()
+                .unwrap()
+                .downcast::<DecoyError>()
+                .unwrap(),
+            DecoyError::Truncated
+        );
+    }
+
+    #[test]
+    fn test_png_decoy() {
+        let mut png = PngBuilder::new()
+            .chunk(Chunk::new(ChunkType::IHDR, vec![0; 13]))
+            .build()
+            .unwrap();
+        let decoy = Envelope::new(b"shopping list".to_vec());
+        let real = Envelope::new(b"meet at dawn".to_vec()).with_filename("plan.txt");
+
+        png.embed_with_decoy(
+            StegoMethod::Chunk,
+            "ruSt",
+            ("decoy", &decoy),
+            ("real", &real),
+        )
+        .unwrap();
+        assert_eq!(
+            png.extract_deniable(StegoMethod::Chunk, "ruSt", "decoy")
+                .unwrap(),
+            decoy
+        );
+        assert_eq!(
+            png.extract_deniable(StegoMethod::Chunk, "ruSt", "real")
+                .unwrap(),
+            real
+        );
+        assert!(png.extract(StegoMethod::Chunk, "ruSt").unwrap().len() > SLOT_OVERHEAD * 2);
+    }
+}
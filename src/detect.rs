@@ -0,0 +1,565 @@
+use crate::{chunk::Chunk, chunk_type::ChunkType, png::Png, stego::StegoMethod};
+use anyhow::{bail, Result};
+use std::{collections::HashMap, fmt::Display};
+
+const LARGE_CHUNK_LENGTH: usize = 64 * 1024;
+const CHI_SQUARE_THRESHOLD: f64 = 0.9;
+
+// Offsets are file offsets, counting the signature.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Finding {
+    UnknownChunk {
+        chunk_type: ChunkType,
+        length: usize,
+        offset: usize,
+    },
+    TrailingData {
+        length: usize,
+        offset: usize,
+    },
+    AnomalousChunkSize {
+        chunk_type: ChunkType,
+        length: usize,
+        offset: usize,
+    },
+    DuplicateChunk {
+        chunk_type: ChunkType,
+        count: usize,
+    },
+    LsbChiSquare {
+        probability: f64,
+    },
+    // Only found in raw bytes, which are read leniently so a damaged chunk does not hide the rest.
+    InvalidChunkType {
+        chunk_type: ChunkType,
+        offset: usize,
+    },
+    InvalidCrc {
+        chunk_type: ChunkType,
+        offset: usize,
+    },
+    TruncatedChunk {
+        length: usize,
+        offset: usize,
+    },
+}
+
+impl Finding {
+    pub fn weight(&self) -> u32 {
+        match self {
+            Self::UnknownChunk { .. } => 30,
+            Self::TrailingData { .. } => 40,
+            Self::AnomalousChunkSize { .. } => 20,
+            Self::DuplicateChunk { .. } => 15,
+            Self::LsbChiSquare { probability } => (probability * 60.0).round() as u32,
+            Self::InvalidChunkType { .. } => 30,
+            Self::InvalidCrc { .. } => 25,
+            Self::TruncatedChunk { .. } => 20,
+        }
+    }
+
+    // Where in the file the finding points, if it points anywhere in particular.
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            Self::UnknownChunk { offset, .. }
+            | Self::TrailingData { offset, .. }
+            | Self::AnomalousChunkSize { offset, .. }
+            | Self::InvalidChunkType { offset, .. }
+            | Self::InvalidCrc { offset, .. }
+            | Self::TruncatedChunk { offset, .. } => Some(*offset),
+            Self::DuplicateChunk { .. } | Self::LsbChiSquare { .. } => None,
+        }
+    }
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownChunk {
+                chunk_type,
+                length,
+                offset,
+            } => write!(
+                f,
+                "unknown chunk {} ({} bytes) at offset {}",
+                chunk_type, length, offset
+            ),
+            Self::TrailingData { length, offset } => {
+                write!(f, "{} bytes after IEND at offset {}", length, offset)
+            }
+            Self::AnomalousChunkSize {
+                chunk_type,
+                length,
+                offset,
+            } => write!(
+                f,
+                "unusual {} length of {} bytes at offset {}",
+                chunk_type, length, offset
+            ),
+            Self::DuplicateChunk { chunk_type, count } => {
+                write!(f, "{} appears {} times", chunk_type, count)
+            }
+            Self::LsbChiSquare { probability } => write!(
+                f,
+                "LSB chi-square test suggests embedding (p = {:.3})",
+                probability
+            ),
+            Self::InvalidChunkType { chunk_type, offset } => write!(
+                f,
+                "invalid chunk type {:?} at offset {}",
+                String::from_utf8_lossy(&chunk_type.bytes()),
+                offset
+            ),
+            Self::InvalidCrc { chunk_type, offset } => {
+                write!(
+                    f,
+                    "{} chunk with a bad CRC at offset {}",
+                    chunk_type, offset
+                )
+            }
+            Self::TruncatedChunk { length, offset } => write!(
+                f,
+                "truncated chunk of {} bytes at offset {}",
+                length, offset
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DetectionReport {
+    pub findings: Vec<Finding>,
+}
+
+impl DetectionReport {
+    pub fn score(&self) -> u32 {
+        self.findings
+            .iter()
+            .map(Finding::weight)
+            .sum::<u32>()
+            .min(100)
+    }
+
+    pub fn is_suspicious(&self) -> bool {
+        !self.findings.is_empty()
+    }
+}
+
+impl Display for DetectionReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "score: {}/100", self.score())?;
+        for finding in &self.findings {
+            writeln!(f, "- {}", finding)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Checks raw file bytes, which unlike a parsed Png can still carry data after IEND. Chunks with
+// bad CRCs or invalid types are kept, so offsets past them stay right, and reported as findings.
+pub fn detect_bytes(bytes: &[u8]) -> Result<DetectionReport> {
+    if !bytes.starts_with(&Png::STANDARD_HEADER) {
+        bail!("Bad header")
+    }
+
+    let mut offset = Png::STANDARD_HEADER.len();
+    let mut chunks = Vec::new();
+    let mut corrupt = Vec::new();
+    while offset < bytes.len() {
+        let rest = &bytes[offset..];
+        let length = match rest.get(..4) {
+            Some(length) => u32::from_be_bytes(length.try_into().unwrap()) as usize,
+            None => 0,
+        };
+        let Some(chunk) = length.checked_add(12).and_then(|end| rest.get(..end)) else {
+            corrupt.push(Finding::TruncatedChunk {
+                length: rest.len(),
+                offset,
+            });
+            offset = bytes.len();
+            break;
+        };
+
+        let type_bytes: [u8; 4] = chunk[4..8].try_into().unwrap();
+        let chunk_type = ChunkType::new_unchecked(type_bytes);
+        if ChunkType::try_from(type_bytes).is_err() {
+            corrupt.push(Finding::InvalidChunkType { chunk_type, offset });
+        }
+        let found = u32::from_be_bytes(chunk[8 + length..].try_into().unwrap());
+        if Chunk::compute_crc_contiguous(&chunk[4..8 + length]) != found {
+            corrupt.push(Finding::InvalidCrc { chunk_type, offset });
+        }
+
+        chunks.push(Chunk::with_crc(
+            chunk_type,
+            chunk[8..8 + length].to_vec(),
+            found,
+        ));
+        offset += chunk.len();
+        if chunk_type == ChunkType::IEND {
+            break;
+        }
+    }
+
+    let mut report = Png::from_chunks(chunks).detect();
+    // An invalid type is reported as such rather than as an unknown chunk as well.
+    report.findings.retain(|finding| {
+        match finding {
+        Finding::UnknownChunk { offset, .. } => !corrupt.iter().any(|corrupt| {
+            matches!(corrupt, Finding::InvalidChunkType { offset: at, .. } if at == offset)
+        }),
+        _ => true,
+    }
+    });
+    report.findings.extend(corrupt);
+    if offset < bytes.len() {
+        report.findings.push(Finding::TrailingData {
+            length: bytes.len() - offset,
+            offset,
+        });
+    }
+
+    Ok(report)
+}
+
+fn expected_length(chunk_type: &ChunkType) -> Option<usize> {
+    match *chunk_type {
+        ChunkType::IHDR => Some(13),
+        ChunkType::IEND => Some(0),
+        ChunkType::gAMA => Some(4),
+        ChunkType::sRGB => Some(1),
+        ChunkType::pHYs => Some(9),
+        ChunkType::cHRM => Some(32),
+        ChunkType::tIME => Some(7),
+        ChunkType::acTL => Some(8),
+        ChunkType::fcTL => Some(26),
+        _ => None,
+    }
+}
+
+fn is_bulk_data(chunk_type: &ChunkType) -> bool {
+    matches!(
+        *chunk_type,
+        ChunkType::IDAT | ChunkType::fdAT | ChunkType::iCCP | ChunkType::eXIf
+    )
+}
+
+fn may_repeat(chunk_type: &ChunkType) -> bool {
+    matches!(
+        *chunk_type,
+        ChunkType::IDAT
+            | ChunkType::fdAT
+            | ChunkType::fcTL
+            | ChunkType::tEXt
+            | ChunkType::zTXt
+            | ChunkType::iTXt
+            | ChunkType::sPLT
+    ) || !chunk_type.is_registered()
+}
+
+impl Png {
+    pub fn detect(&self) -> DetectionReport {
+        let mut findings = Vec::new();
+        let mut seen_end = false;
+        let mut counts: Vec<(ChunkType, usize)> = Vec::new();
+        let mut positions = HashMap::new();
+
+        for (offset, chunk) in self.chunks_with_offsets() {
+            let chunk_type = *chunk.chunk_type();
+            let length = chunk.chunk_data().len();
+
+            if seen_end {
+                findings.push(Finding::TrailingData {
+                    length: length + 12,
+                    offset,
+                });
+                continue;
+            }
+            seen_end = chunk_type == ChunkType::IEND;
+
+            if !chunk_type.is_registered() {
+                findings.push(Finding::UnknownChunk {
+                    chunk_type,
+                    length,
+                    offset,
+                });
+            } else if expected_length(&chunk_type).is_some_and(|expected| expected != length)
+                || (length > LARGE_CHUNK_LENGTH && !is_bulk_data(&chunk_type))
+            {
+                findings.push(Finding::AnomalousChunkSize {
+                    chunk_type,
+                    length,
+                    offset,
+                });
+            }
+
+            let index = *positions.entry(chunk_type).or_insert_with(|| {
+                counts.push((chunk_type, 0));
+                counts.len() - 1
+            });
+            counts[index].1 += 1;
+        }
+
+        findings.extend(
+            counts
+                .into_iter()
+                .filter(|&(chunk_type, count)| count > 1 && !may_repeat(&chunk_type))
+                .map(|(chunk_type, count)| Finding::DuplicateChunk { chunk_type, count }),
+        );
+
+        if let Some(probability) = self.lsb_embedding_probability() {
+            if probability > CHI_SQUARE_THRESHOLD {
+                findings.push(Finding::LsbChiSquare { probability });
+            }
+        }
+
+        DetectionReport { findings }
+    }
+
+    // Pairs-of-values chi-square attack: LSB embedding evens out the counts of 2k and 2k + 1.
+    fn lsb_embedding_probability(&self) -> Option<f64> {
+        let image_data = self.image_data().ok()?;
+        let slots = StegoMethod::Lsb.sequential_slots(&image_data).ok()?;
+
+        let mut histogram = [0u64; 256];
+        for slot in slots {
+            histogram[image_data.pixels()[slot] as usize] += 1;
+        }
+
+        let mut chi_square = 0.0;
+        let mut categories = 0;
+        for pair in histogram.chunks_exact(2) {
+            let expected = (pair[0] + pair[1]) as f64 / 2.0;
+            if expected > 0.0 {
+                chi_square += (pair[0] as f64 - expected).powi(2) / expected;
+                categories += 1;
+            }
+        }
+
+        if categories < 2 {
+            return None;
+        }
+
+        let degrees_of_freedom = (categories - 1) as f64;
+        Some(1.0 - regularized_lower_gamma(degrees_of_freedom / 2.0, chi_square / 2.0))
+    }
+}
+
+fn regularized_lower_gamma(s: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+
+    let log_prefix = s * x.ln() - x - ln_gamma(s);
+    if x < s + 1.0 {
+        // Series expansion.
+        let mut term = 1.0 / s;
+        let mut sum = term;
+        for n in 1..500 {
+            term *= x / (s + n as f64);
+            sum += term;
+            if term < sum * 1e-15 {
+                break;
+            }
+        }
+        (sum.ln() + log_prefix).exp()
+    } else {
+        // Continued fraction for the upper gamma function (modified Lentz).
+        let tiny = 1e-300;
+        let mut b = x + 1.0 - s;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for n in 1..500 {
+            let an = -(n as f64) * (n as f64 - s);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < 1e-15 {
+                break;
+            }
+        }
+        1.0 - (h.ln() + log_prefix).exp()
+    }
+}
+
+// Lanczos approximation.
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.180_091_729_471_46,
+        -86.505_320_329_416_77,
+        24.014_098_240_830_91,
+        -1.231_739_572_450_155,
+        0.001_208_650_973_866_179,
+        -0.000_005_395_239_384_953,
+    ];
+
+    let tmp = x + 5.5 - (x + 0.5) * (x + 5.5).ln();
+    let series = COEFFICIENTS
+        .iter()
+        .enumerate()
+        .fold(1.000_000_000_190_015, |sum, (i, c)| {
+            sum + c / (x + 1.0 + i as f64)
+        });
+
+    -tmp + (2.506_628_274_631_000_5 * series / x).ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builder::PngBuilder,
+        ihdr::{ColorType, Ihdr},
+        imagedata::ImageData,
+    };
+
+    fn testing_png() -> Png {
+        let header = Ihdr::new(32, 32, 8, ColorType::Rgb).unwrap();
+        let pixels = (0..32 * 32 * 3).map(|i| (i * 2 % 256) as u8).collect();
+
+        let mut png = PngBuilder::new()
+            .chunk(header.into())
+            .chunk(Chunk::new(ChunkType::gAMA, vec![0, 0, 177, 143]))
+            .build()
+            .unwrap();
+        png.set_image_data(&ImageData::new(header, pixels).unwrap())
+            .unwrap();
+        png
+    }
+
+    #[test]
+    fn test_clean_image() {
+        let report = testing_png().detect();
+        assert!(!report.is_suspicious());
+        assert_eq!(report.score(), 0);
+    }
+
+    #[test]
+    fn test_detect_chunks() {
+        let mut png = testing_png();
+        png.embed(StegoMethod::Chunk, "ruSt", b"hidden").unwrap();
+        png.chunks_mut()[1] = Chunk::new(ChunkType::gAMA, vec![0, 0, 0]);
+        png.append_chunk(Chunk::new(ChunkType::tEXt, b"a\0b".to_vec()));
+
+        let offset = |chunk_type: ChunkType| {
+            png.chunks_with_offsets()
+                .find(|(_, chunk)| *chunk.chunk_type() == chunk_type)
+                .unwrap()
+                .0
+        };
+        let findings = png.detect().findings;
+        assert!(findings.contains(&Finding::UnknownChunk {
+            chunk_type: ChunkType::from_bytes_const(*b"ruSt"),
+            length: 6,
+            offset: offset(ChunkType::from_bytes_const(*b"ruSt")),
+        }));
+        assert!(findings.contains(&Finding::TrailingData {
+            length: 15,
+            offset: offset(ChunkType::tEXt),
+        }));
+        assert!(findings.contains(&Finding::AnomalousChunkSize {
+            chunk_type: ChunkType::gAMA,
+            length: 3,
+            offset: offset(ChunkType::gAMA),
+        }));
+    }
+
+    #[test]
+    fn test_detect_duplicates() {
+        let mut png = testing_png();
+        png.insert_chunk_ordered(Chunk::new(ChunkType::gAMA, vec![0, 0, 177, 143]));
+
+        assert_eq!(
+            png.detect().findings,
+            vec![Finding::DuplicateChunk {
+                chunk_type: ChunkType::gAMA,
+                count: 2
+            }]
+        );
+    }
+
+    #[test]
+    fn test_detect_trailing_bytes() {
+        let mut bytes = testing_png().as_bytes();
+        bytes.extend(b"tail");
+
+        let report = detect_bytes(&bytes).unwrap();
+        assert_eq!(
+            report.findings,
+            vec![Finding::TrailingData {
+                length: 4,
+                offset: bytes.len() - 4,
+            }]
+        );
+        assert_eq!(report.score(), 40);
+    }
+
+    #[test]
+    fn test_detect_corrupt_chunks() {
+        let mut png = testing_png();
+        png.embed(StegoMethod::Chunk, "ruSt", b"hidden").unwrap();
+        let (offset, _) = png
+            .chunks_with_offsets()
+            .find(|(_, chunk)| chunk.chunk_type().to_string() == "ruSt")
+            .unwrap();
+        let gama = png.chunks_with_offsets().nth(1).unwrap().0;
+        let mut bytes = png.as_bytes();
+        bytes[offset + 6] = b'1';
+        bytes[gama + 8] ^= 1;
+
+        let report = detect_bytes(&bytes).unwrap();
+        let chunk_type = ChunkType::new_unchecked(*b"ru1t");
+        assert_eq!(
+            report.findings,
+            vec![
+                Finding::InvalidCrc {
+                    chunk_type: ChunkType::gAMA,
+                    offset: gama
+                },
+                Finding::InvalidChunkType { chunk_type, offset },
+                Finding::InvalidCrc { chunk_type, offset },
+            ]
+        );
+
+        let truncated = &bytes[..offset + 10];
+        assert_eq!(
+            detect_bytes(truncated).unwrap().findings.last(),
+            Some(&Finding::TruncatedChunk { length: 10, offset })
+        );
+    }
+
+    #[test]
+    fn test_detect_lsb() {
+        let mut png = testing_png();
+        let capacity = png.capacity(StegoMethod::Lsb).unwrap();
+        let payload: Vec<u8> = (0..capacity).map(|i| (i * 73 % 251) as u8).collect();
+        png.embed(StegoMethod::Lsb, "ruSt", &payload).unwrap();
+
+        let report = png.detect();
+        assert!(matches!(
+            report.findings.as_slice(),
+            [Finding::LsbChiSquare { probability }] if *probability > 0.9
+        ));
+        assert!(report.to_string().starts_with("score: "));
+    }
+
+    #[test]
+    fn test_regularized_lower_gamma() {
+        // Chi-square CDF with 2 degrees of freedom is 1 - exp(-x / 2).
+        for x in [0.5, 2.0, 10.0] {
+            let expected = 1.0 - (-x / 2.0f64).exp();
+            assert!((regularized_lower_gamma(1.0, x / 2.0) - expected).abs() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use crate::{chunk::Chunk, chunk_type::ChunkType, png::Png};
+
+// A chunk is identified by its type and which occurrence of that type it is, so inserting a chunk
+// of one type does not show every later chunk as changed.
+#[derive(Debug, Clone)]
+pub enum ChunkDiff<'a> {
+    Added {
+        index: usize,
+        chunk: &'a Chunk,
+    },
+    Removed {
+        index: usize,
+        chunk: &'a Chunk,
+    },
+    Modified {
+        index: usize,
+        before: &'a Chunk,
+        after: &'a Chunk,
+    },
+}
+
+impl ChunkDiff<'_> {
+    pub fn chunk_type(&self) -> &ChunkType {
+        match self {
+            Self::Added { chunk, .. } | Self::Removed { chunk, .. } => chunk.chunk_type(),
+            Self::Modified { after, .. } => after.chunk_type(),
+        }
+    }
+
+    // Which occurrence of the chunk type this is, counting from 0.
+    pub fn index(&self) -> usize {
+        match self {
+            Self::Added { index, .. }
+            | Self::Removed { index, .. }
+            | Self::Modified { index, .. } => *index,
+        }
+    }
+}
+
+// Chunk types are reported in the order they first appear, in before and then in after.
+pub fn diff<'a>(before: &'a Png, after: &'a Png) -> Vec<ChunkDiff<'a>> {
+    let mut types: Vec<ChunkType> = Vec::new();
+    for chunk in before.chunks().iter().chain(after.chunks()) {
+        if !types.contains(chunk.chunk_type()) {
+            types.push(*chunk.chunk_type());
+        }
+    }
+
+    let of_type = |png: &'a Png, chunk_type: ChunkType| -> Vec<&'a Chunk> {
+        png.chunks()
+            .iter()
+            .filter(|c| *c.chunk_type() == chunk_type)
+            .collect()
+    };
+
+    let mut diffs = Vec::new();
+    for chunk_type in types {
+        let (old, new) = (of_type(before, chunk_type), of_type(after, chunk_type));
+        for index in 0..old.len().max(new.len()) {
+            match (old.get(index), new.get(index)) {
+                (Some(&before), Some(&after)) if before.chunk_data() != after.chunk_data() => {
+                    diffs.push(ChunkDiff::Modified {
+                        index,
+                        before,
+                        after,
+                    });
+                }
+                (Some(&chunk), None) => diffs.push(ChunkDiff::Removed { index, chunk }),
+                (None, Some(&chunk)) => diffs.push(ChunkDiff::Added { index, chunk }),
+                _ => {}
+            }
+        }
+    }
+
+    diffs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &str) -> Chunk {
+        Chunk::new(
+            ChunkType::from_str(chunk_type).unwrap(),
+            data.as_bytes().to_vec(),
+        )
+    }
+
+    #[test]
+    fn test_diff() {
+        let before = Png::from_chunks(vec![
+            chunk("IHDR", "header"),
+            chunk("tEXt", "one"),
+            chunk("tEXt", "two"),
+            chunk("ruSt", "hidden"),
+            chunk("IEND", ""),
+        ]);
+        let after = Png::from_chunks(vec![
+            chunk("IHDR", "header"),
+            chunk("tEXt", "one"),
+            chunk("tEXt", "TWO"),
+            chunk("tIME", "now"),
+            chunk("IEND", ""),
+        ]);
+
+        let diffs = diff(&before, &after);
+        assert_eq!(diffs.len(), 3);
+        assert!(matches!(diffs[0], ChunkDiff::Modified { index: 1, .. }));
+        assert_eq!(diffs[0].chunk_type().to_string(), "tEXt");
+        assert!(matches!(diffs[1], ChunkDiff::Removed { index: 0, .. }));
+        assert_eq!(diffs[1].chunk_type().to_string(), "ruSt");
+        assert!(matches!(diffs[2], ChunkDiff::Added { index: 0, .. }));
+        assert_eq!(diffs[2].chunk_type().to_string(), "tIME");
+
+        assert!(diff(&before, &before).is_empty());
+    }
+}
@@ -0,0 +1,48 @@
+use crate::png::Png;
+use anyhow::{Context, Result};
+use image::{DynamicImage, ImageFormat};
+use std::io::Cursor;
+
+// Conversions to and from the image crate, so its users can read and splice chunks without
+// handling the encoded bytes themselves. The image crate only keeps pixels, so chunks it does not
+// understand are lost on the way to a DynamicImage.
+impl Png {
+    pub fn from_dynamic_image(image: &DynamicImage) -> Result<Self> {
+        let mut bytes = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .context("Could not encode the image as PNG")?;
+        Png::try_from(bytes.as_slice())
+    }
+
+    pub fn to_dynamic_image(&self) -> Result<DynamicImage> {
+        image::load_from_memory_with_format(&self.as_bytes(), ImageFormat::Png)
+            .context("Could not decode the PNG")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chunk::Chunk, chunk_type::ChunkType};
+    use image::{Rgba, RgbaImage};
+
+    #[test]
+    fn test_round_trip() {
+        let mut pixels = RgbaImage::new(4, 3);
+        pixels.put_pixel(1, 2, Rgba([10, 20, 30, 40]));
+        let image = DynamicImage::ImageRgba8(pixels);
+
+        let mut png = Png::from_dynamic_image(&image).unwrap();
+        assert_eq!(png.header().unwrap().width, 4);
+        png.insert_chunk_ordered(Chunk::new("ruSt".parse().unwrap(), b"hidden".to_vec()));
+        assert!(png.chunk_by_type("ruSt").is_some());
+
+        let decoded = png.to_dynamic_image().unwrap();
+        assert_eq!(decoded.to_rgba8().get_pixel(1, 2), &Rgba([10, 20, 30, 40]));
+        assert_eq!(decoded.height(), 3);
+
+        let broken = Png::from_chunks(vec![Chunk::new(ChunkType::IEND, Vec::new())]);
+        assert!(broken.to_dynamic_image().is_err());
+    }
+}
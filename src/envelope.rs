@@ -0,0 +1,1059 @@
+#[cfg(feature = "gpg")]
+use crate::gpg::Gpg;
+use crate::{
+    carrier::Carrier,
+    compression::{Compression, DEFAULT_MAX_DECOMPRESSED_LENGTH},
+    crypto::{
+        decrypt_with_identities, decrypt_with_passphrase, ed25519_public_key,
+        encrypt_to_recipients, encrypt_with_passphrase, hmac_tag, sign_ed25519, verify_ed25519,
+        verify_hmac, CryptoError, Encryption, PassphraseParams, Signature, ED25519_KEY_LENGTH,
+    },
+    stego::StegoMethod,
+};
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+// The browser has no filesystem, so reading and writing files is left out of wasm builds.
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+pub const MAGIC: [u8; 4] = *b"PNGm";
+pub const VERSION: u8 = 1;
+pub const SEGMENT_MAGIC: [u8; 4] = *b"PNGs";
+pub const DEFAULT_SEGMENT_LENGTH: usize = 64 * 1024;
+pub(crate) const SEGMENT_HEADER_LENGTH: usize = SEGMENT_MAGIC.len() + 4;
+
+const DEFAULT_FILENAME: &str = "payload.bin";
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum EnvelopeError {
+    #[error("Payload does not start with an envelope header.")]
+    InvalidMagic,
+    #[error("Unsupported envelope version {found:?} (expected at most {VERSION}).")]
+    UnsupportedVersion { found: u8 },
+    #[error("Envelope is truncated.")]
+    Truncated,
+    #[error("Envelope checksum does not match the payload.")]
+    ChecksumMismatch,
+    #[error("Envelope {field} is too long.")]
+    FieldTooLong { field: &'static str },
+    #[error("Envelope {field} is not valid UTF-8.")]
+    InvalidUtf8 { field: &'static str },
+    #[error("Payload needs {found:?} segments, at most {max} are supported.")]
+    TooManySegments { found: usize, max: u16 },
+    #[error("Segment {index:?} of {count:?} is missing.")]
+    MissingSegment { index: u16, count: u16 },
+    #[error("Segment {index:?} appears more than once.")]
+    DuplicateSegment { index: u16 },
+    #[error("Segments disagree on the segment count.")]
+    SegmentCountMismatch,
+    #[error("At least one carrier image is needed.")]
+    NoCarriers,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub struct EnvelopeFlags(u8);
+
+impl EnvelopeFlags {
+    pub const COMPRESSED: Self = Self(1);
+    pub const ENCRYPTED: Self = Self(1 << 1);
+    pub const SIGNED: Self = Self(1 << 2);
+
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+
+    pub fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0
+    }
+
+    pub fn remove(&mut self, other: Self) {
+        self.0 &= !other.0
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Envelope {
+    pub flags: EnvelopeFlags,
+    pub compression: Compression,
+    pub encryption: Option<Encryption>,
+    pub signature: Option<Signature>,
+    pub mime_type: Option<String>,
+    pub filename: Option<String>,
+    pub payload: Vec<u8>,
+}
+
+impl Envelope {
+    pub fn new(payload: Vec<u8>) -> Self {
+        Self {
+            payload,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_mime_type(mut self, mime_type: &str) -> Self {
+        self.mime_type = Some(mime_type.to_string());
+        self
+    }
+
+    pub fn with_filename(mut self, filename: &str) -> Self {
+        self.filename = Some(filename.to_string());
+        self
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut envelope = Self::new(fs::read(path)?);
+        envelope.filename = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned());
+        envelope.mime_type = mime_type_for(path).map(str::to_string);
+
+        Ok(envelope)
+    }
+
+    // Only the last component of the recorded name is used, so payloads cannot escape `dir`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn write_to_dir(&self, dir: impl AsRef<Path>) -> Result<PathBuf> {
+        let filename = self
+            .filename
+            .as_deref()
+            .and_then(|name| Path::new(name).file_name())
+            .filter(|name| !name.is_empty())
+            .unwrap_or(DEFAULT_FILENAME.as_ref());
+
+        let path = dir.as_ref().join(filename);
+        fs::write(&path, &self.payload)?;
+
+        Ok(path)
+    }
+
+    pub fn compressed(mut self, compression: Compression) -> Self {
+        self.payload = compression.compress(&self.payload);
+        self.compression = compression;
+        if compression != Compression::None {
+            self.flags.insert(EnvelopeFlags::COMPRESSED);
+        }
+        self
+    }
+
+    pub fn decompressed(self) -> Result<Self> {
+        self.decompressed_within(DEFAULT_MAX_DECOMPRESSED_LENGTH)
+    }
+
+    // Fails rather than expanding the payload past the limit.
+    pub fn decompressed_within(mut self, limit: usize) -> Result<Self> {
+        self.payload = self.compression.decompress_within(&self.payload, limit)?;
+        self.compression = Compression::None;
+        self.flags.remove(EnvelopeFlags::COMPRESSED);
+        Ok(self)
+    }
+
+    // Compress before encrypting, ciphertext does not compress.
+    pub fn encrypted(mut self, passphrase: &str) -> Result<Self> {
+        let (params, ciphertext) = encrypt_with_passphrase(passphrase, &self.payload)?;
+        self.payload = ciphertext;
+        self.encryption = Some(Encryption::Passphrase(params));
+        self.flags.insert(EnvelopeFlags::ENCRYPTED);
+        Ok(self)
+    }
+
+    pub fn encrypted_to(mut self, recipients: &[&str]) -> Result<Self> {
+        self.payload = encrypt_to_recipients(recipients, &self.payload)?;
+        self.encryption = Some(Encryption::Recipients);
+        self.flags.insert(EnvelopeFlags::ENCRYPTED);
+        Ok(self)
+    }
+
+    pub fn decrypted_with_identities(mut self, identities: &str) -> Result<Self> {
+        if self.encryption != Some(Encryption::Recipients) {
+            return Err(CryptoError::NotEncrypted.into());
+        }
+
+        self.payload = decrypt_with_identities(identities, &self.payload)?;
+        self.encryption = None;
+        self.flags.remove(EnvelopeFlags::ENCRYPTED);
+        Ok(self)
+    }
+
+    pub fn decrypted(mut self, passphrase: &str) -> Result<Self> {
+        let Some(Encryption::Passphrase(params)) = &self.encryption else {
+            return Err(CryptoError::NotEncrypted.into());
+        };
+
+        self.payload = decrypt_with_passphrase(params, passphrase, &self.payload)?;
+        self.encryption = None;
+        self.flags.remove(EnvelopeFlags::ENCRYPTED);
+        Ok(self)
+    }
+
+    #[cfg(feature = "gpg")]
+    pub fn gpg_encrypted(mut self, gpg: &Gpg, recipients: &[&str]) -> Result<Self> {
+        self.payload = gpg.encrypt(recipients, &self.payload)?;
+        self.encryption = Some(Encryption::Gpg);
+        self.flags.insert(EnvelopeFlags::ENCRYPTED);
+        Ok(self)
+    }
+
+    #[cfg(feature = "gpg")]
+    pub fn gpg_decrypted(mut self, gpg: &Gpg) -> Result<Self> {
+        if self.encryption != Some(Encryption::Gpg) {
+            return Err(CryptoError::NotEncrypted.into());
+        }
+
+        self.payload = gpg.decrypt(&self.payload)?.data;
+        self.encryption = None;
+        self.flags.remove(EnvelopeFlags::ENCRYPTED);
+        Ok(self)
+    }
+
+    // Sign before encrypting so the signature is hidden along with the data.
+    #[cfg(feature = "gpg")]
+    pub fn gpg_signed(mut self, gpg: &Gpg, key: Option<&str>) -> Result<Self> {
+        self.payload = gpg.sign(key, &self.payload)?;
+        self.signature = Some(Signature::Gpg);
+        self.flags.insert(EnvelopeFlags::SIGNED);
+        Ok(self)
+    }
+
+    // Returns the unwrapped envelope and the user ID of the signer.
+    #[cfg(feature = "gpg")]
+    pub fn gpg_verified(mut self, gpg: &Gpg) -> Result<(Self, String)> {
+        if self.signature != Some(Signature::Gpg) {
+            return Err(CryptoError::NotSigned.into());
+        }
+
+        let verified = gpg.verify(&self.payload)?;
+        self.payload = verified.data;
+        self.signature = None;
+        self.flags.remove(EnvelopeFlags::SIGNED);
+        Ok((self, verified.signer.unwrap_or_default()))
+    }
+
+    // Signs the envelope as serialized without a signature, so metadata is covered too.
+    // Sign last, after compressing and encrypting.
+    pub fn signed_ed25519(mut self, secret_key: &[u8; ED25519_KEY_LENGTH]) -> Result<Self> {
+        let signature = sign_ed25519(secret_key, &self.unsigned_bytes()?);
+        self.signature = Some(Signature::Ed25519 {
+            public_key: ed25519_public_key(secret_key),
+            signature,
+        });
+        self.flags.insert(EnvelopeFlags::SIGNED);
+        Ok(self)
+    }
+
+    pub fn verify_ed25519(&self, public_key: &[u8; ED25519_KEY_LENGTH]) -> Result<()> {
+        let Some(Signature::Ed25519 { signature, .. }) = &self.signature else {
+            return Err(CryptoError::NotSigned.into());
+        };
+
+        verify_ed25519(public_key, &self.unsigned_bytes()?, signature)
+    }
+
+    pub fn authenticated(mut self, passphrase: &str) -> Result<Self> {
+        let params = PassphraseParams::random();
+        let tag = hmac_tag(&params.derive_key(passphrase)?, &self.unsigned_bytes()?);
+        self.signature = Some(Signature::Hmac { params, tag });
+        self.flags.insert(EnvelopeFlags::SIGNED);
+        Ok(self)
+    }
+
+    pub fn verify_hmac(&self, passphrase: &str) -> Result<()> {
+        let Some(Signature::Hmac { params, tag }) = &self.signature else {
+            return Err(CryptoError::NotSigned.into());
+        };
+
+        verify_hmac(
+            &params.derive_key(passphrase)?,
+            &self.unsigned_bytes()?,
+            tag,
+        )
+    }
+
+    fn unsigned_bytes(&self) -> Result<Vec<u8>> {
+        let mut unsigned = self.clone();
+        unsigned.signature = None;
+        unsigned.flags.remove(EnvelopeFlags::SIGNED);
+        unsigned.to_bytes()
+    }
+
+    pub fn is_envelope(bytes: &[u8]) -> bool {
+        bytes.starts_with(&MAGIC)
+    }
+
+    // Layout: magic, version, flags, header length (u16), header, payload.
+    // Header: mime type (u8 length), filename (u16 length), payload length (u32), SHA-256 of the payload,
+    // compression algorithm (u8), encryption scheme (u8) and its parameters, signature scheme (u8) and its parameters.
+    // Readers skip header bytes they do not know, so later versions can append fields.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mime_type = self.mime_type.as_deref().unwrap_or_default().as_bytes();
+        let filename = self.filename.as_deref().unwrap_or_default().as_bytes();
+
+        let mime_length = u8::try_from(mime_type.len())
+            .map_err(|_| EnvelopeError::FieldTooLong { field: "MIME type" })?;
+        let filename_length = u16::try_from(filename.len())
+            .map_err(|_| EnvelopeError::FieldTooLong { field: "filename" })?;
+        let payload_length = u32::try_from(self.payload.len())
+            .map_err(|_| EnvelopeError::FieldTooLong { field: "payload" })?;
+
+        let mut header = vec![mime_length];
+        header.extend(mime_type);
+        header.extend(filename_length.to_be_bytes());
+        header.extend(filename);
+        header.extend(payload_length.to_be_bytes());
+        header.extend(Sha256::digest(&self.payload));
+        header.push(self.compression as u8);
+        match &self.encryption {
+            Some(encryption) => {
+                header.push(encryption.scheme());
+                header.extend(encryption.parameters());
+            }
+            None => header.push(0),
+        }
+        match &self.signature {
+            Some(signature) => {
+                header.push(signature.scheme());
+                header.extend(signature.parameters());
+            }
+            None => header.push(0),
+        }
+
+        let header_length = u16::try_from(header.len())
+            .map_err(|_| EnvelopeError::FieldTooLong { field: "header" })?;
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend([VERSION, self.flags.bits()]);
+        bytes.extend(header_length.to_be_bytes());
+        bytes.extend(header);
+        bytes.extend(&self.payload);
+
+        Ok(bytes)
+    }
+
+    // The bytes to_bytes adds around the payload.
+    pub fn overhead(&self) -> usize {
+        let mime_type = self.mime_type.as_deref().unwrap_or_default();
+        let filename = self.filename.as_deref().unwrap_or_default();
+        let encryption = self
+            .encryption
+            .as_ref()
+            .map_or(0, |encryption| encryption.parameters().len());
+        let signature = self
+            .signature
+            .as_ref()
+            .map_or(0, |signature| signature.parameters().len());
+
+        // Magic, version, flags and header length, then the fixed size header fields.
+        MAGIC.len()
+            + 4
+            + 1
+            + 2
+            + 4
+            + 32
+            + 3
+            + mime_type.len()
+            + filename.len()
+            + encryption
+            + signature
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader(bytes);
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(EnvelopeError::InvalidMagic.into());
+        }
+
+        let [version, flags] = *reader.take(2)? else {
+            unreachable!()
+        };
+        if version == 0 || version > VERSION {
+            return Err(EnvelopeError::UnsupportedVersion { found: version }.into());
+        }
+
+        let header_length = reader.u16()? as usize;
+        let mut header = Reader(reader.take(header_length)?);
+
+        let mime_length = header.take(1)?[0] as usize;
+        let mime_type = header.string(mime_length, "MIME type")?;
+        let filename_length = header.u16()? as usize;
+        let filename = header.string(filename_length, "filename")?;
+        let payload_length = u32::from_be_bytes(header.take(4)?.try_into().unwrap()) as usize;
+        let checksum = header.take(32)?;
+        let compression = match header.take(1) {
+            Ok(&[id]) => Compression::try_from(id)?,
+            _ => Compression::None,
+        };
+        let encryption = match header.take(1) {
+            Ok(&[1]) => Some(Encryption::Passphrase(PassphraseParams::from_bytes(
+                header
+                    .take(PassphraseParams::ENCODED_LENGTH)?
+                    .try_into()
+                    .unwrap(),
+            )?)),
+            Ok(&[2]) => Some(Encryption::Recipients),
+            Ok(&[3]) => Some(Encryption::Gpg),
+            Ok(&[found]) if found != 0 => return Err(CryptoError::UnknownScheme { found }.into()),
+            _ => None,
+        };
+        let signature = match header.take(1) {
+            Ok(&[1]) => Some(Signature::Gpg),
+            Ok(&[2]) => Some(Signature::ed25519_from_bytes(
+                header
+                    .take(Signature::ED25519_ENCODED_LENGTH)?
+                    .try_into()
+                    .unwrap(),
+            )),
+            Ok(&[3]) => Some(Signature::hmac_from_bytes(
+                header
+                    .take(Signature::HMAC_ENCODED_LENGTH)?
+                    .try_into()
+                    .unwrap(),
+            )?),
+            Ok(&[found]) if found != 0 => {
+                return Err(CryptoError::UnknownSignatureScheme { found }.into())
+            }
+            _ => None,
+        };
+
+        let payload = reader.take(payload_length)?;
+        if Sha256::digest(payload).as_slice() != checksum {
+            return Err(EnvelopeError::ChecksumMismatch.into());
+        }
+
+        Ok(Self {
+            flags: EnvelopeFlags::from_bits(flags),
+            compression,
+            encryption,
+            signature,
+            mime_type: (!mime_type.is_empty()).then_some(mime_type),
+            filename: (!filename.is_empty()).then_some(filename),
+            payload: payload.to_vec(),
+        })
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn mime_type_for(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    let mime_type = match extension.as_str() {
+        "txt" => "text/plain",
+        "md" => "text/markdown",
+        "html" | "htm" => "text/html",
+        "json" => "application/json",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        "bin" => "application/octet-stream",
+        _ => return None,
+    };
+
+    Some(mime_type)
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8]> {
+        if self.0.len() < length {
+            return Err(EnvelopeError::Truncated.into());
+        }
+
+        let (taken, rest) = self.0.split_at(length);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self, length: usize, field: &'static str) -> Result<String> {
+        let bytes = self.take(length)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| EnvelopeError::InvalidUtf8 { field }.into())
+    }
+}
+
+// Segment layout: magic, index (u16), count (u16), part of the envelope bytes.
+// The envelope checksum covers the reassembled whole.
+pub(crate) fn split_segments(bytes: &[u8], segment_length: usize) -> Result<Vec<Vec<u8>>> {
+    let parts: Vec<&[u8]> = bytes.chunks(segment_length.max(1)).collect();
+    let count = u16::try_from(parts.len()).map_err(|_| EnvelopeError::TooManySegments {
+        found: parts.len(),
+        max: u16::MAX,
+    })?;
+
+    Ok(parts
+        .into_iter()
+        .enumerate()
+        .map(|(index, part)| {
+            let mut segment = SEGMENT_MAGIC.to_vec();
+            segment.extend((index as u16).to_be_bytes());
+            segment.extend(count.to_be_bytes());
+            segment.extend(part);
+            segment
+        })
+        .collect())
+}
+
+pub(crate) fn join_segments<'a>(segments: impl IntoIterator<Item = &'a [u8]>) -> Result<Vec<u8>> {
+    let mut parts: Vec<(u16, &[u8])> = Vec::new();
+    let mut expected_count = None;
+
+    for segment in segments {
+        let mut reader = Reader(segment);
+        reader.take(SEGMENT_MAGIC.len())?;
+        let index = reader.u16()?;
+        let count = reader.u16()?;
+
+        if *expected_count.get_or_insert(count) != count {
+            return Err(EnvelopeError::SegmentCountMismatch.into());
+        }
+        parts.push((index, reader.0));
+    }
+
+    parts.sort_by_key(|&(index, _)| index);
+    let count = expected_count.unwrap_or_default();
+    for expected in 0..count {
+        match parts.get(expected as usize) {
+            Some(&(index, _)) if index == expected => {}
+            Some(&(index, _)) if index < expected => {
+                return Err(EnvelopeError::DuplicateSegment { index }.into())
+            }
+            _ => {
+                return Err(EnvelopeError::MissingSegment {
+                    index: expected,
+                    count,
+                }
+                .into())
+            }
+        }
+    }
+    if let Some(&(index, _)) = parts.get(count as usize) {
+        return Err(EnvelopeError::DuplicateSegment { index }.into());
+    }
+
+    Ok(parts
+        .into_iter()
+        .flat_map(|(_, part)| part.to_vec())
+        .collect())
+}
+
+// Shards the envelope evenly over the carriers, any carrier order works for joining.
+pub fn split_across_carriers<C: Carrier>(
+    carriers: &mut [C],
+    method: StegoMethod,
+    slot: &str,
+    envelope: &Envelope,
+) -> Result<()> {
+    if carriers.is_empty() {
+        return Err(EnvelopeError::NoCarriers.into());
+    }
+
+    let bytes = envelope.to_bytes()?;
+    let shard_length = bytes.len().div_ceil(carriers.len());
+    let shards = split_segments(&bytes, shard_length)?;
+
+    for (carrier, shard) in carriers.iter_mut().zip(shards) {
+        carrier.embed(method, slot, &shard)?;
+    }
+
+    Ok(())
+}
+
+pub fn join_from_carriers<C: Carrier>(
+    carriers: &[C],
+    method: StegoMethod,
+    slot: &str,
+) -> Result<Envelope> {
+    let shards = carriers
+        .iter()
+        .map(|carrier| carrier.extract(method, slot))
+        .collect::<Result<Vec<_>>>()?;
+
+    Envelope::from_bytes(&join_segments(shards.iter().map(Vec::as_slice))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builder::PngBuilder,
+        chunk::Chunk,
+        chunk_type::ChunkType,
+        crypto::{MAX_MEMORY_COST, MAX_TIME_COST, NONCE_LENGTH, SALT_LENGTH},
+    };
+
+    fn testing_envelope() -> Envelope {
+        Envelope::new(b"%PDF-1.7".to_vec())
+            .with_mime_type("application/pdf")
+            .with_filename("secret.pdf")
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut envelope = testing_envelope();
+        envelope.flags.insert(EnvelopeFlags::COMPRESSED);
+
+        let bytes = envelope.to_bytes().unwrap();
+        assert!(Envelope::is_envelope(&bytes));
+        assert_eq!(bytes[4..6], [VERSION, 1]);
+
+        let decoded = Envelope::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded, envelope);
+        assert!(decoded.flags.contains(EnvelopeFlags::COMPRESSED));
+        assert!(!decoded.flags.contains(EnvelopeFlags::ENCRYPTED));
+    }
+
+    #[test]
+    fn test_empty_metadata() {
+        let envelope = Envelope::new(vec![1, 2, 3]);
+        let decoded = Envelope::from_bytes(&envelope.to_bytes().unwrap()).unwrap();
+
+        assert_eq!(decoded.mime_type, None);
+        assert_eq!(decoded.filename, None);
+        assert_eq!(decoded.payload, [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_overhead() {
+        for envelope in [testing_envelope(), Envelope::new(vec![1, 2, 3])] {
+            let length = envelope.to_bytes().unwrap().len();
+            assert_eq!(envelope.overhead(), length - envelope.payload.len());
+        }
+    }
+
+    #[test]
+    fn test_skips_unknown_header_fields() {
+        let mut bytes = testing_envelope().to_bytes().unwrap();
+        let header_length = u16::from_be_bytes([bytes[6], bytes[7]]) as usize;
+        bytes.insert(8 + header_length, 0xaa);
+        bytes[6..8].copy_from_slice(&(header_length as u16 + 1).to_be_bytes());
+
+        assert_eq!(Envelope::from_bytes(&bytes).unwrap(), testing_envelope());
+    }
+
+    #[test]
+    fn test_invalid_envelopes() {
+        let bytes = testing_envelope().to_bytes().unwrap();
+        let error = |bytes: &[u8]| {
+            Envelope::from_bytes(bytes)
+                .err()
+                .unwrap()
+                .downcast::<EnvelopeError>()
+                .unwrap()
+        };
+
+        assert_eq!(error(b"raw message"), EnvelopeError::InvalidMagic);
+        assert_eq!(error(&bytes[..bytes.len() - 1]), EnvelopeError::Truncated);
+
+        let mut corrupted = bytes.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        assert_eq!(error(&corrupted), EnvelopeError::ChecksumMismatch);
+
+        let mut future = bytes;
+        future[4] = VERSION + 1;
+        assert_eq!(
+            error(&future),
+            EnvelopeError::UnsupportedVersion { found: VERSION + 1 }
+        );
+    }
+
+    #[test]
+    fn test_file_round_trip() {
+        let dir = std::env::temp_dir().join(format!("pngme-envelope-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("secret.PDF");
+        fs::write(&source, b"%PDF-1.7").unwrap();
+
+        let envelope = Envelope::from_file(&source).unwrap();
+        assert_eq!(envelope.filename.as_deref(), Some("secret.PDF"));
+        assert_eq!(envelope.mime_type.as_deref(), Some("application/pdf"));
+
+        let output = dir.join("out");
+        fs::create_dir_all(&output).unwrap();
+        let mut decoded = Envelope::from_bytes(&envelope.to_bytes().unwrap()).unwrap();
+        let written = decoded.write_to_dir(&output).unwrap();
+        assert_eq!(written, output.join("secret.PDF"));
+        assert_eq!(fs::read(&written).unwrap(), b"%PDF-1.7");
+
+        decoded.filename = Some("../../etc/passwd".to_string());
+        assert_eq!(
+            decoded.write_to_dir(&output).unwrap(),
+            output.join("passwd")
+        );
+        decoded.filename = None;
+        assert_eq!(
+            decoded.write_to_dir(&output).unwrap(),
+            output.join(DEFAULT_FILENAME)
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_segmented_envelope() {
+        let mut png = PngBuilder::new()
+            .chunk(Chunk::new(ChunkType::IHDR, vec![0; 13]))
+            .build()
+            .unwrap();
+        let envelope = Envelope::new((0..1000).map(|i| (i % 256) as u8).collect());
+
+        png.embed_envelope_with_segment_length(StegoMethod::Chunk, "ruSt", &envelope, 300)
+            .unwrap();
+        let segments: Vec<&Chunk> = png
+            .chunks()
+            .iter()
+            .filter(|c| c.chunk_type().to_string() == "ruSt")
+            .collect();
+        assert_eq!(segments.len(), 4);
+        assert!(segments.iter().all(|c| c.length() <= 308));
+
+        assert_eq!(
+            png.extract_envelope(StegoMethod::Chunk, "ruSt").unwrap(),
+            envelope
+        );
+
+        // Segments are ordered by their index, not their position.
+        let first = png.remove_first_chunk("ruSt").unwrap();
+        png.insert_chunk_ordered(first);
+        assert_eq!(
+            png.extract_envelope(StegoMethod::Chunk, "ruSt").unwrap(),
+            envelope
+        );
+    }
+
+    #[test]
+    fn test_join_segments_errors() {
+        let segments = split_segments(&[7; 10], 3).unwrap();
+        let error = |segments: Vec<&Vec<u8>>| {
+            join_segments(segments.into_iter().map(Vec::as_slice))
+                .err()
+                .unwrap()
+                .downcast::<EnvelopeError>()
+                .unwrap()
+        };
+
+        assert_eq!(
+            join_segments(segments.iter().map(Vec::as_slice)).unwrap(),
+            [7; 10]
+        );
+        assert_eq!(
+            error(vec![&segments[0], &segments[1], &segments[3]]),
+            EnvelopeError::MissingSegment { index: 2, count: 4 }
+        );
+        assert_eq!(
+            error(vec![&segments[0], &segments[1], &segments[1], &segments[2]]),
+            EnvelopeError::DuplicateSegment { index: 1 }
+        );
+    }
+
+    #[test]
+    fn test_split_across_carriers() {
+        let carrier = PngBuilder::new()
+            .chunk(Chunk::new(ChunkType::IHDR, vec![0; 13]))
+            .build()
+            .unwrap();
+        let mut carriers = vec![carrier.clone(), carrier.clone(), carrier];
+        let envelope = testing_envelope();
+
+        split_across_carriers(&mut carriers, StegoMethod::Chunk, "ruSt", &envelope).unwrap();
+        assert!(carriers.iter().all(|c| c.chunk_by_type("ruSt").is_some()));
+
+        carriers.swap(0, 2);
+        assert_eq!(
+            join_from_carriers(&carriers, StegoMethod::Chunk, "ruSt").unwrap(),
+            envelope
+        );
+
+        assert_eq!(
+            join_from_carriers(&carriers[..2], StegoMethod::Chunk, "ruSt")
+                .err()
+                .unwrap()
+                .downcast::<EnvelopeError>()
+                .unwrap(),
+            EnvelopeError::MissingSegment { index: 0, count: 3 }
+        );
+    }
+
+    #[test]
+    fn test_compressed_envelope() {
+        let text = b"All work and no play makes Jack a dull boy. ".repeat(50);
+        let mut png = PngBuilder::new()
+            .chunk(Chunk::new(ChunkType::IHDR, vec![0; 13]))
+            .build()
+            .unwrap();
+
+        for compression in [Compression::Deflate, Compression::Zstd] {
+            let envelope = Envelope::new(text.clone()).compressed(compression);
+            assert!(envelope.flags.contains(EnvelopeFlags::COMPRESSED));
+            assert!(envelope.payload.len() * 10 < text.len());
+
+            png.embed_envelope(StegoMethod::Chunk, "ruSt", &envelope)
+                .unwrap();
+            let stored = png
+                .extract_stored_envelope(StegoMethod::Chunk, "ruSt")
+                .unwrap();
+            assert_eq!(stored.compression, compression);
+
+            let decoded = png.extract_envelope(StegoMethod::Chunk, "ruSt").unwrap();
+            assert_eq!(decoded.payload, text);
+            assert!(!decoded.flags.contains(EnvelopeFlags::COMPRESSED));
+            png.remove_all_chunks("ruSt").unwrap();
+        }
+    }
+
+    #[test]
+    fn test_encrypted_envelope() {
+        let mut png = PngBuilder::new()
+            .chunk(Chunk::new(ChunkType::IHDR, vec![0; 13]))
+            .build()
+            .unwrap();
+        let envelope = testing_envelope()
+            .compressed(Compression::Deflate)
+            .encrypted("hunter2")
+            .unwrap();
+        assert!(envelope.flags.contains(EnvelopeFlags::ENCRYPTED));
+
+        png.embed_envelope(StegoMethod::Chunk, "ruSt", &envelope)
+            .unwrap();
+        let stored = png.extract_envelope(StegoMethod::Chunk, "ruSt").unwrap();
+        assert_eq!(stored, envelope);
+        assert!(!png
+            .chunk_by_type("ruSt")
+            .unwrap()
+            .to_string()
+            .contains("PDF"));
+
+        let decoded = png
+            .extract_envelope_with_passphrase(StegoMethod::Chunk, "ruSt", "hunter2")
+            .unwrap();
+        assert_eq!(decoded, testing_envelope());
+
+        assert_eq!(
+            png.extract_envelope_with_passphrase(StegoMethod::Chunk, "ruSt", "hunter3")
+                .err()
+                .unwrap()
+                .downcast::<CryptoError>()
+                .unwrap(),
+            CryptoError::DecryptionFailed
+        );
+    }
+
+    #[test]
+    fn test_encrypted_envelope_cost_limit() {
+        let envelope = testing_envelope().encrypted("hunter2").unwrap();
+        let Some(Encryption::Passphrase(params)) = envelope.encryption else {
+            unreachable!()
+        };
+        let mut bytes = envelope.to_bytes().unwrap();
+        let offset = bytes
+            .windows(PassphraseParams::ENCODED_LENGTH)
+            .position(|window| window == params.to_bytes())
+            .unwrap()
+            + SALT_LENGTH
+            + NONCE_LENGTH;
+        bytes[offset..offset + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+
+        // Parsing fails, so no passphrase is ever run through Argon2 with these costs.
+        assert_eq!(
+            Envelope::from_bytes(&bytes)
+                .unwrap_err()
+                .downcast::<CryptoError>()
+                .unwrap(),
+            CryptoError::ParamsOutOfRange {
+                field: "memory cost",
+                found: u32::MAX,
+                max: MAX_MEMORY_COST
+            }
+        );
+    }
+
+    #[test]
+    fn test_recipient_envelope() {
+        use age::secrecy::ExposeSecret;
+
+        let mut png = PngBuilder::new()
+            .chunk(Chunk::new(ChunkType::IHDR, vec![0; 13]))
+            .build()
+            .unwrap();
+        let identity = age::x25519::Identity::generate();
+        let recipient = identity.to_public().to_string();
+        let envelope = testing_envelope()
+            .compressed(Compression::Zstd)
+            .encrypted_to(&[&recipient])
+            .unwrap();
+        assert_eq!(envelope.encryption, Some(Encryption::Recipients));
+
+        png.embed_envelope(StegoMethod::Chunk, "ruSt", &envelope)
+            .unwrap();
+        assert_eq!(
+            png.extract_envelope(StegoMethod::Chunk, "ruSt").unwrap(),
+            envelope
+        );
+
+        let decoded = png
+            .extract_envelope_with_identities(
+                StegoMethod::Chunk,
+                "ruSt",
+                identity.to_string().expose_secret(),
+            )
+            .unwrap();
+        assert_eq!(decoded, testing_envelope());
+
+        assert!(png
+            .extract_envelope_with_passphrase(StegoMethod::Chunk, "ruSt", "hunter2")
+            .is_err());
+    }
+
+    #[test]
+    fn test_gpg_schemes_round_trip() {
+        let envelope = Envelope {
+            encryption: Some(Encryption::Gpg),
+            signature: Some(Signature::Gpg),
+            ..testing_envelope()
+        };
+        let bytes = envelope.to_bytes().unwrap();
+        assert_eq!(Envelope::from_bytes(&bytes).unwrap(), envelope);
+    }
+
+    #[test]
+    fn test_ed25519_signed_envelope() {
+        let secret_key = [3; ED25519_KEY_LENGTH];
+        let public_key = ed25519_public_key(&secret_key);
+        let envelope = testing_envelope()
+            .encrypted("hunter2")
+            .unwrap()
+            .signed_ed25519(&secret_key)
+            .unwrap();
+        assert!(envelope.flags.contains(EnvelopeFlags::SIGNED));
+
+        let mut stored = Envelope::from_bytes(&envelope.to_bytes().unwrap()).unwrap();
+        assert_eq!(stored, envelope);
+        assert!(stored.verify_ed25519(&public_key).is_ok());
+        assert!(stored
+            .verify_ed25519(&ed25519_public_key(&[4; ED25519_KEY_LENGTH]))
+            .is_err());
+
+        stored.filename = Some("other.pdf".to_string());
+        assert_eq!(
+            stored
+                .verify_ed25519(&public_key)
+                .err()
+                .unwrap()
+                .downcast::<CryptoError>()
+                .unwrap(),
+            CryptoError::BadSignature
+        );
+
+        assert_eq!(
+            testing_envelope()
+                .verify_ed25519(&public_key)
+                .err()
+                .unwrap()
+                .downcast::<CryptoError>()
+                .unwrap(),
+            CryptoError::NotSigned
+        );
+    }
+
+    #[test]
+    fn test_hmac_envelope() {
+        let envelope = testing_envelope()
+            .compressed(Compression::Deflate)
+            .authenticated("hunter2")
+            .unwrap();
+        assert!(envelope.flags.contains(EnvelopeFlags::SIGNED));
+
+        let mut stored = Envelope::from_bytes(&envelope.to_bytes().unwrap()).unwrap();
+        assert_eq!(stored, envelope);
+        assert!(stored.verify_hmac("hunter2").is_ok());
+        assert!(stored.verify_hmac("hunter3").is_err());
+
+        stored.payload.pop();
+        assert_eq!(
+            stored
+                .verify_hmac("hunter2")
+                .err()
+                .unwrap()
+                .downcast::<CryptoError>()
+                .unwrap(),
+            CryptoError::AuthenticationFailed
+        );
+    }
+
+    #[test]
+    fn test_hmac_cost_limit() {
+        let envelope = testing_envelope().authenticated("hunter2").unwrap();
+        let Some(Signature::Hmac { params, tag }) = envelope.signature else {
+            unreachable!()
+        };
+        let params = PassphraseParams {
+            time_cost: u32::MAX,
+            ..params
+        };
+        let out_of_range = CryptoError::ParamsOutOfRange {
+            field: "time cost",
+            found: u32::MAX,
+            max: MAX_TIME_COST,
+        };
+
+        let mut bytes = envelope.to_bytes().unwrap();
+        let encoded: Vec<u8> = params.to_bytes().into_iter().chain(tag).collect();
+        let offset = bytes.len() - envelope.payload.len() - encoded.len();
+        bytes[offset..offset + encoded.len()].copy_from_slice(&encoded);
+        assert_eq!(
+            Envelope::from_bytes(&bytes)
+                .unwrap_err()
+                .downcast::<CryptoError>()
+                .unwrap(),
+            out_of_range
+        );
+
+        // Built in memory rather than parsed, the check happens before key derivation instead.
+        let envelope = Envelope {
+            signature: Some(Signature::Hmac { params, tag }),
+            ..envelope
+        };
+        assert_eq!(
+            envelope
+                .verify_hmac("hunter2")
+                .unwrap_err()
+                .downcast::<CryptoError>()
+                .unwrap(),
+            out_of_range
+        );
+    }
+
+    #[test]
+    fn test_png_envelope() {
+        let mut png = PngBuilder::new()
+            .chunk(Chunk::new(ChunkType::IHDR, vec![0; 13]))
+            .build()
+            .unwrap();
+
+        png.embed_envelope(StegoMethod::Chunk, "ruSt", &testing_envelope())
+            .unwrap();
+        assert_eq!(
+            png.extract_envelope(StegoMethod::Chunk, "ruSt").unwrap(),
+            testing_envelope()
+        );
+    }
+}
@@ -0,0 +1,369 @@
+use crate::{chunk::Chunk, chunk_type::ChunkType, png::Png};
+use anyhow::Result;
+use std::fmt::Display;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ExifError {
+    #[error("EXIF data has to start with a TIFF header (II*\\0 or MM\\0*).")]
+    InvalidHeader,
+    #[error("EXIF data ends inside a tag directory or value.")]
+    Truncated,
+}
+
+const LITTLE_ENDIAN_HEADER: [u8; 4] = *b"II*\0";
+const BIG_ENDIAN_HEADER: [u8; 4] = *b"MM\0*";
+
+// Pointers from the first directory to the directories holding photographic and GPS tags.
+const EXIF_IFD_POINTER: u16 = 0x8769;
+const GPS_IFD_POINTER: u16 = 0x8825;
+const ORIENTATION: u16 = 0x0112;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExifValue {
+    Ascii(String),
+    Unsigned(Vec<u32>),
+    Signed(Vec<i32>),
+    Rational(Vec<(u32, u32)>),
+    SignedRational(Vec<(i32, i32)>),
+    Undefined(Vec<u8>),
+}
+
+impl Display for ExifValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn join<T>(values: &[T], format: impl Fn(&T) -> String) -> String {
+            values.iter().map(format).collect::<Vec<_>>().join(", ")
+        }
+        let ratio = |n: f64, d: f64| match d {
+            0.0 => "undefined".to_string(),
+            _ if n / d >= 1.0 || n == 0.0 => format!("{}", n / d),
+            _ => format!("1/{}", (d / n).round()),
+        };
+
+        let string = match self {
+            Self::Ascii(text) => text.clone(),
+            Self::Unsigned(values) => join(values, u32::to_string),
+            Self::Signed(values) => join(values, i32::to_string),
+            Self::Rational(values) => join(values, |&(n, d)| ratio(n as f64, d as f64)),
+            Self::SignedRational(values) => join(values, |&(n, d)| ratio(n as f64, d as f64)),
+            Self::Undefined(bytes) => format!("{} bytes", bytes.len()),
+        };
+        write!(f, "{}", string)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExifTag {
+    pub id: u16,
+    pub value: ExifValue,
+}
+
+impl ExifTag {
+    pub fn name(&self) -> Option<&'static str> {
+        let name = match self.id {
+            0x010E => "ImageDescription",
+            0x010F => "Make",
+            0x0110 => "Model",
+            ORIENTATION => "Orientation",
+            0x011A => "XResolution",
+            0x011B => "YResolution",
+            0x0128 => "ResolutionUnit",
+            0x0131 => "Software",
+            0x0132 => "DateTime",
+            0x013B => "Artist",
+            0x8298 => "Copyright",
+            0x829A => "ExposureTime",
+            0x829D => "FNumber",
+            0x8827 => "ISOSpeedRatings",
+            0x9003 => "DateTimeOriginal",
+            0x9004 => "DateTimeDigitized",
+            0x9010 => "OffsetTime",
+            0x920A => "FocalLength",
+            0xA002 => "PixelXDimension",
+            0xA003 => "PixelYDimension",
+            0xA433 => "LensMake",
+            0xA434 => "LensModel",
+            _ => return None,
+        };
+
+        Some(name)
+    }
+}
+
+impl Display for ExifTag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{}: {}", name, self.value)?,
+            None => write!(f, "{:#06x}: {}", self.id, self.value)?,
+        }
+
+        let orientation = match (self.id, &self.value) {
+            (ORIENTATION, ExifValue::Unsigned(values)) => values.first().copied(),
+            _ => None,
+        };
+        let description = match orientation {
+            Some(1) => "normal",
+            Some(2) => "mirrored horizontally",
+            Some(3) => "rotated 180°",
+            Some(4) => "mirrored vertically",
+            Some(5) => "mirrored horizontally, rotated 270° clockwise",
+            Some(6) => "rotated 90° clockwise",
+            Some(7) => "mirrored horizontally, rotated 90° clockwise",
+            Some(8) => "rotated 270° clockwise",
+            _ => return Ok(()),
+        };
+        write!(f, " ({})", description)
+    }
+}
+
+struct Tiff<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl Tiff<'_> {
+    fn bytes(&self, offset: usize, length: usize) -> Result<&[u8]> {
+        offset
+            .checked_add(length)
+            .and_then(|end| self.data.get(offset..end))
+            .ok_or_else(|| ExifError::Truncated.into())
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16> {
+        let bytes = self.bytes(offset, 2)?.try_into().unwrap();
+        Ok(match self.big_endian {
+            true => u16::from_be_bytes(bytes),
+            false => u16::from_le_bytes(bytes),
+        })
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32> {
+        let bytes = self.bytes(offset, 4)?.try_into().unwrap();
+        Ok(match self.big_endian {
+            true => u32::from_be_bytes(bytes),
+            false => u32::from_le_bytes(bytes),
+        })
+    }
+
+    // Reads one directory, following the pointers to the Exif and GPS directories.
+    fn read_ifd(&self, offset: usize, tags: &mut Vec<ExifTag>, depth: usize) -> Result<()> {
+        let count = self.u16(offset)? as usize;
+        for index in 0..count {
+            let entry = offset + 2 + index * 12;
+            let id = self.u16(entry)?;
+            let kind = self.u16(entry + 2)?;
+            let count = self.u32(entry + 4)? as usize;
+
+            let size = match kind {
+                1 | 2 | 6 | 7 => 1,
+                3 | 8 => 2,
+                4 | 9 => 4,
+                5 | 10 => 8,
+                // Floating point and unknown types are skipped.
+                _ => continue,
+            };
+            let length = count.checked_mul(size).ok_or(ExifError::Truncated)?;
+            let start = match length <= 4 {
+                true => entry + 8,
+                false => self.u32(entry + 8)? as usize,
+            };
+            let bytes = self.bytes(start, length)?;
+
+            let value = match kind {
+                2 => ExifValue::Ascii(
+                    String::from_utf8_lossy(bytes)
+                        .trim_end_matches('\0')
+                        .to_string(),
+                ),
+                1 => ExifValue::Unsigned(bytes.iter().map(|&b| b as u32).collect()),
+                3 => ExifValue::Unsigned(
+                    (0..count)
+                        .map(|i| self.u16(start + i * 2).map(u32::from))
+                        .collect::<Result<_>>()?,
+                ),
+                4 => ExifValue::Unsigned(
+                    (0..count)
+                        .map(|i| self.u32(start + i * 4))
+                        .collect::<Result<_>>()?,
+                ),
+                6 => ExifValue::Signed(bytes.iter().map(|&b| b as i8 as i32).collect()),
+                8 => ExifValue::Signed(
+                    (0..count)
+                        .map(|i| self.u16(start + i * 2).map(|v| v as i16 as i32))
+                        .collect::<Result<_>>()?,
+                ),
+                9 => ExifValue::Signed(
+                    (0..count)
+                        .map(|i| self.u32(start + i * 4).map(|v| v as i32))
+                        .collect::<Result<_>>()?,
+                ),
+                5 => ExifValue::Rational(
+                    (0..count)
+                        .map(|i| Ok((self.u32(start + i * 8)?, self.u32(start + i * 8 + 4)?)))
+                        .collect::<Result<_>>()?,
+                ),
+                10 => ExifValue::SignedRational(
+                    (0..count)
+                        .map(|i| {
+                            Ok((
+                                self.u32(start + i * 8)? as i32,
+                                self.u32(start + i * 8 + 4)? as i32,
+                            ))
+                        })
+                        .collect::<Result<_>>()?,
+                ),
+                _ => ExifValue::Undefined(bytes.to_vec()),
+            };
+
+            // Pointers are followed rather than listed, at most one level deep so that a
+            // malicious file cannot loop.
+            match (id, &value) {
+                (EXIF_IFD_POINTER | GPS_IFD_POINTER, ExifValue::Unsigned(pointer)) => {
+                    if let (Some(&pointer), 0) = (pointer.first(), depth) {
+                        self.read_ifd(pointer as usize, tags, depth + 1)?;
+                    }
+                }
+                _ => tags.push(ExifTag { id, value }),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Decodes the tags of the first image directory along with its Exif and GPS directories.
+pub fn parse_exif(data: &[u8]) -> Result<Vec<ExifTag>> {
+    let big_endian = match data.get(..4) {
+        Some(header) if header == LITTLE_ENDIAN_HEADER => false,
+        Some(header) if header == BIG_ENDIAN_HEADER => true,
+        _ => return Err(ExifError::InvalidHeader.into()),
+    };
+    let tiff = Tiff { data, big_endian };
+
+    let mut tags = Vec::new();
+    tiff.read_ifd(tiff.u32(4)? as usize, &mut tags, 0)?;
+    Ok(tags)
+}
+
+impl Png {
+    pub fn exif(&self) -> Option<&[u8]> {
+        self.chunks()
+            .iter()
+            .find(|c| *c.chunk_type() == ChunkType::eXIf)
+            .map(|c| c.chunk_data())
+    }
+
+    pub fn set_exif(&mut self, exif: Vec<u8>) -> Result<()> {
+        if !exif.starts_with(&LITTLE_ENDIAN_HEADER) && !exif.starts_with(&BIG_ENDIAN_HEADER) {
+            return Err(ExifError::InvalidHeader.into());
+        }
+
+        match self
+            .chunks_mut()
+            .iter_mut()
+            .find(|c| *c.chunk_type() == ChunkType::eXIf)
+        {
+            Some(chunk) => chunk.set_data(exif),
+            None => self.insert_chunk_ordered(Chunk::new(ChunkType::eXIf, exif)),
+        }
+
+        Ok(())
+    }
+
+    pub fn remove_exif(&mut self) -> Option<Vec<u8>> {
+        let chunks = self.remove_all_chunks("eXIf").ok()?;
+        chunks.into_iter().next().map(Chunk::into_data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::PngBuilder;
+
+    const EXIF: &[u8] = b"MM\0*\0\0\0\x08\0\0\0\0\0\0";
+
+    fn testing_png() -> Png {
+        PngBuilder::new()
+            .chunk(Chunk::new(ChunkType::IHDR, vec![0; 13]))
+            .chunk(Chunk::new(ChunkType::IDAT, vec![1, 2, 3]))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_set_exif() {
+        let mut png = testing_png();
+        assert!(png.exif().is_none());
+
+        png.set_exif(EXIF.to_vec()).unwrap();
+        assert_eq!(png.exif(), Some(EXIF));
+        assert_eq!(png.chunks()[1].chunk_type(), &ChunkType::eXIf);
+
+        png.set_exif(b"II*\0\x08\0\0\0".to_vec()).unwrap();
+        assert_eq!(png.exif(), Some(&b"II*\0\x08\0\0\0"[..]));
+        assert_eq!(png.chunks().len(), 4);
+    }
+
+    #[test]
+    fn test_set_exif_invalid_header() {
+        let mut png = testing_png();
+        assert_eq!(
+            png.set_exif(b"JFIF".to_vec())
+                .err()
+                .unwrap()
+                .downcast::<ExifError>()
+                .unwrap(),
+            ExifError::InvalidHeader
+        );
+    }
+
+    // Big endian: Make "Canon", Orientation 6, and an Exif directory holding ExposureTime 1/250.
+    fn testing_exif() -> Vec<u8> {
+        let mut exif = b"MM\0*\0\0\0\x08".to_vec();
+        exif.extend([0, 3]);
+        exif.extend([0x01, 0x0F, 0, 2, 0, 0, 0, 6, 0, 0, 0, 50]);
+        exif.extend([0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 6, 0, 0]);
+        exif.extend([0x87, 0x69, 0, 4, 0, 0, 0, 1, 0, 0, 0, 56]);
+        exif.extend([0, 0, 0, 0]);
+        exif.extend(b"Canon\0");
+        exif.extend([0, 1]);
+        exif.extend([0x82, 0x9A, 0, 5, 0, 0, 0, 1, 0, 0, 0, 74]);
+        exif.extend([0, 0, 0, 0]);
+        exif.extend([0, 0, 0, 1, 0, 0, 0, 250]);
+        exif
+    }
+
+    #[test]
+    fn test_parse_exif() {
+        let tags = parse_exif(&testing_exif()).unwrap();
+        let lines: Vec<String> = tags.iter().map(ExifTag::to_string).collect();
+
+        assert_eq!(
+            lines,
+            [
+                "Make: Canon",
+                "Orientation: 6 (rotated 90° clockwise)",
+                "ExposureTime: 1/250"
+            ]
+        );
+        assert_eq!(parse_exif(EXIF).unwrap(), []);
+    }
+
+    #[test]
+    fn test_parse_exif_invalid() {
+        let exif = testing_exif();
+        assert!(parse_exif(&exif[..40]).is_err());
+        assert!(parse_exif(b"JFIF").is_err());
+    }
+
+    #[test]
+    fn test_remove_exif() {
+        let mut png = testing_png();
+        png.set_exif(EXIF.to_vec()).unwrap();
+
+        assert_eq!(png.remove_exif(), Some(EXIF.to_vec()));
+        assert!(png.exif().is_none());
+        assert_eq!(png.remove_exif(), None);
+    }
+}
@@ -0,0 +1,344 @@
+use anyhow::Result;
+use thiserror::Error;
+
+pub const MAGIC: [u8; 4] = *b"PNGf";
+pub const PARITY_LENGTH: usize = 32;
+
+const BLOCK_LENGTH: usize = 255;
+const DATA_LENGTH: usize = BLOCK_LENGTH - PARITY_LENGTH;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum FecError {
+    #[error("Block {block:?} has too many errors to correct.")]
+    TooManyErrors { block: usize },
+    #[error("Data is not protected by error correction.")]
+    InvalidMagic,
+    #[error("Error corrected data is truncated.")]
+    Truncated,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recovered {
+    pub data: Vec<u8>,
+    pub corrected: usize,
+}
+
+// Reed-Solomon over GF(2^8) with 32 parity bytes per 255 byte block, correcting up to 16 byte errors per block.
+// Layout before encoding: magic, data length (u32), data.
+pub fn protect(data: &[u8]) -> Vec<u8> {
+    let mut stream = MAGIC.to_vec();
+    stream.extend((data.len() as u32).to_be_bytes());
+    stream.extend(data);
+
+    let generator = generator_poly(PARITY_LENGTH);
+    stream
+        .chunks(DATA_LENGTH)
+        .flat_map(|block| encode_block(block, &generator))
+        .collect()
+}
+
+pub fn recover(data: &[u8]) -> Result<Recovered> {
+    let mut stream = Vec::with_capacity(data.len());
+    let mut corrected = 0;
+
+    for (index, block) in data.chunks(BLOCK_LENGTH).enumerate() {
+        if block.len() <= PARITY_LENGTH {
+            return Err(FecError::Truncated.into());
+        }
+
+        let (block, errors) =
+            correct_block(block).ok_or(FecError::TooManyErrors { block: index })?;
+        stream.extend_from_slice(&block[..block.len() - PARITY_LENGTH]);
+        corrected += errors;
+    }
+
+    if stream.len() < 8 {
+        return Err(FecError::Truncated.into());
+    }
+    if stream[..4] != MAGIC {
+        return Err(FecError::InvalidMagic.into());
+    }
+
+    let length = u32::from_be_bytes([stream[4], stream[5], stream[6], stream[7]]) as usize;
+    let Some(data) = stream.get(8..8 + length) else {
+        return Err(FecError::Truncated.into());
+    };
+
+    Ok(Recovered {
+        data: data.to_vec(),
+        corrected,
+    })
+}
+
+pub fn is_protected(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC)
+}
+
+const fn tables() -> ([u8; 512], [u8; 256]) {
+    let mut exp = [0u8; 512];
+    let mut log = [0u8; 256];
+    let mut x: u16 = 1;
+    let mut i = 0;
+    while i < 255 {
+        exp[i] = x as u8;
+        log[x as usize] = i as u8;
+        x <<= 1;
+        if x & 0x100 != 0 {
+            x ^= 0x11d;
+        }
+        i += 1;
+    }
+    while i < 512 {
+        exp[i] = exp[i - 255];
+        i += 1;
+    }
+
+    (exp, log)
+}
+
+const EXP: [u8; 512] = tables().0;
+const LOG: [u8; 256] = tables().1;
+
+fn mul(a: u8, b: u8) -> u8 {
+    if a == 0 || b == 0 {
+        return 0;
+    }
+
+    EXP[LOG[a as usize] as usize + LOG[b as usize] as usize]
+}
+
+fn div(a: u8, b: u8) -> u8 {
+    if a == 0 {
+        return 0;
+    }
+
+    EXP[(LOG[a as usize] as usize + 255 - LOG[b as usize] as usize) % 255]
+}
+
+fn pow(x: u8, power: i32) -> u8 {
+    EXP[(LOG[x as usize] as i32 * power).rem_euclid(255) as usize]
+}
+
+fn inverse(x: u8) -> u8 {
+    EXP[255 - LOG[x as usize] as usize]
+}
+
+// Polynomials are stored with the highest degree coefficient first.
+fn poly_scale(p: &[u8], x: u8) -> Vec<u8> {
+    p.iter().map(|&c| mul(c, x)).collect()
+}
+
+fn poly_add(p: &[u8], q: &[u8]) -> Vec<u8> {
+    let length = p.len().max(q.len());
+    let mut sum = vec![0; length];
+    for (i, &c) in p.iter().enumerate() {
+        sum[i + length - p.len()] = c;
+    }
+    for (i, &c) in q.iter().enumerate() {
+        sum[i + length - q.len()] ^= c;
+    }
+
+    sum
+}
+
+fn poly_mul(p: &[u8], q: &[u8]) -> Vec<u8> {
+    let mut product = vec![0; p.len() + q.len() - 1];
+    for (j, &b) in q.iter().enumerate() {
+        for (i, &a) in p.iter().enumerate() {
+            product[i + j] ^= mul(a, b);
+        }
+    }
+
+    product
+}
+
+fn poly_eval(p: &[u8], x: u8) -> u8 {
+    p.iter().fold(0, |y, &c| mul(y, x) ^ c)
+}
+
+fn generator_poly(parity_length: usize) -> Vec<u8> {
+    (0..parity_length).fold(vec![1], |g, i| poly_mul(&g, &[1, pow(2, i as i32)]))
+}
+
+fn encode_block(data: &[u8], generator: &[u8]) -> Vec<u8> {
+    let mut block = data.to_vec();
+    block.resize(data.len() + generator.len() - 1, 0);
+
+    for i in 0..data.len() {
+        let coefficient = block[i];
+        if coefficient != 0 {
+            for (j, &g) in generator.iter().enumerate().skip(1) {
+                block[i + j] ^= mul(g, coefficient);
+            }
+        }
+    }
+
+    block[..data.len()].copy_from_slice(data);
+    block
+}
+
+fn syndromes(block: &[u8]) -> Vec<u8> {
+    (0..PARITY_LENGTH)
+        .map(|i| poly_eval(block, pow(2, i as i32)))
+        .collect()
+}
+
+// Berlekamp-Massey.
+fn error_locator(syndromes: &[u8]) -> Option<Vec<u8>> {
+    let mut locator = vec![1];
+    let mut old_locator = vec![1];
+
+    for (i, &syndrome) in syndromes.iter().enumerate() {
+        let mut delta = syndrome;
+        for j in 1..locator.len() {
+            delta ^= mul(locator[locator.len() - 1 - j], syndromes[i - j]);
+        }
+
+        old_locator.push(0);
+        if delta != 0 {
+            if old_locator.len() > locator.len() {
+                let new_locator = poly_scale(&old_locator, delta);
+                old_locator = poly_scale(&locator, inverse(delta));
+                locator = new_locator;
+            }
+            locator = poly_add(&locator, &poly_scale(&old_locator, delta));
+        }
+    }
+
+    let leading = locator.iter().position(|&c| c != 0)?;
+    let locator = locator[leading..].to_vec();
+    if (locator.len() - 1) * 2 > syndromes.len() {
+        return None;
+    }
+
+    Some(locator)
+}
+
+// Chien search, returns indices into the block.
+fn error_positions(locator: &[u8], length: usize) -> Option<Vec<usize>> {
+    let reversed: Vec<u8> = locator.iter().rev().copied().collect();
+    let positions: Vec<usize> = (0..length)
+        .filter(|&i| poly_eval(&reversed, pow(2, i as i32)) == 0)
+        .map(|i| length - 1 - i)
+        .collect();
+
+    (positions.len() == locator.len() - 1).then_some(positions)
+}
+
+// Forney algorithm.
+fn correct_errata(block: &mut [u8], syndromes: &[u8], positions: &[usize]) {
+    let coefficient_positions: Vec<usize> = positions.iter().map(|p| block.len() - 1 - p).collect();
+
+    let errata_locator = coefficient_positions.iter().fold(vec![1], |locator, &i| {
+        poly_mul(&locator, &poly_add(&[1], &[pow(2, i as i32), 0]))
+    });
+
+    // Error evaluator: syndromes times locator, modulo x^(errors + 1).
+    let reversed_syndromes: Vec<u8> = syndromes.iter().rev().copied().chain([0]).collect();
+    let product = poly_mul(&reversed_syndromes, &errata_locator);
+    let evaluator = &product[product.len() - errata_locator.len()..];
+
+    let locations: Vec<u8> = coefficient_positions
+        .iter()
+        .map(|&p| pow(2, p as i32))
+        .collect();
+
+    for (i, &location) in locations.iter().enumerate() {
+        let location_inverse = inverse(location);
+        let derivative = locations
+            .iter()
+            .enumerate()
+            .filter(|&(j, _)| j != i)
+            .fold(1, |product, (_, &other)| {
+                mul(product, 1 ^ mul(location_inverse, other))
+            });
+
+        let y = mul(location, poly_eval(evaluator, location_inverse));
+        block[positions[i]] ^= div(y, derivative);
+    }
+}
+
+fn correct_block(block: &[u8]) -> Option<(Vec<u8>, usize)> {
+    let mut block = block.to_vec();
+    let syndromes = syndromes(&block);
+    if syndromes.iter().all(|&s| s == 0) {
+        return Some((block, 0));
+    }
+
+    let locator = error_locator(&syndromes)?;
+    let positions = error_positions(&locator, block.len())?;
+    correct_errata(&mut block, &syndromes, &positions);
+
+    self::syndromes(&block)
+        .iter()
+        .all(|&s| s == 0)
+        .then_some((block, positions.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data() -> Vec<u8> {
+        (0..1000).map(|i| (i * 37 % 256) as u8).collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let protected = protect(&data());
+        assert!(is_protected(&protected));
+        assert_eq!(protected.len(), 1008 + 5 * PARITY_LENGTH);
+
+        let recovered = recover(&protected).unwrap();
+        assert_eq!(recovered.data, data());
+        assert_eq!(recovered.corrected, 0);
+    }
+
+    #[test]
+    fn test_corrects_errors() {
+        let mut protected = protect(&data());
+        for i in (0..protected.len()).step_by(17) {
+            protected[i] ^= 0x5a;
+        }
+        protected[0] = 0;
+
+        let recovered = recover(&protected).unwrap();
+        assert_eq!(recovered.data, data());
+        assert_eq!(recovered.corrected, 69);
+    }
+
+    #[test]
+    fn test_corrects_short_block() {
+        let mut protected = protect(b"hi");
+        assert_eq!(protected.len(), 10 + PARITY_LENGTH);
+        for byte in &mut protected[..16] {
+            *byte = !*byte;
+        }
+
+        let recovered = recover(&protected).unwrap();
+        assert_eq!(recovered.data, b"hi");
+        assert_eq!(recovered.corrected, 16);
+    }
+
+    #[test]
+    fn test_too_many_errors() {
+        let mut protected = protect(&data());
+        for byte in &mut protected[255..255 + 17] {
+            *byte = !*byte;
+        }
+
+        assert_eq!(
+            recover(&protected)
+                .err()
+                .unwrap()
+                .downcast::<FecError>()
+                .unwrap(),
+            FecError::TooManyErrors { block: 1 }
+        );
+    }
+
+    #[test]
+    fn test_not_protected() {
+        assert!(recover(b"no parity").is_err());
+    }
+}
@@ -0,0 +1,290 @@
+use crate::{
+    carrier::Carrier, envelope::Envelope, png::Png, stego::StegoError, stego::StegoMethod,
+};
+use anyhow::{Context, Result};
+use serde_json::json;
+use std::{
+    cell::RefCell,
+    ffi::{c_char, CStr, CString},
+    ptr, slice,
+};
+
+// A C ABI for embedding pngme without a subprocess. include/pngme.h is generated from this file by
+// cbindgen when building with the ffi feature. Functions return one of the status codes below,
+// which match the command line's exit statuses, and pngme_last_error describes the last failure.
+
+pub const PNGME_OK: i32 = 0;
+pub const PNGME_ERROR: i32 = 1;
+pub const PNGME_NOT_FOUND: i32 = 2;
+pub const PNGME_INVALID_PNG: i32 = 3;
+
+/// Bytes allocated by pngme. Release them with pngme_free.
+#[repr(C)]
+pub struct PngmeBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl PngmeBuffer {
+    fn empty() -> Self {
+        Self {
+            data: ptr::null_mut(),
+            len: 0,
+        }
+    }
+
+    fn from_vec(bytes: Vec<u8>) -> Self {
+        let bytes = Box::into_raw(bytes.into_boxed_slice());
+        Self {
+            data: bytes as *mut u8,
+            len: bytes.len(),
+        }
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+enum Failure {
+    InvalidPng(anyhow::Error),
+    Other(anyhow::Error),
+}
+
+impl From<anyhow::Error> for Failure {
+    fn from(error: anyhow::Error) -> Self {
+        Self::Other(error)
+    }
+}
+
+// Runs an operation, storing its result in `out` or its error for pngme_last_error.
+fn run(out: *mut PngmeBuffer, operation: impl FnOnce() -> Result<Vec<u8>, Failure>) -> i32 {
+    if out.is_null() {
+        set_last_error("out must not be null".to_string());
+        return PNGME_ERROR;
+    }
+    // SAFETY: out was checked for null and the caller guarantees it is writable.
+    unsafe { out.write(PngmeBuffer::empty()) };
+
+    let (status, error) = match operation() {
+        Ok(bytes) => {
+            // SAFETY: as above.
+            unsafe { out.write(PngmeBuffer::from_vec(bytes)) };
+            return PNGME_OK;
+        }
+        Err(Failure::InvalidPng(error)) => (PNGME_INVALID_PNG, error),
+        Err(Failure::Other(error)) => match error.downcast_ref::<StegoError>() {
+            Some(StegoError::NoPayload) => (PNGME_NOT_FOUND, error),
+            _ => (PNGME_ERROR, error),
+        },
+    };
+    set_last_error(format!("{:#}", error));
+    status
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).expect("nul bytes were replaced");
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// # Safety
+/// `data` must be null or point to `len` readable bytes.
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> Result<&'a [u8]> {
+    match data.is_null() {
+        true if len == 0 => Ok(&[]),
+        true => anyhow::bail!("data must not be null"),
+        false => Ok(slice::from_raw_parts(data, len)),
+    }
+}
+
+/// # Safety
+/// `string` must be null or a nul terminated string.
+unsafe fn string<'a>(string: *const c_char) -> Result<&'a str> {
+    anyhow::ensure!(!string.is_null(), "string must not be null");
+    CStr::from_ptr(string)
+        .to_str()
+        .context("string is not valid UTF-8")
+}
+
+fn parse(png: &[u8]) -> Result<Png, Failure> {
+    Png::try_from(png).map_err(Failure::InvalidPng)
+}
+
+/// Lists the chunks of a PNG as a JSON array of `{"type", "length", "crc"}` objects.
+///
+/// # Safety
+/// `png` must point to `png_len` readable bytes and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_parse(png: *const u8, png_len: usize, out: *mut PngmeBuffer) -> i32 {
+    run(out, || {
+        let png = parse(bytes(png, png_len)?)?;
+        let chunks: Vec<_> = png
+            .chunks()
+            .iter()
+            .map(|chunk| {
+                json!({
+                    "type": chunk.chunk_type().to_string(),
+                    "length": chunk.length(),
+                    "crc": chunk.crc(),
+                })
+            })
+            .collect();
+        Ok(serde_json::to_vec(&chunks).map_err(anyhow::Error::from)?)
+    })
+}
+
+/// Hides `message` in a chunk of `chunk_type`, writing the new PNG to `out`.
+///
+/// # Safety
+/// `png` and `message` must point to their lengths in readable bytes, `chunk_type` must be a nul
+/// terminated string and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_encode(
+    png: *const u8,
+    png_len: usize,
+    chunk_type: *const c_char,
+    message: *const u8,
+    message_len: usize,
+    out: *mut PngmeBuffer,
+) -> i32 {
+    run(out, || {
+        let mut png = parse(bytes(png, png_len)?)?;
+        let envelope = Envelope::new(bytes(message, message_len)?.to_vec());
+        png.embed_envelope(StegoMethod::Chunk, string(chunk_type)?, &envelope)?;
+        Ok(png.as_bytes())
+    })
+}
+
+/// Reveals the payload hidden in a chunk of `chunk_type`, writing it to `out`.
+///
+/// # Safety
+/// `png` must point to `png_len` readable bytes, `chunk_type` must be a nul terminated string and
+/// `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_decode(
+    png: *const u8,
+    png_len: usize,
+    chunk_type: *const c_char,
+    out: *mut PngmeBuffer,
+) -> i32 {
+    run(out, || {
+        let png = parse(bytes(png, png_len)?)?;
+        let envelope = png.extract_envelope(StegoMethod::Chunk, string(chunk_type)?)?;
+        if envelope.encryption.is_some() {
+            return Err(anyhow::anyhow!(
+                "The payload is encrypted, which the C interface does not support"
+            )
+            .into());
+        }
+        Ok(envelope.payload)
+    })
+}
+
+/// Releases a buffer returned by pngme. Freeing an empty buffer does nothing.
+///
+/// # Safety
+/// `buffer` must come from pngme and not have been freed already.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_free(buffer: PngmeBuffer) {
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            buffer.data,
+            buffer.len,
+        )));
+    }
+}
+
+/// Describes the last failure on this thread, or returns null if nothing has failed. The string
+/// stays valid until the next call into pngme on the same thread.
+#[no_mangle]
+pub extern "C" fn pngme_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| match &*last.borrow() {
+        Some(message) => message.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chunk::Chunk, chunk_type::ChunkType};
+
+    fn testing_png() -> Vec<u8> {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::IHDR, vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ])
+        .as_bytes()
+    }
+
+    unsafe fn to_vec(buffer: &PngmeBuffer) -> Vec<u8> {
+        slice::from_raw_parts(buffer.data, buffer.len).to_vec()
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let png = testing_png();
+        let chunk_type = c"ruSt";
+        let message = b"hello";
+        let mut encoded = PngmeBuffer::empty();
+        let mut decoded = PngmeBuffer::empty();
+        unsafe {
+            let status = pngme_encode(
+                png.as_ptr(),
+                png.len(),
+                chunk_type.as_ptr(),
+                message.as_ptr(),
+                message.len(),
+                &mut encoded,
+            );
+            assert_eq!(status, PNGME_OK);
+
+            let status = pngme_decode(encoded.data, encoded.len, chunk_type.as_ptr(), &mut decoded);
+            assert_eq!(status, PNGME_OK);
+            assert_eq!(to_vec(&decoded), message);
+
+            pngme_free(encoded);
+            pngme_free(decoded);
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let png = testing_png();
+        let mut out = PngmeBuffer::empty();
+        unsafe {
+            assert_eq!(pngme_parse(png.as_ptr(), png.len(), &mut out), PNGME_OK);
+            let chunks: serde_json::Value = serde_json::from_slice(&to_vec(&out)).unwrap();
+            assert_eq!(chunks[0]["type"], "IHDR");
+            pngme_free(out);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let png = testing_png();
+        let mut out = PngmeBuffer::empty();
+        unsafe {
+            assert_eq!(
+                pngme_parse(b"GIF89a".as_ptr(), 6, &mut out),
+                PNGME_INVALID_PNG
+            );
+            assert!(out.data.is_null());
+            assert!(!pngme_last_error().is_null());
+
+            let status = pngme_decode(png.as_ptr(), png.len(), c"ruSt".as_ptr(), &mut out);
+            assert_eq!(status, PNGME_NOT_FOUND);
+
+            let status = pngme_decode(png.as_ptr(), png.len(), ptr::null(), &mut out);
+            assert_eq!(status, PNGME_ERROR);
+            let error = CStr::from_ptr(pngme_last_error()).to_str().unwrap();
+            assert_eq!(error, "string must not be null");
+
+            assert_eq!(
+                pngme_parse(png.as_ptr(), png.len(), ptr::null_mut()),
+                PNGME_ERROR
+            );
+            pngme_free(PngmeBuffer::empty());
+        }
+    }
+}
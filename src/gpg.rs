@@ -0,0 +1,278 @@
+use anyhow::Result;
+use std::{
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum GpgError {
+    #[error("Could not find the gpg executable.")]
+    NotFound,
+    #[error("gpg failed: {message}")]
+    Failed { message: String },
+    #[error("Payload does not carry a good GPG signature.")]
+    BadSignature,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verified {
+    pub data: Vec<u8>,
+    pub signer: Option<String>,
+}
+
+// Shells out to GnuPG so existing keyrings, agents and trust settings are used as is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gpg {
+    program: PathBuf,
+    homedir: Option<PathBuf>,
+}
+
+impl Default for Gpg {
+    fn default() -> Self {
+        Self {
+            program: PathBuf::from("gpg"),
+            homedir: None,
+        }
+    }
+}
+
+impl Gpg {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_program(mut self, program: impl Into<PathBuf>) -> Self {
+        self.program = program.into();
+        self
+    }
+
+    pub fn with_homedir(mut self, homedir: impl Into<PathBuf>) -> Self {
+        self.homedir = Some(homedir.into());
+        self
+    }
+
+    pub fn homedir(&self) -> Option<&Path> {
+        self.homedir.as_deref()
+    }
+
+    pub fn encrypt(&self, recipients: &[&str], plaintext: &[u8]) -> Result<Vec<u8>> {
+        let mut args = vec!["--encrypt", "--trust-model", "always"];
+        for recipient in recipients {
+            args.extend(["--recipient", recipient]);
+        }
+
+        Ok(self.run(&args, plaintext)?.0)
+    }
+
+    pub fn sign(&self, key: Option<&str>, data: &[u8]) -> Result<Vec<u8>> {
+        let mut args = vec!["--sign"];
+        if let Some(key) = key {
+            args.extend(["--local-user", key]);
+        }
+
+        Ok(self.run(&args, data)?.0)
+    }
+
+    // Works on both encrypted and signed messages, gpg reports signatures through the status lines.
+    pub fn decrypt(&self, message: &[u8]) -> Result<Verified> {
+        let (data, status) = self.run(&["--decrypt"], message)?;
+
+        Ok(Verified {
+            data,
+            signer: good_signer(&status),
+        })
+    }
+
+    pub fn verify(&self, message: &[u8]) -> Result<Verified> {
+        let verified = self.decrypt(message).map_err(|_| GpgError::BadSignature)?;
+        if verified.signer.is_none() {
+            return Err(GpgError::BadSignature.into());
+        }
+
+        Ok(verified)
+    }
+
+    fn run(&self, args: &[&str], input: &[u8]) -> Result<(Vec<u8>, String)> {
+        let mut command = Command::new(&self.program);
+        if let Some(homedir) = &self.homedir {
+            command.arg("--homedir").arg(homedir);
+        }
+
+        let mut child = command
+            .args([
+                "--batch",
+                "--yes",
+                "--quiet",
+                "--status-fd",
+                "2",
+                "--output",
+                "-",
+            ])
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| match e.kind() {
+                ErrorKind::NotFound => GpgError::NotFound,
+                _ => GpgError::Failed {
+                    message: e.to_string(),
+                },
+            })?;
+
+        // Feed stdin from another thread so a full stdout pipe cannot deadlock us.
+        let mut stdin = child.stdin.take().unwrap();
+        let input = input.to_vec();
+        let writer = thread::spawn(move || stdin.write_all(&input));
+
+        let output = child.wait_with_output()?;
+        let _ = writer.join();
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+
+        if !output.status.success() {
+            let message = stderr
+                .lines()
+                .filter(|line| !line.starts_with("[GNUPG:]"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            return Err(GpgError::Failed { message }.into());
+        }
+
+        Ok((output.stdout, stderr))
+    }
+}
+
+fn good_signer(status: &str) -> Option<String> {
+    if status
+        .lines()
+        .any(|line| line.starts_with("[GNUPG:] BADSIG") || line.starts_with("[GNUPG:] ERRSIG"))
+    {
+        return None;
+    }
+
+    status.lines().find_map(|line| {
+        let rest = line.strip_prefix("[GNUPG:] GOODSIG ")?;
+        let (_, user_id) = rest.split_once(' ')?;
+        Some(user_id.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Keyring(PathBuf);
+
+    impl Keyring {
+        fn new(name: &str) -> Self {
+            let homedir =
+                std::env::temp_dir().join(format!("pngme-gpg-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&homedir);
+            std::fs::create_dir_all(&homedir).unwrap();
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(&homedir, std::fs::Permissions::from_mode(0o700)).unwrap();
+            }
+
+            let status = Command::new("gpg")
+                .arg("--homedir")
+                .arg(&homedir)
+                .args(["--batch", "--quiet", "--passphrase", "", "--quick-gen-key"])
+                .args([
+                    "Ferris <ferris@example.com>",
+                    "future-default",
+                    "default",
+                    "never",
+                ])
+                .stderr(Stdio::null())
+                .status()
+                .unwrap();
+            assert!(status.success());
+
+            Self(homedir)
+        }
+
+        fn gpg(&self) -> Gpg {
+            Gpg::new().with_homedir(&self.0)
+        }
+    }
+
+    impl Drop for Keyring {
+        fn drop(&mut self) {
+            let _ = Command::new("gpgconf")
+                .arg("--homedir")
+                .arg(&self.0)
+                .args(["--kill", "gpg-agent"])
+                .status();
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_encrypt_round_trip() {
+        let keyring = Keyring::new("encrypt");
+        let gpg = keyring.gpg();
+
+        let ciphertext = gpg
+            .encrypt(&["ferris@example.com"], b"secret message")
+            .unwrap();
+        assert!(!ciphertext.windows(6).any(|window| window == b"secret"));
+
+        let decrypted = gpg.decrypt(&ciphertext).unwrap();
+        assert_eq!(decrypted.data, b"secret message");
+        assert_eq!(decrypted.signer, None);
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let keyring = Keyring::new("sign");
+        let gpg = keyring.gpg();
+
+        let signed = gpg.sign(None, b"signed message").unwrap();
+        let verified = gpg.verify(&signed).unwrap();
+        assert_eq!(verified.data, b"signed message");
+        assert_eq!(
+            verified.signer.as_deref(),
+            Some("Ferris <ferris@example.com>")
+        );
+
+        let mut tampered = signed.clone();
+        let last = tampered.len() - 10;
+        tampered[last] ^= 0xff;
+        assert!(gpg.verify(&tampered).is_err());
+        assert!(gpg.verify(b"not signed").is_err());
+    }
+
+    #[test]
+    fn test_missing_program() {
+        assert_eq!(
+            Gpg::new()
+                .with_program("pngme-no-such-gpg")
+                .encrypt(&["ferris@example.com"], b"secret")
+                .err()
+                .unwrap()
+                .downcast::<GpgError>()
+                .unwrap(),
+            GpgError::NotFound
+        );
+    }
+
+    #[test]
+    fn test_good_signer() {
+        let status =
+            "[GNUPG:] NEWSIG\n[GNUPG:] GOODSIG 0123456789ABCDEF Ferris <ferris@example.com>\n";
+        assert_eq!(
+            good_signer(status).as_deref(),
+            Some("Ferris <ferris@example.com>")
+        );
+        assert_eq!(
+            good_signer("[GNUPG:] BADSIG 0123456789ABCDEF Ferris\n"),
+            None
+        );
+        assert_eq!(good_signer(""), None);
+    }
+}
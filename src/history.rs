@@ -0,0 +1,192 @@
+use crate::{ancillary::TypedChunk, chunk_type::ChunkType, png::Png, time::Time};
+use anyhow::Result;
+use std::{
+    fmt::Display,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use thiserror::Error;
+
+// Who changed the file and when, kept in a private chunk that is safe to copy so editors carry it
+// along. One line per change, oldest first, with tab separated fields:
+//
+//     unix timestamp, pngme version, operation, signer
+//
+// The signer is empty when none was given.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum HistoryError {
+    #[error("History entry {line:?} is malformed.")]
+    InvalidEntry { line: usize },
+    #[error("History {field} must not contain tabs or line breaks.")]
+    InvalidField { field: &'static str },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    pub timestamp: u64,
+    pub version: String,
+    pub operation: String,
+    pub signer: Option<String>,
+}
+
+impl HistoryEntry {
+    // An entry for a change made now by this version of pngme.
+    pub fn new(operation: &str, signer: Option<&str>) -> Result<Self> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let entry = Self {
+            timestamp,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            operation: operation.to_string(),
+            signer: signer.map(str::to_string),
+        };
+        entry.validate()?;
+        Ok(entry)
+    }
+
+    pub fn time(&self) -> Time {
+        Time::from_unix(self.timestamp)
+    }
+
+    fn validate(&self) -> Result<()> {
+        let fields = [
+            ("version", Some(&self.version)),
+            ("operation", Some(&self.operation)),
+            ("signer", self.signer.as_ref()),
+        ];
+        for (field, value) in fields {
+            if value.is_some_and(|value| value.contains(['\t', '\n', '\r'])) {
+                return Err(HistoryError::InvalidField { field }.into());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for HistoryEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} pngme {} {}",
+            self.time(),
+            self.version,
+            self.operation
+        )?;
+        if let Some(signer) = &self.signer {
+            write!(f, " by {}", signer)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct History {
+    pub entries: Vec<HistoryEntry>,
+}
+
+impl TypedChunk for History {
+    const CHUNK_TYPE: ChunkType = ChunkType::from_bytes_const(*b"hiSt");
+
+    fn from_data(data: &[u8]) -> Result<Self> {
+        let text = std::str::from_utf8(data).map_err(|_| HistoryError::InvalidEntry { line: 0 })?;
+        let entries = text
+            .lines()
+            .enumerate()
+            .map(|(line, entry)| {
+                let invalid = || HistoryError::InvalidEntry { line };
+                let [timestamp, version, operation, signer] = entry
+                    .split('\t')
+                    .collect::<Vec<_>>()
+                    .try_into()
+                    .map_err(|_| invalid())?;
+                Ok(HistoryEntry {
+                    timestamp: timestamp.parse().map_err(|_| invalid())?,
+                    version: version.to_string(),
+                    operation: operation.to_string(),
+                    signer: (!signer.is_empty()).then(|| signer.to_string()),
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { entries })
+    }
+
+    fn to_data(&self) -> Vec<u8> {
+        let mut data = String::new();
+        for entry in &self.entries {
+            data.push_str(&format!(
+                "{}\t{}\t{}\t{}\n",
+                entry.timestamp,
+                entry.version,
+                entry.operation,
+                entry.signer.as_deref().unwrap_or_default()
+            ));
+        }
+        data.into_bytes()
+    }
+}
+
+impl Png {
+    pub fn history(&self) -> Result<Option<History>> {
+        self.typed_chunk()
+    }
+
+    // Adds the entry to the history chunk, starting one if the file has none.
+    pub fn record_history(&mut self, entry: HistoryEntry) -> Result<()> {
+        entry.validate()?;
+        let mut history = self.history()?.unwrap_or_default();
+        history.entries.push(entry);
+        self.set_typed_chunk(&history);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{builder::PngBuilder, chunk::Chunk};
+
+    fn testing_png() -> Png {
+        PngBuilder::new()
+            .chunk(Chunk::new(ChunkType::IHDR, vec![0; 13]))
+            .chunk(Chunk::new(ChunkType::IDAT, vec![1, 2, 3]))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_record_history() {
+        let mut png = testing_png();
+        assert_eq!(png.history().unwrap(), None);
+
+        png.record_history(HistoryEntry::new("encode", None).unwrap())
+            .unwrap();
+        png.record_history(HistoryEntry::new("strip", Some("alice")).unwrap())
+            .unwrap();
+        let png = Png::try_from(png.as_bytes().as_slice()).unwrap();
+        let entries = png.history().unwrap().unwrap().entries;
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].operation, "encode");
+        assert_eq!(entries[0].signer, None);
+        assert_eq!(entries[1].signer.as_deref(), Some("alice"));
+        assert_eq!(entries[1].version, env!("CARGO_PKG_VERSION"));
+        assert!(entries[1].to_string().ends_with(" strip by alice"));
+        let chunk_type = History::CHUNK_TYPE;
+        assert!(!chunk_type.is_public() && chunk_type.is_safe_to_copy());
+    }
+
+    #[test]
+    fn test_invalid_history() {
+        assert!(HistoryEntry::new("text\tset", None).is_err());
+        assert!(HistoryEntry::new("encode", Some("alice\nbob")).is_err());
+        assert_eq!(
+            History::from_data(b"1\t0.1.0\tencode\t\nbad\n")
+                .unwrap_err()
+                .downcast::<HistoryError>()
+                .unwrap(),
+            HistoryError::InvalidEntry { line: 1 }
+        );
+    }
+}
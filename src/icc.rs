@@ -0,0 +1,196 @@
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    compression::{deflate, inflate},
+    png::Png,
+    text::{decode_latin1, encode_latin1, validate_keyword},
+};
+use anyhow::{Error, Result};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum IccError {
+    #[error("iCCP chunk is missing the profile name separator.")]
+    MissingSeparator,
+    #[error("Unknown compression method {found:?}.")]
+    InvalidCompressionMethod { found: u8 },
+    #[error("Expected an iCCP chunk, got {found:?}.")]
+    WrongChunkType { found: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IccProfile {
+    name: String,
+    profile: Vec<u8>,
+}
+
+impl TryFrom<&Chunk> for IccProfile {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        if *chunk.chunk_type() != ChunkType::iCCP {
+            return Err(IccError::WrongChunkType {
+                found: chunk.chunk_type().to_string(),
+            }
+            .into());
+        }
+
+        let data = chunk.chunk_data();
+        let Some(separator) = data.iter().position(|&b| b == 0) else {
+            return Err(IccError::MissingSeparator.into());
+        };
+
+        let name = decode_latin1(&data[..separator]);
+        validate_keyword(&name)?;
+
+        match data.get(separator + 1) {
+            Some(0) => Ok(Self {
+                name,
+                profile: inflate(&data[separator + 2..])?,
+            }),
+            Some(&found) => Err(IccError::InvalidCompressionMethod { found }.into()),
+            None => Err(IccError::MissingSeparator.into()),
+        }
+    }
+}
+
+impl From<IccProfile> for Chunk {
+    fn from(profile: IccProfile) -> Self {
+        let mut data = encode_latin1(&profile.name);
+        data.extend([0, 0]);
+        data.extend(deflate(&profile.profile));
+
+        Chunk::new(ChunkType::iCCP, data)
+    }
+}
+
+impl IccProfile {
+    pub fn new(name: &str, profile: Vec<u8>) -> Result<Self> {
+        validate_keyword(name)?;
+
+        Ok(Self {
+            name: name.to_string(),
+            profile,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn profile(&self) -> &[u8] {
+        &self.profile
+    }
+
+    pub fn into_profile(self) -> Vec<u8> {
+        self.profile
+    }
+}
+
+impl Png {
+    pub fn icc_profile(&self) -> Result<Option<IccProfile>> {
+        self.chunks()
+            .iter()
+            .find(|c| *c.chunk_type() == ChunkType::iCCP)
+            .map(IccProfile::try_from)
+            .transpose()
+    }
+
+    pub fn set_icc_profile(&mut self, name: &str, profile: Vec<u8>) -> Result<()> {
+        let chunk = Chunk::from(IccProfile::new(name, profile)?);
+
+        // iCCP and sRGB are mutually exclusive.
+        let _ = self.remove_all_chunks("sRGB");
+
+        match self
+            .chunks_mut()
+            .iter_mut()
+            .find(|c| *c.chunk_type() == ChunkType::iCCP)
+        {
+            Some(existing) => *existing = chunk,
+            None => self.insert_chunk_ordered(chunk),
+        }
+
+        Ok(())
+    }
+
+    pub fn remove_icc_profile(&mut self) -> Result<Option<IccProfile>> {
+        let Ok(chunks) = self.remove_all_chunks("iCCP") else {
+            return Ok(None);
+        };
+
+        chunks.first().map(IccProfile::try_from).transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::PngBuilder;
+
+    fn testing_png() -> Png {
+        PngBuilder::new()
+            .chunk(Chunk::new(ChunkType::IHDR, vec![0; 13]))
+            .chunk(Chunk::new(ChunkType::sRGB, vec![0]))
+            .chunk(Chunk::new(ChunkType::IDAT, vec![1, 2, 3]))
+            .build()
+            .unwrap()
+    }
+
+    fn profile() -> Vec<u8> {
+        (0..2048).map(|i| (i % 7) as u8).collect()
+    }
+
+    #[test]
+    fn test_icc_profile_round_trip() {
+        let profile = IccProfile::new("Display P3", profile()).unwrap();
+        let chunk = Chunk::from(profile.clone());
+
+        assert_eq!(&chunk.chunk_data()[..12], b"Display P3\0\0");
+        assert!(chunk.length() < 2048);
+        assert_eq!(IccProfile::try_from(&chunk).unwrap(), profile);
+    }
+
+    #[test]
+    fn test_icc_profile_invalid_method() {
+        let chunk = Chunk::new(ChunkType::iCCP, b"Name\0\x05data".to_vec());
+        assert_eq!(
+            IccProfile::try_from(&chunk)
+                .err()
+                .unwrap()
+                .downcast::<IccError>()
+                .unwrap(),
+            IccError::InvalidCompressionMethod { found: 5 }
+        );
+    }
+
+    #[test]
+    fn test_set_icc_profile() {
+        let mut png = testing_png();
+        assert!(png.icc_profile().unwrap().is_none());
+
+        png.set_icc_profile("Display P3", profile()).unwrap();
+        let stored = png.icc_profile().unwrap().unwrap();
+        assert_eq!(stored.name(), "Display P3");
+        assert_eq!(stored.profile(), profile());
+
+        assert!(png.chunk_by_type("sRGB").is_none());
+        assert_eq!(png.chunks()[1].chunk_type(), &ChunkType::iCCP);
+
+        png.set_icc_profile("sRGB IEC61966-2.1", vec![1, 2, 3])
+            .unwrap();
+        assert_eq!(png.chunks().len(), 4);
+        assert_eq!(png.icc_profile().unwrap().unwrap().profile(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_remove_icc_profile() {
+        let mut png = testing_png();
+        png.set_icc_profile("Display P3", profile()).unwrap();
+
+        let removed = png.remove_icc_profile().unwrap().unwrap();
+        assert_eq!(removed.into_profile(), profile());
+        assert!(png.icc_profile().unwrap().is_none());
+        assert!(png.remove_icc_profile().unwrap().is_none());
+    }
+}
@@ -0,0 +1,261 @@
+use crate::{chunk::Chunk, chunk_type::ChunkType};
+use anyhow::{Error, Result};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum IhdrError {
+    #[error("Expected IHDR data of 13 bytes, got {found:?} bytes.")]
+    InvalidLength { found: usize },
+    #[error("Expected an IHDR chunk, got {found:?}.")]
+    WrongChunkType { found: String },
+    #[error("Image dimensions have to be non-zero, got {width:?}x{height:?}.")]
+    InvalidDimensions { width: u32, height: u32 },
+    #[error("Unknown color type {found:?}.")]
+    InvalidColorType { found: u8 },
+    #[error("Bit depth {bit_depth:?} is not allowed for color type {color_type:?}.")]
+    InvalidBitDepth { bit_depth: u8, color_type: u8 },
+    #[error("Unknown {field} method {found:?}.")]
+    InvalidMethod { field: &'static str, found: u8 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorType {
+    Grayscale = 0,
+    Rgb = 2,
+    Indexed = 3,
+    GrayscaleAlpha = 4,
+    Rgba = 6,
+}
+
+impl TryFrom<u8> for ColorType {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(Self::Grayscale),
+            2 => Ok(Self::Rgb),
+            3 => Ok(Self::Indexed),
+            4 => Ok(Self::GrayscaleAlpha),
+            6 => Ok(Self::Rgba),
+            found => Err(IhdrError::InvalidColorType { found }.into()),
+        }
+    }
+}
+
+impl ColorType {
+    pub fn channels(&self) -> u8 {
+        match self {
+            Self::Grayscale | Self::Indexed => 1,
+            Self::GrayscaleAlpha => 2,
+            Self::Rgb => 3,
+            Self::Rgba => 4,
+        }
+    }
+
+    pub fn has_alpha(&self) -> bool {
+        matches!(self, Self::GrayscaleAlpha | Self::Rgba)
+    }
+
+    fn allowed_bit_depths(&self) -> &'static [u8] {
+        match self {
+            Self::Grayscale => &[1, 2, 4, 8, 16],
+            Self::Indexed => &[1, 2, 4, 8],
+            Self::Rgb | Self::GrayscaleAlpha | Self::Rgba => &[8, 16],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ihdr {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: ColorType,
+    pub compression_method: u8,
+    pub filter_method: u8,
+    pub interlace_method: u8,
+}
+
+impl TryFrom<&[u8]> for Ihdr {
+    type Error = Error;
+
+    fn try_from(value: &[u8]) -> Result<Self> {
+        let Ok(bytes) = <[u8; 13]>::try_from(value) else {
+            return Err(IhdrError::InvalidLength { found: value.len() }.into());
+        };
+
+        let width = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let height = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        let ihdr = Self {
+            width,
+            height,
+            bit_depth: bytes[8],
+            color_type: ColorType::try_from(bytes[9])?,
+            compression_method: bytes[10],
+            filter_method: bytes[11],
+            interlace_method: bytes[12],
+        };
+        ihdr.validate()?;
+
+        Ok(ihdr)
+    }
+}
+
+impl TryFrom<&Chunk> for Ihdr {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        if *chunk.chunk_type() != ChunkType::IHDR {
+            return Err(IhdrError::WrongChunkType {
+                found: chunk.chunk_type().to_string(),
+            }
+            .into());
+        }
+
+        Self::try_from(chunk.chunk_data())
+    }
+}
+
+impl From<Ihdr> for Chunk {
+    fn from(ihdr: Ihdr) -> Self {
+        Chunk::new(ChunkType::IHDR, ihdr.as_bytes().to_vec())
+    }
+}
+
+impl Ihdr {
+    pub fn new(width: u32, height: u32, bit_depth: u8, color_type: ColorType) -> Result<Self> {
+        let ihdr = Self {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: 0,
+        };
+        ihdr.validate()?;
+
+        Ok(ihdr)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.width == 0 || self.height == 0 {
+            return Err(IhdrError::InvalidDimensions {
+                width: self.width,
+                height: self.height,
+            }
+            .into());
+        }
+
+        if !self
+            .color_type
+            .allowed_bit_depths()
+            .contains(&self.bit_depth)
+        {
+            return Err(IhdrError::InvalidBitDepth {
+                bit_depth: self.bit_depth,
+                color_type: self.color_type as u8,
+            }
+            .into());
+        }
+
+        for (field, found, max) in [
+            ("compression", self.compression_method, 0),
+            ("filter", self.filter_method, 0),
+            ("interlace", self.interlace_method, 1),
+        ] {
+            if found > max {
+                return Err(IhdrError::InvalidMethod { field, found }.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn is_interlaced(&self) -> bool {
+        self.interlace_method == 1
+    }
+
+    pub fn as_bytes(&self) -> [u8; 13] {
+        let mut bytes = [0u8; 13];
+        bytes[0..4].copy_from_slice(&self.width.to_be_bytes());
+        bytes[4..8].copy_from_slice(&self.height.to_be_bytes());
+        bytes[8] = self.bit_depth;
+        bytes[9] = self.color_type as u8;
+        bytes[10] = self.compression_method;
+        bytes[11] = self.filter_method;
+        bytes[12] = self.interlace_method;
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ihdr_round_trip() {
+        let ihdr = Ihdr::new(50, 40, 8, ColorType::Rgba).unwrap();
+        let chunk = Chunk::from(ihdr);
+
+        assert_eq!(chunk.chunk_type(), &ChunkType::IHDR);
+        assert_eq!(chunk.length(), 13);
+        assert_eq!(Ihdr::try_from(&chunk).unwrap(), ihdr);
+    }
+
+    #[test]
+    fn test_ihdr_invalid_length() {
+        let ihdr = Ihdr::try_from(&[0u8; 12][..]);
+        assert_eq!(
+            ihdr.err().unwrap().downcast::<IhdrError>().unwrap(),
+            IhdrError::InvalidLength { found: 12 }
+        );
+    }
+
+    #[test]
+    fn test_ihdr_invalid_bit_depth() {
+        let ihdr = Ihdr::new(1, 1, 4, ColorType::Rgb);
+        assert_eq!(
+            ihdr.err().unwrap().downcast::<IhdrError>().unwrap(),
+            IhdrError::InvalidBitDepth {
+                bit_depth: 4,
+                color_type: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_ihdr_invalid_dimensions() {
+        assert!(Ihdr::new(0, 10, 8, ColorType::Grayscale).is_err());
+    }
+
+    #[test]
+    fn test_ihdr_invalid_color_type() {
+        let mut bytes = Ihdr::new(1, 1, 8, ColorType::Rgb).unwrap().as_bytes();
+        bytes[9] = 5;
+        assert_eq!(
+            Ihdr::try_from(&bytes[..])
+                .err()
+                .unwrap()
+                .downcast::<IhdrError>()
+                .unwrap(),
+            IhdrError::InvalidColorType { found: 5 }
+        );
+    }
+
+    #[test]
+    fn test_ihdr_wrong_chunk_type() {
+        let chunk = Chunk::new(ChunkType::IDAT, vec![0; 13]);
+        assert!(Ihdr::try_from(&chunk).is_err());
+    }
+
+    #[test]
+    fn test_color_type_channels() {
+        assert_eq!(ColorType::Grayscale.channels(), 1);
+        assert_eq!(ColorType::GrayscaleAlpha.channels(), 2);
+        assert_eq!(ColorType::Rgb.channels(), 3);
+        assert_eq!(ColorType::Rgba.channels(), 4);
+        assert!(ColorType::Rgba.has_alpha());
+        assert!(!ColorType::Indexed.has_alpha());
+    }
+}
@@ -0,0 +1,606 @@
+use crate::{chunk::Chunk, chunk_type::ChunkType, compression::deflate, ihdr::Ihdr, png::Png};
+use anyhow::Result;
+use flate2::read::ZlibDecoder;
+use std::io::Read;
+use thiserror::Error;
+
+const DEFAULT_IDAT_LENGTH: usize = 8192;
+
+// Adam7 passes as x offset, y offset, x step and y step.
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ImageDataError {
+    #[error("IDAT chunks have to hold at least one byte each.")]
+    ZeroChunkLength,
+    #[error("Unknown filter type {found:?}.")]
+    InvalidFilterType { found: u8 },
+    #[error("Expected {expected:?} bytes of image data, got {found:?}.")]
+    InvalidDataLength { expected: usize, found: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterType {
+    None = 0,
+    Sub = 1,
+    Up = 2,
+    Average = 3,
+    Paeth = 4,
+}
+
+impl TryFrom<u8> for FilterType {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(Self::None),
+            1 => Ok(Self::Sub),
+            2 => Ok(Self::Up),
+            3 => Ok(Self::Average),
+            4 => Ok(Self::Paeth),
+            found => Err(ImageDataError::InvalidFilterType { found }.into()),
+        }
+    }
+}
+
+impl FilterType {
+    const ALL: [FilterType; 5] = [Self::None, Self::Sub, Self::Up, Self::Average, Self::Paeth];
+
+    fn predict(&self, left: u8, up: u8, up_left: u8) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Sub => left,
+            Self::Up => up,
+            Self::Average => ((left as u16 + up as u16) / 2) as u8,
+            Self::Paeth => paeth(left, up, up_left),
+        }
+    }
+
+    fn unfilter(&self, row: &mut [u8], previous: &[u8], bpp: usize) {
+        for i in 0..row.len() {
+            let left = if i >= bpp { row[i - bpp] } else { 0 };
+            let up_left = if i >= bpp { previous[i - bpp] } else { 0 };
+            row[i] = row[i].wrapping_add(self.predict(left, previous[i], up_left));
+        }
+    }
+
+    fn filter(&self, row: &[u8], previous: &[u8], bpp: usize, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+        for i in 0..row.len() {
+            let left = if i >= bpp { row[i - bpp] } else { 0 };
+            let up_left = if i >= bpp { previous[i - bpp] } else { 0 };
+            out.push(row[i].wrapping_sub(self.predict(left, previous[i], up_left)));
+        }
+    }
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let p = left as i16 + up as i16 - up_left as i16;
+    let pa = (p - left as i16).abs();
+    let pb = (p - up as i16).abs();
+    let pc = (p - up_left as i16).abs();
+
+    if pa <= pb && pa <= pc {
+        left
+    } else if pb <= pc {
+        up
+    } else {
+        up_left
+    }
+}
+
+// Pixels are always kept in plain row order. Interlaced images are split into their Adam7 passes
+// only when encoded, so they keep their interlacing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageData {
+    header: Ihdr,
+    pixels: Vec<u8>,
+}
+
+impl ImageData {
+    pub fn new(header: Ihdr, pixels: Vec<u8>) -> Result<Self> {
+        header.validate()?;
+
+        let expected = stride(&header) * header.height as usize;
+        if pixels.len() != expected {
+            return Err(ImageDataError::InvalidDataLength {
+                expected,
+                found: pixels.len(),
+            }
+            .into());
+        }
+
+        Ok(Self { header, pixels })
+    }
+
+    // How many bytes the image data of a header inflates to, filter bytes included.
+    pub fn inflated_length(header: &Ihdr) -> usize {
+        passes(header)
+            .map(|(pass, _)| (stride(&pass) + 1) * pass.height as usize)
+            .sum()
+    }
+
+    // Inflates no more than the header describes, however far the stream would expand.
+    pub fn decode(header: Ihdr, compressed: &[u8]) -> Result<Self> {
+        let expected = Self::inflated_length(&header);
+        let mut filtered = Vec::new();
+        ZlibDecoder::new(compressed)
+            .take(expected as u64 + 1)
+            .read_to_end(&mut filtered)?;
+        if filtered.len() < expected {
+            return Err(ImageDataError::InvalidDataLength {
+                expected,
+                found: filtered.len(),
+            }
+            .into());
+        }
+
+        if !header.is_interlaced() {
+            return Self::new(header, unfilter(&header, &filtered)?);
+        }
+
+        let stride = stride(&header);
+        let bits = bits_per_pixel(&header);
+        let mut pixels = vec![0; stride * header.height as usize];
+        let mut offset = 0;
+        for (pass, (x0, y0, dx, dy)) in passes(&header) {
+            let length = (self::stride(&pass) + 1) * pass.height as usize;
+            let pass_pixels = unfilter(&pass, &filtered[offset..offset + length])?;
+            offset += length;
+
+            let pass_rows = pass_pixels.chunks_exact(self::stride(&pass));
+            for (row, y) in pass_rows.zip((y0..).step_by(dy)) {
+                let target = &mut pixels[y * stride..(y + 1) * stride];
+                for (x, to_x) in (0..pass.width as usize).zip((x0..).step_by(dx)) {
+                    copy_pixel(bits, row, x, target, to_x);
+                }
+            }
+        }
+
+        Self::new(header, pixels)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        if !self.header.is_interlaced() {
+            return deflate(&filter(&self.header, &self.pixels));
+        }
+
+        let bits = bits_per_pixel(&self.header);
+        let mut filtered = Vec::new();
+        for (pass, (x0, y0, dx, dy)) in passes(&self.header) {
+            let pass_stride = stride(&pass);
+            let mut pass_pixels = vec![0; pass_stride * pass.height as usize];
+            let rows = pass_pixels.chunks_exact_mut(pass_stride);
+            for (row, y) in rows.zip((y0..).step_by(dy)) {
+                let source = self.row(y).expect("passes stay inside the image");
+                for (x, from_x) in (0..pass.width as usize).zip((x0..).step_by(dx)) {
+                    copy_pixel(bits, source, from_x, row, x);
+                }
+            }
+            filtered.extend(filter(&pass, &pass_pixels));
+        }
+
+        deflate(&filtered)
+    }
+
+    // The same pixels without interlacing, which usually compresses better.
+    pub fn deinterlaced(mut self) -> Self {
+        self.header.interlace_method = 0;
+        self
+    }
+
+    pub fn header(&self) -> &Ihdr {
+        &self.header
+    }
+
+    pub fn stride(&self) -> usize {
+        stride(&self.header)
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [u8] {
+        &mut self.pixels
+    }
+
+    pub fn into_pixels(self) -> Vec<u8> {
+        self.pixels
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        self.pixels.chunks_exact(self.stride())
+    }
+
+    pub fn row(&self, y: usize) -> Option<&[u8]> {
+        let stride = self.stride();
+        self.pixels.get(y * stride..(y + 1) * stride)
+    }
+
+    pub fn row_mut(&mut self, y: usize) -> Option<&mut [u8]> {
+        let stride = self.stride();
+        self.pixels.get_mut(y * stride..(y + 1) * stride)
+    }
+}
+
+fn unfilter(header: &Ihdr, filtered: &[u8]) -> Result<Vec<u8>> {
+    let stride = stride(header);
+    let height = header.height as usize;
+    let bpp = bytes_per_pixel(header);
+    let mut pixels = vec![0; stride * height];
+    let mut previous = vec![0; stride];
+    for (y, line) in filtered.chunks_exact(stride + 1).take(height).enumerate() {
+        let filter_type = FilterType::try_from(line[0])?;
+        let row = &mut pixels[y * stride..(y + 1) * stride];
+        row.copy_from_slice(&line[1..]);
+        filter_type.unfilter(row, &previous, bpp);
+        previous.copy_from_slice(row);
+    }
+
+    Ok(pixels)
+}
+
+fn filter(header: &Ihdr, pixels: &[u8]) -> Vec<u8> {
+    let stride = stride(header);
+    let bpp = bytes_per_pixel(header);
+    let mut filtered = Vec::with_capacity((stride + 1) * header.height as usize);
+    let mut previous = vec![0; stride];
+    let mut candidate = Vec::with_capacity(stride + 1);
+    let mut best = Vec::with_capacity(stride + 1);
+
+    // Pick the filter with the smallest sum of absolute differences per row.
+    for row in pixels.chunks_exact(stride) {
+        let mut best_score = u64::MAX;
+        for filter_type in FilterType::ALL {
+            candidate.clear();
+            filter_type.filter(row, &previous, bpp, &mut candidate);
+            let score = candidate[1..]
+                .iter()
+                .map(|&b| (b as i8).unsigned_abs() as u64)
+                .sum();
+            if score < best_score {
+                best_score = score;
+                std::mem::swap(&mut best, &mut candidate);
+            }
+        }
+
+        filtered.extend_from_slice(&best);
+        previous.copy_from_slice(row);
+    }
+
+    filtered
+}
+
+// The headers of the reduced images stored one after the other, with where their pixels sit in
+// the full image. A plain image is a single pass covering everything, and Adam7 passes that hold
+// no pixels are left out as they are not stored at all.
+fn passes(header: &Ihdr) -> impl Iterator<Item = (Ihdr, (usize, usize, usize, usize))> + '_ {
+    let adam7: &[_] = match header.is_interlaced() {
+        true => &ADAM7,
+        false => &[(0, 0, 1, 1)],
+    };
+    adam7.iter().filter_map(|&(x0, y0, dx, dy)| {
+        let width = (header.width as usize).saturating_sub(x0).div_ceil(dx);
+        let height = (header.height as usize).saturating_sub(y0).div_ceil(dy);
+        let pass = Ihdr {
+            width: width as u32,
+            height: height as u32,
+            interlace_method: 0,
+            ..*header
+        };
+        (width > 0 && height > 0).then_some((pass, (x0, y0, dx, dy)))
+    })
+}
+
+// Copies pixel `from_x` of one row to pixel `to_x` of another, for any bit depth.
+fn copy_pixel(bits: usize, from: &[u8], from_x: usize, to: &mut [u8], to_x: usize) {
+    if bits >= 8 {
+        let bytes = bits / 8;
+        to[to_x * bytes..(to_x + 1) * bytes]
+            .copy_from_slice(&from[from_x * bytes..(from_x + 1) * bytes]);
+        return;
+    }
+
+    // Sub-byte pixels are packed from the most significant bit.
+    let mask = (1u8 << bits) - 1;
+    let shift = |x: usize| 8 - bits - x * bits % 8;
+    let value = from[from_x * bits / 8] >> shift(from_x) & mask;
+    let byte = &mut to[to_x * bits / 8];
+    *byte = *byte & !(mask << shift(to_x)) | value << shift(to_x);
+}
+
+fn bits_per_pixel(header: &Ihdr) -> usize {
+    header.color_type.channels() as usize * header.bit_depth as usize
+}
+
+fn stride(header: &Ihdr) -> usize {
+    (header.width as usize * bits_per_pixel(header)).div_ceil(8)
+}
+
+// Filters work on whole bytes, so sub-byte pixels count as one byte.
+fn bytes_per_pixel(header: &Ihdr) -> usize {
+    bits_per_pixel(header).div_ceil(8).max(1)
+}
+
+impl Png {
+    pub fn image_data(&self) -> Result<ImageData> {
+        ImageData::decode(self.header()?, &self.idat_data())
+    }
+
+    pub fn set_image_data(&mut self, image_data: &ImageData) -> Result<()> {
+        self.set_header(*image_data.header())?;
+
+        let max_len = self
+            .chunks()
+            .iter()
+            .filter(|c| *c.chunk_type() == ChunkType::IDAT)
+            .map(|c| c.chunk_data().len())
+            .max()
+            .filter(|&len| len > 0)
+            .unwrap_or(DEFAULT_IDAT_LENGTH);
+
+        if !self
+            .chunks()
+            .iter()
+            .any(|c| *c.chunk_type() == ChunkType::IDAT)
+        {
+            self.insert_chunk_ordered(Chunk::new(ChunkType::IDAT, vec![]));
+        }
+
+        self.rechunk_idat(image_data.encode(), max_len);
+        Ok(())
+    }
+
+    pub fn idat_data(&self) -> Vec<u8> {
+        self.chunks()
+            .iter()
+            .filter(|c| *c.chunk_type() == ChunkType::IDAT)
+            .flat_map(|c| c.chunk_data().iter().copied())
+            .collect()
+    }
+
+    pub fn consolidate_idat(&mut self) {
+        self.rechunk_idat(self.idat_data(), usize::MAX)
+    }
+
+    pub fn split_idat(&mut self, max_len: usize) -> Result<()> {
+        if max_len == 0 {
+            return Err(ImageDataError::ZeroChunkLength.into());
+        }
+
+        self.rechunk_idat(self.idat_data(), max_len);
+        Ok(())
+    }
+
+    // Replaces every IDAT chunk with `data` split into consecutive chunks at the position of the first one.
+    pub(crate) fn rechunk_idat(&mut self, data: Vec<u8>, max_len: usize) {
+        let Some(first) = self
+            .chunks()
+            .iter()
+            .position(|c| *c.chunk_type() == ChunkType::IDAT)
+        else {
+            return;
+        };
+
+        let mut chunks: Vec<Chunk> = self
+            .chunks()
+            .iter()
+            .filter(|c| *c.chunk_type() != ChunkType::IDAT)
+            .cloned()
+            .collect();
+        let idat = data
+            .chunks(max_len)
+            .map(|part| Chunk::new(ChunkType::IDAT, part.to_vec()));
+        chunks.splice(first..first, idat);
+
+        self.replace_chunks(chunks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{builder::PngBuilder, compression::inflate, ihdr::ColorType};
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::IHDR, vec![0; 13]),
+            Chunk::new(ChunkType::IDAT, vec![1, 2]),
+            Chunk::new(ChunkType::IDAT, vec![3]),
+            Chunk::new(ChunkType::from_bytes_const(*b"ruSt"), vec![]),
+            Chunk::new(ChunkType::IDAT, vec![4, 5, 6, 7]),
+            Chunk::new(ChunkType::IEND, vec![]),
+        ])
+    }
+
+    fn chunk_types(png: &Png) -> Vec<String> {
+        png.chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_consolidate_idat() {
+        let mut png = testing_png();
+        png.consolidate_idat();
+
+        assert_eq!(chunk_types(&png), vec!["IHDR", "IDAT", "ruSt", "IEND"]);
+        assert_eq!(png.chunks()[1].chunk_data(), [1, 2, 3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn test_split_idat() {
+        let mut png = testing_png();
+        png.split_idat(3).unwrap();
+
+        assert_eq!(
+            chunk_types(&png),
+            vec!["IHDR", "IDAT", "IDAT", "IDAT", "ruSt", "IEND"]
+        );
+        assert_eq!(png.chunks()[3].chunk_data(), [7]);
+        assert_eq!(png.idat_data(), [1, 2, 3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn test_split_idat_zero_length() {
+        assert_eq!(
+            testing_png()
+                .split_idat(0)
+                .err()
+                .unwrap()
+                .downcast::<ImageDataError>()
+                .unwrap(),
+            ImageDataError::ZeroChunkLength
+        );
+    }
+
+    #[test]
+    fn test_without_idat() {
+        let mut png = Png::from_chunks(vec![Chunk::new(ChunkType::IEND, vec![])]);
+        png.consolidate_idat();
+        assert_eq!(chunk_types(&png), vec!["IEND"]);
+    }
+
+    fn gradient(header: Ihdr) -> ImageData {
+        let length = stride(&header) * header.height as usize;
+        let pixels = (0..length).map(|i| (i * 7 % 251) as u8).collect();
+        ImageData::new(header, pixels).unwrap()
+    }
+
+    #[test]
+    fn test_filters_round_trip() {
+        let previous = [10, 200, 30, 40, 50, 60];
+        let row = [1, 2, 250, 4, 100, 6];
+
+        for filter_type in FilterType::ALL {
+            let mut filtered = Vec::new();
+            filter_type.filter(&row, &previous, 3, &mut filtered);
+            assert_eq!(filtered[0], filter_type as u8);
+
+            let mut unfiltered = filtered[1..].to_vec();
+            filter_type.unfilter(&mut unfiltered, &previous, 3);
+            assert_eq!(unfiltered, row);
+        }
+    }
+
+    #[test]
+    fn test_image_data_round_trip() {
+        for (bit_depth, color_type) in [
+            (8, ColorType::Rgb),
+            (16, ColorType::Rgba),
+            (1, ColorType::Grayscale),
+            (4, ColorType::Indexed),
+        ] {
+            let header = Ihdr::new(13, 5, bit_depth, color_type).unwrap();
+            let image_data = gradient(header);
+
+            let decoded = ImageData::decode(header, &image_data.encode()).unwrap();
+            assert_eq!(decoded, image_data);
+        }
+    }
+
+    #[test]
+    fn test_interlaced_round_trip() {
+        // Rows of sub-byte pixels fill whole bytes here, passes do not carry padding bits over.
+        for (width, height, bit_depth, color_type) in [
+            (13, 5, 8, ColorType::Rgb),
+            (9, 11, 16, ColorType::Rgba),
+            (12, 3, 2, ColorType::Grayscale),
+            (1, 1, 8, ColorType::GrayscaleAlpha),
+        ] {
+            let mut header = Ihdr::new(width, height, bit_depth, color_type).unwrap();
+            header.interlace_method = 1;
+            let image_data = gradient(header);
+
+            let decoded = ImageData::decode(header, &image_data.encode()).unwrap();
+            assert_eq!(decoded, image_data);
+            let plain = decoded.deinterlaced();
+            assert!(!plain.header().is_interlaced());
+            let plain = ImageData::decode(*plain.header(), &plain.encode()).unwrap();
+            assert_eq!(plain.pixels(), image_data.pixels());
+        }
+    }
+
+    #[test]
+    fn test_adam7_layout() {
+        // Each pixel holds its own index, so the order of the passes shows in the filtered data.
+        let mut header = Ihdr::new(3, 3, 8, ColorType::Grayscale).unwrap();
+        header.interlace_method = 1;
+        let image_data = ImageData::new(header, (0..9).collect()).unwrap();
+
+        let filtered = inflate(&image_data.encode()).unwrap();
+        let pixels: Vec<u8> = unfilter_passes(header, &filtered);
+        assert_eq!(pixels, [0, 2, 6, 8, 1, 7, 3, 4, 5]);
+    }
+
+    // The raw samples of each pass in storage order, with their filters undone.
+    fn unfilter_passes(header: Ihdr, filtered: &[u8]) -> Vec<u8> {
+        let mut offset = 0;
+        let mut pixels = Vec::new();
+        for (pass, _) in passes(&header) {
+            let length = (stride(&pass) + 1) * pass.height as usize;
+            pixels.extend(unfilter(&pass, &filtered[offset..offset + length]).unwrap());
+            offset += length;
+        }
+        pixels
+    }
+
+    #[test]
+    fn test_set_image_data() {
+        let header = Ihdr::new(16, 16, 8, ColorType::Rgba).unwrap();
+        let mut png = PngBuilder::new().chunk(header.into()).build().unwrap();
+        let image_data = gradient(header);
+
+        png.set_image_data(&image_data).unwrap();
+        let png = Png::try_from(png.as_bytes().as_slice()).unwrap();
+        assert_eq!(png.image_data().unwrap(), image_data);
+        assert_eq!(chunk_types(&png), vec!["IHDR", "IDAT", "IEND"]);
+    }
+
+    #[test]
+    fn test_image_data_invalid_length() {
+        let header = Ihdr::new(4, 4, 8, ColorType::Rgb).unwrap();
+        assert_eq!(
+            ImageData::new(header, vec![0; 47])
+                .err()
+                .unwrap()
+                .downcast::<ImageDataError>()
+                .unwrap(),
+            ImageDataError::InvalidDataLength {
+                expected: 48,
+                found: 47
+            }
+        );
+    }
+
+    #[test]
+    fn test_decode_stops_at_expected_length() {
+        // Far more data than a 4x4 image holds, only what the header describes is inflated.
+        let header = Ihdr::new(4, 4, 8, ColorType::Grayscale).unwrap();
+        let decoded = ImageData::decode(header, &deflate(&vec![0; 10_000_000])).unwrap();
+        assert_eq!(decoded.pixels(), [0; 16]);
+    }
+
+    #[test]
+    fn test_image_data_invalid_filter() {
+        let header = Ihdr::new(1, 1, 8, ColorType::Grayscale).unwrap();
+        assert_eq!(
+            ImageData::decode(header, &deflate(&[5, 0]))
+                .err()
+                .unwrap()
+                .downcast::<ImageDataError>()
+                .unwrap(),
+            ImageDataError::InvalidFilterType { found: 5 }
+        );
+    }
+}
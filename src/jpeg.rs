@@ -0,0 +1,343 @@
+use crate::{
+    carrier::Carrier,
+    chunk_type::ChunkType,
+    stego::{StegoError, StegoMethod},
+};
+use anyhow::{Error, Result};
+use std::str::FromStr;
+use thiserror::Error;
+
+// Payloads live in their own APP15 or COM segments, tagged so they are never confused with what
+// other tools put there:
+//
+//     PAYLOAD_ID, the slot as a four letter chunk type, the payload
+//
+// Only the segments before the first scan are parsed, the compressed image data after it is kept
+// as is.
+pub const PAYLOAD_ID: [u8; 6] = *b"pngme\0";
+
+const SOI: u8 = 0xd8;
+const EOI: u8 = 0xd9;
+const SOS: u8 = 0xda;
+const APP0: u8 = 0xe0;
+const APP15: u8 = 0xef;
+const COM: u8 = 0xfe;
+
+// The length field counts itself.
+const MAX_SEGMENT_DATA: usize = u16::MAX as usize - 2;
+const PAYLOAD_HEADER_LENGTH: usize = PAYLOAD_ID.len() + 4;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum JpegError {
+    #[error("Not a JPEG, the file does not start with a start of image marker.")]
+    InvalidSignature,
+    #[error("JPEG is truncated.")]
+    Truncated,
+    #[error("Expected a marker at offset {offset:?}.")]
+    ExpectedMarker { offset: usize },
+    #[error("JPEG carriers only support chunk embedding, in APP15 or COM segments.")]
+    UnsupportedMethod,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PayloadSegment {
+    #[default]
+    App15,
+    Comment,
+}
+
+impl FromStr for PayloadSegment {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "app15" => Ok(Self::App15),
+            "com" | "comment" => Ok(Self::Comment),
+            _ => anyhow::bail!("Unknown JPEG segment {:?} (expected app15 or com)", s),
+        }
+    }
+}
+
+impl PayloadSegment {
+    fn marker(&self) -> u8 {
+        match self {
+            Self::App15 => APP15,
+            Self::Comment => COM,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub marker: u8,
+    pub data: Vec<u8>,
+}
+
+impl Segment {
+    // The slot and payload, if pngme wrote this segment.
+    fn payload(&self) -> Option<(&[u8], &[u8])> {
+        if self.marker != APP15 && self.marker != COM {
+            return None;
+        }
+        let rest = self.data.strip_prefix(&PAYLOAD_ID)?;
+        (rest.len() >= 4).then(|| rest.split_at(4))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Jpeg {
+    segments: Vec<Segment>,
+    // Everything from the first start of scan marker on, including the end of image marker.
+    scan: Vec<u8>,
+    payload_segment: PayloadSegment,
+}
+
+impl TryFrom<&[u8]> for Jpeg {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        if !bytes.starts_with(&[0xff, SOI]) {
+            return Err(JpegError::InvalidSignature.into());
+        }
+
+        let mut segments = Vec::new();
+        let mut offset = 2;
+        loop {
+            if bytes.get(offset) != Some(&0xff) {
+                return Err(match offset >= bytes.len() {
+                    true => JpegError::Truncated,
+                    false => JpegError::ExpectedMarker { offset },
+                }
+                .into());
+            }
+            // Any number of 0xff bytes may pad the gap before a marker.
+            let start = offset;
+            while bytes.get(offset) == Some(&0xff) {
+                offset += 1;
+            }
+            let marker = *bytes.get(offset).ok_or(JpegError::Truncated)?;
+            offset += 1;
+
+            match marker {
+                SOS | EOI => {
+                    return Ok(Self {
+                        segments,
+                        scan: bytes[start..].to_vec(),
+                        payload_segment: PayloadSegment::default(),
+                    })
+                }
+                // Markers without a length.
+                0x01 | 0xd0..=0xd7 => segments.push(Segment {
+                    marker,
+                    data: Vec::new(),
+                }),
+                _ => {
+                    let length = bytes.get(offset..offset + 2).ok_or(JpegError::Truncated)?;
+                    let length = u16::from_be_bytes([length[0], length[1]]) as usize;
+                    let data = bytes
+                        .get(offset + 2..offset + length.max(2))
+                        .ok_or(JpegError::Truncated)?;
+                    segments.push(Segment {
+                        marker,
+                        data: data.to_vec(),
+                    });
+                    offset += length.max(2);
+                }
+            }
+        }
+    }
+}
+
+impl Jpeg {
+    pub const SIGNATURE: [u8; 3] = [0xff, SOI, 0xff];
+
+    pub fn is_jpeg(bytes: &[u8]) -> bool {
+        bytes.starts_with(&Self::SIGNATURE)
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    // Where new payloads go, extraction reads both kinds.
+    pub fn with_payload_segment(mut self, payload_segment: PayloadSegment) -> Self {
+        self.payload_segment = payload_segment;
+        self
+    }
+
+    // Removes every payload segment in the slot, returning how many there were.
+    pub fn remove_slot(&mut self, slot: &str) -> usize {
+        let before = self.segments.len();
+        self.segments
+            .retain(|segment| !matches!(segment.payload(), Some((s, _)) if s == slot.as_bytes()));
+        before - self.segments.len()
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let length: usize = self.segments.iter().map(|s| s.data.len() + 4).sum();
+        let mut bytes = Vec::with_capacity(2 + length + self.scan.len());
+        bytes.extend([0xff, SOI]);
+        for segment in &self.segments {
+            bytes.extend([0xff, segment.marker]);
+            if segment.marker != 0x01 && !(0xd0..=0xd7).contains(&segment.marker) {
+                bytes.extend((segment.data.len() as u16 + 2).to_be_bytes());
+                bytes.extend(&segment.data);
+            }
+        }
+        bytes.extend(&self.scan);
+        bytes
+    }
+}
+
+impl Carrier for Jpeg {
+    fn format_name(&self) -> &'static str {
+        "JPEG"
+    }
+
+    fn slots(&self) -> Vec<String> {
+        let mut slots: Vec<String> = Vec::new();
+        for (slot, _) in self.segments.iter().filter_map(Segment::payload) {
+            let slot = String::from_utf8_lossy(slot).into_owned();
+            if !slots.contains(&slot) {
+                slots.push(slot);
+            }
+        }
+        slots
+    }
+
+    fn capacity(&self, method: StegoMethod) -> Result<usize> {
+        match method {
+            StegoMethod::Chunk => Ok(MAX_SEGMENT_DATA - PAYLOAD_HEADER_LENGTH),
+            _ => Err(JpegError::UnsupportedMethod.into()),
+        }
+    }
+
+    // Payload segments go after the application and comment segments at the start, keeping JFIF
+    // or Exif first where decoders look for them.
+    fn embed(&mut self, method: StegoMethod, slot: &str, payload: &[u8]) -> Result<()> {
+        let capacity = self.capacity(method)?;
+        if payload.len() > capacity {
+            return Err(StegoError::PayloadTooLarge {
+                capacity,
+                found: payload.len(),
+            }
+            .into());
+        }
+
+        let mut data = PAYLOAD_ID.to_vec();
+        data.extend(ChunkType::from_str(slot)?.bytes());
+        data.extend(payload);
+        let index = self
+            .segments
+            .iter()
+            .position(|s| !(APP0..=APP15).contains(&s.marker) && s.marker != COM)
+            .unwrap_or(self.segments.len());
+        self.segments.insert(
+            index,
+            Segment {
+                marker: self.payload_segment.marker(),
+                data,
+            },
+        );
+        Ok(())
+    }
+
+    fn extract_all(&self, method: StegoMethod, slot: &str) -> Result<Vec<Vec<u8>>> {
+        self.capacity(method)?;
+        let blocks: Vec<_> = self
+            .segments
+            .iter()
+            .filter_map(Segment::payload)
+            .filter(|(s, _)| *s == slot.as_bytes())
+            .map(|(_, payload)| payload.to_vec())
+            .collect();
+        if blocks.is_empty() {
+            return Err(StegoError::NoPayload.into());
+        }
+        Ok(blocks)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.as_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::Envelope;
+
+    // A JFIF header, a quantization table stand-in and a scan.
+    fn testing_jpeg() -> Vec<u8> {
+        let mut bytes = vec![0xff, SOI];
+        bytes.extend([0xff, APP0, 0, 7]);
+        bytes.extend(b"JFIF\0");
+        bytes.extend([0xff, 0xdb, 0, 4, 1, 2]);
+        bytes.extend([0xff, SOS, 0, 2, 0x12, 0xff, 0x00, 0x34, 0xff, EOI]);
+        bytes
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let bytes = testing_jpeg();
+        assert!(Jpeg::is_jpeg(&bytes));
+        let jpeg = Jpeg::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(jpeg.segments().len(), 2);
+        assert_eq!(jpeg.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_invalid() {
+        let error = |bytes: &[u8]| {
+            Jpeg::try_from(bytes)
+                .unwrap_err()
+                .downcast::<JpegError>()
+                .unwrap()
+        };
+        let bytes = testing_jpeg();
+        assert_eq!(error(b"\x89PNG"), JpegError::InvalidSignature);
+        assert_eq!(error(&bytes[..8]), JpegError::Truncated);
+        assert_eq!(error(&bytes[..13]), JpegError::Truncated);
+        assert_eq!(
+            error(&[0xff, SOI, 0x00]),
+            JpegError::ExpectedMarker { offset: 2 }
+        );
+    }
+
+    #[test]
+    fn test_embed_extract() {
+        let mut jpeg = Jpeg::try_from(testing_jpeg().as_slice()).unwrap();
+        jpeg.embed(StegoMethod::Chunk, "ruSt", b"hello").unwrap();
+        let mut jpeg = jpeg.with_payload_segment(PayloadSegment::Comment);
+        jpeg.embed(StegoMethod::Chunk, "abCd", b"there").unwrap();
+
+        let markers: Vec<u8> = jpeg.segments().iter().map(|s| s.marker).collect();
+        assert_eq!(markers, [APP0, APP15, COM, 0xdb]);
+        let jpeg = Jpeg::try_from(jpeg.as_bytes().as_slice()).unwrap();
+        assert_eq!(jpeg.slots(), ["ruSt", "abCd"]);
+        assert_eq!(jpeg.extract(StegoMethod::Chunk, "abCd").unwrap(), b"there");
+        assert!(jpeg.extract(StegoMethod::Chunk, "xyZw").is_err());
+        assert!(jpeg.extract(StegoMethod::Lsb, "ruSt").is_err());
+
+        let mut jpeg = jpeg;
+        assert_eq!(jpeg.remove_slot("ruSt"), 1);
+        assert_eq!(jpeg.slots(), ["abCd"]);
+    }
+
+    #[test]
+    fn test_segmented_envelope() {
+        let mut jpeg = Jpeg::try_from(testing_jpeg().as_slice()).unwrap();
+        let envelope = Envelope::new(vec![7; 100_000])
+            .encrypted("hunter2")
+            .unwrap();
+        jpeg.embed_envelope(StegoMethod::Chunk, "ruSt", &envelope)
+            .unwrap();
+        assert_eq!(jpeg.segments().len(), 4);
+
+        let jpeg = Jpeg::try_from(jpeg.as_bytes().as_slice()).unwrap();
+        let decoded = jpeg
+            .extract_envelope_with_passphrase(StegoMethod::Chunk, "ruSt", "hunter2")
+            .unwrap();
+        assert_eq!(decoded.payload, vec![7; 100_000]);
+    }
+}
@@ -0,0 +1,104 @@
+use ::keyring::{Entry, Error as EntryError};
+use anyhow::Result;
+use thiserror::Error;
+
+pub const SERVICE: &str = "pngme";
+pub const DEFAULT_PROFILE: &str = "default";
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum KeyringError {
+    #[error("No passphrase stored in the keyring for profile {profile:?}.")]
+    NoPassphrase { profile: String },
+    #[error("Keyring profile name must not be empty.")]
+    EmptyProfile,
+}
+
+// Passphrases live in the platform store (Secret Service, Keychain or Credential Manager),
+// one entry per profile under the "pngme" service.
+pub struct Keyring {
+    profile: String,
+    entry: Entry,
+}
+
+impl Keyring {
+    pub fn new(profile: &str) -> Result<Self> {
+        if profile.is_empty() {
+            return Err(KeyringError::EmptyProfile.into());
+        }
+
+        Ok(Self {
+            profile: profile.to_string(),
+            entry: Entry::new(SERVICE, profile)?,
+        })
+    }
+
+    pub fn profile(&self) -> &str {
+        &self.profile
+    }
+
+    pub fn store(&self, passphrase: &str) -> Result<()> {
+        Ok(self.entry.set_password(passphrase)?)
+    }
+
+    pub fn load(&self) -> Result<String> {
+        self.entry.get_password().map_err(|e| self.map_error(e))
+    }
+
+    pub fn delete(&self) -> Result<()> {
+        self.entry
+            .delete_credential()
+            .map_err(|e| self.map_error(e))
+    }
+
+    fn map_error(&self, error: EntryError) -> anyhow::Error {
+        match error {
+            EntryError::NoEntry => KeyringError::NoPassphrase {
+                profile: self.profile.clone(),
+            }
+            .into(),
+            e => e.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_and_load() {
+        ::keyring::set_default_credential_builder(::keyring::mock::default_credential_builder());
+
+        let keyring = Keyring::new("batch").unwrap();
+        assert_eq!(keyring.profile(), "batch");
+        assert_eq!(
+            keyring
+                .load()
+                .err()
+                .unwrap()
+                .downcast::<KeyringError>()
+                .unwrap(),
+            KeyringError::NoPassphrase {
+                profile: "batch".to_string()
+            }
+        );
+
+        keyring.store("hunter2").unwrap();
+        assert_eq!(keyring.load().unwrap(), "hunter2");
+
+        keyring.delete().unwrap();
+        assert!(keyring.load().is_err());
+    }
+
+    #[test]
+    fn test_empty_profile() {
+        assert_eq!(
+            Keyring::new("")
+                .err()
+                .unwrap()
+                .downcast::<KeyringError>()
+                .unwrap(),
+            KeyringError::EmptyProfile
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use crate::{ancillary::TypedChunk, chunk_type::ChunkType, png::Png};
+use anyhow::Result;
+use ciborium::Value;
+use thiserror::Error;
+
+// Key-value metadata in one private chunk that is safe to copy, stored as a CBOR map of text keys
+// to text values. Keys keep the order they were first set in.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum KvError {
+    #[error("Key-value chunk is not valid CBOR.")]
+    InvalidData,
+    #[error("Key-value chunk must hold a map of text keys to text values.")]
+    NotTextMap,
+    #[error("Key {key:?} appears more than once.")]
+    DuplicateKey { key: String },
+    #[error("Keys must not be empty.")]
+    EmptyKey,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct KvStore {
+    entries: Vec<(String, String)>,
+}
+
+impl TypedChunk for KvStore {
+    const CHUNK_TYPE: ChunkType = ChunkType::from_bytes_const(*b"kvSt");
+
+    fn from_data(data: &[u8]) -> Result<Self> {
+        let value: Value = ciborium::from_reader(data).map_err(|_| KvError::InvalidData)?;
+        let Value::Map(pairs) = value else {
+            return Err(KvError::NotTextMap.into());
+        };
+
+        let mut store = Self::default();
+        for pair in pairs {
+            let (Value::Text(key), Value::Text(value)) = pair else {
+                return Err(KvError::NotTextMap.into());
+            };
+            if store.get(&key).is_some() {
+                return Err(KvError::DuplicateKey { key }.into());
+            }
+            store.entries.push((key, value));
+        }
+
+        Ok(store)
+    }
+
+    fn to_data(&self) -> Vec<u8> {
+        let map = Value::Map(
+            self.entries
+                .iter()
+                .map(|(key, value)| (Value::Text(key.clone()), Value::Text(value.clone())))
+                .collect(),
+        );
+        let mut data = Vec::new();
+        ciborium::into_writer(&map, &mut data).expect("writing to a Vec cannot fail");
+        data
+    }
+}
+
+impl KvStore {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    // Replaces the value in place, so updating a key does not move it.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        if key.is_empty() {
+            return Err(KvError::EmptyKey.into());
+        }
+        match self.entries.iter_mut().find(|(k, _)| k == key) {
+            Some((_, existing)) => *existing = value.to_string(),
+            None => self.entries.push((key.to_string(), value.to_string())),
+        }
+        Ok(())
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        let index = self.entries.iter().position(|(k, _)| k == key)?;
+        Some(self.entries.remove(index).1)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl Png {
+    pub fn kv_store(&self) -> Result<KvStore> {
+        Ok(self.typed_chunk()?.unwrap_or_default())
+    }
+
+    // An empty store removes the chunk rather than leaving an empty map behind.
+    pub fn set_kv_store(&mut self, store: &KvStore) {
+        match store.is_empty() {
+            true => {
+                let _ = self.remove_all_chunks(&KvStore::CHUNK_TYPE.to_string());
+            }
+            false => self.set_typed_chunk(store),
+        }
+    }
+
+    pub fn kv_get(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.kv_store()?.get(key).map(str::to_string))
+    }
+
+    pub fn kv_set(&mut self, key: &str, value: &str) -> Result<()> {
+        let mut store = self.kv_store()?;
+        store.set(key, value)?;
+        self.set_kv_store(&store);
+        Ok(())
+    }
+
+    pub fn kv_remove(&mut self, key: &str) -> Result<Option<String>> {
+        let mut store = self.kv_store()?;
+        let removed = store.remove(key);
+        if removed.is_some() {
+            self.set_kv_store(&store);
+        }
+        Ok(removed)
+    }
+
+    pub fn kv_iter(&self) -> Result<impl Iterator<Item = (String, String)>> {
+        Ok(self.kv_store()?.entries.into_iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{builder::PngBuilder, chunk::Chunk};
+
+    fn testing_png() -> Png {
+        PngBuilder::new()
+            .chunk(Chunk::new(ChunkType::IHDR, vec![0; 13]))
+            .chunk(Chunk::new(ChunkType::IDAT, vec![1, 2, 3]))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_kv() {
+        let mut png = testing_png();
+        assert_eq!(png.kv_get("build").unwrap(), None);
+
+        png.kv_set("build", "1.2.3").unwrap();
+        png.kv_set("commit", "abc123").unwrap();
+        png.kv_set("build", "1.2.4").unwrap();
+        assert!(png.kv_set("", "x").is_err());
+        let mut png = Png::try_from(png.as_bytes().as_slice()).unwrap();
+
+        assert_eq!(png.kv_get("build").unwrap().as_deref(), Some("1.2.4"));
+        let keys: Vec<String> = png.kv_iter().unwrap().map(|(key, _)| key).collect();
+        assert_eq!(keys, ["build", "commit"]);
+        let stores = png
+            .chunks()
+            .iter()
+            .filter(|c| *c.chunk_type() == KvStore::CHUNK_TYPE);
+        assert_eq!(stores.count(), 1);
+
+        assert_eq!(png.kv_remove("build").unwrap().as_deref(), Some("1.2.4"));
+        assert_eq!(png.kv_remove("build").unwrap(), None);
+        png.kv_remove("commit").unwrap();
+        assert!(png.chunk_by_type("kvSt").is_none());
+    }
+
+    #[test]
+    fn test_invalid_store() {
+        let error = |data: &[u8]| {
+            KvStore::from_data(data)
+                .unwrap_err()
+                .downcast::<KvError>()
+                .unwrap()
+        };
+        // An array, a map with an integer value, and a map repeating a key.
+        assert_eq!(error(&[0x80]), KvError::NotTextMap);
+        assert_eq!(error(&[0xa1, 0x61, b'a', 0x01]), KvError::NotTextMap);
+        assert_eq!(
+            error(&[0xa2, 0x61, b'a', 0x60, 0x61, b'a', 0x60]),
+            KvError::DuplicateKey {
+                key: "a".to_string()
+            }
+        );
+        assert_eq!(error(&[0xff]), KvError::InvalidData);
+    }
+}
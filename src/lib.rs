@@ -0,0 +1,51 @@
+// The core types are also exported here; see prelude for the wider set most programs use.
+pub use crate::{chunk::Chunk, chunk_type::ChunkType, png::Png};
+
+pub mod ancillary;
+pub mod apng;
+pub mod builder;
+pub mod carrier;
+pub mod chunk;
+pub mod chunk_type;
+#[cfg(feature = "clipboard")]
+pub mod clipboard;
+#[cfg(feature = "png-codec")]
+pub mod codec;
+pub mod compression;
+pub mod cover;
+pub mod crypto;
+pub mod decoy;
+pub mod detect;
+pub mod diff;
+#[cfg(feature = "image")]
+pub mod dynamic_image;
+pub mod envelope;
+pub mod exif;
+pub mod fec;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "gpg")]
+pub mod gpg;
+pub mod history;
+pub mod icc;
+pub mod ihdr;
+pub mod imagedata;
+pub mod jpeg;
+#[cfg(feature = "keyring")]
+pub mod keyring;
+pub mod kv;
+pub mod lint;
+pub mod obfuscation;
+pub mod options;
+pub mod palette;
+pub mod png;
+pub mod prelude;
+pub mod render;
+pub mod repair;
+pub mod sanitize;
+pub mod signing;
+pub mod stego;
+pub mod text;
+pub mod time;
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -0,0 +1,481 @@
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    ihdr::{ColorType, Ihdr},
+    options::VerifyOptions,
+    png::Png,
+    text::Text,
+};
+use rayon::prelude::*;
+use std::fmt::Display;
+
+// Ancillary chunks the specification requires before PLTE and IDAT, between them, and before IDAT.
+const BEFORE_PLTE: [ChunkType; 8] = [
+    ChunkType::cHRM,
+    ChunkType::gAMA,
+    ChunkType::iCCP,
+    ChunkType::sBIT,
+    ChunkType::sRGB,
+    ChunkType::cICP,
+    ChunkType::mDCV,
+    ChunkType::cLLI,
+];
+const AFTER_PLTE: [ChunkType; 3] = [ChunkType::tRNS, ChunkType::bKGD, ChunkType::hIST];
+const BEFORE_IDAT: [ChunkType; 3] = [ChunkType::pHYs, ChunkType::sPLT, ChunkType::acTL];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Pass => "pass",
+            Self::Warn => "warn",
+            Self::Fail => "fail",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: &'static str,
+    pub issues: Vec<Issue>,
+}
+
+impl Check {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            issues: Vec::new(),
+        }
+    }
+
+    fn fail(&mut self, message: impl Display) {
+        self.issues.push(Issue {
+            severity: Severity::Fail,
+            message: message.to_string(),
+        });
+    }
+
+    fn warn(&mut self, message: impl Display) {
+        self.issues.push(Issue {
+            severity: Severity::Warn,
+            message: message.to_string(),
+        });
+    }
+
+    pub fn status(&self) -> Severity {
+        self.issues
+            .iter()
+            .map(|issue| issue.severity)
+            .max()
+            .unwrap_or(Severity::Pass)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintReport {
+    pub checks: Vec<Check>,
+}
+
+impl LintReport {
+    pub fn status(&self) -> Severity {
+        self.checks
+            .iter()
+            .map(Check::status)
+            .max()
+            .unwrap_or(Severity::Pass)
+    }
+}
+
+impl Display for LintReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for check in &self.checks {
+            writeln!(
+                f,
+                "{:<4} {}",
+                check.status().to_string().to_uppercase(),
+                check.name
+            )?;
+            for issue in &check.issues {
+                writeln!(f, "     - {}: {}", issue.severity, issue.message)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Validates raw file bytes against the PNG specification. Unlike parsing, this keeps going past
+// the first problem so the report lists everything wrong with a file.
+pub fn lint_bytes(bytes: &[u8]) -> LintReport {
+    lint_bytes_with_options(bytes, &VerifyOptions::default())
+}
+
+pub fn lint_bytes_with_options(bytes: &[u8], options: &VerifyOptions) -> LintReport {
+    let mut signature = Check::new("signature");
+    if !bytes.starts_with(&Png::STANDARD_HEADER) {
+        signature.fail("the file does not start with the PNG signature");
+        return LintReport {
+            checks: vec![signature],
+        };
+    }
+
+    let mut structure = Check::new("structure");
+    let mut crc = Check::new("crc");
+    let layout = read_layout(&bytes[Png::STANDARD_HEADER.len()..], &mut structure);
+    let chunks = check_crcs(layout, options, &mut crc);
+
+    LintReport {
+        checks: vec![
+            signature,
+            structure,
+            crc,
+            check_ordering(&chunks),
+            check_uniqueness(&chunks),
+            check_image_data(&chunks),
+            check_palette(&chunks),
+            check_text(&chunks),
+        ],
+    }
+}
+
+// A chunk as laid out in the file, before its CRC is checked.
+struct RawChunk<'a> {
+    chunk_type: ChunkType,
+    // The type and data together, which is what the CRC covers.
+    type_and_data: &'a [u8],
+    found: u32,
+}
+
+// The structural pass, which reads the layout of the chunks without computing any CRCs.
+fn read_layout<'a>(mut bytes: &'a [u8], structure: &mut Check) -> Vec<RawChunk<'a>> {
+    let mut chunks = Vec::new();
+    while !bytes.is_empty() {
+        let index = chunks.len();
+        if bytes.len() < 12 {
+            structure.fail(format!("chunk {} is truncated", index));
+            break;
+        }
+
+        let length = u32::from_be_bytes(bytes[..4].try_into().unwrap()) as usize;
+        let type_bytes: [u8; 4] = bytes[4..8].try_into().unwrap();
+        let Ok(chunk_type) = ChunkType::try_from(type_bytes) else {
+            structure.fail(format!(
+                "chunk {} has an invalid type {:?}",
+                index,
+                String::from_utf8_lossy(&type_bytes)
+            ));
+            break;
+        };
+        if length > i32::MAX as usize {
+            structure.fail(format!(
+                "{} chunk {} declares {} bytes, more than PNG allows",
+                chunk_type, index, length
+            ));
+            break;
+        }
+        if bytes.len() < length + 12 {
+            structure.fail(format!(
+                "{} chunk {} declares {} bytes but only {} remain",
+                chunk_type,
+                index,
+                length,
+                bytes.len() - 12
+            ));
+            break;
+        }
+
+        if !chunk_type.is_reserved_bit_valid() {
+            structure.fail(format!(
+                "{} chunk {} has the reserved bit set",
+                chunk_type, index
+            ));
+        }
+        if chunk_type.is_critical() && !chunk_type.is_standard() {
+            structure.fail(format!(
+                "{} chunk {} is critical but unknown, decoders must reject it",
+                chunk_type, index
+            ));
+        }
+
+        chunks.push(RawChunk {
+            chunk_type,
+            type_and_data: &bytes[4..8 + length],
+            found: u32::from_be_bytes(bytes[8 + length..12 + length].try_into().unwrap()),
+        });
+        bytes = &bytes[12 + length..];
+
+        if chunk_type == ChunkType::IEND {
+            if !bytes.is_empty() {
+                structure.warn(format!("{} bytes after IEND", bytes.len()));
+            }
+            break;
+        }
+    }
+
+    chunks
+}
+
+fn check_crcs(layout: Vec<RawChunk>, options: &VerifyOptions, crc: &mut Check) -> Vec<Chunk> {
+    let compute = |raw: &RawChunk| Chunk::compute_crc_contiguous(raw.type_and_data);
+    let computed: Vec<u32> = match options.parallel {
+        true => layout.par_iter().map(compute).collect(),
+        false => layout.iter().map(compute).collect(),
+    };
+
+    layout
+        .into_iter()
+        .zip(computed)
+        .enumerate()
+        .map(|(index, (raw, computed))| {
+            if raw.found != computed {
+                crc.fail(format!(
+                    "{} chunk {} has CRC {:#010x}, its data gives {:#010x}",
+                    raw.chunk_type, index, raw.found, computed
+                ));
+            }
+            Chunk::with_crc(raw.chunk_type, raw.type_and_data[4..].to_vec(), computed)
+        })
+        .collect()
+}
+
+fn positions(chunks: &[Chunk], chunk_type: ChunkType) -> Vec<usize> {
+    chunks
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| *c.chunk_type() == chunk_type)
+        .map(|(index, _)| index)
+        .collect()
+}
+
+fn check_ordering(chunks: &[Chunk]) -> Check {
+    let mut check = Check::new("ordering");
+    if chunks.first().map(|c| *c.chunk_type()) != Some(ChunkType::IHDR) {
+        check.fail("IHDR is not the first chunk");
+    }
+    if chunks.last().map(|c| *c.chunk_type()) != Some(ChunkType::IEND) {
+        check.fail("IEND is not the last chunk");
+    }
+
+    let plte = positions(chunks, ChunkType::PLTE).first().copied();
+    let idat = positions(chunks, ChunkType::IDAT).first().copied();
+    if let (Some(plte), Some(idat)) = (plte, idat) {
+        if plte > idat {
+            check.fail("PLTE comes after IDAT");
+        }
+    }
+
+    for (index, chunk) in chunks.iter().enumerate() {
+        let chunk_type = *chunk.chunk_type();
+        let after = |position: Option<usize>| position.is_some_and(|p| index > p);
+        if BEFORE_PLTE.contains(&chunk_type) && after(plte) {
+            check.fail(format!("{} chunk {} comes after PLTE", chunk_type, index));
+        }
+        if AFTER_PLTE.contains(&chunk_type) && plte.is_some_and(|p| index < p) {
+            check.fail(format!("{} chunk {} comes before PLTE", chunk_type, index));
+        }
+        if (BEFORE_PLTE.contains(&chunk_type)
+            || AFTER_PLTE.contains(&chunk_type)
+            || BEFORE_IDAT.contains(&chunk_type))
+            && after(idat)
+        {
+            check.fail(format!("{} chunk {} comes after IDAT", chunk_type, index));
+        }
+    }
+
+    check
+}
+
+fn check_uniqueness(chunks: &[Chunk]) -> Check {
+    let mut check = Check::new("uniqueness");
+    let mut seen = Vec::new();
+    for chunk in chunks {
+        let chunk_type = *chunk.chunk_type();
+        if !chunk_type.is_single_instance() || seen.contains(&chunk_type) {
+            continue;
+        }
+        seen.push(chunk_type);
+        let count = positions(chunks, chunk_type).len();
+        if count > 1 {
+            check.fail(format!("{} appears {} times", chunk_type, count));
+        }
+    }
+
+    if chunks.iter().any(|c| *c.chunk_type() == ChunkType::sRGB)
+        && chunks.iter().any(|c| *c.chunk_type() == ChunkType::iCCP)
+    {
+        check.warn("both sRGB and iCCP are present");
+    }
+
+    check
+}
+
+fn header(chunks: &[Chunk]) -> Option<Ihdr> {
+    chunks
+        .iter()
+        .find(|c| *c.chunk_type() == ChunkType::IHDR)
+        .and_then(|c| Ihdr::try_from(c).ok())
+}
+
+fn check_image_data(chunks: &[Chunk]) -> Check {
+    let mut check = Check::new("image data");
+    match chunks.iter().find(|c| *c.chunk_type() == ChunkType::IHDR) {
+        Some(chunk) => {
+            if let Err(e) = Ihdr::try_from(chunk) {
+                check.fail(format!("IHDR is invalid: {}", e));
+            }
+        }
+        None => check.fail("there is no IHDR chunk"),
+    }
+
+    let idat = positions(chunks, ChunkType::IDAT);
+    match (idat.first(), idat.last()) {
+        (Some(first), Some(last)) if last - first + 1 != idat.len() => {
+            check.fail("IDAT chunks are not consecutive")
+        }
+        (None, _) => check.fail("there is no IDAT chunk"),
+        _ => {}
+    }
+
+    check
+}
+
+fn check_palette(chunks: &[Chunk]) -> Check {
+    let mut check = Check::new("palette");
+    let plte = chunks.iter().find(|c| *c.chunk_type() == ChunkType::PLTE);
+    if let Some(plte) = plte {
+        let length = plte.chunk_data().len();
+        if length == 0 || length % 3 != 0 || length > 256 * 3 {
+            check.fail(format!(
+                "PLTE holds {} bytes, not 1 to 256 RGB entries",
+                length
+            ));
+        }
+    }
+
+    match (header(chunks).map(|h| h.color_type), plte) {
+        (Some(ColorType::Indexed), None) => check.fail("indexed color requires a PLTE chunk"),
+        (Some(ColorType::Grayscale | ColorType::GrayscaleAlpha), Some(_)) => {
+            check.fail("grayscale images must not have a PLTE chunk")
+        }
+        _ => {}
+    }
+
+    check
+}
+
+fn check_text(chunks: &[Chunk]) -> Check {
+    let mut check = Check::new("text");
+    for (index, chunk) in chunks.iter().enumerate() {
+        let chunk_type = *chunk.chunk_type();
+        if ![ChunkType::tEXt, ChunkType::zTXt, ChunkType::iTXt].contains(&chunk_type) {
+            continue;
+        }
+        if let Err(e) = Text::try_from(chunk) {
+            check.fail(format!("{} chunk {}: {}", chunk_type, index, e));
+        }
+    }
+
+    check
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::PngBuilder;
+
+    fn testing_png() -> Png {
+        PngBuilder::new()
+            .chunk(Chunk::from(Ihdr::new(1, 1, 8, ColorType::Rgb).unwrap()))
+            .chunk(Chunk::new(ChunkType::IDAT, vec![0; 4]))
+            .chunk(Chunk::new(ChunkType::tEXt, b"Comment\0hello".to_vec()))
+            .build()
+            .unwrap()
+    }
+
+    fn failures(report: &LintReport) -> Vec<&'static str> {
+        report
+            .checks
+            .iter()
+            .filter(|check| check.status() == Severity::Fail)
+            .map(|check| check.name)
+            .collect()
+    }
+
+    #[test]
+    fn test_conforming() {
+        let report = lint_bytes(&testing_png().as_bytes());
+        assert_eq!(report.status(), Severity::Pass);
+        assert_eq!(report.checks.len(), 8);
+        assert!(report.to_string().starts_with("PASS signature\n"));
+    }
+
+    #[test]
+    fn test_signature_and_crc() {
+        let report = lint_bytes(b"GIF89a");
+        assert_eq!(failures(&report), ["signature"]);
+
+        let mut bytes = testing_png().as_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        bytes.extend_from_slice(b"junk");
+        let report = lint_bytes(&bytes);
+        assert_eq!(failures(&report), ["crc"]);
+        assert_eq!(report.checks[1].status(), Severity::Warn);
+    }
+
+    #[test]
+    fn test_parallel_crc() {
+        let mut bytes = testing_png().as_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        bytes[Png::STANDARD_HEADER.len() + 12 + 13 - 1] ^= 1;
+
+        let parallel = lint_bytes_with_options(&bytes, &VerifyOptions { parallel: true });
+        assert_eq!(parallel, lint_bytes(&bytes));
+        assert_eq!(parallel.checks[2].issues.len(), 2);
+        assert!(parallel.checks[2].issues[0]
+            .message
+            .starts_with("IHDR chunk 0"));
+    }
+
+    #[test]
+    fn test_ordering_and_uniqueness() {
+        let mut chunks = testing_png().chunks().to_vec();
+        chunks.insert(2, Chunk::new(ChunkType::gAMA, vec![0; 4]));
+        chunks.insert(2, Chunk::new(ChunkType::gAMA, vec![0; 4]));
+        chunks.insert(1, Chunk::new(ChunkType::IDAT, vec![0; 4]));
+        let report = lint_bytes(&Png::from_chunks(chunks).as_bytes());
+
+        assert_eq!(failures(&report), ["ordering", "uniqueness", "image data"]);
+        assert!(report.to_string().contains("gAMA chunk 3 comes after IDAT"));
+        assert!(report
+            .to_string()
+            .contains("IDAT chunks are not consecutive"));
+    }
+
+    #[test]
+    fn test_palette_and_text() {
+        let png = PngBuilder::new()
+            .chunk(Chunk::from(Ihdr::new(1, 1, 8, ColorType::Indexed).unwrap()))
+            .chunk(Chunk::new(ChunkType::IDAT, vec![0; 4]))
+            .chunk(Chunk::new(ChunkType::tEXt, b" bad\0hello".to_vec()))
+            .build()
+            .unwrap();
+        let report = lint_bytes(&png.as_bytes());
+
+        assert_eq!(failures(&report), ["palette", "text"]);
+    }
+}
@@ -1,7 +1,58 @@
-mod chunk;
-mod chunk_type;
-mod png;
+mod args;
+mod commands;
+mod config;
+mod manifest;
+mod output;
+#[cfg(feature = "server")]
+mod server;
+mod status;
 
-fn main() {
-    todo!()
+use clap::CommandFactory;
+use status::{ExitStatus, StatusError};
+use std::{
+    io::{self, IsTerminal},
+    process::ExitCode,
+};
+use tracing_subscriber::EnvFilter;
+
+fn main() -> ExitCode {
+    // Usage errors exit with their own status instead of clap's 2, which means not found here.
+    let matches = match args::Cli::command().try_get_matches() {
+        Ok(matches) => matches,
+        Err(e) => {
+            let _ = e.print();
+            return match e.use_stderr() {
+                true => ExitStatus::Usage.into(),
+                false => ExitStatus::Success.into(),
+            };
+        }
+    };
+
+    let result = config::cli_from_matches(&matches).and_then(|cli| {
+        init_logging(cli.global.verbose);
+        commands::run(cli)
+    });
+    match result {
+        Ok(()) => ExitStatus::Success.into(),
+        Err(e) => {
+            if !StatusError::is_silent(&e) {
+                eprintln!("Error: {:?}", e);
+            }
+            ExitStatus::of(&e).into()
+        }
+    }
+}
+
+fn init_logging(verbose: u8) {
+    let level = match verbose {
+        0 => "warn",
+        1 => "pngme=debug",
+        _ => "pngme=trace",
+    };
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(level));
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr)
+        .with_ansi(io::stderr().is_terminal())
+        .init();
 }
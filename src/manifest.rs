@@ -0,0 +1,432 @@
+use anyhow::{bail, Context, Result};
+use pngme::{chunk_type::ChunkType, compression::Compression, stego::StegoMethod};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+// A reviewable list of edits, read from TOML such as:
+//
+//     [[file]]
+//     path = "logo.png"
+//     output = "dist/logo.png"
+//
+//     [[file.step]]
+//     op = "encode"
+//     chunk_type = "ruSt"
+//     message = "build 1234"
+//
+//     [[file.step]]
+//     op = "sign"
+//     key = "release.key"
+//
+// Relative paths are resolved against the manifest's directory, so it runs the same from anywhere.
+//
+// A single step can also be written inline as an op and its settings, where repeating a setting
+// that takes a list adds to it and a bare flag is true:
+//
+//     strip:keep=tEXt,keep=iCCP
+//     encode:type=ruSt,file=msg.bin,replace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    pub files: Vec<Entry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub output: Option<PathBuf>,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    Encode {
+        chunk_type: ChunkType,
+        message: Option<String>,
+        file: Option<PathBuf>,
+        method: StegoMethod,
+        compress: Option<Compression>,
+        replace: bool,
+    },
+    Remove {
+        chunk_type: ChunkType,
+    },
+    Strip {
+        keep: Vec<ChunkType>,
+    },
+    SetText {
+        keyword: String,
+        value: String,
+    },
+    RemoveText {
+        keyword: String,
+    },
+    Sign {
+        key: PathBuf,
+    },
+}
+
+impl Step {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Encode { .. } => "encode",
+            Self::Remove { .. } => "remove",
+            Self::Strip { .. } => "strip",
+            Self::SetText { .. } => "set-text",
+            Self::RemoveText { .. } => "remove-text",
+            Self::Sign { .. } => "sign",
+        }
+    }
+}
+
+impl FromStr for Step {
+    type Err = anyhow::Error;
+
+    // Inline steps come from the command line, so their paths are relative to where it runs.
+    fn from_str(s: &str) -> Result<Self> {
+        let (op, settings) = s.split_once(':').unwrap_or((s, ""));
+        let mut table = toml::Table::new();
+        table.insert("op".to_string(), op.trim().into());
+        for setting in settings.split(',').filter(|s| !s.trim().is_empty()) {
+            let (key, value) = match setting.split_once('=') {
+                Some((key, value)) => (key.trim(), toml::Value::from(value)),
+                None => (setting.trim(), toml::Value::from(true)),
+            };
+            let key = match key {
+                "type" => "chunk_type",
+                key => key,
+            };
+            match (key, table.get_mut(key)) {
+                ("keep", Some(toml::Value::Array(list))) => list.push(value),
+                ("keep", None) => {
+                    table.insert(key.to_string(), toml::Value::Array(vec![value]));
+                }
+                (_, Some(_)) => bail!("{} is set twice", key),
+                (_, None) => {
+                    table.insert(key.to_string(), parse_flag(key, value)?);
+                }
+            }
+        }
+
+        parse_step(&toml::Value::Table(table), Path::new(""))
+            .with_context(|| format!("Invalid op {:?}", s))
+    }
+}
+
+// Flags are written replace=true or replace=false inline.
+fn parse_flag(key: &str, value: toml::Value) -> Result<toml::Value> {
+    match (key, value.as_str()) {
+        ("replace", Some(flag)) => Ok(bool::from_str(flag)
+            .with_context(|| format!("{} must be true or false", key))?
+            .into()),
+        _ => Ok(value),
+    }
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        Self::parse(&text, base).with_context(|| format!("Invalid manifest {}", path.display()))
+    }
+
+    pub fn parse(text: &str, base: &Path) -> Result<Self> {
+        let table: toml::Table = text.parse()?;
+        if let Some(key) = table.keys().find(|key| *key != "file") {
+            bail!("Unknown setting {:?}", key)
+        }
+        let files = match table.get("file") {
+            Some(files) => files
+                .as_array()
+                .context("file must be an array of tables, written [[file]]")?
+                .iter()
+                .enumerate()
+                .map(|(index, file)| {
+                    parse_entry(file, base).with_context(|| format!("In file {}", index + 1))
+                })
+                .collect::<Result<_>>()?,
+            None => Vec::new(),
+        };
+
+        Ok(Self { files })
+    }
+}
+
+fn parse_entry(value: &toml::Value, base: &Path) -> Result<Entry> {
+    let table = value.as_table().context("file must be a table")?;
+    let fields = Fields { table };
+    fields.only(&["path", "output", "step"])?;
+
+    let steps = match table.get("step") {
+        Some(steps) => steps
+            .as_array()
+            .context("step must be an array of tables, written [[file.step]]")?
+            .iter()
+            .enumerate()
+            .map(|(index, step)| {
+                parse_step(step, base).with_context(|| format!("In step {}", index + 1))
+            })
+            .collect::<Result<_>>()?,
+        None => Vec::new(),
+    };
+
+    Ok(Entry {
+        path: base.join(fields.string("path")?),
+        output: fields.optional("output")?.map(|output| base.join(output)),
+        steps,
+    })
+}
+
+fn parse_step(value: &toml::Value, base: &Path) -> Result<Step> {
+    let table = value.as_table().context("step must be a table")?;
+    let fields = Fields { table };
+    let chunk_type = || ChunkType::from_str(fields.string("chunk_type")?);
+
+    let step = match fields.string("op")? {
+        "encode" => {
+            fields.only(&[
+                "op",
+                "chunk_type",
+                "message",
+                "file",
+                "method",
+                "compress",
+                "replace",
+            ])?;
+            let message = fields.optional("message")?.map(str::to_string);
+            let file = fields.optional("file")?.map(|file| base.join(file));
+            if message.is_some() == file.is_some() {
+                bail!("encode needs either message or file")
+            }
+            Step::Encode {
+                chunk_type: chunk_type()?,
+                message,
+                file,
+                method: match fields.optional("method")? {
+                    Some(method) => StegoMethod::from_str(method)?,
+                    None => StegoMethod::Chunk,
+                },
+                compress: fields
+                    .optional("compress")?
+                    .map(Compression::from_str)
+                    .transpose()?,
+                replace: fields.bool("replace")?,
+            }
+        }
+        "remove" => {
+            fields.only(&["op", "chunk_type"])?;
+            Step::Remove {
+                chunk_type: chunk_type()?,
+            }
+        }
+        "strip" => {
+            fields.only(&["op", "keep"])?;
+            let keep = match table.get("keep") {
+                Some(keep) => keep
+                    .as_array()
+                    .context("keep must be a list of chunk types")?
+                    .iter()
+                    .map(|t| ChunkType::from_str(t.as_str().context("keep must hold strings")?))
+                    .collect::<Result<_>>()?,
+                None => Vec::new(),
+            };
+            Step::Strip { keep }
+        }
+        "set-text" => {
+            fields.only(&["op", "keyword", "value"])?;
+            Step::SetText {
+                keyword: fields.string("keyword")?.to_string(),
+                value: fields.string("value")?.to_string(),
+            }
+        }
+        "remove-text" => {
+            fields.only(&["op", "keyword"])?;
+            Step::RemoveText {
+                keyword: fields.string("keyword")?.to_string(),
+            }
+        }
+        "sign" => {
+            fields.only(&["op", "key"])?;
+            Step::Sign {
+                key: base.join(fields.string("key")?),
+            }
+        }
+        op => bail!(
+            "Unknown op {:?} (expected encode, remove, strip, set-text, remove-text or sign)",
+            op
+        ),
+    };
+
+    Ok(step)
+}
+
+struct Fields<'a> {
+    table: &'a toml::Table,
+}
+
+impl Fields<'_> {
+    // Misspelled keys are errors rather than silently ignored, a manifest is meant to be exact.
+    fn only(&self, allowed: &[&str]) -> Result<()> {
+        match self
+            .table
+            .keys()
+            .find(|key| !allowed.contains(&key.as_str()))
+        {
+            Some(key) => bail!("Unknown setting {:?}", key),
+            None => Ok(()),
+        }
+    }
+
+    fn optional(&self, key: &str) -> Result<Option<&str>> {
+        self.table
+            .get(key)
+            .map(|value| {
+                value
+                    .as_str()
+                    .with_context(|| format!("{} must be a string", key))
+            })
+            .transpose()
+    }
+
+    fn string(&self, key: &str) -> Result<&str> {
+        self.optional(key)?
+            .with_context(|| format!("{} is required", key))
+    }
+
+    fn bool(&self, key: &str) -> Result<bool> {
+        match self.table.get(key) {
+            Some(value) => value
+                .as_bool()
+                .with_context(|| format!("{} must be true or false", key)),
+            None => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"
+        [[file]]
+        path = "logo.png"
+        output = "dist/logo.png"
+
+        [[file.step]]
+        op = "strip"
+        keep = ["iCCP"]
+
+        [[file.step]]
+        op = "encode"
+        chunk_type = "ruSt"
+        file = "payload.bin"
+        compress = "zstd"
+        replace = true
+
+        [[file.step]]
+        op = "set-text"
+        keyword = "Author"
+        value = "Ferris"
+
+        [[file]]
+        path = "/abs/icon.png"
+
+        [[file.step]]
+        op = "sign"
+        key = "release.key"
+    "#;
+
+    #[test]
+    fn test_parse() {
+        let manifest = Manifest::parse(MANIFEST, Path::new("assets")).unwrap();
+        assert_eq!(manifest.files.len(), 2);
+
+        let logo = &manifest.files[0];
+        assert_eq!(logo.path, Path::new("assets/logo.png"));
+        assert_eq!(
+            logo.output.as_deref(),
+            Some(Path::new("assets/dist/logo.png"))
+        );
+        assert_eq!(
+            logo.steps[0],
+            Step::Strip {
+                keep: vec![ChunkType::from_str("iCCP").unwrap()]
+            }
+        );
+        assert_eq!(
+            logo.steps[1],
+            Step::Encode {
+                chunk_type: ChunkType::from_str("ruSt").unwrap(),
+                message: None,
+                file: Some(PathBuf::from("assets/payload.bin")),
+                method: StegoMethod::Chunk,
+                compress: Some(Compression::Zstd),
+                replace: true,
+            }
+        );
+        assert_eq!(logo.steps[2].name(), "set-text");
+
+        let icon = &manifest.files[1];
+        assert_eq!(icon.path, Path::new("/abs/icon.png"));
+        assert_eq!(
+            icon.steps,
+            [Step::Sign {
+                key: PathBuf::from("assets/release.key")
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_inline() {
+        let step = |s: &str| Step::from_str(s);
+        assert_eq!(
+            step("strip:keep=tEXt,keep=iCCP").unwrap(),
+            Step::Strip {
+                keep: vec![ChunkType::tEXt, ChunkType::iCCP]
+            }
+        );
+        assert_eq!(step("strip").unwrap(), Step::Strip { keep: Vec::new() });
+        assert_eq!(
+            step("encode:type=ruSt,file=msg.bin,compress=zstd,replace").unwrap(),
+            Step::Encode {
+                chunk_type: ChunkType::from_str("ruSt").unwrap(),
+                message: None,
+                file: Some(PathBuf::from("msg.bin")),
+                method: StegoMethod::Chunk,
+                compress: Some(Compression::Zstd),
+                replace: true,
+            }
+        );
+        assert_eq!(
+            step("sign:key=k.ed25519").unwrap(),
+            Step::Sign {
+                key: PathBuf::from("k.ed25519")
+            }
+        );
+
+        assert!(step("encode:type=ruSt,message=a,message=b").is_err());
+        assert!(step("encode:type=ruSt,message=a,replace=maybe").is_err());
+        assert!(step("remove:kind=ruSt").is_err());
+        assert!(step("paint").is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let parse = |text: &str| Manifest::parse(text, Path::new(""));
+        assert!(parse("").unwrap().files.is_empty());
+        assert!(parse("files = []").is_err());
+        assert!(parse("[[file]]\noutput = \"a.png\"").is_err());
+        assert!(parse("[[file]]\npath = \"a.png\"\n[[file.step]]\nop = \"paint\"").is_err());
+        assert!(parse(
+            "[[file]]\npath = \"a.png\"\n[[file.step]]\nop = \"encode\"\nchunk_type = \"ruSt\""
+        )
+        .is_err());
+        assert!(parse(
+            "[[file]]\npath = \"a.png\"\n[[file.step]]\nop = \"remove\"\nchunktype = \"ruSt\""
+        )
+        .is_err());
+    }
+}
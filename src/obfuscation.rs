@@ -0,0 +1,176 @@
+use crate::{
+    chunk::Chunk, chunk_type::ChunkType, crypto::stretch_passphrase, png::Png, stego::StegoError,
+};
+use anyhow::Result;
+use hkdf::Hkdf;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use sha2::Sha256;
+
+// Fixed, as the placement has to be found from the passphrase alone.
+const SALT: &[u8] = b"pngme chunk placement";
+const INFO: &[u8] = b"pngme chunk placement";
+const MASK_INFO: &[u8] = b"pngme chunk mask";
+
+struct Placement {
+    chunk_type: ChunkType,
+    position: u32,
+    mask: [u8; 32],
+}
+
+impl Placement {
+    // The stored data is masked too, so the envelope magic and header do not give the chunk away.
+    fn apply_mask(&self, data: &mut [u8]) {
+        let mut keystream = vec![0; data.len()];
+        ChaCha20Rng::from_seed(self.mask).fill_bytes(&mut keystream);
+        data.iter_mut().zip(keystream).for_each(|(b, k)| *b ^= k);
+    }
+}
+
+// Stretched with Argon2 first, so guessing the passphrase from the chunk type is slow.
+fn placement(passphrase: &str) -> Result<Placement> {
+    let key = stretch_passphrase(passphrase, SALT)?;
+    let hkdf = Hkdf::<Sha256>::from_prk(&key).expect("32 bytes is a valid HKDF key length");
+    let mut okm = [0; 8];
+    let mut mask = [0; 32];
+    hkdf.expand(INFO, &mut okm)
+        .and_then(|_| hkdf.expand(MASK_INFO, &mut mask))
+        .expect("8 and 32 bytes are valid HKDF output lengths");
+
+    // Ancillary, private, reserved bit clear; only the safe-to-copy bit is left to chance.
+    let letter = |b: u8, upper: bool| match upper {
+        true => b'A' + b % 26,
+        false => b'a' + b % 26,
+    };
+    let bytes = [
+        letter(okm[0], false),
+        letter(okm[1], false),
+        letter(okm[2], true),
+        letter(okm[3] / 2, okm[3] % 2 == 0),
+    ];
+
+    Ok(Placement {
+        chunk_type: ChunkType::try_from(bytes).expect("derived chunk type is valid"),
+        position: u32::from_be_bytes(okm[4..].try_into().unwrap()),
+        mask,
+    })
+}
+
+pub fn derive_chunk_type(passphrase: &str) -> Result<ChunkType> {
+    Ok(placement(passphrase)?.chunk_type)
+}
+
+impl Png {
+    // The chunk type and its position among the chunks are derived from the passphrase,
+    // so there is no fixed type for scanners to look for.
+    pub fn embed_obfuscated(&mut self, passphrase: &str, payload: &[u8]) -> Result<ChunkType> {
+        let placement = placement(passphrase)?;
+        let Placement {
+            chunk_type,
+            position,
+            ..
+        } = placement;
+
+        // Anywhere after IHDR and before IEND, but never between two IDAT chunks.
+        let chunks = self.chunks();
+        let candidates: Vec<usize> = (1..chunks.len().max(1))
+            .filter(|&i| {
+                !(*chunks[i - 1].chunk_type() == ChunkType::IDAT
+                    && *chunks[i].chunk_type() == ChunkType::IDAT)
+            })
+            .collect();
+        let index = match candidates.len() {
+            0 => chunks.len(),
+            n => candidates[position as usize % n],
+        };
+
+        let mut chunks = chunks.to_vec();
+        let mut data = payload.to_vec();
+        placement.apply_mask(&mut data);
+        chunks.insert(index, Chunk::new(chunk_type, data));
+        self.replace_chunks(chunks);
+
+        Ok(chunk_type)
+    }
+
+    pub fn extract_obfuscated(&self, passphrase: &str) -> Result<Vec<u8>> {
+        let placement = placement(passphrase)?;
+        let chunk = self
+            .chunks()
+            .iter()
+            .find(|c| *c.chunk_type() == placement.chunk_type)
+            .ok_or(StegoError::NoPayload)?;
+
+        let mut data = chunk.chunk_data().to_vec();
+        placement.apply_mask(&mut data);
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{builder::PngBuilder, envelope::Envelope};
+    use std::str::FromStr;
+
+    fn testing_png() -> Png {
+        PngBuilder::new()
+            .chunk(Chunk::new(ChunkType::IHDR, vec![0; 13]))
+            .chunk(Chunk::new(ChunkType::from_str("gAMA").unwrap(), vec![0; 4]))
+            .chunk(Chunk::new(ChunkType::IDAT, vec![1]))
+            .chunk(Chunk::new(ChunkType::IDAT, vec![2]))
+            .chunk(Chunk::new(ChunkType::IEND, vec![]))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_derived_chunk_type() {
+        for passphrase in ["hunter2", "correct horse battery staple", ""] {
+            let chunk_type = derive_chunk_type(passphrase).unwrap();
+            assert!(!chunk_type.is_critical());
+            assert!(!chunk_type.is_public());
+            assert!(chunk_type.is_reserved_bit_valid());
+            assert_eq!(chunk_type, derive_chunk_type(passphrase).unwrap());
+        }
+
+        assert_ne!(
+            derive_chunk_type("hunter2").unwrap(),
+            derive_chunk_type("hunter3").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_obfuscated_round_trip() {
+        for passphrase in ["a", "b", "c", "d", "e", "f"] {
+            let mut png = testing_png();
+            let chunk_type = png.embed_obfuscated(passphrase, b"secret").unwrap();
+
+            let chunks = png.chunks();
+            let index = chunks
+                .iter()
+                .position(|c| *c.chunk_type() == chunk_type)
+                .unwrap();
+            assert!(index > 0 && index < chunks.len() - 1);
+            assert!(
+                !(*chunks[index - 1].chunk_type() == ChunkType::IDAT
+                    && *chunks[index + 1].chunk_type() == ChunkType::IDAT)
+            );
+
+            assert_ne!(chunks[index].chunk_data(), b"secret");
+            assert_eq!(png.extract_obfuscated(passphrase).unwrap(), b"secret");
+            assert!(png.extract_obfuscated("wrong").is_err());
+        }
+    }
+
+    #[test]
+    fn test_masks_envelope() {
+        let bytes = Envelope::new(b"secret".to_vec()).to_bytes().unwrap();
+        let mut png = testing_png();
+        let chunk_type = png.embed_obfuscated("hunter2", &bytes).unwrap();
+
+        let chunk = png.chunk_by_type(&chunk_type.to_string()).unwrap();
+        assert!(!Envelope::is_envelope(chunk.chunk_data()));
+        assert_eq!(png.extract_obfuscated("hunter2").unwrap(), bytes);
+    }
+}
@@ -0,0 +1,110 @@
+use anyhow::Result;
+use thiserror::Error;
+
+pub const SPEC_MAX_CHUNK_LENGTH: u32 = (1 << 31) - 1;
+pub const DEFAULT_MAX_CHUNK_LENGTH: u32 = 256 * 1024 * 1024;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    #[error("Parsing needs {needed:?} bytes, more than the memory budget of {budget:?} bytes.")]
+    MemoryBudgetExceeded { needed: usize, budget: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseOptions {
+    pub max_chunk_length: u32,
+    pub lenient_chunk_types: bool,
+    // Bounds the chunk data a whole parse may buffer, for untrusted input where many chunks just
+    // under max_chunk_length would otherwise add up. None is unbounded.
+    pub max_total_memory: Option<usize>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            max_chunk_length: DEFAULT_MAX_CHUNK_LENGTH,
+            lenient_chunk_types: false,
+            max_total_memory: None,
+        }
+    }
+}
+
+impl ParseOptions {
+    pub fn unlimited() -> Self {
+        Self {
+            max_chunk_length: SPEC_MAX_CHUNK_LENGTH,
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn effective_max_chunk_length(&self) -> u32 {
+        self.max_chunk_length.min(SPEC_MAX_CHUNK_LENGTH)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct VerifyOptions {
+    // Checks chunk CRCs on the rayon thread pool once the structure has been read, which pays off
+    // when large IDAT chunks dominate a file.
+    pub parallel: bool,
+}
+
+// What a parse has buffered so far, charged before each allocation so a file over budget fails
+// without the allocation ever happening.
+#[derive(Debug)]
+pub(crate) struct MemoryBudget {
+    used: usize,
+    budget: Option<usize>,
+}
+
+impl MemoryBudget {
+    pub(crate) fn new(options: &ParseOptions) -> Self {
+        Self {
+            used: 0,
+            budget: options.max_total_memory,
+        }
+    }
+
+    pub(crate) fn charge(&mut self, bytes: usize) -> Result<()> {
+        let needed = self.used.saturating_add(bytes);
+        match self.budget {
+            Some(budget) if needed > budget => {
+                Err(ParseError::MemoryBudgetExceeded { needed, budget }.into())
+            }
+            _ => {
+                self.used = needed;
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_budget() {
+        let mut unbounded = MemoryBudget::new(&ParseOptions::default());
+        assert!(unbounded.charge(usize::MAX).is_ok());
+        assert!(unbounded.charge(1).is_ok());
+
+        let mut budget = MemoryBudget::new(&ParseOptions {
+            max_total_memory: Some(100),
+            ..Default::default()
+        });
+        assert!(budget.charge(60).is_ok());
+        assert!(budget.charge(40).is_ok());
+        assert_eq!(
+            budget
+                .charge(1)
+                .unwrap_err()
+                .downcast::<ParseError>()
+                .unwrap(),
+            ParseError::MemoryBudgetExceeded {
+                needed: 101,
+                budget: 100
+            }
+        );
+    }
+}
@@ -0,0 +1,670 @@
+use base64::Engine;
+use clap::ValueEnum;
+use pngme::{
+    chunk::Chunk,
+    detect::{DetectionReport, Finding},
+    diff::ChunkDiff,
+    envelope::Envelope,
+    lint::LintReport,
+};
+use serde_json::{json, Value};
+use std::{
+    io::{self, IsTerminal},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+// Set once from --quiet.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed)
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+// Normal output, which --quiet suppresses. Warnings and errors are always shown.
+macro_rules! out {
+    ($($arg:tt)*) => {
+        if !$crate::output::is_quiet() {
+            print!($($arg)*)
+        }
+    };
+}
+
+macro_rules! outln {
+    ($($arg:tt)*) => {
+        if !$crate::output::is_quiet() {
+            println!($($arg)*)
+        }
+    };
+}
+
+// Informational messages on standard error, also suppressed by --quiet.
+macro_rules! noteln {
+    ($($arg:tt)*) => {
+        if !$crate::output::is_quiet() {
+            eprintln!($($arg)*)
+        }
+    };
+}
+
+pub(crate) use {noteln, out, outln};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ReportFormat {
+    /// Human readable text
+    #[default]
+    Text,
+    /// JSON, with binary data as base64
+    Json,
+}
+
+// Listing commands print one row per item, so they can also be laid out as a table or CSV.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ListFormat {
+    /// Human readable text
+    #[default]
+    Text,
+    /// JSON, with binary data as base64
+    Json,
+    /// Aligned columns
+    Table,
+    /// Comma separated values with a header row
+    Csv,
+    /// YAML, with binary data as base64
+    Yaml,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ColorChoice {
+    /// Color when standard output is a terminal and NO_COLOR is unset
+    #[default]
+    Auto,
+    /// Always color, even when piped
+    Always,
+    /// Never color
+    Never,
+}
+
+impl ColorChoice {
+    pub fn enabled(self) -> bool {
+        match self {
+            Self::Auto => io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+            Self::Always => true,
+            Self::Never => false,
+        }
+    }
+}
+
+// A column header and the JSON pointer of its value within a row.
+pub type Column = (&'static str, &'static str);
+
+pub fn print_listing(format: ListFormat, columns: &[Column], rows: Vec<Value>) {
+    match format {
+        ListFormat::Text => unreachable!("text listings are printed by each command"),
+        ListFormat::Json => print_json(&Value::Array(rows)),
+        ListFormat::Yaml => out!("{}", to_yaml(&Value::Array(rows))),
+        ListFormat::Table => out!("{}", to_table(columns, &rows)),
+        ListFormat::Csv => out!("{}", to_csv(columns, &rows)),
+    }
+}
+
+fn cells(columns: &[Column], row: &Value) -> Vec<String> {
+    columns
+        .iter()
+        .map(|(_, pointer)| match row.pointer(pointer) {
+            None | Some(Value::Null) => String::new(),
+            Some(Value::String(s)) => s.clone(),
+            Some(value) => value.to_string(),
+        })
+        .collect()
+}
+
+fn to_table(columns: &[Column], rows: &[Value]) -> String {
+    let mut lines = vec![columns
+        .iter()
+        .map(|(header, _)| header.to_string())
+        .collect()];
+    lines.extend(rows.iter().map(|row| cells(columns, row)));
+
+    let widths: Vec<usize> = (0..columns.len())
+        .map(|i| {
+            lines
+                .iter()
+                .map(|line: &Vec<String>| line[i].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let mut table = String::new();
+    for line in lines {
+        let padded: Vec<String> = line
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        table.push_str(padded.join("  ").trim_end());
+        table.push('\n');
+    }
+
+    table
+}
+
+fn to_csv(columns: &[Column], rows: &[Value]) -> String {
+    let field = |cell: &str| match cell.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", cell.replace('"', "\"\"")),
+        false => cell.to_string(),
+    };
+
+    let mut csv = String::new();
+    let headers: Vec<String> = columns.iter().map(|(header, _)| field(header)).collect();
+    csv.push_str(&headers.join(","));
+    csv.push('\n');
+    for row in rows {
+        let fields: Vec<String> = cells(columns, row).iter().map(|cell| field(cell)).collect();
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+fn to_yaml(value: &Value) -> String {
+    let mut yaml = String::new();
+    write_yaml(&mut yaml, value, 0);
+    yaml
+}
+
+// Writes a value whose first line is already positioned, indenting the lines after it.
+fn write_yaml(out: &mut String, value: &Value, indent: usize) {
+    let pad = " ".repeat(indent);
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (i, (key, value)) in map.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(&pad);
+                }
+                out.push_str(&yaml_key(key));
+                out.push(':');
+                match value {
+                    Value::Object(map) if !map.is_empty() => {
+                        out.push('\n');
+                        out.push_str(&pad);
+                        out.push_str("  ");
+                        write_yaml(out, value, indent + 2);
+                    }
+                    Value::Array(items) if !items.is_empty() => {
+                        out.push('\n');
+                        out.push_str(&pad);
+                        write_yaml(out, value, indent);
+                    }
+                    scalar => {
+                        out.push(' ');
+                        out.push_str(&yaml_scalar(scalar));
+                        out.push('\n');
+                    }
+                }
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(&pad);
+                }
+                out.push_str("- ");
+                write_yaml(out, item, indent + 2);
+            }
+        }
+        scalar => {
+            out.push_str(&yaml_scalar(scalar));
+            out.push('\n');
+        }
+    }
+}
+
+fn yaml_key(key: &str) -> String {
+    match !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        true => key.to_string(),
+        false => yaml_scalar(&Value::String(key.to_string())),
+    }
+}
+
+// JSON strings are valid double quoted YAML scalars, which sidesteps YAML's implicit typing.
+fn yaml_scalar(value: &Value) -> String {
+    match value {
+        Value::Array(_) => "[]".to_string(),
+        Value::Object(_) => "{}".to_string(),
+        scalar => scalar.to_string(),
+    }
+}
+
+pub const DATA_PREVIEW_LENGTH: usize = 16;
+
+// Data over the limit as its first and last bytes, half the limit each.
+pub fn preview_ends(data: &[u8], limit: Option<usize>) -> Option<(&[u8], &[u8])> {
+    let limit = limit.filter(|&limit| data.len() > limit)?;
+    let head = limit.div_ceil(2);
+    Some((&data[..head], &data[data.len() - (limit - head)..]))
+}
+
+// Printable ASCII as is and everything else as a dot, like the right hand column of hexdump -C.
+pub fn ascii_preview(data: &[u8]) -> String {
+    data.iter()
+        .map(|&byte| match byte {
+            0x20..=0x7e => byte as char,
+            _ => '.',
+        })
+        .collect()
+}
+
+// Sixteen bytes per line as offset, hex in two groups of eight and ASCII, ending with the length,
+// the same layout as hexdump -C.
+pub fn hexdump(data: &[u8]) -> String {
+    let mut dump = String::new();
+    for (line, bytes) in data.chunks(16).enumerate() {
+        let hex: Vec<String> = (0..16)
+            .map(|i| match bytes.get(i) {
+                Some(byte) => format!("{:02x}", byte),
+                None => "  ".to_string(),
+            })
+            .collect();
+        dump.push_str(&format!(
+            "{:08x}  {}  {}  |{}|\n",
+            line * 16,
+            hex[..8].join(" "),
+            hex[8..].join(" "),
+            ascii_preview(bytes)
+        ));
+    }
+    dump.push_str(&format!("{:08x}\n", data.len()));
+
+    dump
+}
+
+// The hexdump lines that differ between two buffers, prefixed - and +.
+pub fn hexdump_diff(before: &[u8], after: &[u8]) -> String {
+    let (before, after) = (hexdump(before), hexdump(after));
+    let (before, after): (Vec<&str>, Vec<&str>) =
+        (before.lines().collect(), after.lines().collect());
+    let mut dump = String::new();
+    for line in 0..before.len().max(after.len()) {
+        let (old, new) = (before.get(line), after.get(line));
+        if old == new {
+            continue;
+        }
+        if let Some(old) = old {
+            dump.push_str(&format!("- {}\n", old));
+        }
+        if let Some(new) = new {
+            dump.push_str(&format!("+ {}\n", new));
+        }
+    }
+
+    dump
+}
+
+fn colored_type(chunk: &Chunk) -> String {
+    let chunk_type = chunk.chunk_type();
+    // Bold for critical, cyan for public ancillary and yellow for private chunks.
+    let style = match (chunk_type.is_critical(), chunk_type.is_public()) {
+        (true, true) => "1",
+        (false, true) => "36",
+        (_, false) => "33",
+    };
+    format!("\x1b[{}m{}\x1b[0m", style, chunk_type)
+}
+
+// The text listing of print: one aligned row per chunk with a preview of its data, cut down to its
+// ends past the limit.
+pub fn chunk_table(
+    chunks: &[Chunk],
+    offsets: &[usize],
+    limit: Option<usize>,
+    color: bool,
+) -> String {
+    let headers = ["index", "offset", "type", "length", "crc", "data"];
+    let mut lines = vec![headers.map(String::from)];
+    for (index, chunk) in chunks.iter().enumerate() {
+        let data = chunk.chunk_data();
+        let preview = match preview_ends(data, limit) {
+            Some((head, tail)) => format!("{}…{}", ascii_preview(head), ascii_preview(tail)),
+            None => ascii_preview(data),
+        };
+        lines.push([
+            index.to_string(),
+            offsets[index].to_string(),
+            chunk.chunk_type().to_string(),
+            chunk.length().to_string(),
+            format!("{:08x}", chunk.crc()),
+            preview,
+        ]);
+    }
+
+    let widths: Vec<usize> = (0..headers.len())
+        .map(|i| {
+            lines
+                .iter()
+                .map(|line| line[i].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let mut table = String::new();
+    for (row, line) in lines.iter().enumerate() {
+        let padded: Vec<String> = line
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(column, (cell, &width))| match column {
+                // Chunk types are always four characters, so color never upsets the alignment.
+                2 if color && row > 0 => colored_type(&chunks[row - 1]),
+                0 | 1 | 3 => format!("{:>width$}", cell, width = width),
+                _ => format!("{:<width$}", cell, width = width),
+            })
+            .collect();
+        table.push_str(padded.join("  ").trim_end());
+        table.push('\n');
+    }
+
+    table
+}
+
+pub fn print_json(value: &Value) {
+    outln!(
+        "{}",
+        serde_json::to_string_pretty(value).expect("JSON values always serialize")
+    );
+}
+
+fn base64(data: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(data)
+}
+
+pub fn chunk_json(index: usize, offset: usize, chunk: &Chunk) -> Value {
+    let chunk_type = chunk.chunk_type();
+    json!({
+        "index": index,
+        "offset": offset,
+        "type": chunk_type.to_string(),
+        "length": chunk.length(),
+        "crc": chunk.crc(),
+        "description": chunk_type.description(),
+        "flags": {
+            "critical": chunk_type.is_critical(),
+            "public": chunk_type.is_public(),
+            "reserved_bit_valid": chunk_type.is_reserved_bit_valid(),
+            "safe_to_copy": chunk_type.is_safe_to_copy(),
+        },
+        "data": base64(chunk.chunk_data()),
+    })
+}
+
+pub fn envelope_json(envelope: &Envelope) -> Value {
+    json!({
+        "mime_type": envelope.mime_type,
+        "filename": envelope.filename,
+        "length": envelope.payload.len(),
+        "payload": base64(&envelope.payload),
+    })
+}
+
+pub fn raw_json(payload: &[u8]) -> Value {
+    json!({
+        "length": payload.len(),
+        "payload": base64(payload),
+    })
+}
+
+pub fn detection_json(report: &DetectionReport) -> Value {
+    let findings: Vec<Value> = report
+        .findings
+        .iter()
+        .map(|finding| {
+            json!({
+                "kind": finding_kind(finding),
+                "weight": finding.weight(),
+                "offset": finding.offset(),
+                "description": finding.to_string(),
+            })
+        })
+        .collect();
+
+    json!({
+        "score": report.score(),
+        "suspicious": report.is_suspicious(),
+        "findings": findings,
+    })
+}
+
+pub fn diff_json(diffs: &[ChunkDiff]) -> Value {
+    let summary = |chunk: &Chunk| json!({"length": chunk.length(), "crc": chunk.crc()});
+    let changes: Vec<Value> = diffs
+        .iter()
+        .map(|diff| {
+            let (change, before, after) = match diff {
+                ChunkDiff::Added { chunk, .. } => ("added", None, Some(summary(chunk))),
+                ChunkDiff::Removed { chunk, .. } => ("removed", Some(summary(chunk)), None),
+                ChunkDiff::Modified { before, after, .. } => {
+                    ("modified", Some(summary(before)), Some(summary(after)))
+                }
+            };
+            json!({
+                "change": change,
+                "type": diff.chunk_type().to_string(),
+                "index": diff.index(),
+                "before": before,
+                "after": after,
+            })
+        })
+        .collect();
+
+    json!({
+        "identical": diffs.is_empty(),
+        "changes": changes,
+    })
+}
+
+pub fn lint_json(report: &LintReport) -> Value {
+    let checks: Vec<Value> = report
+        .checks
+        .iter()
+        .map(|check| {
+            let issues: Vec<Value> = check
+                .issues
+                .iter()
+                .map(|issue| {
+                    json!({
+                        "severity": issue.severity.to_string(),
+                        "message": issue.message,
+                    })
+                })
+                .collect();
+            json!({
+                "name": check.name,
+                "status": check.status().to_string(),
+                "issues": issues,
+            })
+        })
+        .collect();
+
+    json!({
+        "status": report.status().to_string(),
+        "checks": checks,
+    })
+}
+
+fn finding_kind(finding: &Finding) -> &'static str {
+    match finding {
+        Finding::UnknownChunk { .. } => "unknown_chunk",
+        Finding::TrailingData { .. } => "trailing_data",
+        Finding::AnomalousChunkSize { .. } => "anomalous_chunk_size",
+        Finding::DuplicateChunk { .. } => "duplicate_chunk",
+        Finding::LsbChiSquare { .. } => "lsb_chi_square",
+        Finding::InvalidChunkType { .. } => "invalid_chunk_type",
+        Finding::InvalidCrc { .. } => "invalid_crc",
+        Finding::TruncatedChunk { .. } => "truncated_chunk",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pngme::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_chunk_json() {
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"hi".to_vec());
+        let value = chunk_json(2, 33, &chunk);
+
+        assert_eq!(value["index"], 2);
+        assert_eq!(value["offset"], 33);
+        assert_eq!(value["type"], "ruSt");
+        assert_eq!(value["length"], 2);
+        assert_eq!(value["crc"], chunk.crc());
+        assert_eq!(value["description"], Value::Null);
+        assert_eq!(value["flags"]["critical"], false);
+        assert_eq!(value["flags"]["safe_to_copy"], true);
+        assert_eq!(value["data"], "aGk=");
+    }
+
+    #[test]
+    fn test_envelope_json() {
+        let mut envelope = Envelope::new(b"hello".to_vec());
+        envelope.filename = Some("note.txt".to_string());
+        let value = envelope_json(&envelope);
+
+        assert_eq!(value["filename"], "note.txt");
+        assert_eq!(value["mime_type"], Value::Null);
+        assert_eq!(value["length"], 5);
+        assert_eq!(value["payload"], "aGVsbG8=");
+    }
+
+    const COLUMNS: &[Column] = &[("type", "/type"), ("length", "/length"), ("note", "/note")];
+
+    #[test]
+    fn test_table() {
+        let rows = vec![
+            json!({"type": "IHDR", "length": 13, "note": null}),
+            json!({"type": "ruSt", "length": 1024, "note": "hidden"}),
+        ];
+
+        assert_eq!(
+            to_table(COLUMNS, &rows),
+            "type  length  note\nIHDR  13\nruSt  1024    hidden\n"
+        );
+    }
+
+    #[test]
+    fn test_csv() {
+        let rows = vec![json!({"type": "tEXt", "length": 5, "note": "a, \"quoted\" note"})];
+
+        assert_eq!(
+            to_csv(COLUMNS, &rows),
+            "type,length,note\ntEXt,5,\"a, \"\"quoted\"\" note\"\n"
+        );
+    }
+
+    #[test]
+    fn test_yaml() {
+        let value = json!([
+            {"index": 0, "flags": {"critical": true}, "description": null, "tags": ["a", "b"]},
+            {"index": 1, "flags": {}, "description": "yes: no", "tags": []},
+        ]);
+
+        assert_eq!(
+            to_yaml(&value),
+            "- description: null\n  flags:\n    critical: true\n  index: 0\n  tags:\n  - \"a\"\n  - \"b\"\n\
+             - description: \"yes: no\"\n  flags: {}\n  index: 1\n  tags: []\n"
+        );
+        assert_eq!(to_yaml(&json!([])), "[]\n");
+    }
+
+    #[test]
+    fn test_chunk_table() {
+        let chunks = [
+            Chunk::new(ChunkType::IHDR, vec![0, 0, 0, 1]),
+            Chunk::new(
+                ChunkType::from_str("ruSt").unwrap(),
+                b"a hidden message\n!".to_vec(),
+            ),
+        ];
+        let offsets = [8, 24];
+
+        assert_eq!(
+            chunk_table(&chunks, &offsets, Some(DATA_PREVIEW_LENGTH), false),
+            format!(
+                "index  offset  type  length  crc       data\n    \
+                     0       8  IHDR       4  {:08x}  ....\n    \
+                     1      24  ruSt      18  {:08x}  a hidden…essage.!\n",
+                chunks[0].crc(),
+                chunks[1].crc()
+            )
+        );
+        let full = chunk_table(&chunks, &offsets, None, false);
+        assert!(full.ends_with("a hidden message.!\n"));
+
+        let colored = chunk_table(&chunks, &offsets, Some(DATA_PREVIEW_LENGTH), true);
+        assert!(colored.contains("\x1b[1mIHDR\x1b[0m"));
+        assert!(colored.contains("\x1b[33mruSt\x1b[0m"));
+        assert!(colored.starts_with("index  offset  type  "));
+    }
+
+    #[test]
+    fn test_preview_ends() {
+        assert_eq!(preview_ends(b"abcdef", None), None);
+        assert_eq!(preview_ends(b"abcdef", Some(6)), None);
+        assert_eq!(
+            preview_ends(b"abcdef", Some(3)),
+            Some((&b"ab"[..], &b"f"[..]))
+        );
+        assert_eq!(preview_ends(b"abcdef", Some(0)), Some((&b""[..], &b""[..])));
+    }
+
+    #[test]
+    fn test_hexdump() {
+        assert_eq!(
+            hexdump(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0"),
+            "00000000  89 50 4e 47 0d 0a 1a 0a  00 00 00 0d 49 48 44 52  |.PNG........IHDR|\n\
+             00000010  00 00                                             |..|\n\
+             00000012\n"
+        );
+        assert_eq!(hexdump(b""), "00000000\n");
+    }
+
+    #[test]
+    fn test_hexdump_diff() {
+        let before = [0u8; 32];
+        let mut after = before;
+        after[20] = b'A';
+        let diff = hexdump_diff(&before, &after);
+
+        assert_eq!(diff.lines().count(), 2);
+        assert!(diff.starts_with("- 00000010  00"));
+        assert!(diff.contains("+ 00000010  00 00 00 00 41"));
+        assert_eq!(hexdump_diff(&before, &before), "");
+    }
+
+    #[test]
+    fn test_detection_json() {
+        let report = DetectionReport {
+            findings: vec![Finding::TrailingData {
+                length: 4,
+                offset: 100,
+            }],
+        };
+        let value = detection_json(&report);
+
+        assert_eq!(value["suspicious"], true);
+        assert_eq!(value["findings"][0]["kind"], "trailing_data");
+        assert_eq!(value["findings"][0]["offset"], 100);
+        assert_eq!(
+            value["findings"][0]["description"],
+            "4 bytes after IEND at offset 100"
+        );
+    }
+}
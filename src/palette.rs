@@ -0,0 +1,291 @@
+use crate::{chunk::Chunk, chunk_type::ChunkType, ihdr::ColorType, png::Png};
+use anyhow::Result;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum PaletteError {
+    #[error("PLTE data length has to be a multiple of 3, got {found:?}.")]
+    InvalidLength { found: usize },
+    #[error("A palette holds 1 to 256 entries, got {found:?}.")]
+    InvalidEntryCount { found: usize },
+    #[error("Palette index {index:?} is out of range for {entries:?} entries.")]
+    IndexOutOfRange { index: usize, entries: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PaletteEntry {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: u8,
+}
+
+impl PaletteEntry {
+    pub fn rgb(red: u8, green: u8, blue: u8) -> Self {
+        Self::rgba(red, green, blue, u8::MAX)
+    }
+
+    pub fn rgba(red: u8, green: u8, blue: u8, alpha: u8) -> Self {
+        Self {
+            red,
+            green,
+            blue,
+            alpha,
+        }
+    }
+
+    pub fn is_opaque(&self) -> bool {
+        self.alpha == u8::MAX
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Palette {
+    entries: Vec<PaletteEntry>,
+}
+
+impl Palette {
+    pub fn new(entries: Vec<PaletteEntry>) -> Result<Self> {
+        if entries.is_empty() || entries.len() > 256 {
+            return Err(PaletteError::InvalidEntryCount {
+                found: entries.len(),
+            }
+            .into());
+        }
+
+        Ok(Self { entries })
+    }
+
+    pub fn from_chunks(plte: &Chunk, trns: Option<&Chunk>) -> Result<Self> {
+        let data = plte.chunk_data();
+        if !data.len().is_multiple_of(3) {
+            return Err(PaletteError::InvalidLength { found: data.len() }.into());
+        }
+
+        let alphas = trns.map(|c| c.chunk_data()).unwrap_or_default();
+        let entries = data
+            .chunks_exact(3)
+            .enumerate()
+            .map(|(i, rgb)| {
+                let alpha = alphas.get(i).copied().unwrap_or(u8::MAX);
+                PaletteEntry::rgba(rgb[0], rgb[1], rgb[2], alpha)
+            })
+            .collect();
+
+        Self::new(entries)
+    }
+
+    pub fn entries(&self) -> &[PaletteEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&PaletteEntry> {
+        self.entries.get(index)
+    }
+
+    pub fn set(&mut self, index: usize, entry: PaletteEntry) -> Result<()> {
+        let entries = self.entries.len();
+        match self.entries.get_mut(index) {
+            Some(existing) => {
+                *existing = entry;
+                Ok(())
+            }
+            None => Err(PaletteError::IndexOutOfRange { index, entries }.into()),
+        }
+    }
+
+    pub fn set_alpha(&mut self, index: usize, alpha: u8) -> Result<()> {
+        let entries = self.entries.len();
+        match self.entries.get_mut(index) {
+            Some(existing) => {
+                existing.alpha = alpha;
+                Ok(())
+            }
+            None => Err(PaletteError::IndexOutOfRange { index, entries }.into()),
+        }
+    }
+
+    pub fn push(&mut self, entry: PaletteEntry) -> Result<()> {
+        if self.entries.len() == 256 {
+            return Err(PaletteError::InvalidEntryCount { found: 257 }.into());
+        }
+
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    pub fn has_transparency(&self) -> bool {
+        self.entries.iter().any(|e| !e.is_opaque())
+    }
+
+    pub fn to_plte_chunk(&self) -> Chunk {
+        let data = self
+            .entries
+            .iter()
+            .flat_map(|e| [e.red, e.green, e.blue])
+            .collect();
+
+        Chunk::new(ChunkType::PLTE, data)
+    }
+
+    pub fn to_trns_chunk(&self) -> Option<Chunk> {
+        // Trailing opaque entries can be left out of tRNS.
+        let length = self.entries.iter().rposition(|e| !e.is_opaque())? + 1;
+        let data = self.entries[..length].iter().map(|e| e.alpha).collect();
+
+        Some(Chunk::new(ChunkType::tRNS, data))
+    }
+}
+
+impl Png {
+    pub fn palette(&self) -> Result<Option<Palette>> {
+        let Some(plte) = self
+            .chunks()
+            .iter()
+            .find(|c| *c.chunk_type() == ChunkType::PLTE)
+        else {
+            return Ok(None);
+        };
+
+        let trns = self
+            .chunks()
+            .iter()
+            .find(|c| *c.chunk_type() == ChunkType::tRNS)
+            .filter(|_| self.trns_holds_palette_alphas());
+
+        Palette::from_chunks(plte, trns).map(Some)
+    }
+
+    pub fn set_palette(&mut self, palette: &Palette) -> Result<()> {
+        if palette.is_empty() {
+            return Err(PaletteError::InvalidEntryCount { found: 0 }.into());
+        }
+
+        if self.trns_holds_palette_alphas() {
+            let _ = self.remove_all_chunks("tRNS");
+            if let Some(trns) = palette.to_trns_chunk() {
+                self.insert_chunk_ordered(trns);
+            }
+        }
+
+        let plte = palette.to_plte_chunk();
+        match self
+            .chunks_mut()
+            .iter_mut()
+            .find(|c| *c.chunk_type() == ChunkType::PLTE)
+        {
+            Some(existing) => *existing = plte,
+            None => self.insert_chunk_ordered(plte),
+        }
+
+        Ok(())
+    }
+
+    pub fn remove_palette(&mut self) -> Result<Option<Palette>> {
+        let palette = self.palette()?;
+        if palette.is_some() {
+            let _ = self.remove_all_chunks("PLTE");
+            if self.trns_holds_palette_alphas() {
+                let _ = self.remove_all_chunks("tRNS");
+            }
+        }
+
+        Ok(palette)
+    }
+
+    // For greyscale and truecolor images tRNS holds a single transparent colour instead.
+    fn trns_holds_palette_alphas(&self) -> bool {
+        !matches!(self.header(), Ok(ihdr) if ihdr.color_type != ColorType::Indexed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{builder::PngBuilder, ihdr::Ihdr};
+
+    fn testing_png(color_type: ColorType) -> Png {
+        PngBuilder::new()
+            .chunk(Ihdr::new(4, 4, 8, color_type).unwrap().into())
+            .chunk(Chunk::new(
+                ChunkType::PLTE,
+                vec![255, 0, 0, 0, 255, 0, 0, 0, 255],
+            ))
+            .chunk(Chunk::new(ChunkType::tRNS, vec![0, 128]))
+            .chunk(Chunk::new(ChunkType::IDAT, vec![1, 2, 3]))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_palette() {
+        let png = testing_png(ColorType::Indexed);
+        let palette = png.palette().unwrap().unwrap();
+
+        assert_eq!(palette.len(), 3);
+        assert_eq!(palette.get(0), Some(&PaletteEntry::rgba(255, 0, 0, 0)));
+        assert_eq!(palette.get(1), Some(&PaletteEntry::rgba(0, 255, 0, 128)));
+        assert_eq!(palette.get(2), Some(&PaletteEntry::rgb(0, 0, 255)));
+        assert!(palette.has_transparency());
+    }
+
+    #[test]
+    fn test_palette_ignores_trns_for_truecolor() {
+        let png = testing_png(ColorType::Rgb);
+        let palette = png.palette().unwrap().unwrap();
+        assert!(!palette.has_transparency());
+    }
+
+    #[test]
+    fn test_palette_invalid_length() {
+        let plte = Chunk::new(ChunkType::PLTE, vec![1, 2, 3, 4]);
+        assert_eq!(
+            Palette::from_chunks(&plte, None)
+                .err()
+                .unwrap()
+                .downcast::<PaletteError>()
+                .unwrap(),
+            PaletteError::InvalidLength { found: 4 }
+        );
+    }
+
+    #[test]
+    fn test_set_palette() {
+        let mut png = testing_png(ColorType::Indexed);
+        let mut palette = png.palette().unwrap().unwrap();
+
+        palette.set_alpha(0, 255).unwrap();
+        palette.set_alpha(1, 255).unwrap();
+        palette.push(PaletteEntry::rgba(1, 2, 3, 4)).unwrap();
+        assert!(palette.set_alpha(4, 0).is_err());
+
+        png.set_palette(&palette).unwrap();
+        assert_eq!(png.palette().unwrap().unwrap(), palette);
+        assert_eq!(
+            png.chunk_by_type("tRNS").unwrap().chunk_data(),
+            [255, 255, 255, 4]
+        );
+
+        palette.set_alpha(3, 255).unwrap();
+        png.set_palette(&palette).unwrap();
+        assert!(png.chunk_by_type("tRNS").is_none());
+        assert_eq!(png.chunk_by_type("PLTE").unwrap().length(), 12);
+    }
+
+    #[test]
+    fn test_remove_palette() {
+        let mut png = testing_png(ColorType::Indexed);
+        assert!(png.remove_palette().unwrap().is_some());
+        assert!(png.chunk_by_type("PLTE").is_none());
+        assert!(png.chunk_by_type("tRNS").is_none());
+        assert!(png.remove_palette().unwrap().is_none());
+    }
+}
@@ -1,24 +1,90 @@
-use crate::{chunk::Chunk, chunk_type::ChunkType};
+use crate::{
+    builder::canonical_rank,
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    ihdr::Ihdr,
+    options::{MemoryBudget, ParseOptions},
+};
 use anyhow::{bail, Error, Result};
 use std::{
+    collections::HashSet,
     fmt::Display,
-    io::{BufReader, Read},
+    io::{BufRead, Write},
 };
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Png {
     chunks: Vec<Chunk>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    touch_time: bool,
 }
 
 impl TryFrom<&[u8]> for Png {
     type Error = Error;
 
     fn try_from(value: &[u8]) -> Result<Self> {
-        let mut reader = BufReader::new(value);
+        Self::from_bytes_with_options(value, &ParseOptions::default())
+    }
+}
+
+impl Display for Png {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut string = String::new();
+
+        for chunk in self.chunks.clone() {
+            string += &chunk.to_string()
+        }
+
+        write!(f, "{}", string)
+    }
+}
+
+impl Png {
+    pub const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+    pub fn from_bytes_with_options(value: &[u8], options: &ParseOptions) -> Result<Self> {
+        let header = value.get(..8).unwrap_or(value);
+        if header != Self::STANDARD_HEADER {
+            bail!(
+                "Bad header (expected {:?}, got {:?})",
+                Self::STANDARD_HEADER,
+                header
+            )
+        }
 
+        let _span = tracing::debug_span!("parse", length = value.len()).entered();
+        let mut chunks = Vec::new();
+        let mut budget = MemoryBudget::new(options);
+        let mut offset = header.len();
+        while offset < value.len() {
+            let (chunk, length) = Chunk::from_slice_within(&value[offset..], options, &mut budget)
+                .inspect_err(|e| {
+                    // Located here rather than in a span per chunk, which costs more than the
+                    // parsing itself on files of small chunks.
+                    tracing::debug!(index = chunks.len(), offset, error = %e, "could not read chunk")
+                })?;
+            tracing::trace!(chunk_type = %chunk.chunk_type(), length = chunk.length(), "read chunk");
+            offset += length;
+            chunks.push(chunk);
+        }
+        tracing::debug!(chunks = chunks.len(), "parsed PNG");
+
+        Ok(Png::from_chunks(chunks))
+    }
+
+    // Parses chunks as they arrive and stops at IEND, so a stream such as a download is never
+    // buffered whole and anything after IEND is not read.
+    pub fn read_from(reader: impl BufRead) -> Result<Self> {
+        Self::read_from_with_options(reader, &ParseOptions::default())
+    }
+
+    pub fn read_from_with_options(
+        mut reader: impl BufRead,
+        options: &ParseOptions,
+    ) -> Result<Self> {
         let mut header = [0u8; 8];
         reader.read_exact(&mut header)?;
-
         if header != Self::STANDARD_HEADER {
             bail!(
                 "Bad header (expected {:?}, got {:?})",
@@ -28,70 +94,285 @@ impl TryFrom<&[u8]> for Png {
         }
 
         let mut chunks = Vec::new();
-        let mut length_buffer = [0u8; 4];
+        let mut budget = MemoryBudget::new(options);
+        while !reader.fill_buf()?.is_empty() {
+            let (chunk, _) = Chunk::read_from_within(&mut reader, options, &mut budget)?;
+            let end = *chunk.chunk_type() == ChunkType::IEND;
+            chunks.push(chunk);
+            if end {
+                break;
+            }
+        }
 
-        while let Ok(()) = reader.read_exact(&mut length_buffer) {
-            let final_position = 4 + u32::from_be_bytes(length_buffer) + 4;
-            let mut buffer = vec![0u8; usize::try_from(final_position)?];
-            reader.read_exact(&mut buffer)?;
+        Ok(Png::from_chunks(chunks))
+    }
+
+    pub fn from_chunks(chunks: Vec<Chunk>) -> Self {
+        Self {
+            chunks,
+            touch_time: false,
+        }
+    }
+
+    pub fn touch_time(&self) -> bool {
+        self.touch_time
+    }
+
+    pub fn set_touch_time(&mut self, touch_time: bool) {
+        self.touch_time = touch_time
+    }
+
+    fn modified(&mut self) {
+        if self.touch_time {
+            self.touch()
+        }
+    }
 
-            let all_bytes: Vec<u8> = length_buffer
+    pub fn append_chunk(&mut self, chunk: Chunk) {
+        self.chunks.push(chunk);
+        self.modified()
+    }
+
+    pub fn insert_chunk_ordered(&mut self, chunk: Chunk) {
+        let rank = canonical_rank(chunk.chunk_type());
+        match self
+            .chunks
+            .iter()
+            .position(|c| canonical_rank(c.chunk_type()) > rank)
+        {
+            Some(index) => self.chunks.insert(index, chunk),
+            None => self.chunks.push(chunk),
+        }
+        self.modified()
+    }
+
+    // Copies every chunk of the given types from another PNG, each to its canonical position.
+    // Chunks the specification allows only once replace any this PNG already has.
+    pub fn copy_chunks_from(&mut self, other: &Png, types: &[ChunkType]) -> usize {
+        let mut copied = 0;
+        for chunk_type in types {
+            let chunks: Vec<Chunk> = other
+                .chunks
                 .iter()
-                .copied()
-                .chain(buffer.into_iter())
+                .filter(|c| c.chunk_type() == chunk_type)
+                .cloned()
                 .collect();
+            if chunks.is_empty() {
+                continue;
+            }
+            if chunk_type.is_single_instance() {
+                self.chunks.retain(|c| c.chunk_type() != chunk_type);
+            }
+            copied += chunks.len();
+            for chunk in chunks {
+                self.insert_chunk_ordered(chunk);
+            }
+        }
 
-            let chunk = Chunk::try_from(all_bytes.as_slice())?;
-            chunks.push(chunk);
+        copied
+    }
+
+    // Removes all but the first of byte-identical ancillary or private chunks. Critical public
+    // chunks are left alone, repeating those is not harmless duplication.
+    pub fn dedupe(&mut self) -> Vec<Chunk> {
+        // The CRC follows from the type and data, so those two decide whether chunks are equal.
+        let mut seen = HashSet::new();
+        let duplicates: Vec<bool> = self
+            .chunks
+            .iter()
+            .map(|chunk| {
+                let chunk_type = chunk.chunk_type();
+                let removable = !chunk_type.is_critical() || !chunk_type.is_public();
+                let first = seen.insert((*chunk_type, chunk.chunk_data()));
+                removable && !first
+            })
+            .collect();
+
+        let (removed, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut self.chunks)
+            .into_iter()
+            .zip(duplicates)
+            .partition(|(_, duplicate)| *duplicate);
+        self.chunks = kept.into_iter().map(|(chunk, _)| chunk).collect();
+        let removed: Vec<Chunk> = removed.into_iter().map(|(chunk, _)| chunk).collect();
+
+        if !removed.is_empty() {
+            self.modified();
         }
+        removed
+    }
 
-        Ok(Png::from_chunks(chunks))
+    // Stable sorts the chunks into the order the specification recommends, which also makes IDAT
+    // contiguous. Returns whether anything moved.
+    pub fn canonicalize(&mut self) -> bool {
+        // The sort is stable, so it only moves anything when the ranks are out of order.
+        let moved = !self
+            .chunks
+            .is_sorted_by_key(|c| canonical_rank(c.chunk_type()));
+
+        if moved {
+            self.chunks.sort_by_key(|c| canonical_rank(c.chunk_type()));
+            self.modified();
+        }
+        moved
     }
-}
 
-impl Display for Png {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut string = String::new();
+    pub(crate) fn replace_chunks(&mut self, chunks: Vec<Chunk>) {
+        self.chunks = chunks;
+        self.modified()
+    }
 
-        for chunk in self.chunks.clone() {
-            string += &chunk.to_string()
+    pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk> {
+        self.remove_first_chunk(chunk_type)
+    }
+
+    pub fn remove_first_chunk(&mut self, chunk_type: &str) -> Result<Chunk> {
+        let Some(index) = self
+            .chunks
+            .iter()
+            .position(|c| c.chunk_type().to_string() == chunk_type)
+        else {
+            bail!("No chunk with that type")
+        };
+
+        let chunk = self.chunks.remove(index);
+        self.modified();
+        Ok(chunk)
+    }
+
+    // The nth chunk of a type, counting from zero, for files that hold several.
+    pub fn remove_nth_chunk(&mut self, chunk_type: &str, n: usize) -> Result<Chunk> {
+        let Some(index) = self.nth_position(chunk_type, n) else {
+            bail!("No chunk with that type at index {}", n)
+        };
+
+        let chunk = self.chunks.remove(index);
+        self.modified();
+        Ok(chunk)
+    }
+
+    pub fn remove_all_chunks(&mut self, chunk_type: &str) -> Result<Vec<Chunk>> {
+        let removed = self.retain(|c| c.chunk_type().to_string() != chunk_type);
+        if removed.is_empty() {
+            bail!("No chunk with that type")
         }
 
-        write!(f, "{}", string)
+        Ok(removed)
     }
-}
 
-impl Png {
-    pub const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+    // Keeps the chunks the predicate accepts, in their order, and returns the rest.
+    pub fn retain(&mut self, mut keep: impl FnMut(&Chunk) -> bool) -> Vec<Chunk> {
+        let (kept, removed) = std::mem::take(&mut self.chunks)
+            .into_iter()
+            .partition(|c| keep(c));
+        self.chunks = kept;
 
-    fn from_chunks(chunks: Vec<Chunk>) -> Self {
-        Self { chunks }
+        if !removed.is_empty() {
+            self.modified();
+        }
+        removed
     }
 
-    fn append_chunk(&mut self, chunk: Chunk) {
-        self.chunks.push(chunk)
+    // Passes each chunk through the closure, which can change it, swap it for another or drop it
+    // by returning None.
+    pub fn filter_map_chunks(&mut self, f: impl FnMut(Chunk) -> Option<Chunk>) {
+        let chunks = std::mem::take(&mut self.chunks)
+            .into_iter()
+            .filter_map(f)
+            .collect();
+        self.replace_chunks(chunks);
     }
 
-    fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk> {
-        for (i, chunk) in self.chunks.clone().into_iter().enumerate() {
-            if chunk.chunk_type().to_string() == chunk_type {
-                self.chunks.remove(i);
-                return Ok(chunk);
-            }
+    // Swaps every chunk of a type for the replacements, which go where the first one was.
+    pub fn replace_chunks_of_type(
+        &mut self,
+        chunk_type: &str,
+        replacements: Vec<Chunk>,
+    ) -> Result<Vec<Chunk>> {
+        let Some(index) = self.nth_position(chunk_type, 0) else {
+            bail!("No chunk with that type")
+        };
+
+        let removed = self.remove_all_chunks(chunk_type)?;
+        self.chunks.splice(index..index, replacements);
+        self.modified();
+        Ok(removed)
+    }
+
+    // Removes every ancillary chunk whose type is not kept, leaving the image itself untouched.
+    pub fn strip_ancillary(&mut self, keep: &[ChunkType]) -> Vec<Chunk> {
+        self.retain(|c| c.chunk_type().is_critical() || keep.contains(c.chunk_type()))
+    }
+
+    pub fn remove_chunk_at(&mut self, index: usize) -> Result<Chunk> {
+        if index >= self.chunks.len() {
+            bail!(
+                "Chunk index out of range (got {}, have {} chunks)",
+                index,
+                self.chunks.len()
+            )
         }
 
-        bail!("No chunk with that type")
+        let chunk = self.chunks.remove(index);
+        self.modified();
+        Ok(chunk)
     }
 
-    fn header(&self) -> &[u8; 8] {
+    pub fn signature(&self) -> &[u8; 8] {
         &Self::STANDARD_HEADER
     }
 
-    fn chunks(&self) -> &[Chunk] {
+    pub fn header(&self) -> Result<Ihdr> {
+        let Some(chunk) = self
+            .chunks
+            .iter()
+            .find(|c| *c.chunk_type() == ChunkType::IHDR)
+        else {
+            bail!("No IHDR chunk")
+        };
+
+        Ihdr::try_from(chunk)
+    }
+
+    pub fn set_header(&mut self, ihdr: Ihdr) -> Result<()> {
+        ihdr.validate()?;
+
+        match self
+            .chunks
+            .iter_mut()
+            .find(|c| *c.chunk_type() == ChunkType::IHDR)
+        {
+            Some(chunk) => chunk.set_data(ihdr.as_bytes().to_vec()),
+            None => self.chunks.insert(0, ihdr.into()),
+        }
+        self.modified();
+
+        Ok(())
+    }
+
+    pub fn chunks(&self) -> &[Chunk] {
         &self.chunks
     }
 
-    fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
+    // Handing out the chunks counts as a modification, so tIME is touched before they are edited.
+    pub fn chunks_mut(&mut self) -> &mut [Chunk] {
+        self.modified();
+        &mut self.chunks
+    }
+
+    // Each chunk with its file offset, counting the signature. Parsing keeps every chunk in file
+    // order and as_bytes writes them back the same way, so for a parsed PNG these are where the
+    // chunks were read from; after an edit they are where as_bytes puts them.
+    pub fn chunks_with_offsets(&self) -> impl Iterator<Item = (usize, &Chunk)> {
+        self.chunks
+            .iter()
+            .scan(Self::STANDARD_HEADER.len(), |offset, chunk| {
+                let start = *offset;
+                *offset += chunk.chunk_data().len() + 12;
+                Some((start, chunk))
+            })
+    }
+
+    pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
         let mut chunk = self
             .chunks
             .iter()
@@ -100,9 +381,49 @@ impl Png {
         chunk.next()
     }
 
-    fn as_bytes(&self) -> Vec<u8> {
-        let chunks: Vec<u8> = self.chunks.iter().flat_map(|c| c.as_bytes()).collect();
-        self.header().iter().chain(chunks.iter()).copied().collect()
+    pub fn nth_chunk_by_type(&self, chunk_type: &str, n: usize) -> Option<&Chunk> {
+        self.nth_position(chunk_type, n)
+            .map(|index| &self.chunks[index])
+    }
+
+    // Where the first chunk of a type sits among the chunks.
+    pub fn position_of(&self, chunk_type: &str) -> Option<usize> {
+        self.nth_position(chunk_type, 0)
+    }
+
+    fn nth_position(&self, chunk_type: &str, n: usize) -> Option<usize> {
+        self.chunks
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.chunk_type().to_string() == chunk_type)
+            .nth(n)
+            .map(|(index, _)| index)
+    }
+
+    pub fn chunk_by_type_mut(&mut self, chunk_type: &str) -> Option<&mut Chunk> {
+        self.chunk_by_type(chunk_type)?;
+        self.modified();
+        self.chunks
+            .iter_mut()
+            .find(|c| c.chunk_type().to_string() == chunk_type)
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let length: usize = self.chunks.iter().map(|c| c.chunk_data().len() + 12).sum();
+        let mut bytes = Vec::with_capacity(Self::STANDARD_HEADER.len() + length);
+        self.write_to(&mut bytes)
+            .expect("writing to a Vec cannot fail");
+        bytes
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> Result<()> {
+        let _span = tracing::trace_span!("write", chunks = self.chunks.len()).entered();
+        writer.write_all(self.signature())?;
+        for chunk in &self.chunks {
+            chunk.write_to(writer)?;
+        }
+
+        Ok(())
     }
 }
 
@@ -111,6 +432,7 @@ mod tests {
     use super::*;
     use crate::chunk::Chunk;
     use crate::chunk_type::ChunkType;
+    use crate::ihdr::ColorType;
     use std::convert::TryFrom;
     use std::str::FromStr;
 
@@ -128,8 +450,6 @@ mod tests {
     }
 
     fn chunk_from_strings(chunk_type: &str, data: &str) -> Result<Chunk> {
-        use std::str::FromStr;
-
         let chunk_type = ChunkType::from_str(chunk_type)?;
         let data: Vec<u8> = data.bytes().collect();
 
@@ -162,6 +482,30 @@ mod tests {
         assert!(png.is_ok());
     }
 
+    #[test]
+    fn test_read_from() {
+        let mut png = testing_png();
+        png.append_chunk(Chunk::new(ChunkType::IEND, Vec::new()));
+        let mut bytes = png.as_bytes();
+        let length = bytes.len();
+        bytes.extend_from_slice(b"after IEND");
+
+        let mut reader = bytes.as_slice();
+        let read = Png::read_from(&mut reader).unwrap();
+        assert_eq!(read.as_bytes(), png.as_bytes());
+        assert_eq!(reader, b"after IEND");
+
+        assert!(Png::read_from(&bytes[..length - 2]).is_err());
+        assert!(Png::read_from(&bytes[1..]).is_err());
+        assert_eq!(
+            Png::read_from(&testing_png().as_bytes()[..])
+                .unwrap()
+                .chunks()
+                .len(),
+            3
+        );
+    }
+
     #[test]
     fn test_invalid_header() {
         let chunk_bytes: Vec<u8> = testing_chunks()
@@ -202,6 +546,51 @@ mod tests {
         assert!(png.is_err());
     }
 
+    #[test]
+    fn test_max_total_memory() {
+        let options = ParseOptions {
+            max_total_memory: Some(PNG_FILE.len() * 4),
+            ..Default::default()
+        };
+        assert!(Png::from_bytes_with_options(&PNG_FILE[..], &options).is_ok());
+
+        let options = ParseOptions {
+            max_total_memory: Some(64),
+            ..Default::default()
+        };
+        let over_budget = |result: Result<Png>| {
+            matches!(
+                result
+                    .err()
+                    .unwrap()
+                    .downcast::<crate::options::ParseError>(),
+                Ok(crate::options::ParseError::MemoryBudgetExceeded { budget: 64, .. })
+            )
+        };
+        assert!(over_budget(Png::from_bytes_with_options(
+            &PNG_FILE[..],
+            &options
+        )));
+        assert!(over_budget(Png::read_from_with_options(
+            &PNG_FILE[..],
+            &options
+        )));
+    }
+
+    #[test]
+    fn test_chunk_too_large() {
+        let options = ParseOptions {
+            max_chunk_length: 1024,
+            ..Default::default()
+        };
+
+        let png = Png::from_bytes_with_options(&PNG_FILE[..], &options);
+        assert!(matches!(
+            png.err().unwrap().downcast::<crate::chunk::ChunkError>(),
+            Ok(crate::chunk::ChunkError::ChunkTooLarge { max: 1024, .. })
+        ));
+    }
+
     #[test]
     fn test_list_chunks() {
         let png = testing_png();
@@ -209,6 +598,21 @@ mod tests {
         assert_eq!(chunks.len(), 3);
     }
 
+    #[test]
+    fn test_chunks_with_offsets() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let offsets: Vec<usize> = png
+            .chunks_with_offsets()
+            .map(|(offset, _)| offset)
+            .collect();
+        assert_eq!(offsets.len(), png.chunks().len());
+        assert_eq!(offsets[0], 8);
+        for (offset, chunk) in png.chunks_with_offsets() {
+            let length = chunk.chunk_data().len() + 12;
+            assert_eq!(PNG_FILE[offset..offset + length], chunk.as_bytes());
+        }
+    }
+
     #[test]
     fn test_chunk_by_type() {
         let png = testing_png();
@@ -217,6 +621,18 @@ mod tests {
         assert_eq!(&chunk.to_string(), "I am the first chunk");
     }
 
+    #[test]
+    fn test_chunk_by_type_mut() {
+        let mut png = testing_png();
+        png.chunk_by_type_mut("miDl")
+            .unwrap()
+            .set_data(b"I was edited".to_vec());
+
+        let reparsed = Png::try_from(png.as_bytes().as_ref()).unwrap();
+        let chunk = reparsed.chunk_by_type("miDl").unwrap();
+        assert_eq!(&chunk.to_string(), "I was edited");
+    }
+
     #[test]
     fn test_append_chunk() {
         let mut png = testing_png();
@@ -226,6 +642,85 @@ mod tests {
         assert_eq!(&chunk.to_string(), "Message");
     }
 
+    #[test]
+    fn test_insert_chunk_ordered() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.insert_chunk_ordered(chunk_from_strings("ruSt", "hidden").unwrap());
+        png.insert_chunk_ordered(chunk_from_strings("tEXt", "Author\0me").unwrap());
+
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(
+            types,
+            vec!["IHDR", "sRGB", "gAMA", "pHYs", "tEXt", "IDAT", "RuSt", "ruSt", "IEND"]
+        );
+    }
+
+    #[test]
+    fn test_copy_chunks_from() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("tIME", "old").unwrap());
+        let mut other = testing_png();
+        other.append_chunk(chunk_from_strings("tIME", "new").unwrap());
+        other.append_chunk(chunk_from_strings("tEXt", "one").unwrap());
+        other.append_chunk(chunk_from_strings("tEXt", "two").unwrap());
+
+        let copied =
+            png.copy_chunks_from(&other, &[ChunkType::tIME, ChunkType::tEXt, ChunkType::eXIf]);
+        assert_eq!(copied, 3);
+        assert_eq!(png.chunks().len(), 6);
+        assert_eq!(&png.chunk_by_type("tIME").unwrap().to_string(), "new");
+        assert_eq!(
+            &png.nth_chunk_by_type("tEXt", 1).unwrap().to_string(),
+            "two"
+        );
+    }
+
+    #[test]
+    fn test_dedupe() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("ruSt", "hidden").unwrap());
+        png.append_chunk(chunk_from_strings("ruSt", "hidden").unwrap());
+        png.append_chunk(chunk_from_strings("ruSt", "other").unwrap());
+        png.append_chunk(chunk_from_strings("ruSu", "hidden").unwrap());
+        png.append_chunk(chunk_from_strings("IDAT", "data").unwrap());
+        png.append_chunk(chunk_from_strings("IDAT", "data").unwrap());
+
+        let removed = png.dedupe();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(&removed[0].to_string(), "hidden");
+        assert_eq!(png.chunks().len(), 8);
+        assert!(png.dedupe().is_empty());
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let mut png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("IDAT", "one").unwrap(),
+            chunk_from_strings("tEXt", "text").unwrap(),
+            chunk_from_strings("IDAT", "two").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+            chunk_from_strings("gAMA", "gamma").unwrap(),
+        ]);
+
+        assert!(png.canonicalize());
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["IHDR", "gAMA", "tEXt", "IDAT", "IDAT", "IEND"]);
+        assert_eq!(
+            &png.nth_chunk_by_type("IDAT", 1).unwrap().to_string(),
+            "two"
+        );
+        assert!(!png.canonicalize());
+    }
+
     #[test]
     fn test_remove_chunk() {
         let mut png = testing_png();
@@ -235,6 +730,155 @@ mod tests {
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_remove_first_chunk() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("TeSt", "First").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "Second").unwrap());
+
+        let removed = png.remove_first_chunk("TeSt").unwrap();
+        assert_eq!(&removed.to_string(), "First");
+
+        let chunk = png.chunk_by_type("TeSt").unwrap();
+        assert_eq!(&chunk.to_string(), "Second");
+    }
+
+    #[test]
+    fn test_remove_first_chunk_missing() {
+        let mut png = testing_png();
+        assert!(png.remove_first_chunk("TeSt").is_err());
+        assert_eq!(png.chunks().len(), 3);
+    }
+
+    #[test]
+    fn test_nth_chunk_by_type() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("TeSt", "First").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "Second").unwrap());
+
+        assert_eq!(
+            &png.nth_chunk_by_type("TeSt", 0).unwrap().to_string(),
+            "First"
+        );
+        assert_eq!(
+            &png.nth_chunk_by_type("TeSt", 1).unwrap().to_string(),
+            "Second"
+        );
+        assert!(png.nth_chunk_by_type("TeSt", 2).is_none());
+    }
+
+    #[test]
+    fn test_remove_nth_chunk() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("TeSt", "First").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "Second").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "Third").unwrap());
+
+        let removed = png.remove_nth_chunk("TeSt", 1).unwrap();
+        assert_eq!(&removed.to_string(), "Second");
+        assert!(png.remove_nth_chunk("TeSt", 2).is_err());
+        assert_eq!(
+            &png.nth_chunk_by_type("TeSt", 1).unwrap().to_string(),
+            "Third"
+        );
+        assert_eq!(png.chunks().len(), 5);
+    }
+
+    #[test]
+    fn test_remove_all_chunks() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("TeSt", "First").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "Second").unwrap());
+
+        let removed = png.remove_all_chunks("TeSt").unwrap();
+        assert_eq!(removed.len(), 2);
+        assert_eq!(&removed[0].to_string(), "First");
+        assert_eq!(&removed[1].to_string(), "Second");
+        assert!(png.chunk_by_type("TeSt").is_none());
+        assert_eq!(png.chunks().len(), 3);
+    }
+
+    #[test]
+    fn test_replace_chunks_of_type() {
+        let mut png = testing_png();
+        png.insert_chunk_ordered(chunk_from_strings("TeSt", "First").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "Second").unwrap());
+        let index = png.nth_position("TeSt", 0).unwrap();
+
+        let removed = png
+            .replace_chunks_of_type("TeSt", vec![chunk_from_strings("TeSt", "New").unwrap()])
+            .unwrap();
+        assert_eq!(removed.len(), 2);
+        assert_eq!(png.chunks().len(), 4);
+        assert_eq!(&png.chunks()[index].to_string(), "New");
+        assert!(png.nth_chunk_by_type("TeSt", 1).is_none());
+
+        assert!(png.replace_chunks_of_type("MiSs", vec![]).is_err());
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("prIV", "private, unsafe to copy").unwrap());
+        png.append_chunk(chunk_from_strings("prIv", "private, safe to copy").unwrap());
+
+        let removed =
+            png.retain(|c| c.chunk_type().is_public() || c.chunk_type().is_safe_to_copy());
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].chunk_type().to_string(), "prIV");
+        assert_eq!(png.position_of("prIv"), Some(png.chunks().len() - 1));
+        assert_eq!(png.position_of("prIV"), None);
+        assert!(png.retain(|_| true).is_empty());
+    }
+
+    #[test]
+    fn test_filter_map_chunks() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("tEXt", "Comment\0hi").unwrap());
+        let length = png.chunks().len();
+
+        png.filter_map_chunks(|chunk| match chunk.chunk_type().to_string().as_str() {
+            "tEXt" => Some(Chunk::new(ChunkType::tEXt, b"Comment\0bye".to_vec())),
+            "FrSt" => None,
+            _ => Some(chunk),
+        });
+        assert_eq!(png.chunks().len(), length - 1);
+        assert_eq!(png.position_of("FrSt"), None);
+        let text = png.chunk_by_type("tEXt").unwrap();
+        assert_eq!(text.chunk_data(), b"Comment\0bye");
+    }
+
+    #[test]
+    fn test_strip_ancillary() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("tEXt", "Comment\0hi").unwrap());
+        png.append_chunk(chunk_from_strings("pHYs", "123456789").unwrap());
+
+        let removed = png.strip_ancillary(&[ChunkType::pHYs]);
+        assert_eq!(removed.len(), 2);
+        assert_eq!(removed[0].chunk_type().to_string(), "miDl");
+        assert_eq!(removed[1].chunk_type().to_string(), "tEXt");
+        assert_eq!(png.chunks().len(), 3);
+        assert!(png.chunk_by_type("pHYs").is_some());
+        assert!(png.strip_ancillary(&[ChunkType::pHYs]).is_empty());
+    }
+
+    #[test]
+    fn test_remove_chunk_at() {
+        let mut png = testing_png();
+        let removed = png.remove_chunk_at(1).unwrap();
+        assert_eq!(&removed.chunk_type().to_string(), "miDl");
+        assert_eq!(png.chunks().len(), 2);
+        assert_eq!(&png.chunks()[1].chunk_type().to_string(), "LASt");
+    }
+
+    #[test]
+    fn test_remove_chunk_at_out_of_range() {
+        let mut png = testing_png();
+        assert!(png.remove_chunk_at(3).is_err());
+        assert_eq!(png.chunks().len(), 3);
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
@@ -249,6 +893,48 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn test_write_to() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let mut written = Vec::new();
+        png.write_to(&mut written).unwrap();
+        assert_eq!(written, PNG_FILE.to_vec());
+    }
+
+    #[test]
+    fn test_header() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let ihdr = png.header().unwrap();
+
+        assert_eq!(ihdr.width, 50);
+        assert_eq!(ihdr.height, 50);
+        assert_eq!(ihdr.bit_depth, 8);
+        assert_eq!(ihdr.color_type, ColorType::Rgba);
+        assert!(!ihdr.is_interlaced());
+    }
+
+    #[test]
+    fn test_header_missing() {
+        assert!(testing_png().header().is_err());
+    }
+
+    #[test]
+    fn test_set_header() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let mut ihdr = png.header().unwrap();
+        ihdr.width = 100;
+        png.set_header(ihdr).unwrap();
+
+        assert_eq!(png.header().unwrap().width, 100);
+        assert_eq!(png.chunks()[0].chunk_type(), &ChunkType::IHDR);
+
+        let mut png = testing_png();
+        png.set_header(Ihdr::new(1, 1, 8, ColorType::Rgb).unwrap())
+            .unwrap();
+        assert_eq!(png.chunks()[0].chunk_type(), &ChunkType::IHDR);
+        assert_eq!(png.chunks().len(), 4);
+    }
+
     #[test]
     fn test_png_trait_impls() {
         let chunk_bytes: Vec<u8> = testing_chunks()
@@ -267,6 +953,15 @@ mod tests {
         let _png_string = format!("{}", png);
     }
 
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_png_serde_round_trip() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let json = serde_json::to_string(&png).unwrap();
+        let parsed: Png = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.as_bytes(), PNG_FILE.to_vec());
+    }
+
     // This is the raw bytes for a shrunken version of the `dice.png` image on Wikipedia
     const PNG_FILE: [u8; 4803] = [
         137, 80, 78, 71, 13, 10, 26, 10, 0, 0, 0, 13, 73, 72, 68, 82, 0, 0, 0, 50, 0, 0, 0, 50, 8,
@@ -0,0 +1,49 @@
+// The types most programs need, so `use pngme::prelude::*` is enough to read, edit and write PNGs,
+// hide envelopes in them and match on what went wrong. Everything else stays in its own module.
+pub use crate::{
+    builder::PngBuilder,
+    carrier::Carrier,
+    chunk::{Chunk, ChunkError},
+    chunk_type::{ChunkType, ChunkTypeError},
+    compression::{Compression, CompressionError},
+    crypto::CryptoError,
+    envelope::{Envelope, EnvelopeError},
+    ihdr::{ColorType, Ihdr, IhdrError},
+    jpeg::{Jpeg, JpegError},
+    options::ParseOptions,
+    png::Png,
+    stego::{StegoError, StegoMethod},
+    text::{Text, TextError},
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_prelude() {
+        let mut png = PngBuilder::new()
+            .chunk(Chunk::from(Ihdr::new(1, 1, 8, ColorType::Rgb).unwrap()))
+            .build()
+            .unwrap();
+        let envelope = Envelope::new(b"hello".to_vec()).compressed(Compression::Zstd);
+        png.embed_envelope(StegoMethod::Chunk, "ruSt", &envelope)
+            .unwrap();
+
+        let bytes = png.as_bytes();
+        let png = Png::from_bytes_with_options(&bytes, &ParseOptions::default()).unwrap();
+        let decoded = png.extract_envelope(StegoMethod::Chunk, "ruSt").unwrap();
+        assert_eq!(decoded.payload, b"hello");
+
+        let error = png.extract(StegoMethod::Chunk, "abCd").unwrap_err();
+        assert_eq!(
+            error.downcast::<StegoError>().unwrap(),
+            StegoError::NoPayload
+        );
+        assert!(ChunkType::from_str("ru5t")
+            .unwrap_err()
+            .downcast::<ChunkTypeError>()
+            .is_ok());
+    }
+}
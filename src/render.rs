@@ -0,0 +1,162 @@
+use anyhow::{anyhow, bail, Error, Result};
+use base64::Engine;
+use std::{fmt::Write, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DataFormat {
+    Hex,
+    Base64,
+    #[default]
+    LossyUtf8,
+    EscapedAscii,
+}
+
+impl FromStr for DataFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "hex" => Ok(Self::Hex),
+            "base64" => Ok(Self::Base64),
+            "utf8" | "lossy-utf8" => Ok(Self::LossyUtf8),
+            "ascii" | "escaped-ascii" => Ok(Self::EscapedAscii),
+            _ => bail!(
+                "Unknown data format {:?} (expected hex, base64, utf8 or ascii)",
+                s
+            ),
+        }
+    }
+}
+
+impl DataFormat {
+    pub fn render(&self, data: &[u8]) -> String {
+        match self {
+            Self::Hex => data.iter().fold(String::new(), |mut string, byte| {
+                let _ = write!(string, "{:02x}", byte);
+                string
+            }),
+            Self::Base64 => base64::engine::general_purpose::STANDARD.encode(data),
+            Self::LossyUtf8 => String::from_utf8_lossy(data).into_owned(),
+            Self::EscapedAscii => data.escape_ascii().to_string(),
+        }
+    }
+
+    // The inverse of render. Whitespace is ignored in hex and base64, which are often wrapped.
+    pub fn parse(&self, text: &str) -> Result<Vec<u8>> {
+        let compact = || text.split_whitespace().collect::<String>();
+        match self {
+            // Checked digit by digit first, as slicing pairs out of other characters could split
+            // one in the middle.
+            Self::Hex => {
+                let digits = compact();
+                if let Some(found) = digits.chars().find(|c| !c.is_ascii_hexdigit()) {
+                    bail!("{:?} is not a hex digit", found)
+                }
+                if !digits.len().is_multiple_of(2) {
+                    bail!("Hex data has an odd number of digits")
+                }
+                Ok(digits
+                    .as_bytes()
+                    .chunks_exact(2)
+                    .map(|pair| (hex_value(pair[0]) << 4) | hex_value(pair[1]))
+                    .collect())
+            }
+            Self::Base64 => Ok(base64::engine::general_purpose::STANDARD.decode(compact())?),
+            Self::LossyUtf8 => Ok(text.as_bytes().to_vec()),
+            Self::EscapedAscii => unescape_ascii(text),
+        }
+    }
+}
+
+fn hex_value(digit: u8) -> u8 {
+    match digit {
+        b'0'..=b'9' => digit - b'0',
+        _ => digit.to_ascii_lowercase() - b'a' + 10,
+    }
+}
+
+fn unescape_ascii(text: &str) -> Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buffer = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            continue;
+        }
+        let byte = match chars.next() {
+            Some('n') => b'\n',
+            Some('r') => b'\r',
+            Some('t') => b'\t',
+            Some('0') => 0,
+            Some(c @ ('\\' | '\'' | '"')) => c as u8,
+            Some('x') => {
+                let digits: String = chars.by_ref().take(2).collect();
+                u8::from_str_radix(&digits, 16)
+                    .map_err(|_| anyhow!("\\x{} is not a valid escape", digits))?
+            }
+            other => bail!("\\{} is not a valid escape", other.unwrap_or_default()),
+        };
+        bytes.push(byte);
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATA: &[u8] = b"Hi\0\xff\n";
+
+    #[test]
+    fn test_render_hex() {
+        assert_eq!(DataFormat::Hex.render(DATA), "486900ff0a");
+    }
+
+    #[test]
+    fn test_render_base64() {
+        assert_eq!(DataFormat::Base64.render(DATA), "SGkA/wo=");
+    }
+
+    #[test]
+    fn test_render_lossy_utf8() {
+        assert_eq!(DataFormat::LossyUtf8.render(DATA), "Hi\0\u{FFFD}\n");
+    }
+
+    #[test]
+    fn test_render_escaped_ascii() {
+        assert_eq!(DataFormat::EscapedAscii.render(DATA), "Hi\\x00\\xff\\n");
+    }
+
+    #[test]
+    fn test_parse() {
+        for format in [
+            DataFormat::Hex,
+            DataFormat::Base64,
+            DataFormat::EscapedAscii,
+        ] {
+            assert_eq!(format.parse(&format.render(DATA)).unwrap(), DATA);
+        }
+        assert_eq!(DataFormat::Hex.parse("48 69\n00").unwrap(), b"Hi\0");
+        assert_eq!(DataFormat::LossyUtf8.parse("Hi").unwrap(), b"Hi");
+        assert!(DataFormat::Hex.parse("486").is_err());
+        assert!(DataFormat::Hex.parse("zz").is_err());
+        assert!(DataFormat::Hex.parse("aé0").is_err());
+        assert!(DataFormat::Hex.parse("+f").is_err());
+        assert_eq!(DataFormat::Hex.parse("FFab").unwrap(), [0xff, 0xab]);
+        assert!(DataFormat::Base64.parse("SGk!").is_err());
+        assert!(DataFormat::EscapedAscii.parse("\\q").is_err());
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!(DataFormat::from_str("HEX").unwrap(), DataFormat::Hex);
+        assert_eq!(DataFormat::from_str("base64").unwrap(), DataFormat::Base64);
+        assert_eq!(DataFormat::from_str("utf8").unwrap(), DataFormat::LossyUtf8);
+        assert_eq!(
+            DataFormat::from_str("ascii").unwrap(),
+            DataFormat::EscapedAscii
+        );
+        assert!(DataFormat::from_str("binary").is_err());
+    }
+}
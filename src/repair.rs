@@ -0,0 +1,263 @@
+use crate::{chunk::Chunk, chunk_type::ChunkType, png::Png};
+use anyhow::{bail, Result};
+use std::fmt::Display;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Repair {
+    Crc {
+        index: usize,
+        chunk_type: ChunkType,
+        found: u32,
+        actual: u32,
+    },
+    Length {
+        index: usize,
+        chunk_type: ChunkType,
+        found: u32,
+        actual: u32,
+    },
+    TruncatedChunk {
+        chunk_type: ChunkType,
+        length: usize,
+    },
+    TrailingData {
+        length: usize,
+    },
+    MissingIend,
+}
+
+impl Display for Repair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Crc {
+                index,
+                chunk_type,
+                found,
+                actual,
+            } => write!(
+                f,
+                "recomputed the CRC of {} chunk {} ({:#010x} -> {:#010x})",
+                chunk_type, index, found, actual
+            ),
+            Self::Length {
+                index,
+                chunk_type,
+                found,
+                actual,
+            } => write!(
+                f,
+                "corrected the length of {} chunk {} ({} -> {})",
+                chunk_type, index, found, actual
+            ),
+            Self::TruncatedChunk { chunk_type, length } => write!(
+                f,
+                "dropped a truncated {} chunk ({} bytes)",
+                chunk_type, length
+            ),
+            Self::TrailingData { length } => {
+                write!(f, "dropped {} bytes after the last chunk", length)
+            }
+            Self::MissingIend => write!(f, "appended the missing IEND chunk"),
+        }
+    }
+}
+
+// Reads a file that fails strict parsing, fixing what can be fixed without guessing at the image:
+// CRCs that disagree with the data, length fields that disagree with where the CRC really is,
+// bytes after IEND or a truncated final chunk, and a missing IEND.
+pub fn repair_bytes(bytes: &[u8]) -> Result<(Png, Vec<Repair>)> {
+    if !bytes.starts_with(&Png::STANDARD_HEADER) {
+        bail!("Bad header")
+    }
+
+    let mut chunks = Vec::new();
+    let mut repairs = Vec::new();
+    let mut offset = Png::STANDARD_HEADER.len();
+    while offset < bytes.len() {
+        let Some(chunk_type) = chunk_type_at(bytes, offset) else {
+            break;
+        };
+        let found = read_u32(bytes, offset);
+        let data_start = offset + 8;
+        let index = chunks.len();
+
+        let (length, crc) = match (declared_end(bytes, offset, found), find_end(bytes, offset)) {
+            (Some(end), _) => (found as usize, read_u32(bytes, end)),
+            (None, Some(end)) => {
+                let actual = (end - data_start) as u32;
+                repairs.push(Repair::Length {
+                    index,
+                    chunk_type,
+                    found,
+                    actual,
+                });
+                (actual as usize, read_u32(bytes, end))
+            }
+            (None, None) => {
+                repairs.push(Repair::TruncatedChunk {
+                    chunk_type,
+                    length: bytes.len() - offset,
+                });
+                offset = bytes.len();
+                break;
+            }
+        };
+
+        let chunk = Chunk::new(chunk_type, bytes[data_start..data_start + length].to_vec());
+        if chunk.crc() != crc {
+            repairs.push(Repair::Crc {
+                index,
+                chunk_type,
+                found: crc,
+                actual: chunk.crc(),
+            });
+        }
+        chunks.push(chunk);
+        offset = data_start + length + 4;
+
+        if chunk_type == ChunkType::IEND {
+            break;
+        }
+    }
+
+    if offset < bytes.len() {
+        repairs.push(Repair::TrailingData {
+            length: bytes.len() - offset,
+        });
+    }
+    if chunks.last().map(|c| *c.chunk_type()) != Some(ChunkType::IEND) {
+        chunks.push(Chunk::new(ChunkType::IEND, Vec::new()));
+        repairs.push(Repair::MissingIend);
+    }
+
+    Ok((Png::from_chunks(chunks), repairs))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn chunk_type_at(bytes: &[u8], offset: usize) -> Option<ChunkType> {
+    let type_bytes: [u8; 4] = bytes.get(offset + 4..offset + 8)?.try_into().ok()?;
+    ChunkType::try_from(type_bytes).ok()
+}
+
+// Another chunk header or the end of the file, either of which can follow a chunk.
+fn is_boundary(bytes: &[u8], offset: usize) -> bool {
+    offset == bytes.len() || chunk_type_at(bytes, offset).is_some()
+}
+
+// Where the CRC of a chunk sits if its declared length is right, which it is taken to be when a
+// boundary follows or the CRC matches.
+fn declared_end(bytes: &[u8], offset: usize, length: u32) -> Option<usize> {
+    let end = (offset + 8).checked_add(length as usize)?;
+    if end + 4 > bytes.len() {
+        return None;
+    }
+    let crc_matches = || {
+        let chunk_type = chunk_type_at(bytes, offset)?;
+        Some(Chunk::new(chunk_type, bytes[offset + 8..end].to_vec()).crc() == read_u32(bytes, end))
+    };
+    (is_boundary(bytes, end + 4) || crc_matches() == Some(true)).then_some(end)
+}
+
+// Otherwise the first position followed by a boundary whose four bytes are the CRC of what
+// precedes them.
+fn find_end(bytes: &[u8], offset: usize) -> Option<usize> {
+    let chunk_type = chunk_type_at(bytes, offset)?;
+    (offset + 8..=bytes.len().checked_sub(4)?).find(|&end| {
+        is_boundary(bytes, end + 4)
+            && Chunk::new(chunk_type, bytes[offset + 8..end].to_vec()).crc() == read_u32(bytes, end)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::PngBuilder;
+
+    fn testing_bytes() -> Vec<u8> {
+        PngBuilder::new()
+            .chunk(Chunk::new(
+                ChunkType::IHDR,
+                vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0],
+            ))
+            .chunk(Chunk::new(ChunkType::tEXt, b"Comment\0hello".to_vec()))
+            .build()
+            .unwrap()
+            .as_bytes()
+    }
+
+    // IHDR takes 25 bytes after the signature, so tEXt starts at 33.
+    const TEXT_OFFSET: usize = 33;
+
+    #[test]
+    fn test_intact() {
+        let bytes = testing_bytes();
+        let (png, repairs) = repair_bytes(&bytes).unwrap();
+        assert!(repairs.is_empty());
+        assert_eq!(png.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_crc() {
+        let mut bytes = testing_bytes();
+        bytes[TEXT_OFFSET + 8] = b'c';
+
+        let (png, repairs) = repair_bytes(&bytes).unwrap();
+        assert!(matches!(
+            repairs[..],
+            [Repair::Crc {
+                index: 1,
+                chunk_type: ChunkType::tEXt,
+                ..
+            }]
+        ));
+        assert_eq!(png.chunks()[1].chunk_data(), b"comment\0hello");
+        assert!(Png::try_from(png.as_bytes().as_slice()).is_ok());
+    }
+
+    #[test]
+    fn test_length() {
+        let mut bytes = testing_bytes();
+        bytes[TEXT_OFFSET + 3] = 200;
+
+        let (png, repairs) = repair_bytes(&bytes).unwrap();
+        assert_eq!(
+            repairs,
+            [Repair::Length {
+                index: 1,
+                chunk_type: ChunkType::tEXt,
+                found: 200,
+                actual: 13
+            }]
+        );
+        assert_eq!(png.as_bytes(), testing_bytes());
+    }
+
+    #[test]
+    fn test_trailing_data_and_truncation() {
+        let mut bytes = testing_bytes();
+        bytes.extend_from_slice(b"garbage");
+        let (png, repairs) = repair_bytes(&bytes).unwrap();
+        assert_eq!(repairs, [Repair::TrailingData { length: 7 }]);
+        assert_eq!(png.as_bytes(), testing_bytes());
+
+        let bytes = testing_bytes();
+        let (png, repairs) = repair_bytes(&bytes[..TEXT_OFFSET + 15]).unwrap();
+        assert_eq!(
+            repairs,
+            [
+                Repair::TruncatedChunk {
+                    chunk_type: ChunkType::tEXt,
+                    length: 15
+                },
+                Repair::MissingIend
+            ]
+        );
+        assert_eq!(png.chunks().len(), 2);
+        assert_eq!(*png.chunks()[1].chunk_type(), ChunkType::IEND);
+
+        assert!(repair_bytes(b"GIF89a").is_err());
+    }
+}
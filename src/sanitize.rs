@@ -0,0 +1,154 @@
+use crate::{
+    chunk::Chunk, chunk_type::ChunkType, options::ParseOptions, png::Png, stego::StegoMethod,
+};
+use anyhow::{bail, Result};
+use std::fmt::Display;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sanitized {
+    Chunk {
+        chunk_type: ChunkType,
+        length: usize,
+    },
+    TrailingData {
+        length: usize,
+    },
+    PixelBits {
+        count: usize,
+    },
+}
+
+impl Display for Sanitized {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Chunk { chunk_type, length } => {
+                write!(
+                    f,
+                    "removed unregistered chunk {} ({} bytes)",
+                    chunk_type, length
+                )
+            }
+            Self::TrailingData { length } => write!(f, "removed {} bytes after IEND", length),
+            Self::PixelBits { count } => {
+                write!(f, "randomized the low bits of {} samples", count)
+            }
+        }
+    }
+}
+
+// Destroys the places a covert channel can live: chunks the PNG registry does not define, bytes
+// after IEND and, when asked, the low bit of every sample, which LSB embedding writes to.
+pub fn sanitize_bytes(bytes: &[u8], randomize_pixels: bool) -> Result<(Png, Vec<Sanitized>)> {
+    if !bytes.starts_with(&Png::STANDARD_HEADER) {
+        bail!("Bad header")
+    }
+
+    let mut reader = &bytes[Png::STANDARD_HEADER.len()..];
+    let mut chunks = Vec::new();
+    while !reader.is_empty() {
+        let (chunk, _) = Chunk::read_from_with_options(&mut reader, &ParseOptions::default())?;
+        let end = *chunk.chunk_type() == ChunkType::IEND;
+        chunks.push(chunk);
+        if end {
+            break;
+        }
+    }
+
+    let mut changes = Vec::new();
+    let (kept, removed): (Vec<Chunk>, Vec<Chunk>) = chunks
+        .into_iter()
+        .partition(|c| c.chunk_type().is_registered());
+    changes.extend(removed.iter().map(|c| Sanitized::Chunk {
+        chunk_type: *c.chunk_type(),
+        length: c.chunk_data().len(),
+    }));
+    if !reader.is_empty() {
+        changes.push(Sanitized::TrailingData {
+            length: reader.len(),
+        });
+    }
+
+    let mut png = Png::from_chunks(kept);
+    if randomize_pixels {
+        let mut image_data = png.image_data()?;
+        let slots = StegoMethod::Lsb.sequential_slots(&image_data)?;
+        let pixels = image_data.pixels_mut();
+        for &slot in &slots {
+            pixels[slot] = (pixels[slot] & !1) | rand::random::<bool>() as u8;
+        }
+        png.set_image_data(&image_data)?;
+        changes.push(Sanitized::PixelBits { count: slots.len() });
+    }
+
+    Ok((png, changes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builder::PngBuilder,
+        ihdr::{ColorType, Ihdr},
+        imagedata::ImageData,
+    };
+
+    fn testing_png() -> Png {
+        let header = Ihdr::new(16, 16, 8, ColorType::Rgb).unwrap();
+        let image_data = ImageData::new(header, vec![128; 16 * 16 * 3]).unwrap();
+        let mut png = PngBuilder::new()
+            .chunk(Chunk::from(header))
+            .chunk(Chunk::new(ChunkType::tEXt, b"Comment\0hello".to_vec()))
+            .chunk(Chunk::new("ruSt".parse().unwrap(), b"hidden".to_vec()))
+            .build()
+            .unwrap();
+        png.set_image_data(&image_data).unwrap();
+        png
+    }
+
+    #[test]
+    fn test_sanitize() {
+        let mut bytes = testing_png().as_bytes();
+        bytes.extend_from_slice(b"more hidden");
+
+        let (png, changes) = sanitize_bytes(&bytes, false).unwrap();
+        assert_eq!(
+            changes,
+            [
+                Sanitized::Chunk {
+                    chunk_type: "ruSt".parse().unwrap(),
+                    length: 6
+                },
+                Sanitized::TrailingData { length: 11 }
+            ]
+        );
+        assert!(png.chunk_by_type("tEXt").is_some());
+        assert!(png.chunk_by_type("ruSt").is_none());
+        assert_eq!(
+            png.image_data().unwrap().pixels(),
+            testing_png().image_data().unwrap().pixels()
+        );
+
+        assert!(sanitize_bytes(b"GIF89a", false).is_err());
+    }
+
+    #[test]
+    fn test_randomize_pixels() {
+        let mut png = testing_png();
+        png.embed(StegoMethod::Lsb, "ruSt", b"a secret message")
+            .unwrap();
+
+        let (png, changes) = sanitize_bytes(&png.as_bytes(), true).unwrap();
+        assert_eq!(changes.last(), Some(&Sanitized::PixelBits { count: 768 }));
+        assert_ne!(
+            png.extract(StegoMethod::Lsb, "ruSt").ok().as_deref(),
+            Some(&b"a secret message"[..])
+        );
+        // Only the low bits change.
+        assert!(png
+            .image_data()
+            .unwrap()
+            .pixels()
+            .iter()
+            .all(|&b| b | 1 == 129));
+    }
+}
@@ -0,0 +1,426 @@
+use crate::output::noteln;
+use crate::status::ExitStatus;
+use anyhow::{bail, Context, Result};
+use pngme::{
+    carrier::Carrier, chunk_type::ChunkType, compression::CompressionError, envelope::Envelope,
+    imagedata::ImageData, png::Png, render::DataFormat, stego::StegoMethod,
+};
+use serde_json::json;
+use std::{collections::HashMap, io::Read, str::FromStr};
+use subtle::ConstantTimeEq;
+
+// A small HTTP API over encode and decode:
+//
+//     POST /encode?chunk_type=ruSt   body: PNG, returns the encoded PNG
+//     POST /decode?chunk_type=ruSt   body: PNG, returns the payload
+//     GET  /health
+//
+// encode also takes method and encoding (how the message is written, as for --input-encoding).
+// Secrets stay out of the URL, which proxies log: encode reads the message, percent-encoded, from
+// the X-Pngme-Message header, and both take a passphrase in the X-Pngme-Passphrase header.
+//
+// Pixels and payloads may be inflated to at most MAX_EXPANSION times the body size limit, so a
+// small compression bomb cannot make a request take gigabytes.
+pub const MAX_EXPANSION: usize = 16;
+
+#[derive(Debug, Clone)]
+pub struct ServerOptions {
+    pub max_size: usize,
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: String,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    fn new(status: u16, content_type: &str, body: Vec<u8>) -> Self {
+        Self {
+            status,
+            content_type: content_type.to_string(),
+            body,
+        }
+    }
+
+    fn error(status: u16, message: impl std::fmt::Display) -> Self {
+        let body = json!({ "error": message.to_string() }).to_string();
+        Self::new(status, "application/json", body.into_bytes())
+    }
+}
+
+pub struct Request<'a> {
+    pub method: &'a str,
+    pub url: &'a str,
+    pub header: &'a dyn Fn(&str) -> Option<String>,
+    pub body: &'a [u8],
+}
+
+pub fn handle(request: &Request, options: &ServerOptions) -> Response {
+    // Compared in constant time so response timing does not reveal how much of a guess matched.
+    if let Some(token) = &options.token {
+        let given = (request.header)("Authorization");
+        let given = given
+            .as_deref()
+            .and_then(|value| value.strip_prefix("Bearer "));
+        if !given.is_some_and(|given| bool::from(given.as_bytes().ct_eq(token.as_bytes()))) {
+            return Response::error(401, "Missing or wrong bearer token");
+        }
+    }
+    if request.body.len() > options.max_size {
+        return Response::error(
+            413,
+            format!("Bodies are limited to {} bytes", options.max_size),
+        );
+    }
+
+    let (path, query) = request.url.split_once('?').unwrap_or((request.url, ""));
+    let query = parse_query(query);
+    let passphrase = (request.header)("X-Pngme-Passphrase");
+    let limit = options.max_size.saturating_mul(MAX_EXPANSION);
+    let result = match (request.method, path) {
+        ("GET", "/health") => Ok(Response::new(200, "text/plain", b"ok".to_vec())),
+        ("POST", "/encode") => {
+            let message = (request.header)("X-Pngme-Message").map(|m| percent_decode(&m));
+            encode(
+                request.body,
+                &query,
+                message.as_deref(),
+                passphrase.as_deref(),
+                limit,
+            )
+        }
+        ("POST", "/decode") => decode(request.body, &query, passphrase.as_deref(), limit),
+        (_, "/health" | "/encode" | "/decode") => {
+            return Response::error(405, format!("{} is not allowed here", request.method))
+        }
+        _ => return Response::error(404, format!("No such endpoint {}", path)),
+    };
+
+    result.unwrap_or_else(|e| {
+        let too_large = matches!(
+            e.downcast_ref::<CompressionError>(),
+            Some(CompressionError::TooLarge { .. })
+        );
+        let status = match ExitStatus::of(&e) {
+            _ if too_large => 413,
+            ExitStatus::NotFound => 404,
+            ExitStatus::InvalidPng => 422,
+            ExitStatus::DecryptionFailed | ExitStatus::VerificationFailed => 403,
+            _ => 400,
+        };
+        Response::error(status, format!("{:#}", e))
+    })
+}
+
+fn read_png(body: &[u8]) -> Result<Png> {
+    Png::try_from(body)
+        .with_context(|| ExitStatus::InvalidPng.error("The request body is not a valid PNG"))
+}
+
+fn parameters(query: &HashMap<String, String>) -> Result<(String, StegoMethod)> {
+    let chunk_type = query.get("chunk_type").context("chunk_type is required")?;
+    let chunk_type = ChunkType::from_str(chunk_type)?.to_string();
+    let method = match query.get("method") {
+        Some(method) => StegoMethod::from_str(method)?,
+        None => StegoMethod::Chunk,
+    };
+    Ok((chunk_type, method))
+}
+
+// Pixel methods inflate the whole image, which has to fit the limit however small the body is.
+fn check_inflated_length(png: &Png, method: StegoMethod, limit: usize) -> Result<()> {
+    if method != StegoMethod::Chunk && ImageData::inflated_length(&png.header()?) > limit {
+        return Err(CompressionError::TooLarge { limit }.into());
+    }
+    Ok(())
+}
+
+fn encode(
+    body: &[u8],
+    query: &HashMap<String, String>,
+    message: Option<&str>,
+    passphrase: Option<&str>,
+    limit: usize,
+) -> Result<Response> {
+    let mut png = read_png(body)?;
+    let (chunk_type, method) = parameters(query)?;
+    check_inflated_length(&png, method, limit)?;
+    let message = message.context("The X-Pngme-Message header is required")?;
+    let encoding = match query.get("encoding") {
+        Some(encoding) => DataFormat::from_str(encoding)?,
+        None => DataFormat::LossyUtf8,
+    };
+
+    let mut envelope = Envelope::new(encoding.parse(message)?);
+    if encoding == DataFormat::LossyUtf8 {
+        envelope = envelope.with_mime_type("text/plain");
+    }
+    if let Some(passphrase) = passphrase {
+        envelope = envelope.encrypted(passphrase)?;
+    }
+    match method {
+        StegoMethod::Chunk => png.embed_envelope(method, &chunk_type, &envelope)?,
+        _ => png.embed(method, &chunk_type, &envelope.to_bytes()?)?,
+    }
+
+    Ok(Response::new(200, "image/png", png.as_bytes()))
+}
+
+fn decode(
+    body: &[u8],
+    query: &HashMap<String, String>,
+    passphrase: Option<&str>,
+    limit: usize,
+) -> Result<Response> {
+    let png = read_png(body)?;
+    let (chunk_type, method) = parameters(query)?;
+    check_inflated_length(&png, method, limit)?;
+    let bytes = png.extract(method, &chunk_type)?;
+    if !Envelope::is_envelope(&bytes) {
+        return Ok(Response::new(200, "application/octet-stream", bytes));
+    }
+
+    let mut envelope = png.extract_stored_envelope(method, &chunk_type)?;
+    match (passphrase, envelope.encryption.is_some()) {
+        (Some(passphrase), _) => envelope = envelope.decrypted(passphrase)?,
+        (None, true) => {
+            bail!("The payload is encrypted, pass the passphrase in X-Pngme-Passphrase")
+        }
+        (None, false) => {}
+    }
+    let envelope = envelope.decompressed_within(limit)?;
+    let content_type = envelope
+        .mime_type
+        .as_deref()
+        .unwrap_or("application/octet-stream");
+    Ok(Response::new(200, content_type, envelope.payload.clone()))
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect()
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+            (b'+', _) => decoded.push(b' '),
+            (byte, _) => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// Requests are served one at a time, each body read only up to the size limit.
+pub fn serve(listen: &str, options: &ServerOptions) -> Result<()> {
+    let server = tiny_http::Server::http(listen)
+        .map_err(|e| anyhow::anyhow!("Could not listen on {}: {}", listen, e))?;
+    noteln!("Listening on http://{}", listen);
+
+    for mut request in server.incoming_requests() {
+        let mut body = Vec::new();
+        let limit = options.max_size as u64 + 1;
+        if let Err(e) = request.as_reader().take(limit).read_to_end(&mut body) {
+            tracing::debug!(error = %e, "could not read request body");
+            continue;
+        }
+        let headers: Vec<(String, String)> = request
+            .headers()
+            .iter()
+            .map(|h| (h.field.to_string(), h.value.to_string()))
+            .collect();
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(field, _)| field.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.clone())
+        };
+
+        let method = request.method().to_string();
+        let response = handle(
+            &Request {
+                method: &method,
+                url: request.url(),
+                header: &header,
+                body: &body,
+            },
+            options,
+        );
+        // Only the path, the query may hold more than is worth keeping in logs.
+        let path = request.url().split('?').next().unwrap_or_default();
+        tracing::debug!(method, path, status = response.status, "served");
+
+        let content_type =
+            tiny_http::Header::from_bytes("Content-Type", response.content_type.as_bytes())
+                .expect("content types are valid header values");
+        let reply = tiny_http::Response::from_data(response.body)
+            .with_status_code(response.status)
+            .with_header(content_type);
+        if let Err(e) = request.respond(reply) {
+            tracing::debug!(error = %e, "could not send response");
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pngme::chunk::Chunk;
+
+    fn testing_png() -> Vec<u8> {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::IHDR, vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ])
+        .as_bytes()
+    }
+
+    fn send(method: &str, url: &str, headers: &[(&str, &str)], body: &[u8]) -> Response {
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(field, _)| field.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.to_string())
+        };
+        let options = ServerOptions {
+            max_size: 1024,
+            token: Some("secret".to_string()),
+        };
+        let request = Request {
+            method,
+            url,
+            header: &header,
+            body,
+        };
+        handle(&request, &options)
+    }
+
+    const AUTH: (&str, &str) = ("authorization", "Bearer secret");
+
+    #[test]
+    fn test_encode_decode() {
+        let encoded = send(
+            "POST",
+            "/encode?chunk_type=ruSt",
+            &[AUTH, ("X-Pngme-Message", "hello%20there%21")],
+            &testing_png(),
+        );
+        assert_eq!(encoded.status, 200);
+        assert_eq!(encoded.content_type, "image/png");
+
+        let decoded = send("POST", "/decode?chunk_type=ruSt", &[AUTH], &encoded.body);
+        assert_eq!(decoded.status, 200);
+        assert_eq!(decoded.body, b"hello there!");
+        assert_eq!(decoded.content_type, "text/plain");
+
+        let missing = send("POST", "/decode?chunk_type=abCd", &[AUTH], &encoded.body);
+        assert_eq!(missing.status, 404);
+    }
+
+    #[test]
+    fn test_passphrase() {
+        let passphrase = ("X-Pngme-Passphrase", "hunter2");
+        let url = "/encode?chunk_type=ruSt&encoding=base64";
+        let message = ("X-Pngme-Message", "AP8%3D");
+        let encoded = send("POST", url, &[AUTH, message, passphrase], &testing_png());
+        assert_eq!(encoded.status, 200);
+
+        let locked = send("POST", "/decode?chunk_type=ruSt", &[AUTH], &encoded.body);
+        assert_eq!(locked.status, 400);
+        let decoded = send(
+            "POST",
+            "/decode?chunk_type=ruSt",
+            &[AUTH, passphrase],
+            &encoded.body,
+        );
+        assert_eq!(decoded.body, [0, 0xff]);
+    }
+
+    #[test]
+    fn test_rejections() {
+        let png = testing_png();
+        assert_eq!(send("GET", "/health", &[], b"").status, 401);
+        assert_eq!(
+            send("GET", "/health", &[("Authorization", "Bearer wrong")], b"").status,
+            401
+        );
+        for wrong in ["Bearer secreT", "Bearer secret2", "Bearer ", "secret"] {
+            let status = send("GET", "/health", &[("Authorization", wrong)], b"").status;
+            assert_eq!(status, 401);
+        }
+        assert_eq!(send("GET", "/health", &[AUTH], b"").status, 200);
+        assert_eq!(send("POST", "/decode", &[AUTH], &[0; 2048]).status, 413);
+        assert_eq!(send("GET", "/encode", &[AUTH], &png).status, 405);
+        assert_eq!(send("POST", "/other", &[AUTH], &png).status, 404);
+        assert_eq!(
+            send("POST", "/encode?chunk_type=ruSt", &[AUTH], &png).status,
+            400
+        );
+        assert_eq!(
+            send("POST", "/decode?chunk_type=ruSt", &[AUTH], b"GIF89a").status,
+            422
+        );
+    }
+
+    #[test]
+    fn test_decompression_limit() {
+        use pngme::{compression::Compression, ihdr::ColorType, ihdr::Ihdr};
+
+        // A few hundred bytes of body that inflate well past 16 times the 1024 byte limit.
+        let mut png = Png::try_from(testing_png().as_slice()).unwrap();
+        let envelope = Envelope::new(vec![0; 100_000]).compressed(Compression::Zstd);
+        png.embed_envelope(StegoMethod::Chunk, "ruSt", &envelope)
+            .unwrap();
+        let body = png.as_bytes();
+        assert!(body.len() < 1024);
+        let response = send("POST", "/decode?chunk_type=ruSt", &[AUTH], &body);
+        assert_eq!(response.status, 413);
+
+        let header = Ihdr::new(1000, 1000, 8, ColorType::Rgba).unwrap();
+        let png = Png::from_chunks(vec![
+            header.into(),
+            Chunk::new(ChunkType::IDAT, vec![0; 16]),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ]);
+        let url = "/decode?chunk_type=ruSt&method=lsb";
+        assert_eq!(send("POST", url, &[AUTH], &png.as_bytes()).status, 413);
+        let url = "/encode?chunk_type=ruSt&method=lsb";
+        let message = ("X-Pngme-Message", "hello");
+        let response = send("POST", url, &[AUTH, message], &png.as_bytes());
+        assert_eq!(response.status, 413);
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let query = parse_query("a=1&b=x+y%2Fz&c&=d&e=%zz");
+        assert_eq!(query["a"], "1");
+        assert_eq!(query["b"], "x y/z");
+        assert_eq!(query["c"], "");
+        assert_eq!(query[""], "d");
+        assert_eq!(query["e"], "%zz");
+    }
+}
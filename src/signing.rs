@@ -0,0 +1,158 @@
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    crypto::{
+        ed25519_public_key, sign_ed25519, verify_ed25519, CryptoError, ED25519_KEY_LENGTH,
+        ED25519_SIGNATURE_LENGTH,
+    },
+    png::Png,
+};
+use anyhow::Result;
+use std::str::FromStr;
+
+// Private, not safe to copy: editors that do not know it should drop it along with their edits.
+pub const SIGNATURE_CHUNK_TYPE: &str = "siGN";
+
+impl Png {
+    // Signs the PNG signature and every chunk except an existing signature chunk, then stores the
+    // public key and signature in a signature chunk right before IEND.
+    pub fn sign(&mut self, secret_key: &[u8; ED25519_KEY_LENGTH]) -> Result<()> {
+        let chunk_type = ChunkType::from_str(SIGNATURE_CHUNK_TYPE)?;
+        let mut chunks = self.unsigned_chunks();
+
+        let signature = sign_ed25519(secret_key, &signed_bytes(self.signature(), &chunks));
+        let mut data = ed25519_public_key(secret_key).to_vec();
+        data.extend(signature);
+
+        let index = chunks
+            .iter()
+            .position(|c| *c.chunk_type() == ChunkType::IEND)
+            .unwrap_or(chunks.len());
+        chunks.insert(index, Chunk::new(chunk_type, data));
+        self.replace_chunks(chunks);
+
+        Ok(())
+    }
+
+    pub fn verify(&self, public_key: &[u8; ED25519_KEY_LENGTH]) -> Result<()> {
+        let Some((_, signature)) = self.file_signature() else {
+            return Err(CryptoError::NotSigned.into());
+        };
+
+        let signed = signed_bytes(self.signature(), &self.unsigned_chunks());
+        verify_ed25519(public_key, &signed, &signature)
+    }
+
+    // The public key recorded by the signer, which is only meaningful once verified against a key
+    // the reader trusts.
+    pub fn signed_by(&self) -> Option<[u8; ED25519_KEY_LENGTH]> {
+        self.file_signature().map(|(public_key, _)| public_key)
+    }
+
+    fn unsigned_chunks(&self) -> Vec<Chunk> {
+        self.chunks()
+            .iter()
+            .filter(|c| c.chunk_type().to_string() != SIGNATURE_CHUNK_TYPE)
+            .cloned()
+            .collect()
+    }
+
+    fn file_signature(&self) -> Option<([u8; ED25519_KEY_LENGTH], [u8; ED25519_SIGNATURE_LENGTH])> {
+        let data = self.chunk_by_type(SIGNATURE_CHUNK_TYPE)?.chunk_data();
+        if data.len() != ED25519_KEY_LENGTH + ED25519_SIGNATURE_LENGTH {
+            return None;
+        }
+
+        let (public_key, signature) = data.split_at(ED25519_KEY_LENGTH);
+        Some((public_key.try_into().ok()?, signature.try_into().ok()?))
+    }
+}
+
+fn signed_bytes(signature: &[u8], chunks: &[Chunk]) -> Vec<u8> {
+    let mut bytes = signature.to_vec();
+    for chunk in chunks {
+        bytes.extend(chunk.as_bytes());
+    }
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::PngBuilder;
+
+    const SECRET_KEY: [u8; ED25519_KEY_LENGTH] = [5; ED25519_KEY_LENGTH];
+
+    fn testing_png() -> Png {
+        PngBuilder::new()
+            .chunk(Chunk::new(ChunkType::IHDR, vec![0; 13]))
+            .chunk(Chunk::new(ChunkType::IDAT, vec![1, 2, 3]))
+            .chunk(Chunk::new(ChunkType::IEND, vec![]))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let mut png = testing_png();
+        let public_key = ed25519_public_key(&SECRET_KEY);
+        png.sign(&SECRET_KEY).unwrap();
+
+        assert_eq!(png.chunks().len(), 4);
+        assert_eq!(
+            png.chunks()[2].chunk_type().to_string(),
+            SIGNATURE_CHUNK_TYPE
+        );
+        assert_eq!(png.signed_by(), Some(public_key));
+        assert!(png.verify(&public_key).is_ok());
+        assert!(png.verify(&ed25519_public_key(&[6; 32])).is_err());
+
+        let reparsed = Png::try_from(png.as_bytes().as_slice()).unwrap();
+        assert!(reparsed.verify(&public_key).is_ok());
+
+        // Signing again replaces the signature rather than signing it.
+        png.sign(&SECRET_KEY).unwrap();
+        assert_eq!(png.chunks().len(), 4);
+        assert!(png.verify(&public_key).is_ok());
+    }
+
+    #[test]
+    fn test_detects_changes() {
+        let public_key = ed25519_public_key(&SECRET_KEY);
+        let mut signed = testing_png();
+        signed.sign(&SECRET_KEY).unwrap();
+
+        let mut added = signed.clone();
+        added.insert_chunk_ordered(Chunk::new(ChunkType::from_str("ruSt").unwrap(), vec![1]));
+        let mut removed = signed.clone();
+        removed.remove_first_chunk("IDAT").unwrap();
+        let mut modified = signed.clone();
+        modified.chunks_mut()[1].set_data(vec![1, 2, 4]);
+
+        for png in [added, removed, modified] {
+            assert_eq!(
+                png.verify(&public_key)
+                    .err()
+                    .unwrap()
+                    .downcast::<CryptoError>()
+                    .unwrap(),
+                CryptoError::BadSignature
+            );
+        }
+    }
+
+    #[test]
+    fn test_not_signed() {
+        assert_eq!(
+            testing_png()
+                .verify(&ed25519_public_key(&SECRET_KEY))
+                .err()
+                .unwrap()
+                .downcast::<CryptoError>()
+                .unwrap(),
+            CryptoError::NotSigned
+        );
+        assert_eq!(testing_png().signed_by(), None);
+    }
+}
@@ -0,0 +1,127 @@
+use pngme::{crypto::CryptoError, stego::StegoError};
+use std::{fmt::Display, process::ExitCode};
+
+// Exit statuses are part of the command line interface, scripts branch on them, so existing
+// values must never change. They are listed in the --help text by EXIT_STATUS_HELP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    Success = 0,
+    Failure = 1,
+    NotFound = 2,
+    InvalidPng = 3,
+    VerificationFailed = 4,
+    DecryptionFailed = 5,
+    Usage = 64,
+}
+
+pub const EXIT_STATUS_HELP: &str = "\
+Exit status:
+  0   success
+  1   any other error
+  2   chunk or payload not found
+  3   not a valid PNG
+  4   signature or authentication check failed
+  5   decryption failed, wrong passphrase or key
+  64  invalid command line";
+
+impl ExitStatus {
+    // Tags an error message so the command exits with this status.
+    pub fn error(self, message: impl Display) -> StatusError {
+        StatusError {
+            status: self,
+            message: message.to_string(),
+        }
+    }
+
+    // Exits with this status without printing anything, for commands whose answer is the status.
+    pub fn silent(self) -> StatusError {
+        self.error("")
+    }
+
+    // Tagged errors decide for themselves, library errors are recognized by type.
+    pub fn of(error: &anyhow::Error) -> Self {
+        if let Some(error) = error.downcast_ref::<StatusError>() {
+            return error.status;
+        }
+        match (
+            error.downcast_ref::<StegoError>(),
+            error.downcast_ref::<CryptoError>(),
+        ) {
+            (Some(StegoError::NoPayload), _) => Self::NotFound,
+            (
+                _,
+                Some(
+                    CryptoError::BadSignature
+                    | CryptoError::AuthenticationFailed
+                    | CryptoError::NotSigned,
+                ),
+            ) => Self::VerificationFailed,
+            (_, Some(CryptoError::DecryptionFailed)) => Self::DecryptionFailed,
+            _ => Self::Failure,
+        }
+    }
+}
+
+impl From<ExitStatus> for ExitCode {
+    fn from(status: ExitStatus) -> Self {
+        ExitCode::from(status as u8)
+    }
+}
+
+#[derive(Debug)]
+pub struct StatusError {
+    pub status: ExitStatus,
+    message: String,
+}
+
+impl StatusError {
+    pub fn is_silent(error: &anyhow::Error) -> bool {
+        matches!(error.downcast_ref::<StatusError>(), Some(e) if e.message.is_empty())
+    }
+}
+
+impl Display for StatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for StatusError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_of() {
+        let error = anyhow::Error::from(StegoError::NoPayload).context("Could not decode");
+        assert_eq!(ExitStatus::of(&error), ExitStatus::NotFound);
+
+        let error = anyhow::Error::from(CryptoError::BadSignature);
+        assert_eq!(ExitStatus::of(&error), ExitStatus::VerificationFailed);
+
+        let error = None::<()>
+            .with_context(|| ExitStatus::InvalidPng.error("a.png is not a valid PNG"))
+            .unwrap_err();
+        assert_eq!(ExitStatus::of(&error), ExitStatus::InvalidPng);
+        assert_eq!(error.to_string(), "a.png is not a valid PNG");
+
+        let error =
+            anyhow::anyhow!("Bad header").context(ExitStatus::InvalidPng.error("not a PNG"));
+        assert_eq!(
+            ExitStatus::of(&error.context("outer")),
+            ExitStatus::InvalidPng
+        );
+
+        assert_eq!(
+            ExitStatus::of(&anyhow::anyhow!("Could not read a.png")),
+            ExitStatus::Failure
+        );
+
+        let error = anyhow::Error::from(ExitStatus::Failure.silent());
+        assert!(StatusError::is_silent(&error));
+        assert_eq!(ExitStatus::of(&error), ExitStatus::Failure);
+        assert!(!StatusError::is_silent(&anyhow::anyhow!("Bad header")));
+    }
+}
@@ -0,0 +1,541 @@
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    crypto::{stretch_passphrase, SALT_LENGTH},
+    ihdr::ColorType,
+    imagedata::ImageData,
+    options::DEFAULT_MAX_CHUNK_LENGTH,
+    png::Png,
+};
+use anyhow::{bail, Error, Result};
+use rand::{seq::SliceRandom, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use std::str::FromStr;
+use thiserror::Error;
+
+const LENGTH_BITS: usize = 32;
+const SALT_BITS: usize = SALT_LENGTH * 8;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum StegoError {
+    #[error("LSB embedding needs 8 or 16 bit samples without a palette.")]
+    UnsupportedImage,
+    #[error("Payload of {found:?} bytes exceeds the capacity of {capacity:?} bytes.")]
+    PayloadTooLarge { capacity: usize, found: usize },
+    #[error("No hidden payload found.")]
+    NoPayload,
+    #[error("Alpha embedding needs an image with an alpha channel.")]
+    NoAlphaChannel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StegoMethod {
+    #[default]
+    Chunk,
+    Lsb,
+    Alpha {
+        opaque_only: bool,
+    },
+}
+
+impl FromStr for StegoMethod {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "chunk" => Ok(Self::Chunk),
+            "lsb" => Ok(Self::Lsb),
+            "alpha" => Ok(Self::Alpha { opaque_only: false }),
+            "alpha-opaque" => Ok(Self::Alpha { opaque_only: true }),
+            _ => bail!(
+                "Unknown embedding method {:?} (expected chunk, lsb, alpha or alpha-opaque)",
+                s
+            ),
+        }
+    }
+}
+
+impl StegoMethod {
+    // Indices of the pixel bytes whose least significant bit carries payload, in embedding order.
+    // With a passphrase the first slots hold a random salt, and the rest are shuffled with a key
+    // stretched from both, so each guess at the passphrase costs a full key derivation.
+    fn slots(&self, image_data: &ImageData, passphrase: Option<&str>) -> Result<Vec<usize>> {
+        let mut slots = self.sequential_slots(image_data)?;
+        if let Some(passphrase) = passphrase {
+            if slots.len() < SALT_BITS {
+                return Ok(Vec::new());
+            }
+            let pixels = image_data.pixels();
+            let mut salt_bits = slots[..SALT_BITS].iter().map(|&slot| pixels[slot] & 1);
+            let salt = collect_bytes(&mut salt_bits, SALT_LENGTH)?;
+            slots.drain(..SALT_BITS);
+            let key = stretch_passphrase(passphrase, &salt)?;
+            slots.shuffle(&mut ChaCha20Rng::from_seed(key));
+        }
+
+        Ok(slots)
+    }
+
+    pub(crate) fn sequential_slots(&self, image_data: &ImageData) -> Result<Vec<usize>> {
+        let header = image_data.header();
+        if header.color_type == ColorType::Indexed || header.bit_depth < 8 {
+            return Err(StegoError::UnsupportedImage.into());
+        }
+
+        // Only the low byte of 16 bit samples is used.
+        let sample_bytes = header.bit_depth as usize / 8;
+        let pixels = image_data.pixels();
+
+        match *self {
+            Self::Alpha { opaque_only } => {
+                if !header.color_type.has_alpha() {
+                    return Err(StegoError::NoAlphaChannel.into());
+                }
+
+                let pixel_bytes = header.color_type.channels() as usize * sample_bytes;
+                let slots = (pixel_bytes - 1..pixels.len()).step_by(pixel_bytes);
+
+                // Only the bit embedding changes is ignored, so the same pixels stay selected; the
+                // high byte of a 16 bit sample has to be fully opaque.
+                Ok(slots
+                    .filter(|&slot| {
+                        !opaque_only
+                            || (pixels[slot] | 1 == u8::MAX
+                                && pixels[slot + 1 - sample_bytes..slot]
+                                    .iter()
+                                    .all(|&b| b == u8::MAX))
+                    })
+                    .collect())
+            }
+            _ => Ok((sample_bytes - 1..pixels.len())
+                .step_by(sample_bytes)
+                .collect()),
+        }
+    }
+}
+
+fn lsb_capacity(slots: usize) -> usize {
+    slots.saturating_sub(LENGTH_BITS) / 8
+}
+
+impl Png {
+    // Raw bytes, an envelope embedded here takes Envelope::overhead of them before its payload.
+    // Chunks are only limited by the length field, so report what default parsing accepts.
+    pub fn capacity(&self, method: StegoMethod) -> Result<usize> {
+        match method {
+            StegoMethod::Chunk => Ok(DEFAULT_MAX_CHUNK_LENGTH as usize),
+            StegoMethod::Lsb | StegoMethod::Alpha { .. } => {
+                let slots = method.sequential_slots(&self.image_data()?)?;
+                Ok(lsb_capacity(slots.len()))
+            }
+        }
+    }
+
+    pub fn embed(&mut self, method: StegoMethod, chunk_type: &str, payload: &[u8]) -> Result<()> {
+        self.embed_inner(method, chunk_type, payload, None)
+    }
+
+    pub fn extract(&self, method: StegoMethod, chunk_type: &str) -> Result<Vec<u8>> {
+        self.extract_inner(method, chunk_type, None)
+    }
+
+    // The passphrase scatters pixel embedding; chunk embedding is unaffected by it.
+    pub fn embed_keyed(
+        &mut self,
+        method: StegoMethod,
+        chunk_type: &str,
+        payload: &[u8],
+        passphrase: &str,
+    ) -> Result<()> {
+        self.embed_inner(method, chunk_type, payload, Some(passphrase))
+    }
+
+    pub fn extract_keyed(
+        &self,
+        method: StegoMethod,
+        chunk_type: &str,
+        passphrase: &str,
+    ) -> Result<Vec<u8>> {
+        self.extract_inner(method, chunk_type, Some(passphrase))
+    }
+
+    fn embed_inner(
+        &mut self,
+        method: StegoMethod,
+        chunk_type: &str,
+        payload: &[u8],
+        passphrase: Option<&str>,
+    ) -> Result<()> {
+        match method {
+            StegoMethod::Chunk => {
+                let chunk_type = ChunkType::from_str(chunk_type)?;
+                self.insert_chunk_ordered(Chunk::new(chunk_type, payload.to_vec()));
+                Ok(())
+            }
+            StegoMethod::Lsb | StegoMethod::Alpha { .. } => {
+                self.embed_lsb(method, payload, passphrase)
+            }
+        }
+    }
+
+    fn extract_inner(
+        &self,
+        method: StegoMethod,
+        chunk_type: &str,
+        passphrase: Option<&str>,
+    ) -> Result<Vec<u8>> {
+        match method {
+            StegoMethod::Chunk => match self.chunk_by_type(chunk_type) {
+                Some(chunk) => Ok(chunk.chunk_data().to_vec()),
+                None => Err(StegoError::NoPayload.into()),
+            },
+            StegoMethod::Lsb | StegoMethod::Alpha { .. } => self.extract_lsb(method, passphrase),
+        }
+    }
+
+    fn embed_lsb(
+        &mut self,
+        method: StegoMethod,
+        payload: &[u8],
+        passphrase: Option<&str>,
+    ) -> Result<()> {
+        let mut image_data = self.image_data()?;
+        if passphrase.is_some() {
+            let salt: [u8; SALT_LENGTH] = rand::random();
+            let slots = method.sequential_slots(&image_data)?;
+            let pixels = image_data.pixels_mut();
+            for (&slot, bit) in slots.iter().zip(bits(&salt)) {
+                pixels[slot] = (pixels[slot] & !1) | bit;
+            }
+        }
+        let slots = method.slots(&image_data, passphrase)?;
+
+        let capacity = lsb_capacity(slots.len());
+        if payload.len() > capacity || payload.len() > u32::MAX as usize {
+            return Err(StegoError::PayloadTooLarge {
+                capacity,
+                found: payload.len(),
+            }
+            .into());
+        }
+
+        let length = (payload.len() as u32).to_be_bytes();
+        let pixels = image_data.pixels_mut();
+        for (&slot, bit) in slots.iter().zip(bits(&length).chain(bits(payload))) {
+            pixels[slot] = (pixels[slot] & !1) | bit;
+        }
+
+        self.set_image_data(&image_data)
+    }
+
+    fn extract_lsb(&self, method: StegoMethod, passphrase: Option<&str>) -> Result<Vec<u8>> {
+        let image_data = self.image_data()?;
+        let slots = method.slots(&image_data, passphrase)?;
+        let pixels = image_data.pixels();
+        let mut bits = slots.iter().map(|&slot| pixels[slot] & 1);
+
+        let length = u32::from_be_bytes(collect_bytes(&mut bits, 4)?.try_into().unwrap());
+        if length as usize > lsb_capacity(slots.len()) {
+            return Err(StegoError::NoPayload.into());
+        }
+
+        collect_bytes(&mut bits, length as usize)
+    }
+}
+
+fn bits(bytes: &[u8]) -> impl Iterator<Item = u8> + '_ {
+    bytes
+        .iter()
+        .flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1))
+}
+
+fn collect_bytes(bits: &mut impl Iterator<Item = u8>, count: usize) -> Result<Vec<u8>> {
+    (0..count)
+        .map(|_| {
+            (0..8).try_fold(0u8, |byte, _| match bits.next() {
+                Some(bit) => Ok(byte << 1 | bit),
+                None => Err(StegoError::NoPayload.into()),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{builder::PngBuilder, ihdr::Ihdr};
+
+    fn testing_png(bit_depth: u8, color_type: ColorType) -> Png {
+        let header = Ihdr::new(16, 16, bit_depth, color_type).unwrap();
+        let length = header.width as usize
+            * header.height as usize
+            * color_type.channels() as usize
+            * bit_depth as usize
+            / 8;
+        let pixels = (0..length).map(|i| (i * 31 % 256) as u8).collect();
+
+        let mut png = PngBuilder::new().chunk(header.into()).build().unwrap();
+        png.set_image_data(&ImageData::new(header, pixels).unwrap())
+            .unwrap();
+        png
+    }
+
+    #[test]
+    fn test_interlaced_carrier() {
+        let mut png = testing_png(16, ColorType::Rgba);
+        let mut header = png.header().unwrap();
+        header.interlace_method = 1;
+        let image_data = ImageData::new(header, png.image_data().unwrap().into_pixels()).unwrap();
+        png.set_image_data(&image_data).unwrap();
+
+        png.embed(
+            StegoMethod::Alpha { opaque_only: false },
+            "ruSt",
+            b"interlaced",
+        )
+        .unwrap();
+        let png = Png::try_from(png.as_bytes().as_slice()).unwrap();
+        assert!(png.header().unwrap().is_interlaced());
+        assert_eq!(
+            png.extract(StegoMethod::Alpha { opaque_only: false }, "ruSt")
+                .unwrap(),
+            b"interlaced"
+        );
+    }
+
+    #[test]
+    fn test_method_from_str() {
+        assert_eq!(StegoMethod::from_str("LSB").unwrap(), StegoMethod::Lsb);
+        assert_eq!(StegoMethod::from_str("chunk").unwrap(), StegoMethod::Chunk);
+        assert_eq!(
+            StegoMethod::from_str("alpha-opaque").unwrap(),
+            StegoMethod::Alpha { opaque_only: true }
+        );
+        assert!(StegoMethod::from_str("dct").is_err());
+    }
+
+    #[test]
+    fn test_chunk_round_trip() {
+        let mut png = testing_png(8, ColorType::Rgb);
+        png.embed(StegoMethod::Chunk, "ruSt", b"hidden").unwrap();
+
+        assert_eq!(png.extract(StegoMethod::Chunk, "ruSt").unwrap(), b"hidden");
+        assert!(png.extract(StegoMethod::Chunk, "raSt").is_err());
+    }
+
+    #[test]
+    fn test_lsb_round_trip() {
+        for (bit_depth, color_type) in [(8, ColorType::Rgb), (16, ColorType::GrayscaleAlpha)] {
+            let mut png = testing_png(bit_depth, color_type);
+            let original = png.image_data().unwrap();
+
+            png.embed(StegoMethod::Lsb, "ruSt", b"hidden in plain sight")
+                .unwrap();
+            let png = Png::try_from(png.as_bytes().as_slice()).unwrap();
+
+            assert_eq!(
+                png.extract(StegoMethod::Lsb, "ruSt").unwrap(),
+                b"hidden in plain sight"
+            );
+            assert!(png.chunk_by_type("ruSt").is_none());
+            for (a, b) in original
+                .pixels()
+                .iter()
+                .zip(png.image_data().unwrap().pixels())
+            {
+                assert!(a.abs_diff(*b) <= 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_alpha_round_trip() {
+        for bit_depth in [8, 16] {
+            let mut png = testing_png(bit_depth, ColorType::Rgba);
+            let original = png.image_data().unwrap();
+
+            png.embed(StegoMethod::Alpha { opaque_only: false }, "ruSt", b"alpha")
+                .unwrap();
+            assert_eq!(
+                png.extract(StegoMethod::Alpha { opaque_only: false }, "ruSt")
+                    .unwrap(),
+                b"alpha"
+            );
+
+            // Colour samples stay bit-exact.
+            let sample_bytes = bit_depth as usize / 8;
+            let pixel_bytes = 4 * sample_bytes;
+            for (i, (a, b)) in original
+                .pixels()
+                .iter()
+                .zip(png.image_data().unwrap().pixels())
+                .enumerate()
+            {
+                if i % pixel_bytes < 3 * sample_bytes {
+                    assert_eq!(a, b);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_alpha_opaque_only() {
+        let mut png = testing_png(8, ColorType::Rgba);
+        let mut image_data = png.image_data().unwrap();
+        let opaque: Vec<usize> = (0..256).filter(|i| i % 3 == 0).collect();
+        for (i, pixel) in image_data.pixels_mut().chunks_exact_mut(4).enumerate() {
+            pixel[3] = if opaque.contains(&i) { 255 } else { 128 };
+        }
+        png.set_image_data(&image_data).unwrap();
+
+        let method = StegoMethod::Alpha { opaque_only: true };
+        png.embed(method, "ruSt", b"opaque").unwrap();
+        assert_eq!(png.extract(method, "ruSt").unwrap(), b"opaque");
+
+        for (i, pixel) in png
+            .image_data()
+            .unwrap()
+            .pixels()
+            .chunks_exact(4)
+            .enumerate()
+        {
+            if !opaque.contains(&i) {
+                assert_eq!(pixel[3], 128);
+            }
+        }
+        assert!(png.embed(method, "ruSt", &[0; 7]).is_err());
+    }
+
+    #[test]
+    fn test_alpha_opaque_only_16_bit() {
+        let png = testing_png(16, ColorType::GrayscaleAlpha);
+        let mut image_data = png.image_data().unwrap();
+        let alphas = [
+            [0xff, 0xff],
+            [0xff, 0xfe],
+            [0xfe, 0xff],
+            [0xfe, 0xfe],
+            [0x7f, 0xff],
+        ];
+        for (pixel, alpha) in image_data.pixels_mut().chunks_exact_mut(4).zip(alphas) {
+            pixel[2..].copy_from_slice(&alpha);
+        }
+        for pixel in image_data
+            .pixels_mut()
+            .chunks_exact_mut(4)
+            .skip(alphas.len())
+        {
+            pixel[2..].copy_from_slice(&[0, 0]);
+        }
+
+        let slots = StegoMethod::Alpha { opaque_only: true }
+            .sequential_slots(&image_data)
+            .unwrap();
+        assert_eq!(slots, [3, 7]);
+    }
+
+    #[test]
+    fn test_alpha_without_alpha_channel() {
+        let mut png = testing_png(8, ColorType::Rgb);
+        assert_eq!(
+            png.embed(StegoMethod::Alpha { opaque_only: false }, "ruSt", b"x")
+                .err()
+                .unwrap()
+                .downcast::<StegoError>()
+                .unwrap(),
+            StegoError::NoAlphaChannel
+        );
+    }
+
+    #[test]
+    fn test_keyed_round_trip() {
+        let mut png = testing_png(8, ColorType::Rgb);
+        let original = png.image_data().unwrap();
+
+        png.embed_keyed(StegoMethod::Lsb, "ruSt", b"scattered", "hunter2")
+            .unwrap();
+        assert_eq!(
+            png.extract_keyed(StegoMethod::Lsb, "ruSt", "hunter2")
+                .unwrap(),
+            b"scattered"
+        );
+        assert_ne!(
+            png.extract_keyed(StegoMethod::Lsb, "ruSt", "hunter3").ok(),
+            Some(b"scattered".to_vec())
+        );
+
+        // Bits are spread over the image rather than packed into the first rows.
+        let changed: Vec<usize> = original
+            .pixels()
+            .iter()
+            .zip(png.image_data().unwrap().pixels())
+            .enumerate()
+            .filter(|(_, (a, b))| a != b)
+            .map(|(i, _)| i)
+            .collect();
+        assert!(changed.iter().any(|&i| i > 384));
+
+        // A fresh salt each time, so the same passphrase scatters differently in every image.
+        let mut again = testing_png(8, ColorType::Rgb);
+        again
+            .embed_keyed(StegoMethod::Lsb, "ruSt", b"scattered", "hunter2")
+            .unwrap();
+        assert_ne!(again.image_data().unwrap(), png.image_data().unwrap());
+        assert_eq!(
+            again
+                .extract_keyed(StegoMethod::Lsb, "ruSt", "hunter2")
+                .unwrap(),
+            b"scattered"
+        );
+    }
+
+    #[test]
+    fn test_capacity() {
+        let png = testing_png(8, ColorType::Rgba);
+        assert_eq!(png.capacity(StegoMethod::Lsb).unwrap(), 124);
+        assert_eq!(
+            png.capacity(StegoMethod::Alpha { opaque_only: false })
+                .unwrap(),
+            28
+        );
+        assert_eq!(
+            png.capacity(StegoMethod::Chunk).unwrap(),
+            DEFAULT_MAX_CHUNK_LENGTH as usize
+        );
+
+        let png = testing_png(16, ColorType::Rgb);
+        assert_eq!(png.capacity(StegoMethod::Lsb).unwrap(), 92);
+        assert!(testing_png(4, ColorType::Indexed)
+            .capacity(StegoMethod::Lsb)
+            .is_err());
+    }
+
+    #[test]
+    fn test_lsb_payload_too_large() {
+        let mut png = testing_png(8, ColorType::Grayscale);
+        assert_eq!(
+            png.embed(StegoMethod::Lsb, "ruSt", &[0; 29])
+                .err()
+                .unwrap()
+                .downcast::<StegoError>()
+                .unwrap(),
+            StegoError::PayloadTooLarge {
+                capacity: 28,
+                found: 29
+            }
+        );
+    }
+
+    #[test]
+    fn test_lsb_unsupported_image() {
+        let mut png = testing_png(4, ColorType::Indexed);
+        assert_eq!(
+            png.embed(StegoMethod::Lsb, "ruSt", b"x")
+                .err()
+                .unwrap()
+                .downcast::<StegoError>()
+                .unwrap(),
+            StegoError::UnsupportedImage
+        );
+    }
+}
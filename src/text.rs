@@ -0,0 +1,620 @@
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    compression::{deflate, inflate},
+    png::Png,
+};
+use anyhow::{Error, Result};
+use thiserror::Error;
+
+pub const XMP_KEYWORD: &str = "XML:com.adobe.xmp";
+
+// Text at least this long is compressed by Text::auto.
+pub const COMPRESS_THRESHOLD: usize = 1024;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum TextError {
+    #[error("Keyword has to be 1-79 bytes long, got {found:?} bytes.")]
+    InvalidKeywordLength { found: usize },
+    #[error("Keyword contains a non-printable Latin-1 character {found:?}.")]
+    InvalidKeywordCharacter { found: char },
+    #[error("Keyword must not have leading, trailing or consecutive spaces.")]
+    InvalidKeywordSpacing,
+    #[error("Text contains a character outside Latin-1: {found:?}.")]
+    NotLatin1 { found: char },
+    #[error("Text chunk is missing the keyword separator.")]
+    MissingSeparator,
+    #[error("Compressed text chunk ends before its compression method.")]
+    MissingCompressionMethod,
+    #[error("Unknown compression method {found:?}.")]
+    InvalidCompressionMethod { found: u8 },
+    #[error("Unknown compression flag {found:?}.")]
+    InvalidCompressionFlag { found: u8 },
+    #[error("Language tag {found:?} has to be ASCII letters, digits and hyphens.")]
+    InvalidLanguageTag { found: String },
+    #[error("International text has to be valid UTF-8 without NUL characters.")]
+    InvalidUtf8,
+    #[error("Expected a text chunk, got {found:?}.")]
+    WrongChunkType { found: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Text {
+    keyword: String,
+    text: String,
+    compressed: bool,
+    international: bool,
+    language_tag: String,
+    translated_keyword: String,
+}
+
+impl TryFrom<&Chunk> for Text {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let compressed = match *chunk.chunk_type() {
+            ChunkType::tEXt => false,
+            ChunkType::zTXt => true,
+            ChunkType::iTXt => return Self::from_international(chunk.chunk_data()),
+            _ => {
+                return Err(TextError::WrongChunkType {
+                    found: chunk.chunk_type().to_string(),
+                }
+                .into())
+            }
+        };
+
+        let data = chunk.chunk_data();
+        let Some(separator) = data.iter().position(|&b| b == 0) else {
+            return Err(TextError::MissingSeparator.into());
+        };
+
+        let keyword = decode_latin1(&data[..separator]);
+        validate_keyword(&keyword)?;
+
+        let text = if compressed {
+            match data.get(separator + 1) {
+                Some(0) => decode_latin1(&inflate(&data[separator + 2..])?),
+                Some(&found) => return Err(TextError::InvalidCompressionMethod { found }.into()),
+                None => return Err(TextError::MissingCompressionMethod.into()),
+            }
+        } else {
+            decode_latin1(&data[separator + 1..])
+        };
+
+        Ok(Self {
+            keyword,
+            text,
+            compressed,
+            ..Self::default()
+        })
+    }
+}
+
+impl From<Text> for Chunk {
+    fn from(text: Text) -> Self {
+        let mut data = encode_latin1(&text.keyword);
+        data.push(0);
+
+        if text.international {
+            data.push(text.compressed as u8);
+            data.push(0);
+            data.extend(text.language_tag.as_bytes());
+            data.push(0);
+            data.extend(text.translated_keyword.as_bytes());
+            data.push(0);
+            if text.compressed {
+                data.extend(deflate(text.text.as_bytes()));
+            } else {
+                data.extend(text.text.as_bytes());
+            }
+            Chunk::new(ChunkType::iTXt, data)
+        } else if text.compressed {
+            data.push(0);
+            data.extend(deflate(&encode_latin1(&text.text)));
+            Chunk::new(ChunkType::zTXt, data)
+        } else {
+            data.extend(encode_latin1(&text.text));
+            Chunk::new(ChunkType::tEXt, data)
+        }
+    }
+}
+
+impl Text {
+    pub fn new(keyword: &str, text: &str) -> Result<Self> {
+        validate_keyword(keyword)?;
+        validate_latin1(text)?;
+
+        Ok(Self {
+            keyword: keyword.to_string(),
+            text: text.to_string(),
+            ..Self::default()
+        })
+    }
+
+    pub fn new_international(keyword: &str, text: &str, compressed: bool) -> Result<Self> {
+        validate_keyword(keyword)?;
+        validate_utf8(text)?;
+
+        Ok(Self {
+            keyword: keyword.to_string(),
+            text: text.to_string(),
+            compressed,
+            international: true,
+            ..Self::default()
+        })
+    }
+
+    pub fn with_language(mut self, language_tag: &str, translated_keyword: &str) -> Result<Self> {
+        validate_language_tag(language_tag)?;
+        validate_utf8(translated_keyword)?;
+
+        self.language_tag = language_tag.to_string();
+        self.translated_keyword = translated_keyword.to_string();
+        self.international = true;
+        Ok(self)
+    }
+
+    fn from_international(data: &[u8]) -> Result<Self> {
+        let mut fields = data.splitn(2, |&b| b == 0);
+        let keyword = decode_latin1(fields.next().unwrap_or_default());
+        validate_keyword(&keyword)?;
+
+        let Some(rest) = fields.next() else {
+            return Err(TextError::MissingSeparator.into());
+        };
+        let [flag, method, rest @ ..] = rest else {
+            return Err(TextError::MissingSeparator.into());
+        };
+        let compressed = match flag {
+            0 => false,
+            1 => true,
+            &found => return Err(TextError::InvalidCompressionFlag { found }.into()),
+        };
+        if compressed && *method != 0 {
+            return Err(TextError::InvalidCompressionMethod { found: *method }.into());
+        }
+
+        let mut fields = rest.splitn(3, |&b| b == 0);
+        let (Some(language_tag), Some(translated_keyword), Some(text)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            return Err(TextError::MissingSeparator.into());
+        };
+
+        let language_tag = String::from_utf8(language_tag.to_vec())?;
+        validate_language_tag(&language_tag)?;
+        let text = if compressed {
+            inflate(text)?
+        } else {
+            text.to_vec()
+        };
+
+        Ok(Self {
+            keyword,
+            text: String::from_utf8(text).map_err(|_| TextError::InvalidUtf8)?,
+            compressed,
+            international: true,
+            language_tag,
+            translated_keyword: String::from_utf8(translated_keyword.to_vec())
+                .map_err(|_| TextError::InvalidUtf8)?,
+        })
+    }
+
+    pub fn is_international(&self) -> bool {
+        self.international
+    }
+
+    pub fn language_tag(&self) -> &str {
+        &self.language_tag
+    }
+
+    pub fn translated_keyword(&self) -> &str {
+        &self.translated_keyword
+    }
+
+    // The chunk kind a reader is most likely to handle: tEXt, zTXt for long text and iTXt for text
+    // that Latin-1 cannot hold.
+    pub fn auto(keyword: &str, text: &str) -> Result<Self> {
+        let compressed = text.len() >= COMPRESS_THRESHOLD;
+        if validate_latin1(text).is_err() {
+            return Self::new_international(keyword, text, compressed);
+        }
+
+        match compressed {
+            true => Self::new_compressed(keyword, text),
+            false => Self::new(keyword, text),
+        }
+    }
+
+    pub fn new_compressed(keyword: &str, text: &str) -> Result<Self> {
+        Ok(Self {
+            compressed: true,
+            ..Self::new(keyword, text)?
+        })
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+
+    pub fn keyword(&self) -> &str {
+        &self.keyword
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+impl Png {
+    pub fn texts(&self) -> impl Iterator<Item = Text> + '_ {
+        self.chunks()
+            .iter()
+            .filter_map(|chunk| Text::try_from(chunk).ok())
+    }
+
+    pub fn get_text(&self, keyword: &str) -> Option<String> {
+        self.texts()
+            .find(|text| text.keyword() == keyword)
+            .map(|text| text.text)
+    }
+
+    pub fn set_text(&mut self, keyword: &str, text: &str) -> Result<()> {
+        self.put_text(Text::new(keyword, text)?)
+    }
+
+    pub fn set_compressed_text(&mut self, keyword: &str, text: &str) -> Result<()> {
+        self.put_text(Text::new_compressed(keyword, text)?)
+    }
+
+    pub fn set_international_text(&mut self, keyword: &str, text: &str) -> Result<()> {
+        self.put_text(Text::new_international(keyword, text, false)?)
+    }
+
+    pub fn put_text(&mut self, text: Text) -> Result<()> {
+        let keyword = text.keyword.clone();
+        let chunk = Chunk::from(text);
+
+        match self
+            .chunks_mut()
+            .iter_mut()
+            .find(|c| Text::try_from(&**c).is_ok_and(|t| t.keyword() == keyword))
+        {
+            Some(existing) => *existing = chunk,
+            None => self.insert_chunk_ordered(chunk),
+        }
+
+        Ok(())
+    }
+
+    pub fn xmp(&self) -> Option<String> {
+        self.texts()
+            .find(|text| text.is_international() && text.keyword() == XMP_KEYWORD)
+            .map(|text| text.text)
+    }
+
+    pub fn set_xmp(&mut self, xml: &str) -> Result<()> {
+        self.put_text(Text::new_international(XMP_KEYWORD, xml, false)?)
+    }
+
+    pub fn remove_text(&mut self, keyword: &str) -> Option<String> {
+        let index = self
+            .chunks()
+            .iter()
+            .position(|c| Text::try_from(c).is_ok_and(|t| t.keyword() == keyword))?;
+
+        let chunk = self.remove_chunk_at(index).ok()?;
+        Text::try_from(&chunk).ok().map(|text| text.text)
+    }
+}
+
+pub fn validate_keyword(keyword: &str) -> Result<()> {
+    let length = keyword.chars().count();
+    if !(1..=79).contains(&length) {
+        return Err(TextError::InvalidKeywordLength { found: length }.into());
+    }
+
+    if let Some(found) = keyword
+        .chars()
+        .find(|&c| !matches!(c as u32, 32..=126 | 161..=255))
+    {
+        return Err(TextError::InvalidKeywordCharacter { found }.into());
+    }
+
+    if keyword.starts_with(' ') || keyword.ends_with(' ') || keyword.contains("  ") {
+        return Err(TextError::InvalidKeywordSpacing.into());
+    }
+
+    Ok(())
+}
+
+pub(crate) fn validate_latin1(text: &str) -> Result<()> {
+    match text.chars().find(|&c| c as u32 > 255 || c == '\0') {
+        Some(found) => Err(TextError::NotLatin1 { found }.into()),
+        None => Ok(()),
+    }
+}
+
+fn validate_utf8(text: &str) -> Result<()> {
+    if text.contains('\0') {
+        return Err(TextError::InvalidUtf8.into());
+    }
+
+    Ok(())
+}
+
+fn validate_language_tag(language_tag: &str) -> Result<()> {
+    if !language_tag
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-')
+    {
+        return Err(TextError::InvalidLanguageTag {
+            found: language_tag.to_string(),
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+pub(crate) fn encode_latin1(text: &str) -> Vec<u8> {
+    text.chars().map(|c| c as u8).collect()
+}
+
+pub(crate) fn decode_latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::PngBuilder;
+
+    fn testing_png() -> Png {
+        PngBuilder::new()
+            .chunk(Chunk::new(ChunkType::IHDR, vec![0; 13]))
+            .chunk(Chunk::new(ChunkType::IDAT, vec![1, 2, 3]))
+            .text("Author", "me")
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_text_round_trip() {
+        let text = Text::new("Comment", "Caf\u{e9}").unwrap();
+        let chunk = Chunk::from(text.clone());
+
+        assert_eq!(chunk.chunk_data(), b"Comment\0Caf\xe9");
+        assert_eq!(Text::try_from(&chunk).unwrap(), text);
+    }
+
+    #[test]
+    fn test_compressed_text_round_trip() {
+        let text = Text::new_compressed("Comment", &"Caf\u{e9} ".repeat(100)).unwrap();
+        let chunk = Chunk::from(text.clone());
+
+        assert_eq!(chunk.chunk_type(), &ChunkType::zTXt);
+        assert_eq!(&chunk.chunk_data()[..9], b"Comment\0\0");
+        assert!(chunk.length() < 100);
+
+        let parsed = Text::try_from(&chunk).unwrap();
+        assert!(parsed.is_compressed());
+        assert_eq!(parsed, text);
+    }
+
+    #[test]
+    fn test_compressed_text_bad_method() {
+        let chunk = Chunk::new(ChunkType::zTXt, b"Comment\0\x01data".to_vec());
+        assert_eq!(
+            Text::try_from(&chunk)
+                .err()
+                .unwrap()
+                .downcast::<TextError>()
+                .unwrap(),
+            TextError::InvalidCompressionMethod { found: 1 }
+        );
+
+        let chunk = Chunk::new(ChunkType::zTXt, b"Comment\0".to_vec());
+        assert_eq!(
+            Text::try_from(&chunk)
+                .err()
+                .unwrap()
+                .downcast::<TextError>()
+                .unwrap(),
+            TextError::MissingCompressionMethod
+        );
+    }
+
+    #[test]
+    fn test_set_compressed_text() {
+        let mut png = testing_png();
+        png.set_compressed_text("Author", "someone else").unwrap();
+
+        assert_eq!(png.texts().count(), 1);
+        assert!(png.chunk_by_type("tEXt").is_none());
+        assert!(png.chunk_by_type("zTXt").is_some());
+        assert_eq!(png.get_text("Author"), Some("someone else".to_string()));
+    }
+
+    #[test]
+    fn test_international_text_round_trip() {
+        let text = Text::new_international("Title", "\u{65e5}\u{672c}", false)
+            .unwrap()
+            .with_language("ja", "\u{984c}\u{540d}")
+            .unwrap();
+        let chunk = Chunk::from(text.clone());
+
+        assert_eq!(chunk.chunk_type(), &ChunkType::iTXt);
+        assert_eq!(
+            chunk.chunk_data(),
+            "Title\0\0\0ja\0\u{984c}\u{540d}\0\u{65e5}\u{672c}".as_bytes()
+        );
+
+        let parsed = Text::try_from(&chunk).unwrap();
+        assert!(parsed.is_international());
+        assert_eq!(parsed.language_tag(), "ja");
+        assert_eq!(parsed.translated_keyword(), "\u{984c}\u{540d}");
+        assert_eq!(parsed, text);
+    }
+
+    #[test]
+    fn test_compressed_international_text_round_trip() {
+        let text = Text::new_international("Comment", &"\u{263A}".repeat(200), true).unwrap();
+        let chunk = Chunk::from(text.clone());
+
+        assert!(chunk.length() < 100);
+        assert_eq!(&chunk.chunk_data()[..10], b"Comment\0\x01\0");
+        assert_eq!(Text::try_from(&chunk).unwrap(), text);
+    }
+
+    #[test]
+    fn test_international_text_invalid() {
+        let chunk = Chunk::new(ChunkType::iTXt, b"Title\0\x02\0\0\0text".to_vec());
+        assert_eq!(
+            Text::try_from(&chunk)
+                .err()
+                .unwrap()
+                .downcast::<TextError>()
+                .unwrap(),
+            TextError::InvalidCompressionFlag { found: 2 }
+        );
+
+        let chunk = Chunk::new(ChunkType::iTXt, b"Title\0\0\0en\0".to_vec());
+        assert!(Text::try_from(&chunk).is_err());
+
+        let chunk = Chunk::new(ChunkType::iTXt, b"Title\0\0\0en\0\0\xff".to_vec());
+        assert!(Text::try_from(&chunk).is_err());
+
+        assert!(Text::new_international("Title", "ok", false)
+            .unwrap()
+            .with_language("en us", "")
+            .is_err());
+    }
+
+    #[test]
+    fn test_set_international_text() {
+        let mut png = testing_png();
+        png.set_international_text("Author", "\u{263A}").unwrap();
+
+        assert_eq!(png.texts().count(), 1);
+        assert!(png.chunk_by_type("iTXt").is_some());
+        assert_eq!(png.get_text("Author"), Some("\u{263A}".to_string()));
+    }
+
+    #[test]
+    fn test_xmp() {
+        let xml = "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"></x:xmpmeta>";
+        let mut png = testing_png();
+        assert_eq!(png.xmp(), None);
+
+        png.set_xmp(xml).unwrap();
+        assert_eq!(png.xmp(), Some(xml.to_string()));
+
+        let chunk = png.chunk_by_type("iTXt").unwrap();
+        assert!(chunk
+            .chunk_data()
+            .starts_with(b"XML:com.adobe.xmp\0\0\0\0\0<x:"));
+
+        png.set_xmp("<x:xmpmeta/>").unwrap();
+        assert_eq!(png.xmp(), Some("<x:xmpmeta/>".to_string()));
+        assert_eq!(png.texts().count(), 2);
+
+        assert_eq!(
+            png.remove_text(XMP_KEYWORD),
+            Some("<x:xmpmeta/>".to_string())
+        );
+        assert_eq!(png.xmp(), None);
+    }
+
+    #[test]
+    fn test_keyword_validation() {
+        assert!(validate_keyword("Title").is_ok());
+        assert!(validate_keyword("Creation Time").is_ok());
+        assert_eq!(
+            validate_keyword("")
+                .err()
+                .unwrap()
+                .downcast::<TextError>()
+                .unwrap(),
+            TextError::InvalidKeywordLength { found: 0 }
+        );
+        assert!(validate_keyword(&"k".repeat(80)).is_err());
+        assert!(validate_keyword(&"k".repeat(79)).is_ok());
+        assert_eq!(
+            validate_keyword(" Title")
+                .err()
+                .unwrap()
+                .downcast::<TextError>()
+                .unwrap(),
+            TextError::InvalidKeywordSpacing
+        );
+        assert!(validate_keyword("Two  spaces").is_err());
+        assert!(validate_keyword("Tab\there").is_err());
+        assert!(validate_keyword("\u{263A}").is_err());
+    }
+
+    #[test]
+    fn test_text_not_latin1() {
+        assert_eq!(
+            Text::new("Title", "\u{263A}")
+                .err()
+                .unwrap()
+                .downcast::<TextError>()
+                .unwrap(),
+            TextError::NotLatin1 { found: '\u{263A}' }
+        );
+    }
+
+    #[test]
+    fn test_auto() {
+        let chunk_type = |text: &str| *Chunk::from(Text::auto("Title", text).unwrap()).chunk_type();
+
+        assert_eq!(chunk_type("Dice"), ChunkType::tEXt);
+        assert_eq!(chunk_type("Würfel"), ChunkType::tEXt);
+        assert_eq!(chunk_type(&"a".repeat(COMPRESS_THRESHOLD)), ChunkType::zTXt);
+        assert_eq!(chunk_type("サイコロ"), ChunkType::iTXt);
+        assert!(Text::auto("Title", &"サ".repeat(COMPRESS_THRESHOLD))
+            .unwrap()
+            .is_compressed());
+        assert!(Text::auto(" Title", "Dice").is_err());
+    }
+
+    #[test]
+    fn test_get_text() {
+        let png = testing_png();
+        assert_eq!(png.get_text("Author"), Some("me".to_string()));
+        assert_eq!(png.get_text("Title"), None);
+    }
+
+    #[test]
+    fn test_set_text_inserts_before_idat() {
+        let mut png = testing_png();
+        png.set_text("Title", "Dice").unwrap();
+
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, vec!["IHDR", "tEXt", "tEXt", "IDAT", "IEND"]);
+        assert_eq!(png.get_text("Title"), Some("Dice".to_string()));
+    }
+
+    #[test]
+    fn test_set_text_replaces_existing() {
+        let mut png = testing_png();
+        png.set_text("Author", "you").unwrap();
+
+        assert_eq!(png.texts().count(), 1);
+        assert_eq!(png.get_text("Author"), Some("you".to_string()));
+    }
+
+    #[test]
+    fn test_remove_text() {
+        let mut png = testing_png();
+        assert_eq!(png.remove_text("Author"), Some("me".to_string()));
+        assert_eq!(png.remove_text("Author"), None);
+        assert_eq!(png.texts().count(), 0);
+    }
+}
@@ -0,0 +1,208 @@
+use crate::{
+    ancillary::{AncillaryError, TypedChunk},
+    chunk_type::ChunkType,
+    png::Png,
+};
+use anyhow::Result;
+use std::{
+    fmt::Display,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Time {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl TypedChunk for Time {
+    const CHUNK_TYPE: ChunkType = ChunkType::tIME;
+
+    fn from_data(data: &[u8]) -> Result<Self> {
+        let [y0, y1, month, day, hour, minute, second] = *data else {
+            return Err(AncillaryError::InvalidLength {
+                chunk_type: "tIME",
+                found: data.len(),
+            }
+            .into());
+        };
+
+        Ok(Self {
+            year: u16::from_be_bytes([y0, y1]),
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        })
+    }
+
+    fn to_data(&self) -> Vec<u8> {
+        let [y0, y1] = self.year.to_be_bytes();
+        vec![
+            y0,
+            y1,
+            self.month,
+            self.day,
+            self.hour,
+            self.minute,
+            self.second,
+        ]
+    }
+}
+
+impl Display for Time {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+impl Time {
+    pub fn now() -> Self {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        Self::from_unix(seconds)
+    }
+
+    pub fn from_unix(seconds: u64) -> Self {
+        let days = (seconds / 86_400) as i64;
+        let seconds_of_day = seconds % 86_400;
+
+        // Civil-from-days conversion, see http://howardhinnant.github.io/date_algorithms.html
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let day_of_era = z.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+        Self {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            hour: (seconds_of_day / 3600) as u8,
+            minute: (seconds_of_day / 60 % 60) as u8,
+            second: (seconds_of_day % 60) as u8,
+        }
+    }
+}
+
+impl Png {
+    pub fn last_modified(&self) -> Result<Option<Time>> {
+        self.typed_chunk()
+    }
+
+    pub fn touch(&mut self) {
+        self.touch_at(Time::now())
+    }
+
+    pub fn touch_at(&mut self, time: Time) {
+        let touch_time = self.touch_time();
+        self.set_touch_time(false);
+        self.set_typed_chunk(&time);
+        self.set_touch_time(touch_time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builder::PngBuilder,
+        chunk::Chunk,
+        ihdr::{ColorType, Ihdr},
+    };
+
+    fn testing_png() -> Png {
+        PngBuilder::new()
+            .chunk(Chunk::new(ChunkType::IHDR, vec![0; 13]))
+            .chunk(Chunk::new(ChunkType::IDAT, vec![1, 2, 3]))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_from_unix() {
+        assert_eq!(Time::from_unix(0).to_string(), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            Time::from_unix(951_782_400).to_string(),
+            "2000-02-29T00:00:00Z"
+        );
+        assert_eq!(
+            Time::from_unix(1_700_000_000).to_string(),
+            "2023-11-14T22:13:20Z"
+        );
+    }
+
+    #[test]
+    fn test_time_round_trip() {
+        let time = Time::from_unix(1_700_000_000);
+        assert_eq!(time.to_data(), vec![7, 231, 11, 14, 22, 13, 20]);
+        assert_eq!(Time::from_data(&time.to_data()).unwrap(), time);
+        assert!(Time::from_data(&[0; 6]).is_err());
+    }
+
+    #[test]
+    fn test_touch() {
+        let mut png = testing_png();
+        assert!(png.last_modified().unwrap().is_none());
+
+        png.touch_at(Time::from_unix(0));
+        assert_eq!(png.last_modified().unwrap(), Some(Time::from_unix(0)));
+
+        png.touch();
+        assert!(png.last_modified().unwrap().unwrap() > Time::from_unix(0));
+        assert_eq!(png.chunks().len(), 4);
+    }
+
+    #[test]
+    fn test_touch_time_on_modification() {
+        let mut png = testing_png();
+        png.append_chunk(Chunk::new(ChunkType::from_bytes_const(*b"ruSt"), vec![]));
+        assert!(png.last_modified().unwrap().is_none());
+
+        png.set_touch_time(true);
+        png.remove_first_chunk("ruSt").unwrap();
+        assert!(png.last_modified().unwrap().is_some());
+
+        png.touch_at(Time::from_unix(0));
+        png.set_text("Author", "me").unwrap();
+        assert!(png.last_modified().unwrap().unwrap() > Time::from_unix(0));
+
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, vec!["IHDR", "tEXt", "IDAT", "tIME", "IEND"]);
+
+        // Edits through the header and mutable chunks count too.
+        png.touch_at(Time::from_unix(0));
+        png.set_header(Ihdr::new(2, 2, 8, ColorType::Rgb).unwrap())
+            .unwrap();
+        assert!(png.last_modified().unwrap().unwrap() > Time::from_unix(0));
+
+        png.touch_at(Time::from_unix(0));
+        png.chunks_mut()[1].set_data(b"Author\0you".to_vec());
+        assert!(png.last_modified().unwrap().unwrap() > Time::from_unix(0));
+
+        png.touch_at(Time::from_unix(0));
+        assert!(png.chunk_by_type_mut("zTXt").is_none());
+        assert_eq!(png.last_modified().unwrap(), Some(Time::from_unix(0)));
+    }
+}
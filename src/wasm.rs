@@ -0,0 +1,119 @@
+use crate::{carrier::Carrier, envelope::Envelope, png::Png, stego::StegoMethod};
+use anyhow::{bail, Result};
+use serde_json::json;
+use wasm_bindgen::prelude::*;
+
+// JavaScript bindings over Uint8Array, so a page can hide and reveal messages without a server.
+// Each binding wraps a plain function, which is what the tests call since JsError needs a
+// JavaScript engine.
+
+/// Lists the chunks of a PNG as a JSON array of `{type, length, crc}`.
+#[wasm_bindgen(js_name = chunks)]
+pub fn chunks_js(png: &[u8]) -> Result<String, JsError> {
+    chunks(png).map_err(js_error)
+}
+
+/// Hides `message` in a chunk of `chunkType`, encrypting it when a passphrase is given.
+#[wasm_bindgen(js_name = encode)]
+pub fn encode_js(
+    png: &[u8],
+    chunk_type: &str,
+    message: &[u8],
+    passphrase: Option<String>,
+) -> Result<Vec<u8>, JsError> {
+    encode(png, chunk_type, message, passphrase.as_deref()).map_err(js_error)
+}
+
+/// Reveals the payload hidden in a chunk of `chunkType`.
+#[wasm_bindgen(js_name = decode)]
+pub fn decode_js(
+    png: &[u8],
+    chunk_type: &str,
+    passphrase: Option<String>,
+) -> Result<Vec<u8>, JsError> {
+    decode(png, chunk_type, passphrase.as_deref()).map_err(js_error)
+}
+
+fn js_error(error: anyhow::Error) -> JsError {
+    JsError::new(&format!("{:#}", error))
+}
+
+fn chunks(png: &[u8]) -> Result<String> {
+    let png = Png::try_from(png)?;
+    let chunks: Vec<_> = png
+        .chunks()
+        .iter()
+        .map(|chunk| {
+            json!({
+                "type": chunk.chunk_type().to_string(),
+                "length": chunk.length(),
+                "crc": chunk.crc(),
+            })
+        })
+        .collect();
+    Ok(serde_json::to_string(&chunks)?)
+}
+
+fn encode(
+    png: &[u8],
+    chunk_type: &str,
+    message: &[u8],
+    passphrase: Option<&str>,
+) -> Result<Vec<u8>> {
+    let mut png = Png::try_from(png)?;
+    let mut envelope = Envelope::new(message.to_vec());
+    if let Some(passphrase) = passphrase {
+        envelope = envelope.encrypted(passphrase)?;
+    }
+    png.embed_envelope(StegoMethod::Chunk, chunk_type, &envelope)?;
+    Ok(png.as_bytes())
+}
+
+fn decode(png: &[u8], chunk_type: &str, passphrase: Option<&str>) -> Result<Vec<u8>> {
+    let png = Png::try_from(png)?;
+    let envelope = match passphrase {
+        Some(passphrase) => {
+            png.extract_envelope_with_passphrase(StegoMethod::Chunk, chunk_type, passphrase)?
+        }
+        None => png.extract_envelope(StegoMethod::Chunk, chunk_type)?,
+    };
+    if envelope.encryption.is_some() {
+        bail!("The payload is encrypted, pass the passphrase")
+    }
+    Ok(envelope.payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chunk::Chunk, chunk_type::ChunkType};
+
+    fn testing_png() -> Vec<u8> {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::IHDR, vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ])
+        .as_bytes()
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let encoded = encode(&testing_png(), "ruSt", b"hello", None).unwrap();
+        assert_eq!(decode(&encoded, "ruSt", None).unwrap(), b"hello");
+
+        let encoded = encode(&testing_png(), "ruSt", b"hello", Some("hunter2")).unwrap();
+        assert!(decode(&encoded, "ruSt", None).is_err());
+        assert_eq!(decode(&encoded, "ruSt", Some("hunter2")).unwrap(), b"hello");
+        assert!(decode(&testing_png(), "ruSt", None).is_err());
+    }
+
+    #[test]
+    fn test_chunks() {
+        let chunks: serde_json::Value =
+            serde_json::from_str(&chunks(&testing_png()).unwrap()).unwrap();
+        assert_eq!(chunks[0]["type"], "IHDR");
+        assert_eq!(chunks[0]["length"], 13);
+        assert_eq!(chunks[1]["type"], "IEND");
+        assert!(super::chunks(b"GIF89a").is_err());
+    }
+}